@@ -0,0 +1,69 @@
+//! Compare polling a [`Retry`](backon::Retry) with polling it erased into a
+//! [`DynRetry`](backon::DynRetry).
+//!
+//! Run by `cargo bench --bench erased`.
+
+use std::future::ready;
+use std::future::Future;
+use std::hint::black_box;
+use std::pin::pin;
+use std::sync::Arc;
+use std::task::Context;
+use std::task::Poll;
+use std::task::Wake;
+use std::task::Waker;
+use std::time::Duration;
+use std::time::Instant;
+
+use backon::ConstantBuilder;
+use backon::Retryable;
+
+const ITERATIONS: u32 = 100_000;
+
+struct Noop;
+
+impl Wake for Noop {
+    fn wake(self: Arc<Self>) {}
+}
+
+/// Poll `fut`, ready without waking up, to completion.
+fn block_on<F: Future>(fut: F) -> F::Output {
+    let waker = Waker::from(Arc::new(Noop));
+    let mut cx = Context::from_waker(&waker);
+    let mut fut = pin!(fut);
+    loop {
+        if let Poll::Ready(output) = fut.as_mut().poll(&mut cx) {
+            return output;
+        }
+    }
+}
+
+/// Returns the mean time of `f` over [`ITERATIONS`] iterations.
+fn bench(f: impl Fn() -> Result<usize, &'static str>) -> Duration {
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        black_box(f()).ok();
+    }
+    start.elapsed() / ITERATIONS
+}
+
+fn main() {
+    let retry = || {
+        let mut attempts = 0;
+        (move || {
+            attempts += 1;
+            ready(if attempts < 3 {
+                Err("unavailable")
+            } else {
+                Ok(attempts)
+            })
+        })
+        .retry(ConstantBuilder::default().with_delay(Duration::ZERO))
+        .sleep(|_| ready(()))
+        .when(|e| *e == "unavailable")
+    };
+
+    let generic = bench(|| block_on(retry()));
+    let erased = bench(|| block_on(retry().erase()));
+    println!("retry with 3 attempts: {generic:?} generic, {erased:?} erased");
+}
@@ -1,5 +1,7 @@
 use core::time::Duration;
 
+use crate::backoff::ScaledBuilder;
+
 /// Backoff is an [`Iterator`] that returns [`Duration`].
 ///
 /// - `Some(Duration)` indicates the caller should `sleep(Duration)` and retry the request.
@@ -14,6 +16,33 @@ pub trait BackoffBuilder: Send + Sync + Unpin {
 
     /// Construct a new backoff using the builder.
     fn build(self) -> Self::Backoff;
+
+    /// Multiply every delay of the backoff by `factor`, like for retries ten times faster in
+    /// staging or a multiplier per tenant, without restating the policy.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the input factor is less than `0.0`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use core::time::Duration;
+    ///
+    /// use backon::BackoffBuilder;
+    /// use backon::ConstantBuilder;
+    ///
+    /// let policy = ConstantBuilder::default().with_delay(Duration::from_secs(1));
+    /// let mut staging = policy.scaled_by(0.1).build();
+    ///
+    /// assert_eq!(staging.next(), Some(Duration::from_millis(100)));
+    /// ```
+    fn scaled_by(self, factor: f32) -> ScaledBuilder<Self>
+    where
+        Self: Sized,
+    {
+        ScaledBuilder::new(self, factor)
+    }
 }
 
 impl<B: Backoff> BackoffBuilder for B {
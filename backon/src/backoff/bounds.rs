@@ -0,0 +1,139 @@
+use core::time::Duration;
+
+use crate::BackoffPolicy;
+use crate::Jitter;
+
+/// ScheduleBounds is the least and the most time a backoff can sleep in total, returned by
+/// `schedule_bounds` like [`ExponentialBuilder::schedule_bounds`][crate::ExponentialBuilder::schedule_bounds].
+///
+/// They differ by the jitter, so CI checks and config validation can assert a policy fits
+/// the SLA of an operation before it's deployed, whatever the jitter turns out to be.
+///
+/// # Examples
+///
+/// ```
+/// use core::time::Duration;
+///
+/// use backon::ExponentialBuilder;
+///
+/// let bounds = ExponentialBuilder::default().with_jitter().schedule_bounds();
+/// let bounds = bounds.expect("the backoff has a maximum number of retries");
+///
+/// assert_eq!(bounds.retries(), 3);
+/// assert_eq!(bounds.min(), Duration::from_secs(7));
+/// assert!(bounds.max() <= Duration::from_secs(10));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ScheduleBounds {
+    retries: usize,
+    min: Duration,
+    max: Duration,
+}
+
+impl ScheduleBounds {
+    /// Returns how many times the backoff retries.
+    pub fn retries(&self) -> usize {
+        self.retries
+    }
+
+    /// Returns the least time the backoff sleeps in total, without jitter.
+    pub fn min(&self) -> Duration {
+        self.min
+    }
+
+    /// Returns the most time the backoff sleeps in total, with the largest jitter.
+    pub fn max(&self) -> Duration {
+        self.max
+    }
+}
+
+/// Compute the bounds of `delays` without jitter, retried `max_times`, `jitter` being added
+/// to each delay with `base` the base delay of the builder.
+pub(crate) fn schedule_bounds(
+    mut delays: impl Iterator<Item = Duration>,
+    max_times: Option<usize>,
+    jitter: Jitter,
+    base: Duration,
+) -> Option<ScheduleBounds> {
+    let retries = max_times?;
+    let (mut min, mut max) = (Duration::ZERO, Duration::ZERO);
+    let mut last = [None, None];
+    for done in 0..retries {
+        let Some(dur) = delays.next() else {
+            break;
+        };
+        // The stock backoffs don't change once a delay repeats three times, like at the
+        // maximum delay, so the rest of a long schedule is added at once.
+        let jittered = dur.saturating_add(jitter.max(dur, base));
+        if last == [Some(dur), Some(dur)] {
+            min = min.saturating_add(saturating_mul(dur, retries - done));
+            max = max.saturating_add(saturating_mul(jittered, retries - done));
+            break;
+        }
+        min = min.saturating_add(dur);
+        max = max.saturating_add(jittered);
+        last = [last[1], Some(dur)];
+    }
+    Some(ScheduleBounds { retries, min, max })
+}
+
+fn saturating_mul(dur: Duration, n: usize) -> Duration {
+    let nanos = dur.as_nanos().saturating_mul(n as u128);
+    match u64::try_from(nanos / 1_000_000_000) {
+        Ok(secs) => Duration::new(secs, (nanos % 1_000_000_000) as u32),
+        Err(_) => Duration::MAX,
+    }
+}
+
+impl BackoffPolicy {
+    /// Returns the bounds of the total time the backoff sleeps, see [`ScheduleBounds`], or
+    /// `None` if it retries without a maximum number of times.
+    pub fn schedule_bounds(&self) -> Option<ScheduleBounds> {
+        match self {
+            BackoffPolicy::Constant(builder) => builder.schedule_bounds(),
+            BackoffPolicy::Exponential(builder) => builder.schedule_bounds(),
+            BackoffPolicy::Fibonacci(builder) => builder.schedule_bounds(),
+        }
+    }
+
+    /// Returns the most time the backoff sleeps in total, see [`ScheduleBounds::max`].
+    pub fn max_total_delay(&self) -> Option<Duration> {
+        self.schedule_bounds().map(|bounds| bounds.max())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ConstantBuilder;
+    use crate::FibonacciBuilder;
+
+    #[test]
+    fn test_schedule_bounds() {
+        let bounds = FibonacciBuilder::default()
+            .with_max_delay(Duration::from_secs(3))
+            .with_max_times(usize::MAX)
+            .schedule_bounds()
+            .unwrap();
+        assert_eq!(bounds.min(), bounds.max());
+        assert_eq!(bounds.max(), Duration::MAX);
+
+        let bounds = FibonacciBuilder::default()
+            .with_max_delay(Duration::from_secs(3))
+            .with_max_times(6)
+            .with_jitter()
+            .schedule_bounds()
+            .unwrap();
+        // 1s, 1s, 2s, 3s, 3s and 3s.
+        assert_eq!(bounds.min(), Duration::from_secs(13));
+        #[cfg(feature = "jitter")]
+        assert_eq!(bounds.max(), Duration::from_secs(19));
+
+        let policy = BackoffPolicy::from(
+            ConstantBuilder::default()
+                .with_delay(Duration::from_millis(1))
+                .with_max_times(1_000_000),
+        );
+        assert_eq!(policy.max_total_delay(), Some(Duration::from_secs(1_000)));
+    }
+}
@@ -0,0 +1,51 @@
+/// BackoffChoice is the backoff an error is retried with, returned by the function set by
+/// `backoff_by` like [`Retry::backoff_by`][crate::Retry::backoff_by].
+///
+/// So classes of errors are retried on their own schedules, like throttling errors retried on
+/// a long schedule while transient network errors are retried on a short one. Each class
+/// keeps the state of its backoff apart from the others: a class retried for the first time
+/// starts from the first delay of its backoff, whatever the other classes went through.
+///
+/// # Examples
+///
+/// ```no_run
+/// use core::time::Duration;
+///
+/// use backon::BackoffChoice;
+/// use backon::BackoffPolicy;
+/// use backon::ExponentialBuilder;
+/// use backon::Retryable;
+///
+/// #[derive(Debug)]
+/// enum Error {
+///     Throttled,
+///     Network,
+/// }
+///
+/// async fn fetch() -> Result<String, Error> {
+///     Err(Error::Throttled)
+/// }
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> Result<(), Error> {
+/// let throttled: BackoffPolicy = "constant 5s max=10 jitter".parse().unwrap();
+/// let content = fetch
+///     .retry(ExponentialBuilder::default())
+///     .backoff_by(|e| match e {
+///         Error::Throttled => BackoffChoice::Class("throttled", throttled),
+///         Error::Network => BackoffChoice::Default,
+///     })
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackoffChoice<B> {
+    /// Retry with the backoff of the retry.
+    Default,
+    /// Retry with the backoff built from `B` for the class of errors named by the `&str`.
+    ///
+    /// The backoff is built the first time the class is chosen, so the builders returned
+    /// afterwards for the same class are ignored.
+    Class(&'static str, B),
+}
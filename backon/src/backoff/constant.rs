@@ -1,9 +1,29 @@
+use core::fmt;
 use core::time::Duration;
 
+use crate::backoff::bounds::schedule_bounds;
+use crate::backoff::validate::validate;
 use crate::backoff::BackoffBuilder;
+#[cfg(feature = "duration-str")]
+use crate::duration;
+use crate::duration::Humane;
+use crate::BackoffOverrides;
+#[cfg(feature = "std")]
+use crate::BackoffPolicy;
+use crate::BuildError;
+#[cfg(feature = "std")]
+use crate::FromEnvError;
+use crate::Jitter;
+#[cfg(feature = "duration-str")]
+use crate::ParseDurationError;
+use crate::ScheduleBounds;
+use crate::SchedulePreview;
 
 /// ConstantBuilder is used to create a [`ConstantBackoff`], providing a steady delay with a fixed number of retries.
 ///
+/// With the `serde` feature, it can be loaded from configuration files. Missing fields keep
+/// their defaults and durations are written like `250ms`, `1.5s` or `2m`.
+///
 /// # Default
 ///
 /// - delay: 1s
@@ -31,43 +51,163 @@ use crate::backoff::BackoffBuilder;
 ///     Ok(())
 /// }
 /// ```
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", serde(default, deny_unknown_fields))]
 pub struct ConstantBuilder {
+    #[cfg_attr(feature = "serde", serde(with = "crate::duration::serde"))]
     delay: Duration,
     max_times: Option<usize>,
-    jitter: bool,
+    jitter: Jitter,
 }
 
 impl Default for ConstantBuilder {
     fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConstantBuilder {
+    /// Create a builder with the default parameters, like `default` but usable in `const`
+    /// contexts, so policies can be defined as constants.
+    ///
+    /// ```
+    /// use backon::ConstantBuilder;
+    ///
+    /// const POLICY: ConstantBuilder = ConstantBuilder::new().with_max_times(5);
+    /// ```
+    pub const fn new() -> Self {
         Self {
             delay: Duration::from_secs(1),
             max_times: Some(3),
-            jitter: false,
+            jitter: Jitter::none(),
         }
     }
-}
 
-impl ConstantBuilder {
     /// Set the delay for the backoff.
-    pub fn with_delay(mut self, delay: Duration) -> Self {
+    pub const fn with_delay(mut self, delay: Duration) -> Self {
         self.delay = delay;
         self
     }
 
+    /// Set the delay for the backoff from a human-readable duration like `250ms`,
+    /// `1.5s` or `2m`, see `with_delay`.
+    ///
+    /// Only available with the `duration-str` feature.
+    #[cfg(feature = "duration-str")]
+    pub fn with_delay_str(self, delay: &str) -> Result<Self, ParseDurationError> {
+        Ok(self.with_delay(duration::parse(delay)?))
+    }
+
     /// Set the maximum duration for the backoff.
-    pub fn with_max_times(mut self, max_times: usize) -> Self {
+    pub const fn with_max_times(mut self, max_times: usize) -> Self {
         self.max_times = Some(max_times);
         self
     }
 
+    /// Retry without a maximum number of attempts.
+    #[cfg(feature = "tokio-retry")]
+    pub(crate) const fn without_max_times(mut self) -> Self {
+        self.max_times = None;
+        self
+    }
+
+    /// Set the parameters of `overrides` on top of this builder, keeping the ones it leaves
+    /// unset, see [`BackoffOverrides`].
+    pub fn merge(self, overrides: BackoffOverrides) -> Self {
+        ConstantBuilder {
+            delay: overrides.delay.unwrap_or(self.delay),
+            max_times: overrides.max_times.or(self.max_times),
+            jitter: overrides.jitter.unwrap_or(self.jitter),
+        }
+    }
+
+    /// Returns the least and the most delay of the backoff without jitter.
+    #[cfg(feature = "test-util")]
+    pub(crate) fn delay_range(&self) -> (Duration, Option<Duration>) {
+        (self.delay, Some(self.delay))
+    }
+
+    /// Returns the least and the most time the backoff sleeps in total, see
+    /// [`ScheduleBounds`], or `None` if it retries without a maximum number of times.
+    pub fn schedule_bounds(&self) -> Option<ScheduleBounds> {
+        let delays = Self {
+            jitter: Jitter::none(),
+            ..*self
+        };
+        schedule_bounds(delays.build(), self.max_times, self.jitter, self.delay)
+    }
+
+    /// Returns the most time the backoff sleeps in total, see [`ScheduleBounds::max`].
+    pub fn max_total_delay(&self) -> Option<Duration> {
+        self.schedule_bounds().map(|bounds| bounds.max())
+    }
+
+    /// Returns the first `n` delays of the backoff without jitter, see [`SchedulePreview`].
+    pub fn preview(&self, n: usize) -> SchedulePreview {
+        let delays = Self {
+            jitter: Jitter::none(),
+            ..*self
+        };
+        SchedulePreview::new(delays.build(), n)
+    }
+
+    /// Build the backoff like [`BackoffBuilder::build`], unless the parameters make no sense.
+    ///
+    /// It rejects a maximum of zero retries, instead of producing a surprising schedule.
+    pub fn try_build(self) -> Result<ConstantBackoff, BuildError> {
+        validate(self.delay, None, self.max_times)?;
+        Ok(self.build())
+    }
+
+    /// Create a builder from the environment variables named `prefix` followed by
+    /// `DELAY`, `MAX_TIMES` and `JITTER`, like `MYAPP_RETRY_DELAY=500ms`.
+    ///
+    /// Durations are written like `250ms` or `1.5s`, and `JITTER` is `true` or `false`. Unset
+    /// variables keep the defaults, and invalid ones are returned as an error.
+    ///
+    /// Only available with the `std` feature.
+    #[cfg(feature = "std")]
+    pub fn from_env(prefix: &str) -> Result<Self, FromEnvError> {
+        match BackoffPolicy::Constant(Self::default())
+            .set_from_env(prefix, &["delay", "max_times", "jitter"])?
+        {
+            BackoffPolicy::Constant(builder) => Ok(builder),
+            _ => unreachable!("the environment only sets the parameters of the backoff"),
+        }
+    }
+
     /// Set jitter for the backoff.
     ///
     /// Jitter is a random value added to the delay to prevent a thundering herd problem.
-    pub fn with_jitter(mut self) -> Self {
-        self.jitter = true;
+    pub const fn with_jitter(mut self) -> Self {
+        self.jitter = Jitter::base();
         self
     }
+
+    /// Set the [`Jitter`] added to every delay, shared with the other builders.
+    pub const fn with_jitter_policy(mut self, jitter: Jitter) -> Self {
+        self.jitter = jitter;
+        self
+    }
+}
+
+/// Displays the parameters of the builder as the description parsed by
+/// [`BackoffPolicy`](crate::BackoffPolicy), like `constant 1s max_times=3 jitter`.
+impl fmt::Display for ConstantBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "constant {}", Humane(self.delay))?;
+        if let Some(max_times) = self.max_times {
+            write!(f, " max_times={max_times}")?;
+        }
+        if self.jitter == Jitter::base() {
+            f.write_str(" jitter")?;
+        } else if !self.jitter.is_none() {
+            write!(f, " jitter={}", self.jitter)?;
+        }
+        Ok(())
+    }
 }
 
 impl BackoffBuilder for ConstantBuilder {
@@ -96,23 +236,32 @@ impl BackoffBuilder for &ConstantBuilder {
 ///
 /// This backoff strategy is constructed by [`ConstantBuilder`].
 #[doc(hidden)]
-#[derive(Debug)]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ConstantBackoff {
     delay: Duration,
     max_times: Option<usize>,
 
     attempts: usize,
-    jitter: bool,
+    jitter: Jitter,
+}
+
+impl ConstantBackoff {
+    /// Returns the delays this backoff has left without jitter, as an independent iterator,
+    /// leaving the backoff itself untouched.
+    pub fn planned(&self) -> Self {
+        Self {
+            jitter: Jitter::none(),
+            ..self.clone()
+        }
+    }
 }
 
 impl Iterator for ConstantBackoff {
     type Item = Duration;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let delay = || match self.jitter {
-            true => self.delay + self.delay.mul_f32(fastrand::f32()),
-            false => self.delay,
-        };
+        let delay = || self.jitter.apply(self.delay, self.delay);
         match self.max_times {
             None => Some(delay()),
             Some(max_times) => {
@@ -168,11 +317,12 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "jitter")]
     fn test_constant_with_jitter() {
         let mut it = ConstantBuilder::default().with_jitter().build();
 
         let dur = it.next().unwrap();
-        fastrand::seed(7);
+        crate::seed_jitter_rng(7);
         assert!(dur > Duration::from_secs(1));
     }
 }
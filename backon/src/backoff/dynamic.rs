@@ -0,0 +1,113 @@
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use core::fmt;
+
+use crate::backoff::BackoffBuilder;
+use crate::Backoff;
+
+/// DynBackoffBuilder is the object-safe form of [`BackoffBuilder`], implemented by all
+/// builders that are `Clone`.
+///
+/// It's used through [`SharedBackoffBuilder`].
+pub trait DynBackoffBuilder: Send + Sync {
+    /// Construct a new backoff, boxed.
+    fn build_dyn(&self) -> Box<dyn Backoff>;
+}
+
+impl<B> DynBackoffBuilder for B
+where
+    B: BackoffBuilder + Clone,
+    B::Backoff: 'static,
+{
+    fn build_dyn(&self) -> Box<dyn Backoff> {
+        Box::new(self.clone().build())
+    }
+}
+
+/// SharedBackoffBuilder is a builder of any type, shared cheaply across threads.
+///
+/// Retries using it don't depend on the type of the builder, so it can be swapped at
+/// runtime, like from a feature flag or an admin endpoint, without generics leaking into
+/// every signature.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::RwLock;
+///
+/// use backon::BlockingRetryable;
+/// use backon::ConstantBuilder;
+/// use backon::ExponentialBuilder;
+/// use backon::SharedBackoffBuilder;
+///
+/// let policy = RwLock::new(SharedBackoffBuilder::new(ExponentialBuilder::default()));
+///
+/// // Later, switch every new retry to another policy.
+/// *policy.write().unwrap() = SharedBackoffBuilder::new(ConstantBuilder::default());
+///
+/// let backoff = policy.read().unwrap().clone();
+/// let result = (|| Ok::<_, &str>(42)).retry(backoff).call();
+/// assert_eq!(result, Ok(42));
+/// ```
+#[derive(Clone)]
+pub struct SharedBackoffBuilder(Arc<dyn DynBackoffBuilder>);
+
+impl SharedBackoffBuilder {
+    /// Share `builder`, building its backoffs from a clone of it.
+    pub fn new<B>(builder: B) -> Self
+    where
+        B: BackoffBuilder + Clone + 'static,
+    {
+        SharedBackoffBuilder(Arc::new(builder))
+    }
+}
+
+impl From<Arc<dyn DynBackoffBuilder>> for SharedBackoffBuilder {
+    fn from(builder: Arc<dyn DynBackoffBuilder>) -> Self {
+        SharedBackoffBuilder(builder)
+    }
+}
+
+impl fmt::Debug for SharedBackoffBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SharedBackoffBuilder")
+            .finish_non_exhaustive()
+    }
+}
+
+impl BackoffBuilder for SharedBackoffBuilder {
+    type Backoff = Box<dyn Backoff>;
+
+    fn build(self) -> Self::Backoff {
+        self.0.build_dyn()
+    }
+}
+
+impl BackoffBuilder for &SharedBackoffBuilder {
+    type Backoff = Box<dyn Backoff>;
+
+    fn build(self) -> Self::Backoff {
+        self.0.build_dyn()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+    use core::time::Duration;
+
+    use super::*;
+    use crate::ConstantBuilder;
+
+    #[test]
+    fn test_shared_backoff_builder() {
+        let builder: Arc<dyn DynBackoffBuilder> =
+            Arc::new(ConstantBuilder::default().with_delay(Duration::from_millis(5)));
+        let shared = SharedBackoffBuilder::from(builder);
+
+        let delays: Vec<_> = (&shared).build().collect();
+        assert_eq!(delays, [Duration::from_millis(5); 3]);
+        // Every build starts a new backoff.
+        assert_eq!(shared.build().count(), 3);
+    }
+}
@@ -0,0 +1,101 @@
+use alloc::format;
+use alloc::string::String;
+use core::fmt;
+use std::env;
+
+use crate::BackoffPolicy;
+
+/// The error returned by `from_env` when an environment variable holds an invalid value.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FromEnvError {
+    var: String,
+    reason: &'static str,
+}
+
+impl FromEnvError {
+    /// Returns the name of the invalid environment variable.
+    pub fn var(&self) -> &str {
+        &self.var
+    }
+}
+
+impl fmt::Display for FromEnvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid environment variable `{}`: {}",
+            self.var, self.reason
+        )
+    }
+}
+
+impl core::error::Error for FromEnvError {}
+
+impl BackoffPolicy {
+    /// Set the parameters of the backoff from the environment variables named `prefix`
+    /// followed by each of `keys` in upper case, like `MYAPP_RETRY_MIN_DELAY`.
+    pub(crate) fn set_from_env(
+        mut self,
+        prefix: &str,
+        keys: &[&str],
+    ) -> Result<Self, FromEnvError> {
+        for key in keys {
+            let var = format!("{prefix}{}", key.to_ascii_uppercase());
+            let value = match env::var(&var) {
+                Ok(value) => value,
+                Err(env::VarError::NotPresent) => continue,
+                Err(env::VarError::NotUnicode(_)) => {
+                    return Err(FromEnvError {
+                        var,
+                        reason: "not valid unicode",
+                    })
+                }
+            };
+            self = self
+                .set(&value, key, value.trim())
+                .map_err(|err| FromEnvError {
+                    reason: err.reason(),
+                    var,
+                })?;
+        }
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+    use core::time::Duration;
+
+    use super::*;
+    use crate::BackoffBuilder;
+    use crate::ConstantBuilder;
+    use crate::ExponentialBuilder;
+
+    #[test]
+    fn test_from_env() {
+        env::set_var("BACKON_TEST_EXP_MIN_DELAY", "2s");
+        env::set_var("BACKON_TEST_EXP_FACTOR", "2");
+        env::set_var("BACKON_TEST_EXP_MAX_TIMES", "2");
+        let mut exp = ExponentialBuilder::from_env("BACKON_TEST_EXP_")
+            .unwrap()
+            .build();
+        assert_eq!(Some(Duration::from_secs(2)), exp.next());
+        assert_eq!(Some(Duration::from_secs(4)), exp.next());
+        assert_eq!(None, exp.next());
+
+        // Unset variables keep the defaults.
+        let constant = ConstantBuilder::from_env("BACKON_TEST_UNSET_")
+            .unwrap()
+            .build();
+        assert_eq!(constant.count(), 3);
+
+        env::set_var("BACKON_TEST_BAD_JITTER", "yes");
+        let err = ConstantBuilder::from_env("BACKON_TEST_BAD_").unwrap_err();
+        assert_eq!(err.var(), "BACKON_TEST_BAD_JITTER");
+        assert_eq!(
+            err.to_string(),
+            "invalid environment variable `BACKON_TEST_BAD_JITTER`: invalid jitter, expected a bool, a ratio or a range"
+        );
+    }
+}
@@ -1,9 +1,31 @@
+use core::fmt;
+use core::hash::Hash;
+use core::hash::Hasher;
 use core::time::Duration;
 
+use crate::backoff::bounds::schedule_bounds;
+use crate::backoff::validate::validate;
 use crate::backoff::BackoffBuilder;
+#[cfg(feature = "duration-str")]
+use crate::duration;
+use crate::duration::Humane;
+use crate::BackoffOverrides;
+#[cfg(feature = "std")]
+use crate::BackoffPolicy;
+use crate::BuildError;
+#[cfg(feature = "std")]
+use crate::FromEnvError;
+use crate::Jitter;
+#[cfg(feature = "duration-str")]
+use crate::ParseDurationError;
+use crate::ScheduleBounds;
+use crate::SchedulePreview;
 
 /// ExponentialBuilder is used to construct an [`ExponentialBackoff`] that offers delays with exponential retries.
 ///
+/// With the `serde` feature, it can be loaded from configuration files. Missing fields keep
+/// their defaults and durations are written like `250ms`, `1.5s` or `2m`.
+///
 /// # Default
 ///
 /// - jitter: false
@@ -35,33 +57,145 @@ use crate::backoff::BackoffBuilder;
 /// }
 /// ```
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", serde(default, deny_unknown_fields))]
 pub struct ExponentialBuilder {
-    jitter: bool,
+    jitter: Jitter,
     factor: f32,
+    #[cfg_attr(feature = "serde", serde(with = "crate::duration::serde"))]
     min_delay: Duration,
+    #[cfg_attr(feature = "serde", serde(with = "crate::duration::serde::option"))]
     max_delay: Option<Duration>,
     max_times: Option<usize>,
 }
 
 impl Default for ExponentialBuilder {
     fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ExponentialBuilder {
+    /// Create a builder with the default parameters, like `default` but usable in `const`
+    /// contexts, so policies can be defined as constants.
+    ///
+    /// The setters are `const` too, except `with_factor` that checks the factor.
+    ///
+    /// ```
+    /// use backon::ExponentialBuilder;
+    ///
+    /// const POLICY: ExponentialBuilder = ExponentialBuilder::new().with_max_times(5);
+    /// ```
+    pub const fn new() -> Self {
         Self {
-            jitter: false,
+            jitter: Jitter::none(),
             factor: 2.0,
             min_delay: Duration::from_secs(1),
             max_delay: Some(Duration::from_secs(60)),
             max_times: Some(3),
         }
     }
-}
 
-impl ExponentialBuilder {
+    /// A preset retrying fast operations quickly, like lookups in a local cache or database.
+    ///
+    /// With jitter, it waits 10ms, 20ms, 40ms, 80ms and 160ms, retrying 5 times within
+    /// around 300ms.
+    pub const fn aggressive() -> Self {
+        Self {
+            jitter: Jitter::base(),
+            factor: 2.0,
+            min_delay: Duration::from_millis(10),
+            max_delay: Some(Duration::from_secs(1)),
+            max_times: Some(5),
+        }
+    }
+
+    /// A preset suited to most remote calls, like requests to other services.
+    ///
+    /// With jitter, it waits 100ms, 200ms, 400ms, 800ms and 1.6s, retrying 5 times within
+    /// around 3s.
+    pub const fn standard() -> Self {
+        Self {
+            jitter: Jitter::base(),
+            factor: 2.0,
+            min_delay: Duration::from_millis(100),
+            max_delay: Some(Duration::from_secs(10)),
+            max_times: Some(5),
+        }
+    }
+
+    /// A preset for expensive or rate-limited operations, like batch jobs or third-party
+    /// APIs, giving them time to recover.
+    ///
+    /// With jitter, it waits 1s, 2s, 4s, 8s, 16s, 32s and then 60s twice, retrying 8 times
+    /// within around 3 minutes.
+    pub const fn conservative() -> Self {
+        Self {
+            jitter: Jitter::base(),
+            factor: 2.0,
+            min_delay: Duration::from_secs(1),
+            max_delay: Some(Duration::from_secs(60)),
+            max_times: Some(8),
+        }
+    }
+
+    /// Create a builder retrying `attempts` times and sleeping up to `total` in all, the delay
+    /// doubling after every attempt.
+    ///
+    /// It's solved for the minimum delay, so a schedule is given in terms of the SLA of an
+    /// operation instead of its parameters. The maximum delay is the last delay, so the
+    /// schedule still fits if the retries are extended with `with_max_times`. Jitter, if
+    /// enabled afterwards, is added to `total`, see [`ScheduleBounds`].
+    ///
+    /// ```
+    /// use core::time::Duration;
+    ///
+    /// use backon::ExponentialBuilder;
+    ///
+    /// let builder = ExponentialBuilder::fitting(4, Duration::from_secs(30));
+    ///
+    /// assert_eq!(builder.preview(4).to_string(), "2s, 4s, 8s, 16s (30s in total)");
+    /// ```
+    pub fn fitting(attempts: usize, total: Duration) -> Self {
+        // The delays sum up to `min_delay * (2^attempts - 1)`.
+        let divisor = 1u128
+            .checked_shl(attempts as u32)
+            .filter(|_| attempts < 128)
+            .map_or(u128::MAX, |n| n - 1)
+            .max(1);
+        let mut builder = Self {
+            jitter: Jitter::none(),
+            factor: 2.0,
+            min_delay: nanos(total.as_nanos() / divisor),
+            max_delay: None,
+            max_times: Some(attempts),
+        };
+        // The delays are multiplied as floats, so the minimum delay is lowered until the
+        // rounding errors don't exceed `total`.
+        loop {
+            builder.max_delay = builder.build().last();
+            let sum = builder.max_total_delay().unwrap_or_default();
+            if sum <= total {
+                return builder;
+            }
+            let excess = (sum - total).as_nanos() / divisor + 1;
+            builder.min_delay = nanos(builder.min_delay.as_nanos().saturating_sub(excess));
+        }
+    }
+
     /// Set the jitter for the backoff.
     ///
     /// When jitter is enabled, [`ExponentialBackoff`] will add a random jitter within `(0, min_delay)`
     /// to the current delay.
-    pub fn with_jitter(mut self) -> Self {
-        self.jitter = true;
+    pub const fn with_jitter(mut self) -> Self {
+        self.jitter = Jitter::base();
+        self
+    }
+
+    /// Set the [`Jitter`] added to every delay, shared with the other builders.
+    pub const fn with_jitter_policy(mut self, jitter: Jitter) -> Self {
+        self.jitter = jitter;
         self
     }
 
@@ -78,26 +212,174 @@ impl ExponentialBuilder {
     }
 
     /// Set the minimum delay for the backoff.
-    pub fn with_min_delay(mut self, min_delay: Duration) -> Self {
+    pub const fn with_min_delay(mut self, min_delay: Duration) -> Self {
         self.min_delay = min_delay;
         self
     }
 
+    /// Set the minimum delay for the backoff from a human-readable duration like `250ms`,
+    /// `1.5s` or `2m`, see `with_min_delay`.
+    ///
+    /// Only available with the `duration-str` feature.
+    #[cfg(feature = "duration-str")]
+    pub fn with_min_delay_str(self, min_delay: &str) -> Result<Self, ParseDurationError> {
+        Ok(self.with_min_delay(duration::parse(min_delay)?))
+    }
+
     /// Set the maximum delay for the backoff.
     ///
     /// The delay will not increase if the current delay exceeds the maximum delay.
-    pub fn with_max_delay(mut self, max_delay: Duration) -> Self {
+    pub const fn with_max_delay(mut self, max_delay: Duration) -> Self {
         self.max_delay = Some(max_delay);
         self
     }
 
+    /// Set the maximum delay for the backoff from a human-readable duration like `250ms`,
+    /// `1.5s` or `2m`, see `with_max_delay`.
+    ///
+    /// Only available with the `duration-str` feature.
+    #[cfg(feature = "duration-str")]
+    pub fn with_max_delay_str(self, max_delay: &str) -> Result<Self, ParseDurationError> {
+        Ok(self.with_max_delay(duration::parse(max_delay)?))
+    }
+
     /// Set the maximum number of attempts for the current backoff.
     ///
     /// The backoff will stop if the maximum number of attempts is reached.
-    pub fn with_max_times(mut self, max_times: usize) -> Self {
+    pub const fn with_max_times(mut self, max_times: usize) -> Self {
         self.max_times = Some(max_times);
         self
     }
+
+    /// Retry without a maximum number of attempts.
+    #[cfg(any(feature = "backoff", feature = "tokio-retry"))]
+    pub(crate) const fn without_max_times(mut self) -> Self {
+        self.max_times = None;
+        self
+    }
+
+    /// Increase the delay without a maximum.
+    #[cfg(any(feature = "tokio-retry", feature = "proptest"))]
+    pub(crate) const fn without_max_delay(mut self) -> Self {
+        self.max_delay = None;
+        self
+    }
+
+    /// Set the parameters of `overrides` on top of this builder, keeping the ones it leaves
+    /// unset, see [`BackoffOverrides`].
+    pub fn merge(self, overrides: BackoffOverrides) -> Self {
+        ExponentialBuilder {
+            jitter: overrides.jitter.unwrap_or(self.jitter),
+            factor: overrides.factor.unwrap_or(self.factor),
+            min_delay: overrides.min_delay.unwrap_or(self.min_delay),
+            max_delay: overrides.max_delay.or(self.max_delay),
+            max_times: overrides.max_times.or(self.max_times),
+        }
+    }
+
+    /// Returns the least and the most delay of the backoff without jitter.
+    #[cfg(feature = "test-util")]
+    pub(crate) fn delay_range(&self) -> (Duration, Option<Duration>) {
+        (self.min_delay, self.max_delay)
+    }
+
+    /// Returns the least and the most time the backoff sleeps in total, see
+    /// [`ScheduleBounds`], or `None` if it retries without a maximum number of times.
+    pub fn schedule_bounds(&self) -> Option<ScheduleBounds> {
+        let delays = Self {
+            jitter: Jitter::none(),
+            ..*self
+        };
+        schedule_bounds(delays.build(), self.max_times, self.jitter, self.min_delay)
+    }
+
+    /// Returns the most time the backoff sleeps in total, see [`ScheduleBounds::max`].
+    pub fn max_total_delay(&self) -> Option<Duration> {
+        self.schedule_bounds().map(|bounds| bounds.max())
+    }
+
+    /// Returns the first `n` delays of the backoff without jitter, see [`SchedulePreview`].
+    pub fn preview(&self, n: usize) -> SchedulePreview {
+        let delays = Self {
+            jitter: Jitter::none(),
+            ..*self
+        };
+        SchedulePreview::new(delays.build(), n)
+    }
+
+    /// Build the backoff like [`BackoffBuilder::build`], unless the parameters make no sense.
+    ///
+    /// It rejects a factor lower than 1, a minimum delay greater than the maximum delay and a
+    /// maximum of zero retries, instead of producing a surprising schedule.
+    pub fn try_build(self) -> Result<ExponentialBackoff, BuildError> {
+        if self.factor.is_nan() || self.factor < 1.0 {
+            return Err(BuildError::InvalidFactor(self.factor));
+        }
+        validate(self.min_delay, self.max_delay, self.max_times)?;
+        Ok(self.build())
+    }
+
+    /// Create a builder from the environment variables named `prefix` followed by
+    /// `MIN_DELAY`, `MAX_DELAY`, `FACTOR`, `MAX_TIMES` and `JITTER`, like `MYAPP_RETRY_MIN_DELAY=100ms`.
+    ///
+    /// Durations are written like `250ms` or `1.5s`, and `JITTER` is `true` or `false`. Unset
+    /// variables keep the defaults, and invalid ones are returned as an error.
+    ///
+    /// Only available with the `std` feature.
+    #[cfg(feature = "std")]
+    pub fn from_env(prefix: &str) -> Result<Self, FromEnvError> {
+        match BackoffPolicy::Exponential(Self::default()).set_from_env(
+            prefix,
+            &["min_delay", "max_delay", "factor", "max_times", "jitter"],
+        )? {
+            BackoffPolicy::Exponential(builder) => Ok(builder),
+            _ => unreachable!("the environment only sets the parameters of the backoff"),
+        }
+    }
+}
+
+// The factor is compared by its bits, so builders can be used as keys of maps.
+impl PartialEq for ExponentialBuilder {
+    fn eq(&self, other: &Self) -> bool {
+        self.jitter == other.jitter
+            && self.factor.to_bits() == other.factor.to_bits()
+            && self.min_delay == other.min_delay
+            && self.max_delay == other.max_delay
+            && self.max_times == other.max_times
+    }
+}
+
+impl Eq for ExponentialBuilder {}
+
+impl Hash for ExponentialBuilder {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.jitter.hash(state);
+        self.factor.to_bits().hash(state);
+        self.min_delay.hash(state);
+        self.max_delay.hash(state);
+        self.max_times.hash(state);
+    }
+}
+
+/// Displays the parameters of the builder as the description parsed by
+/// [`BackoffPolicy`](crate::BackoffPolicy), like `exponential 1s..1m x2 max_times=3 jitter`.
+impl fmt::Display for ExponentialBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "exponential {}", Humane(self.min_delay))?;
+        if let Some(max_delay) = self.max_delay {
+            write!(f, "..{}", Humane(max_delay))?;
+        }
+        write!(f, " x{}", self.factor)?;
+        if let Some(max_times) = self.max_times {
+            write!(f, " max_times={max_times}")?;
+        }
+        if self.jitter == Jitter::base() {
+            f.write_str(" jitter")?;
+        } else if !self.jitter.is_none() {
+            write!(f, " jitter={}", self.jitter)?;
+        }
+        Ok(())
+    }
 }
 
 impl BackoffBuilder for ExponentialBuilder {
@@ -129,9 +411,10 @@ impl BackoffBuilder for &ExponentialBuilder {
 ///
 /// This backoff strategy is constructed by [`ExponentialBuilder`].
 #[doc(hidden)]
-#[derive(Debug)]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ExponentialBackoff {
-    jitter: bool,
+    jitter: Jitter,
     factor: f32,
     min_delay: Duration,
     max_delay: Option<Duration>,
@@ -141,6 +424,17 @@ pub struct ExponentialBackoff {
     attempts: usize,
 }
 
+impl ExponentialBackoff {
+    /// Returns the delays this backoff has left without jitter, as an independent iterator,
+    /// leaving the backoff itself untouched.
+    pub fn planned(&self) -> Self {
+        Self {
+            jitter: Jitter::none(),
+            ..self.clone()
+        }
+    }
+}
+
 impl Iterator for ExponentialBackoff {
     type Item = Duration;
 
@@ -150,7 +444,7 @@ impl Iterator for ExponentialBackoff {
         }
         self.attempts += 1;
 
-        let mut tmp_cur = match self.current_delay {
+        let tmp_cur = match self.current_delay {
             None => {
                 // If current_delay is None, it's must be the first time to retry.
                 self.current_delay = Some(self.min_delay);
@@ -172,14 +466,17 @@ impl Iterator for ExponentialBackoff {
                 cur
             }
         };
-        // If jitter is enabled, add random jitter based on min delay.
-        if self.jitter {
-            tmp_cur = tmp_cur.saturating_add(self.min_delay.mul_f32(fastrand::f32()));
-        }
-        Some(tmp_cur)
+        Some(self.jitter.apply(tmp_cur, self.min_delay))
     }
 }
 
+fn nanos(nanos: u128) -> Duration {
+    Duration::new(
+        (nanos / 1_000_000_000) as u64,
+        (nanos % 1_000_000_000) as u32,
+    )
+}
+
 #[inline]
 pub(crate) fn saturating_mul(d: Duration, rhs: f32) -> Duration {
     Duration::try_from_secs_f32(rhs * d.as_secs_f32()).unwrap_or(Duration::MAX)
@@ -194,6 +491,7 @@ mod tests {
 
     use crate::BackoffBuilder;
     use crate::ExponentialBuilder;
+    use crate::Jitter;
 
     #[test]
     fn test_exponential_default() {
@@ -205,6 +503,24 @@ mod tests {
         assert_eq!(None, exp.next());
     }
 
+    #[test]
+    fn test_exponential_const() {
+        const POLICY: ExponentialBuilder = ExponentialBuilder::new()
+            .with_min_delay(Duration::from_secs(2))
+            .with_max_times(1);
+
+        assert_eq!(
+            POLICY,
+            ExponentialBuilder::default()
+                .with_min_delay(Duration::from_secs(2))
+                .with_max_times(1)
+        );
+        assert_eq!(
+            POLICY.build().collect::<alloc::vec::Vec<_>>(),
+            [Duration::from_secs(2)]
+        );
+    }
+
     #[test]
     fn test_exponential_factor() {
         let mut exp = ExponentialBuilder::default().with_factor(1.5).build();
@@ -261,7 +577,7 @@ mod tests {
     #[test]
     fn test_exponential_max_delay_without_default_1() {
         let mut exp = ExponentialBuilder {
-            jitter: false,
+            jitter: Jitter::none(),
             factor: 10_000_000_000_f32,
             min_delay: Duration::from_secs(1),
             max_delay: None,
@@ -278,7 +594,7 @@ mod tests {
     #[test]
     fn test_exponential_max_delay_without_default_2() {
         let mut exp = ExponentialBuilder {
-            jitter: true,
+            jitter: Jitter::base(),
             factor: 10_000_000_000_f32,
             min_delay: Duration::from_secs(10_000_000_000),
             max_delay: None,
@@ -295,7 +611,7 @@ mod tests {
     #[test]
     fn test_exponential_max_delay_without_default_3() {
         let mut exp = ExponentialBuilder {
-            jitter: false,
+            jitter: Jitter::none(),
             factor: 10_000_000_000_f32,
             min_delay: Duration::from_secs(10_000_000_000),
             max_delay: Some(Duration::from_secs(60_000_000_000)),
@@ -315,4 +631,73 @@ mod tests {
         assert_eq!(Some(Duration::from_secs(1)), exp.next());
         assert_eq!(None, exp.next());
     }
+
+    #[test]
+    fn test_exponential_fitting() {
+        for (attempts, total) in [(1, 10), (7, 3_333), (20, 60_000)] {
+            let total = Duration::from_millis(total);
+            let builder = ExponentialBuilder::fitting(attempts, total);
+            let sum: Duration = builder.build().sum();
+            assert!(sum <= total, "{attempts} attempts: {sum:?}");
+            assert!(
+                total - sum < Duration::from_millis(1),
+                "{attempts} attempts: {sum:?}"
+            );
+            assert_eq!(builder.build().count(), attempts);
+        }
+
+        // Degenerate schedules still fit.
+        assert_eq!(
+            ExponentialBuilder::fitting(0, Duration::from_secs(1)).max_total_delay(),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(
+            ExponentialBuilder::fitting(200, Duration::from_millis(1)).max_total_delay(),
+            Some(Duration::ZERO)
+        );
+    }
+
+    #[test]
+    fn test_exponential_planned() {
+        let mut exp = ExponentialBuilder::default().with_jitter().build();
+        exp.next();
+
+        let planned: alloc::vec::Vec<_> = exp.planned().collect();
+        assert_eq!(planned, [2, 4].map(Duration::from_secs));
+
+        let v = exp.next().expect("value must valid");
+        assert!(v >= Duration::from_secs(2), "current: {v:?}");
+        assert!(v < Duration::from_secs(3), "current: {v:?}");
+    }
+
+    #[test]
+    fn test_exponential_try_build_with_invalid_factor() {
+        let builder = ExponentialBuilder {
+            factor: 0.5,
+            ..Default::default()
+        };
+        assert_eq!(
+            builder.try_build().unwrap_err(),
+            crate::BuildError::InvalidFactor(0.5)
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_exponential_serde() {
+        let builder: ExponentialBuilder =
+            serde_json::from_str(r#"{"min_delay": "250ms", "max_delay": "1m", "max_times": 5}"#)
+                .unwrap();
+        let mut exp = builder.build();
+        assert_eq!(Some(Duration::from_millis(250)), exp.next());
+        assert_eq!(Some(Duration::from_millis(500)), exp.next());
+        assert_eq!(exp.count(), 3);
+
+        assert_eq!(
+            serde_json::to_string(&builder).unwrap(),
+            r#"{"jitter":false,"factor":2.0,"min_delay":"250ms","max_delay":"1m","max_times":5}"#
+        );
+        assert!(serde_json::from_str::<ExponentialBuilder>(r#"{"min_delay": "soon"}"#).is_err());
+        assert!(serde_json::from_str::<ExponentialBuilder>(r#"{"min": "1s"}"#).is_err());
+    }
 }
@@ -1,9 +1,29 @@
+use core::fmt;
 use core::time::Duration;
 
+use crate::backoff::bounds::schedule_bounds;
+use crate::backoff::validate::validate;
 use crate::backoff::BackoffBuilder;
+#[cfg(feature = "duration-str")]
+use crate::duration;
+use crate::duration::Humane;
+use crate::BackoffOverrides;
+#[cfg(feature = "std")]
+use crate::BackoffPolicy;
+use crate::BuildError;
+#[cfg(feature = "std")]
+use crate::FromEnvError;
+use crate::Jitter;
+#[cfg(feature = "duration-str")]
+use crate::ParseDurationError;
+use crate::ScheduleBounds;
+use crate::SchedulePreview;
 
 /// FibonacciBuilder is used to build a [`FibonacciBackoff`] which offers a delay with Fibonacci-based retries.
 ///
+/// With the `serde` feature, it can be loaded from configuration files. Missing fields keep
+/// their defaults and durations are written like `250ms`, `1.5s` or `2m`.
+///
 /// # Default
 ///
 /// - jitter: false
@@ -33,55 +53,197 @@ use crate::backoff::BackoffBuilder;
 ///     Ok(())
 /// }
 /// ```
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", serde(default, deny_unknown_fields))]
 pub struct FibonacciBuilder {
-    jitter: bool,
+    jitter: Jitter,
+    #[cfg_attr(feature = "serde", serde(with = "crate::duration::serde"))]
     min_delay: Duration,
+    #[cfg_attr(feature = "serde", serde(with = "crate::duration::serde::option"))]
     max_delay: Option<Duration>,
     max_times: Option<usize>,
 }
 
 impl Default for FibonacciBuilder {
     fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FibonacciBuilder {
+    /// Create a builder with the default parameters, like `default` but usable in `const`
+    /// contexts, so policies can be defined as constants.
+    ///
+    /// ```
+    /// use backon::FibonacciBuilder;
+    ///
+    /// const POLICY: FibonacciBuilder = FibonacciBuilder::new().with_max_times(5);
+    /// ```
+    pub const fn new() -> Self {
         Self {
-            jitter: false,
+            jitter: Jitter::none(),
             min_delay: Duration::from_secs(1),
             max_delay: Some(Duration::from_secs(60)),
             max_times: Some(3),
         }
     }
-}
 
-impl FibonacciBuilder {
     /// Set the jitter for the backoff.
     ///
     /// When jitter is enabled, FibonacciBackoff will add a random jitter between `(0, min_delay)` to the delay.
-    pub fn with_jitter(mut self) -> Self {
-        self.jitter = true;
+    pub const fn with_jitter(mut self) -> Self {
+        self.jitter = Jitter::base();
+        self
+    }
+
+    /// Set the [`Jitter`] added to every delay, shared with the other builders.
+    pub const fn with_jitter_policy(mut self, jitter: Jitter) -> Self {
+        self.jitter = jitter;
         self
     }
 
     /// Set the minimum delay for the backoff.
-    pub fn with_min_delay(mut self, min_delay: Duration) -> Self {
+    pub const fn with_min_delay(mut self, min_delay: Duration) -> Self {
         self.min_delay = min_delay;
         self
     }
 
+    /// Set the minimum delay for the backoff from a human-readable duration like `250ms`,
+    /// `1.5s` or `2m`, see `with_min_delay`.
+    ///
+    /// Only available with the `duration-str` feature.
+    #[cfg(feature = "duration-str")]
+    pub fn with_min_delay_str(self, min_delay: &str) -> Result<Self, ParseDurationError> {
+        Ok(self.with_min_delay(duration::parse(min_delay)?))
+    }
+
     /// Set the maximum delay for the current backoff.
     ///
     /// The delay will not increase if the current delay exceeds the maximum delay.
-    pub fn with_max_delay(mut self, max_delay: Duration) -> Self {
+    pub const fn with_max_delay(mut self, max_delay: Duration) -> Self {
         self.max_delay = Some(max_delay);
         self
     }
 
+    /// Set the maximum delay for the backoff from a human-readable duration like `250ms`,
+    /// `1.5s` or `2m`, see `with_max_delay`.
+    ///
+    /// Only available with the `duration-str` feature.
+    #[cfg(feature = "duration-str")]
+    pub fn with_max_delay_str(self, max_delay: &str) -> Result<Self, ParseDurationError> {
+        Ok(self.with_max_delay(duration::parse(max_delay)?))
+    }
+
     /// Set the maximum number of attempts for the current backoff.
     ///
     /// The backoff will stop if the maximum number of attempts is reached.
-    pub fn with_max_times(mut self, max_times: usize) -> Self {
+    pub const fn with_max_times(mut self, max_times: usize) -> Self {
         self.max_times = Some(max_times);
         self
     }
+
+    /// Retry without a maximum number of attempts.
+    #[cfg(feature = "tokio-retry")]
+    pub(crate) const fn without_max_times(mut self) -> Self {
+        self.max_times = None;
+        self
+    }
+
+    /// Increase the delay without a maximum.
+    #[cfg(any(feature = "tokio-retry", feature = "proptest"))]
+    pub(crate) const fn without_max_delay(mut self) -> Self {
+        self.max_delay = None;
+        self
+    }
+
+    /// Set the parameters of `overrides` on top of this builder, keeping the ones it leaves
+    /// unset, see [`BackoffOverrides`].
+    pub fn merge(self, overrides: BackoffOverrides) -> Self {
+        FibonacciBuilder {
+            jitter: overrides.jitter.unwrap_or(self.jitter),
+            min_delay: overrides.min_delay.unwrap_or(self.min_delay),
+            max_delay: overrides.max_delay.or(self.max_delay),
+            max_times: overrides.max_times.or(self.max_times),
+        }
+    }
+
+    /// Returns the least and the most delay of the backoff without jitter.
+    #[cfg(feature = "test-util")]
+    pub(crate) fn delay_range(&self) -> (Duration, Option<Duration>) {
+        (self.min_delay, self.max_delay)
+    }
+
+    /// Returns the least and the most time the backoff sleeps in total, see
+    /// [`ScheduleBounds`], or `None` if it retries without a maximum number of times.
+    pub fn schedule_bounds(&self) -> Option<ScheduleBounds> {
+        let delays = Self {
+            jitter: Jitter::none(),
+            ..*self
+        };
+        schedule_bounds(delays.build(), self.max_times, self.jitter, self.min_delay)
+    }
+
+    /// Returns the most time the backoff sleeps in total, see [`ScheduleBounds::max`].
+    pub fn max_total_delay(&self) -> Option<Duration> {
+        self.schedule_bounds().map(|bounds| bounds.max())
+    }
+
+    /// Returns the first `n` delays of the backoff without jitter, see [`SchedulePreview`].
+    pub fn preview(&self, n: usize) -> SchedulePreview {
+        let delays = Self {
+            jitter: Jitter::none(),
+            ..*self
+        };
+        SchedulePreview::new(delays.build(), n)
+    }
+
+    /// Build the backoff like [`BackoffBuilder::build`], unless the parameters make no sense.
+    ///
+    /// It rejects a minimum delay greater than the maximum delay and a maximum of zero
+    /// retries, instead of producing a surprising schedule.
+    pub fn try_build(self) -> Result<FibonacciBackoff, BuildError> {
+        validate(self.min_delay, self.max_delay, self.max_times)?;
+        Ok(self.build())
+    }
+
+    /// Create a builder from the environment variables named `prefix` followed by
+    /// `MIN_DELAY`, `MAX_DELAY`, `MAX_TIMES` and `JITTER`, like `MYAPP_RETRY_MIN_DELAY=100ms`.
+    ///
+    /// Durations are written like `250ms` or `1.5s`, and `JITTER` is `true` or `false`. Unset
+    /// variables keep the defaults, and invalid ones are returned as an error.
+    ///
+    /// Only available with the `std` feature.
+    #[cfg(feature = "std")]
+    pub fn from_env(prefix: &str) -> Result<Self, FromEnvError> {
+        match BackoffPolicy::Fibonacci(Self::default())
+            .set_from_env(prefix, &["min_delay", "max_delay", "max_times", "jitter"])?
+        {
+            BackoffPolicy::Fibonacci(builder) => Ok(builder),
+            _ => unreachable!("the environment only sets the parameters of the backoff"),
+        }
+    }
+}
+
+/// Displays the parameters of the builder as the description parsed by
+/// [`BackoffPolicy`](crate::BackoffPolicy), like `fibonacci 1s..1m max_times=3 jitter`.
+impl fmt::Display for FibonacciBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "fibonacci {}", Humane(self.min_delay))?;
+        if let Some(max_delay) = self.max_delay {
+            write!(f, "..{}", Humane(max_delay))?;
+        }
+        if let Some(max_times) = self.max_times {
+            write!(f, " max_times={max_times}")?;
+        }
+        if self.jitter == Jitter::base() {
+            f.write_str(" jitter")?;
+        } else if !self.jitter.is_none() {
+            write!(f, " jitter={}", self.jitter)?;
+        }
+        Ok(())
+    }
 }
 
 impl BackoffBuilder for FibonacciBuilder {
@@ -113,9 +275,10 @@ impl BackoffBuilder for &FibonacciBuilder {
 ///
 /// This backoff strategy is constructed by [`FibonacciBuilder`].
 #[doc(hidden)]
-#[derive(Debug)]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FibonacciBackoff {
-    jitter: bool,
+    jitter: Jitter,
     min_delay: Duration,
     max_delay: Option<Duration>,
     max_times: Option<usize>,
@@ -125,6 +288,17 @@ pub struct FibonacciBackoff {
     attempts: usize,
 }
 
+impl FibonacciBackoff {
+    /// Returns the delays this backoff has left without jitter, as an independent iterator,
+    /// leaving the backoff itself untouched.
+    pub fn planned(&self) -> Self {
+        Self {
+            jitter: Jitter::none(),
+            ..self.clone()
+        }
+    }
+}
+
 impl Iterator for FibonacciBackoff {
     type Item = Duration;
 
@@ -137,34 +311,26 @@ impl Iterator for FibonacciBackoff {
         match self.current_delay {
             None => {
                 // If current_delay is None, it's must be the first time to retry.
-                let mut next = self.min_delay;
+                let next = self.min_delay;
                 self.current_delay = Some(next);
 
-                // If jitter is enabled, add random jitter based on min delay.
-                if self.jitter {
-                    next += self.min_delay.mul_f32(fastrand::f32());
-                }
-
-                Some(next)
+                Some(self.jitter.apply(next, self.min_delay))
             }
             Some(cur) => {
                 let mut next = cur;
 
                 // If current delay larger than max delay, we should stop increment anymore.
-                if next < self.max_delay.unwrap_or(Duration::MAX) {
+                let max_delay = self.max_delay.unwrap_or(Duration::MAX);
+                if next < max_delay {
                     if let Some(prev) = self.previous_delay {
-                        next += prev;
+                        // The sum may jump over the maximum delay, like 2s + 3s with a maximum of 4s.
+                        next = next.saturating_add(prev).min(max_delay);
                         self.current_delay = Some(next);
                     }
                     self.previous_delay = Some(cur);
                 }
 
-                // If jitter is enabled, add random jitter based on min delay.
-                if self.jitter {
-                    next += self.min_delay.mul_f32(fastrand::f32());
-                }
-
-                Some(next)
+                Some(self.jitter.apply(next, self.min_delay))
             }
         }
     }
@@ -172,6 +338,7 @@ impl Iterator for FibonacciBackoff {
 
 #[cfg(test)]
 mod tests {
+    use alloc::vec::Vec;
     use core::time::Duration;
 
     #[cfg(target_arch = "wasm32")]
@@ -233,6 +400,13 @@ mod tests {
         assert_eq!(Some(Duration::from_secs(2)), fib.next());
         assert_eq!(Some(Duration::from_secs(2)), fib.next());
         assert_eq!(None, fib.next());
+
+        let fib = FibonacciBuilder::default()
+            .with_max_times(5)
+            .with_max_delay(Duration::from_secs(4))
+            .build();
+        let delays: Vec<_> = fib.map(|dur| dur.as_secs()).collect();
+        assert_eq!(delays, [1, 1, 2, 3, 4]);
     }
 
     #[test]
@@ -247,4 +421,23 @@ mod tests {
         assert_eq!(Some(Duration::from_secs(8)), fib.next());
         assert_eq!(None, fib.next());
     }
+
+    #[cfg(feature = "duration-str")]
+    #[test]
+    fn test_fibonacci_with_delay_str() {
+        let mut fib = FibonacciBuilder::default()
+            .with_min_delay_str("250ms")
+            .unwrap()
+            .with_max_delay_str("1m")
+            .unwrap()
+            .build();
+
+        assert_eq!(Some(Duration::from_millis(250)), fib.next());
+        assert_eq!(
+            FibonacciBuilder::default()
+                .with_max_delay_str("a minute")
+                .unwrap_err(),
+            crate::ParseDurationError
+        );
+    }
 }
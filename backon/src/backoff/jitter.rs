@@ -0,0 +1,328 @@
+use core::fmt;
+use core::hash::Hash;
+use core::hash::Hasher;
+use core::ops::Range;
+use core::time::Duration;
+
+use crate::backoff::exponential::saturating_mul;
+use crate::duration;
+use crate::duration::Humane;
+
+/// Jitter is the random delay added to every delay of a backoff, so that clients failing
+/// together don't retry together.
+///
+/// It's accepted by every builder through `with_jitter_policy`, like
+/// [`ExponentialBuilder::with_jitter_policy`][crate::ExponentialBuilder::with_jitter_policy],
+/// so the same jitter is shared and configured the same way by all policies. `with_jitter`
+/// is a shorthand for [`Jitter::base`].
+///
+/// Jitter is described by `jitter=` in the description parsed by
+/// [`BackoffPolicy`][crate::BackoffPolicy] and loaded by serde as `true` or `false` for
+/// [`Jitter::base`] or [`Jitter::none`], a number for [`Jitter::ratio`], or a range like
+/// `10ms..50ms` for [`Jitter::range`].
+///
+/// Without the `jitter` feature, enabled by default, the jitter is compiled out along with
+/// the RNG: it's still configured the same way, but the delays are always the ones of the
+/// backoff, for builds that must be deterministic and keep their dependencies minimal. The
+/// RNG is fastrand's with the default `fastrand` feature, or a tiny xorshift RNG without it,
+/// see [`set_jitter_rng`](crate::set_jitter_rng) to draw from another one.
+///
+/// # Examples
+///
+/// ```
+/// use core::time::Duration;
+///
+/// use backon::BackoffBuilder;
+/// use backon::ConstantBuilder;
+/// use backon::Jitter;
+///
+/// let jitter = Jitter::range(Duration::from_millis(10)..Duration::from_millis(50));
+/// let mut backoff = ConstantBuilder::default()
+///     .with_delay(Duration::from_secs(1))
+///     .with_jitter_policy(jitter)
+///     .build();
+///
+/// let delay = backoff.next().unwrap();
+/// # #[cfg(feature = "jitter")]
+/// assert!(delay >= Duration::from_millis(1010) && delay < Duration::from_millis(1050));
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Jitter(Kind);
+
+#[derive(Debug, Clone, Copy, Default)]
+enum Kind {
+    #[default]
+    None,
+    Base,
+    Ratio(f32),
+    Range(Duration, Duration),
+}
+
+impl Jitter {
+    /// No jitter, the delays are the ones of the backoff.
+    pub const fn none() -> Self {
+        Jitter(Kind::None)
+    }
+
+    /// Add up to the base delay of the builder, its minimum delay or the delay of
+    /// [`ConstantBuilder`][crate::ConstantBuilder], like `with_jitter`.
+    pub const fn base() -> Self {
+        Jitter(Kind::Base)
+    }
+
+    /// Add up to `ratio` of every delay, like `0.2` for up to 20% more.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the input ratio is less than `0.0`.
+    pub fn ratio(ratio: f32) -> Self {
+        debug_assert!(ratio >= 0.0, "invalid jitter ratio that lower than 0");
+
+        Jitter(Kind::Ratio(ratio))
+    }
+
+    /// Add from the start of `range` up to its end to every delay.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the start of the range is after its end.
+    pub fn range(range: Range<Duration>) -> Self {
+        debug_assert!(range.start <= range.end, "invalid jitter range");
+
+        Jitter(Kind::Range(range.start, range.end))
+    }
+
+    /// Returns `true` if there's no jitter.
+    pub fn is_none(&self) -> bool {
+        matches!(self.0, Kind::None)
+    }
+
+    /// Add the jitter to `delay`, `base` being the base delay of the builder.
+    #[cfg(feature = "jitter")]
+    pub(crate) fn apply(&self, delay: Duration, base: Duration) -> Duration {
+        let random = match self.0 {
+            Kind::None => return delay,
+            Kind::Base => base.mul_f32(crate::rng::f32()),
+            Kind::Ratio(ratio) => saturating_mul(delay, ratio * crate::rng::f32()),
+            Kind::Range(start, end) => {
+                start.saturating_add((end.saturating_sub(start)).mul_f32(crate::rng::f32()))
+            }
+        };
+        delay.saturating_add(random)
+    }
+
+    /// Returns `delay`, the jitter being compiled out without the `jitter` feature.
+    #[cfg(not(feature = "jitter"))]
+    pub(crate) fn apply(&self, delay: Duration, _: Duration) -> Duration {
+        delay
+    }
+
+    /// Returns the most the jitter adds to `delay`, `base` being the base delay of the builder.
+    pub(crate) fn max(&self, delay: Duration, base: Duration) -> Duration {
+        if cfg!(not(feature = "jitter")) {
+            return Duration::ZERO;
+        }
+        match self.0 {
+            Kind::None => Duration::ZERO,
+            Kind::Base => base,
+            Kind::Ratio(ratio) => saturating_mul(delay, ratio),
+            Kind::Range(_, end) => end,
+        }
+    }
+
+    /// Parse the jitter described by `jitter=` in a [`BackoffPolicy`][crate::BackoffPolicy].
+    pub(crate) fn parse(value: &str) -> Option<Self> {
+        match value {
+            "true" | "1" => return Some(Jitter::base()),
+            "false" | "0" => return Some(Jitter::none()),
+            _ => {}
+        }
+        if let Some((start, end)) = value.split_once("..") {
+            let (start, end) = (duration::parse(start).ok()?, duration::parse(end).ok()?);
+            return (start <= end).then_some(Jitter(Kind::Range(start, end)));
+        }
+        let ratio = value.parse::<f32>().ok()?;
+        (ratio >= 0.0).then_some(Jitter(Kind::Ratio(ratio)))
+    }
+}
+
+// The ratio is compared by its bits, so builders can be used as keys of maps.
+impl PartialEq for Jitter {
+    fn eq(&self, other: &Self) -> bool {
+        match (self.0, other.0) {
+            (Kind::None, Kind::None) | (Kind::Base, Kind::Base) => true,
+            (Kind::Ratio(a), Kind::Ratio(b)) => a.to_bits() == b.to_bits(),
+            (Kind::Range(a, b), Kind::Range(c, d)) => a == c && b == d,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Jitter {}
+
+impl Hash for Jitter {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self.0 {
+            Kind::None => 0.hash(state),
+            Kind::Base => 1.hash(state),
+            Kind::Ratio(ratio) => (2, ratio.to_bits()).hash(state),
+            Kind::Range(start, end) => (3, start, end).hash(state),
+        }
+    }
+}
+
+/// Displays the jitter as the value of `jitter=` parsed by
+/// [`BackoffPolicy`](crate::BackoffPolicy), like `true` or `0.2`.
+impl fmt::Display for Jitter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Kind::None => f.write_str("false"),
+            Kind::Base => f.write_str("true"),
+            // Debug keeps the decimal point, so a ratio of 1 isn't read back as `true`.
+            Kind::Ratio(ratio) => write!(f, "{ratio:?}"),
+            Kind::Range(start, end) => write!(f, "{}..{}", Humane(start), Humane(end)),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde {
+    use alloc::string::ToString;
+    use core::fmt;
+
+    use serde::de;
+    use serde::Deserializer;
+    use serde::Serializer;
+
+    use super::Jitter;
+    use super::Kind;
+
+    impl serde::Serialize for Jitter {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            match self.0 {
+                Kind::None => serializer.serialize_bool(false),
+                Kind::Base => serializer.serialize_bool(true),
+                Kind::Ratio(ratio) => serializer.serialize_f32(ratio),
+                Kind::Range(..) => serializer.serialize_str(&self.to_string()),
+            }
+        }
+    }
+
+    impl<'de> serde::Deserialize<'de> for Jitter {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            deserializer.deserialize_any(JitterVisitor)
+        }
+    }
+
+    struct JitterVisitor;
+
+    impl de::Visitor<'_> for JitterVisitor {
+        type Value = Jitter;
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("a bool, a ratio or a range of durations like `10ms..50ms`")
+        }
+
+        fn visit_bool<E: de::Error>(self, v: bool) -> Result<Jitter, E> {
+            Ok(if v { Jitter::base() } else { Jitter::none() })
+        }
+
+        fn visit_f64<E: de::Error>(self, v: f64) -> Result<Jitter, E> {
+            if v >= 0.0 {
+                Ok(Jitter(Kind::Ratio(v as f32)))
+            } else {
+                Err(E::invalid_value(de::Unexpected::Float(v), &self))
+            }
+        }
+
+        fn visit_u64<E: de::Error>(self, v: u64) -> Result<Jitter, E> {
+            Ok(Jitter(Kind::Ratio(v as f32)))
+        }
+
+        fn visit_i64<E: de::Error>(self, v: i64) -> Result<Jitter, E> {
+            self.visit_f64(v as f64)
+        }
+
+        fn visit_str<E: de::Error>(self, v: &str) -> Result<Jitter, E> {
+            Jitter::parse(v).ok_or_else(|| E::invalid_value(de::Unexpected::Str(v), &self))
+        }
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+mod arbitrary {
+    use core::time::Duration;
+
+    use arbitrary::Arbitrary;
+    use arbitrary::Unstructured;
+
+    use super::Jitter;
+
+    impl<'a> Arbitrary<'a> for Jitter {
+        fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+            // Only the jitters the constructors accept, like serde.
+            Ok(match u.int_in_range(0..=3)? {
+                0 => Jitter::none(),
+                1 => Jitter::base(),
+                2 => match f32::arbitrary(u)? {
+                    ratio if ratio >= 0.0 => Jitter::ratio(ratio),
+                    _ => Jitter::none(),
+                },
+                _ => {
+                    let (a, b) = <(Duration, Duration)>::arbitrary(u)?;
+                    Jitter::range(a.min(b)..a.max(b))
+                }
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "jitter")]
+    fn test_jitter() {
+        let delay = Duration::from_secs(1);
+        let base = Duration::from_millis(100);
+        for _ in 0..100 {
+            let jittered = Jitter::ratio(0.5).apply(delay, base);
+            assert!(jittered >= delay && jittered < Duration::from_millis(1500));
+            let jittered = Jitter::base().apply(delay, base);
+            assert!(jittered >= delay && jittered < Duration::from_millis(1100));
+        }
+        assert_eq!(Jitter::none().apply(delay, base), delay);
+        assert_eq!(
+            Jitter::ratio(0.5).max(delay, base),
+            Duration::from_millis(500)
+        );
+    }
+
+    #[test]
+    #[cfg(not(feature = "jitter"))]
+    fn test_jitter_compiled_out() {
+        let delay = Duration::from_secs(1);
+        let jitter = Jitter::range(Duration::from_millis(10)..Duration::from_millis(50));
+        assert_eq!(jitter.apply(delay, delay), delay);
+        assert_eq!(jitter.max(delay, delay), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_parse_jitter() {
+        for jitter in [
+            Jitter::none(),
+            Jitter::base(),
+            Jitter::ratio(1.0),
+            Jitter::ratio(0.25),
+            Jitter::range(Duration::from_millis(10)..Duration::from_secs(1)),
+        ] {
+            assert_eq!(Jitter::parse(&jitter.to_string()), Some(jitter));
+        }
+        assert_eq!(Jitter::parse("-0.5"), None);
+        assert_eq!(Jitter::parse("2s..1s"), None);
+        assert_eq!(Jitter::parse("yes"), None);
+    }
+}
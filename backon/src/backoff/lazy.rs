@@ -0,0 +1,156 @@
+use core::fmt;
+use core::mem;
+use core::time::Duration;
+
+use crate::backoff::BackoffBuilder;
+
+/// LazyBackoff is a backoff built by its builder only once it's asked for its first delay,
+/// after the first attempt of its retry failed.
+///
+/// `retry` builds the backoff right away, which only copies the settings of the builders of
+/// backon. Wrapping a builder that's costly to build makes a retry succeeding the first time
+/// cost about as much as calling the function directly. Its state is serialized with the
+/// `serde` feature, so a [`RetrySnapshot`](crate::RetrySnapshot) of the retry is too.
+///
+/// # Examples
+///
+/// ```
+/// use backon::BlockingRetryable;
+/// use backon::ExponentialBuilder;
+/// use backon::LazyBackoff;
+///
+/// let result = (|| Ok::<_, &str>(42))
+///     .retry(LazyBackoff::new(ExponentialBuilder::default()))
+///     .call();
+/// assert_eq!(result, Ok(42));
+/// ```
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "B: serde::Serialize, B::Backoff: serde::Serialize",
+        deserialize = "B: serde::Deserialize<'de>, B::Backoff: serde::Deserialize<'de>"
+    ))
+)]
+pub struct LazyBackoff<B: BackoffBuilder>(Lazy<B>);
+
+/// The builder until it's built, the backoff after, sharing the same room.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "B: serde::Serialize, B::Backoff: serde::Serialize",
+        deserialize = "B: serde::Deserialize<'de>, B::Backoff: serde::Deserialize<'de>"
+    ))
+)]
+enum Lazy<B: BackoffBuilder> {
+    Builder(B),
+    Built(B::Backoff),
+    /// Only while the builder builds, or if it panicked.
+    Building,
+}
+
+impl<B: BackoffBuilder> LazyBackoff<B> {
+    /// Create a backoff built by `builder` when it's first asked for a delay.
+    pub fn new(builder: B) -> Self {
+        LazyBackoff(Lazy::Builder(builder))
+    }
+
+    /// Returns whether the backoff has been built.
+    pub fn is_built(&self) -> bool {
+        matches!(self.0, Lazy::Built(_))
+    }
+}
+
+impl<B: BackoffBuilder> Iterator for LazyBackoff<B> {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Lazy::Builder(_) = self.0 {
+            if let Lazy::Builder(builder) = mem::replace(&mut self.0, Lazy::Building) {
+                self.0 = Lazy::Built(builder.build());
+            }
+        }
+        match &mut self.0 {
+            Lazy::Built(backoff) => backoff.next(),
+            _ => None,
+        }
+    }
+}
+
+impl<B> Clone for LazyBackoff<B>
+where
+    B: BackoffBuilder + Clone,
+    B::Backoff: Clone,
+{
+    fn clone(&self) -> Self {
+        LazyBackoff(match &self.0 {
+            Lazy::Builder(builder) => Lazy::Builder(builder.clone()),
+            Lazy::Built(backoff) => Lazy::Built(backoff.clone()),
+            Lazy::Building => Lazy::Building,
+        })
+    }
+}
+
+impl<B> fmt::Debug for LazyBackoff<B>
+where
+    B: BackoffBuilder + fmt::Debug,
+    B::Backoff: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut f = f.debug_tuple("LazyBackoff");
+        match &self.0 {
+            Lazy::Builder(builder) => f.field(builder),
+            Lazy::Built(backoff) => f.field(backoff),
+            Lazy::Building => f.field(&"building"),
+        }
+        .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::sync::atomic::AtomicUsize;
+    use core::sync::atomic::Ordering;
+
+    use super::*;
+    use crate::BlockingRetryable;
+    use crate::ConstantBackoff;
+    use crate::ConstantBuilder;
+
+    static BUILT: AtomicUsize = AtomicUsize::new(0);
+
+    struct Counted;
+
+    impl BackoffBuilder for Counted {
+        type Backoff = ConstantBackoff;
+
+        fn build(self) -> Self::Backoff {
+            BUILT.fetch_add(1, Ordering::Relaxed);
+            ConstantBuilder::default().with_max_times(1).build()
+        }
+    }
+
+    #[test]
+    fn test_lazy_backoff() {
+        let result = (|| Ok::<_, &str>(1))
+            .retry(LazyBackoff::new(Counted))
+            .call();
+        assert_eq!(result, Ok(1));
+        // The backoff isn't built when the first attempt succeeds.
+        assert_eq!(BUILT.load(Ordering::Relaxed), 0);
+
+        let result = (|| Err::<(), _>("unavailable"))
+            .retry(LazyBackoff::new(Counted))
+            .sleep(|_| {})
+            .call();
+        assert_eq!(result, Err("unavailable"));
+        assert_eq!(BUILT.load(Ordering::Relaxed), 1);
+
+        let mut backoff = LazyBackoff::new(ConstantBuilder::default().with_max_times(1));
+        assert!(!backoff.is_built());
+        assert_eq!(backoff.next(), Some(Duration::from_secs(1)));
+        assert!(backoff.is_built());
+        assert_eq!(backoff.next(), None);
+    }
+}
@@ -0,0 +1,195 @@
+//! Conversions from the policies of other retry crates, to migrate to backon one call site
+//! at a time.
+
+use core::time::Duration;
+
+#[cfg(any(feature = "backoff", test))]
+use crate::BackoffBuilder;
+#[cfg(feature = "tokio-retry")]
+use crate::ConstantBuilder;
+use crate::ExponentialBuilder;
+#[cfg(feature = "tokio-retry")]
+use crate::FibonacciBuilder;
+
+/// The longest schedule looked at to convert a policy, past which it's considered unbounded.
+const MAX_SAMPLES: usize = 1024;
+
+/// Convert the policy of the `backoff` crate.
+///
+/// The initial interval becomes the minimum delay, the multiplier the factor and the maximum
+/// interval the maximum delay. A randomization factor enables jitter, which backon adds on
+/// top of the delay instead of around it.
+///
+/// The maximum elapsed time becomes the number of delays that fit in it, not counting the
+/// time spent in the operation itself.
+///
+/// Only available with the `backoff` feature.
+#[cfg(feature = "backoff")]
+impl<C> From<&::backoff::exponential::ExponentialBackoff<C>> for ExponentialBuilder {
+    fn from(policy: &::backoff::exponential::ExponentialBackoff<C>) -> Self {
+        let mut builder = ExponentialBuilder::default()
+            .with_min_delay(policy.initial_interval)
+            .with_max_delay(policy.max_interval)
+            .with_factor(policy.multiplier as f32)
+            .without_max_times();
+        if let Some(max_elapsed_time) = policy.max_elapsed_time {
+            let mut total = Duration::ZERO;
+            let max_times = builder
+                .build()
+                .take(MAX_SAMPLES)
+                .take_while(|dur| {
+                    total = total.saturating_add(*dur);
+                    total <= max_elapsed_time
+                })
+                .count();
+            builder = builder.with_max_times(max_times);
+        }
+        if policy.randomization_factor > 0.0 {
+            builder = builder.with_jitter();
+        }
+        builder
+    }
+}
+
+/// Convert the policy of the `backoff` crate, see the conversion of a reference.
+///
+/// Only available with the `backoff` feature.
+#[cfg(feature = "backoff")]
+impl<C> From<::backoff::exponential::ExponentialBackoff<C>> for ExponentialBuilder {
+    fn from(policy: ::backoff::exponential::ExponentialBackoff<C>) -> Self {
+        ExponentialBuilder::from(&policy)
+    }
+}
+
+/// Returns the first delay of `delays`, the second one, and the delay it settles on after
+/// `skip` delays, if it does.
+#[cfg(feature = "tokio-retry")]
+fn sample(
+    mut delays: impl Iterator<Item = Duration>,
+    skip: usize,
+) -> (Duration, Duration, Option<Duration>) {
+    let first = delays.next().unwrap_or_default();
+    let second = delays.next().unwrap_or(first);
+    let mut last = second;
+    let plateau = delays
+        .skip(skip)
+        .take(MAX_SAMPLES)
+        .find(|dur| core::mem::replace(&mut last, *dur) == *dur);
+    (first, second, plateau)
+}
+
+/// Convert the exponential strategy of the `tokio-retry` crate.
+///
+/// `tokio-retry` doesn't expose the parameters of its strategies, so they're read from the
+/// strategy's schedule, which is deterministic. The converted builder is unbounded like the
+/// strategy, bound it with [`ExponentialBuilder::with_max_times`] like `.take(n)` bounded the
+/// strategy.
+///
+/// Only available with the `tokio-retry` feature.
+#[cfg(feature = "tokio-retry")]
+impl From<tokio_retry::strategy::ExponentialBackoff> for ExponentialBuilder {
+    fn from(strategy: tokio_retry::strategy::ExponentialBackoff) -> Self {
+        let (first, second, plateau) = sample(strategy, 0);
+        let factor = if first.is_zero() {
+            1.0
+        } else {
+            (second.as_secs_f64() / first.as_secs_f64()).max(1.0) as f32
+        };
+        let builder = ExponentialBuilder::default()
+            .with_min_delay(first)
+            .with_factor(factor)
+            .without_max_times();
+        match plateau {
+            Some(max_delay) => builder.with_max_delay(max_delay),
+            None => builder.without_max_delay(),
+        }
+    }
+}
+
+/// Convert the Fibonacci strategy of the `tokio-retry` crate, see the conversion of its
+/// exponential strategy.
+///
+/// Only available with the `tokio-retry` feature.
+#[cfg(feature = "tokio-retry")]
+impl From<tokio_retry::strategy::FibonacciBackoff> for FibonacciBuilder {
+    fn from(strategy: tokio_retry::strategy::FibonacciBackoff) -> Self {
+        // The first two delays of a Fibonacci schedule are the same, skip them.
+        let (first, _, plateau) = sample(strategy, 1);
+        let builder = FibonacciBuilder::default()
+            .with_min_delay(first)
+            .without_max_times();
+        match plateau {
+            Some(max_delay) => builder.with_max_delay(max_delay),
+            None => builder.without_max_delay(),
+        }
+    }
+}
+
+/// Convert the fixed interval strategy of the `tokio-retry` crate, see the conversion of its
+/// exponential strategy.
+///
+/// Only available with the `tokio-retry` feature.
+#[cfg(feature = "tokio-retry")]
+impl From<tokio_retry::strategy::FixedInterval> for ConstantBuilder {
+    fn from(mut strategy: tokio_retry::strategy::FixedInterval) -> Self {
+        ConstantBuilder::default()
+            .with_delay(strategy.next().unwrap_or_default())
+            .without_max_times()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+
+    fn delays(builder: impl BackoffBuilder, n: usize) -> Vec<Duration> {
+        builder.build().take(n).collect()
+    }
+
+    #[cfg(feature = "backoff")]
+    #[test]
+    fn test_from_backoff() {
+        let policy = ::backoff::ExponentialBackoffBuilder::new()
+            .with_initial_interval(Duration::from_secs(1))
+            .with_multiplier(2.0)
+            .with_max_interval(Duration::from_secs(4))
+            .with_max_elapsed_time(Some(Duration::from_secs(12)))
+            .with_randomization_factor(0.0)
+            .build();
+
+        let builder = ExponentialBuilder::from(policy);
+        assert_eq!(
+            delays(builder, usize::MAX),
+            [1, 2, 4, 4].map(Duration::from_secs)
+        );
+    }
+
+    #[cfg(feature = "tokio-retry")]
+    #[test]
+    fn test_from_tokio_retry() {
+        use tokio_retry::strategy;
+
+        let exponential = strategy::ExponentialBackoff::from_millis(2)
+            .factor(500)
+            .max_delay(Duration::from_secs(8));
+        assert_eq!(
+            delays(ExponentialBuilder::from(exponential.clone()), 8),
+            delays(exponential, 8)
+        );
+
+        let fibonacci =
+            strategy::FibonacciBackoff::from_millis(1000).max_delay(Duration::from_secs(5));
+        assert_eq!(
+            delays(FibonacciBuilder::from(fibonacci.clone()), 8),
+            delays(fibonacci, 8)
+        );
+
+        let fixed = strategy::FixedInterval::from_millis(250);
+        assert_eq!(
+            delays(ConstantBuilder::from(fixed.clone()), 8),
+            delays(fixed, 8)
+        );
+    }
+}
@@ -12,3 +12,64 @@ pub use fibonacci::FibonacciBuilder;
 mod exponential;
 pub use exponential::ExponentialBackoff;
 pub use exponential::ExponentialBuilder;
+
+mod jitter;
+pub use jitter::Jitter;
+
+mod lazy;
+pub use lazy::LazyBackoff;
+
+mod precomputed;
+pub use precomputed::PrecomputedBackoff;
+pub use precomputed::PrecomputedBuilder;
+
+mod replay;
+pub use replay::ReplayBackoff;
+pub use replay::ReplayBuilder;
+
+mod overrides;
+pub use overrides::BackoffOverrides;
+
+#[cfg(any(feature = "backoff", feature = "tokio-retry"))]
+mod migrate;
+
+mod scaled;
+pub use scaled::ScaledBackoff;
+pub use scaled::ScaledBuilder;
+
+mod sla;
+pub use sla::SlaBackoff;
+pub use sla::SlaBuilder;
+
+mod policy;
+pub use policy::BackoffPolicy;
+pub use policy::ParseBackoffError;
+
+#[cfg(feature = "std")]
+mod env;
+#[cfg(feature = "std")]
+pub use env::FromEnvError;
+
+mod bounds;
+pub use bounds::ScheduleBounds;
+
+mod preview;
+pub use preview::SchedulePreview;
+
+mod choice;
+pub use choice::BackoffChoice;
+
+mod validate;
+pub use validate::BuildError;
+
+mod preset;
+pub use preset::Preset;
+
+mod dynamic;
+pub use dynamic::DynBackoffBuilder;
+pub use dynamic::SharedBackoffBuilder;
+
+#[cfg(feature = "std")]
+mod watched;
+#[cfg(feature = "std")]
+pub use watched::WatchedPolicy;
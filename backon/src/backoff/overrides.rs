@@ -0,0 +1,173 @@
+use core::time::Duration;
+
+use crate::BackoffPolicy;
+use crate::Jitter;
+
+/// BackoffOverrides is a set of parameters overriding the ones of a builder, through
+/// `merge` like [`ExponentialBuilder::merge`][crate::ExponentialBuilder::merge].
+///
+/// Parameters left unset keep the values of the builder, so a default policy is tweaked per
+/// operation without restating it. Parameters the builder doesn't have are ignored: `delay`
+/// is only used by [`ConstantBuilder`][crate::ConstantBuilder], `min_delay`, `max_delay` by
+/// the others, and `factor` by [`ExponentialBuilder`][crate::ExponentialBuilder].
+///
+/// With the `serde` feature, it's loaded from configuration with the same keys as its
+/// setters, like `max_times = 5`.
+///
+/// # Examples
+///
+/// ```
+/// use core::time::Duration;
+///
+/// use backon::BackoffOverrides;
+/// use backon::ExponentialBuilder;
+///
+/// let default = ExponentialBuilder::standard();
+/// let upload = default.merge(BackoffOverrides::new().with_max_times(10));
+///
+/// assert_eq!(
+///     upload,
+///     ExponentialBuilder::standard().with_max_times(10)
+/// );
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default, deny_unknown_fields))]
+pub struct BackoffOverrides {
+    #[cfg_attr(feature = "serde", serde(with = "crate::duration::serde::option"))]
+    pub(crate) delay: Option<Duration>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::duration::serde::option"))]
+    pub(crate) min_delay: Option<Duration>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::duration::serde::option"))]
+    pub(crate) max_delay: Option<Duration>,
+    pub(crate) factor: Option<f32>,
+    pub(crate) max_times: Option<usize>,
+    pub(crate) jitter: Option<Jitter>,
+}
+
+impl BackoffOverrides {
+    /// Create overrides that keep every parameter.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Override the delay of [`ConstantBuilder`][crate::ConstantBuilder].
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = Some(delay);
+        self
+    }
+
+    /// Override the minimum delay.
+    pub fn with_min_delay(mut self, min_delay: Duration) -> Self {
+        self.min_delay = Some(min_delay);
+        self
+    }
+
+    /// Override the maximum delay.
+    pub fn with_max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = Some(max_delay);
+        self
+    }
+
+    /// Override the factor of [`ExponentialBuilder`][crate::ExponentialBuilder].
+    pub fn with_factor(mut self, factor: f32) -> Self {
+        self.factor = Some(factor);
+        self
+    }
+
+    /// Override the maximum number of retries.
+    pub fn with_max_times(mut self, max_times: usize) -> Self {
+        self.max_times = Some(max_times);
+        self
+    }
+
+    /// Override whether jitter is enabled, so it can also be disabled.
+    pub fn with_jitter(mut self, jitter: bool) -> Self {
+        self.jitter = Some(if jitter {
+            Jitter::base()
+        } else {
+            Jitter::none()
+        });
+        self
+    }
+
+    /// Override the [`Jitter`] added to every delay.
+    pub fn with_jitter_policy(mut self, jitter: Jitter) -> Self {
+        self.jitter = Some(jitter);
+        self
+    }
+
+    /// Returns the overrides of `self`, overridden by the ones set in `other`.
+    pub fn merge(self, other: BackoffOverrides) -> Self {
+        BackoffOverrides {
+            delay: other.delay.or(self.delay),
+            min_delay: other.min_delay.or(self.min_delay),
+            max_delay: other.max_delay.or(self.max_delay),
+            factor: other.factor.or(self.factor),
+            max_times: other.max_times.or(self.max_times),
+            jitter: other.jitter.or(self.jitter),
+        }
+    }
+}
+
+impl BackoffPolicy {
+    /// Returns the policy with the parameters set in `overrides`, see [`BackoffOverrides`].
+    pub fn merge(self, overrides: BackoffOverrides) -> Self {
+        match self {
+            BackoffPolicy::Constant(builder) => BackoffPolicy::Constant(builder.merge(overrides)),
+            BackoffPolicy::Exponential(builder) => {
+                BackoffPolicy::Exponential(builder.merge(overrides))
+            }
+            BackoffPolicy::Fibonacci(builder) => BackoffPolicy::Fibonacci(builder.merge(overrides)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+    use crate::BackoffBuilder;
+    use crate::ConstantBuilder;
+    use crate::FibonacciBuilder;
+
+    #[test]
+    fn test_merge() {
+        let base = BackoffOverrides::new()
+            .with_delay(Duration::from_secs(2))
+            .with_max_times(5);
+        let overrides = base.merge(BackoffOverrides::new().with_max_times(2));
+        assert_eq!(
+            overrides,
+            BackoffOverrides::new()
+                .with_delay(Duration::from_secs(2))
+                .with_max_times(2)
+        );
+
+        let delays: Vec<_> = ConstantBuilder::default()
+            .with_jitter()
+            .merge(overrides.with_jitter(false))
+            .build()
+            .collect();
+        assert_eq!(delays, [Duration::from_secs(2); 2]);
+
+        // Unused parameters are ignored.
+        let policy = BackoffPolicy::from(FibonacciBuilder::default()).merge(overrides);
+        assert_eq!(policy, FibonacciBuilder::default().with_max_times(2).into());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_deserialize_overrides() {
+        let overrides: BackoffOverrides =
+            serde_json::from_str(r#"{ "min_delay": "250ms", "jitter": true }"#).unwrap();
+        assert_eq!(
+            overrides,
+            BackoffOverrides::new()
+                .with_min_delay(Duration::from_millis(250))
+                .with_jitter(true)
+        );
+        assert!(serde_json::from_str::<BackoffOverrides>(r#"{ "retries": 3 }"#).is_err());
+    }
+}
@@ -0,0 +1,407 @@
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::string::ToString;
+use core::fmt;
+use core::str::FromStr;
+#[cfg(feature = "test-util")]
+use core::time::Duration;
+
+use crate::backoff::BackoffBuilder;
+use crate::duration;
+use crate::Backoff;
+use crate::BuildError;
+use crate::ConstantBuilder;
+use crate::ExponentialBuilder;
+use crate::FibonacciBuilder;
+use crate::Jitter;
+use crate::Preset;
+
+/// BackoffPolicy is one of the backoff builders of this crate, chosen at runtime.
+///
+/// It's parsed from a compact description, so policies can be tweaked through flags or
+/// environment variables without a redeploy:
+///
+/// - `constant 500ms max=5`: a constant delay of 500ms, retried 5 times.
+/// - `exponential 100ms..10s x2 jitter max_times=8`: an exponential delay from 100ms up to
+///   10s, doubled after every attempt, with jitter, retried 8 times.
+/// - `fibonacci 1s..1m max=4`: a Fibonacci delay from 1s up to 1m, retried 4 times.
+///
+/// The name of the backoff can also be the name of a [`Preset`], like `standard max_times=3`,
+/// starting from its exponential backoff.
+///
+/// After the name of the backoff, the description is any of:
+///
+/// - a delay like `500ms`, or a range of delays like `100ms..10s` for `exponential` and
+///   `fibonacci`, setting the minimum and maximum delays.
+/// - `xN`, setting the factor of `exponential`. The others have no factor, their number of
+///   retries is set by `max_times=` or `max=`.
+/// - `jitter`, enabling jitter.
+/// - `key=value` with the key `delay`, `min_delay`, `max_delay`, `factor`, `max_times` (or
+///   `max` for short) or `jitter`, the [`Jitter`] being `true`, `false`, a ratio like `0.2` or a range like
+///   `10ms..50ms`.
+///
+/// Anything not described keeps the default of the builder.
+///
+/// # Examples
+///
+/// ```
+/// use core::time::Duration;
+///
+/// use backon::BackoffBuilder;
+/// use backon::BackoffPolicy;
+///
+/// let policy: BackoffPolicy = "exponential 100ms..10s x2 jitter max_times=8".parse()?;
+/// assert_eq!(policy.build().count(), 8);
+///
+/// let policy: BackoffPolicy = "constant 500ms max=5".parse()?;
+/// assert!(policy.build().all(|dur| dur == Duration::from_millis(500)));
+/// # Ok::<(), backon::ParseBackoffError>(())
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub enum BackoffPolicy {
+    /// A backoff built by [`ConstantBuilder`].
+    Constant(ConstantBuilder),
+    /// A backoff built by [`ExponentialBuilder`].
+    Exponential(ExponentialBuilder),
+    /// A backoff built by [`FibonacciBuilder`].
+    Fibonacci(FibonacciBuilder),
+}
+
+impl From<ConstantBuilder> for BackoffPolicy {
+    fn from(builder: ConstantBuilder) -> Self {
+        BackoffPolicy::Constant(builder)
+    }
+}
+
+impl From<ExponentialBuilder> for BackoffPolicy {
+    fn from(builder: ExponentialBuilder) -> Self {
+        BackoffPolicy::Exponential(builder)
+    }
+}
+
+impl From<FibonacciBuilder> for BackoffPolicy {
+    fn from(builder: FibonacciBuilder) -> Self {
+        BackoffPolicy::Fibonacci(builder)
+    }
+}
+
+impl fmt::Display for BackoffPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BackoffPolicy::Constant(builder) => builder.fmt(f),
+            BackoffPolicy::Exponential(builder) => builder.fmt(f),
+            BackoffPolicy::Fibonacci(builder) => builder.fmt(f),
+        }
+    }
+}
+
+impl BackoffBuilder for BackoffPolicy {
+    type Backoff = Box<dyn Backoff>;
+
+    fn build(self) -> Self::Backoff {
+        match self {
+            BackoffPolicy::Constant(builder) => Box::new(builder.build()),
+            BackoffPolicy::Exponential(builder) => Box::new(builder.build()),
+            BackoffPolicy::Fibonacci(builder) => Box::new(builder.build()),
+        }
+    }
+}
+
+impl BackoffBuilder for &BackoffPolicy {
+    type Backoff = Box<dyn Backoff>;
+
+    fn build(self) -> Self::Backoff {
+        (*self).build()
+    }
+}
+
+impl FromStr for BackoffPolicy {
+    type Err = ParseBackoffError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut tokens = s.split_whitespace();
+        let name = tokens
+            .next()
+            .ok_or_else(|| ParseBackoffError::new(s, "empty backoff policy"))?;
+        let mut policy = match name {
+            "constant" => BackoffPolicy::Constant(ConstantBuilder::default()),
+            "exponential" => BackoffPolicy::Exponential(ExponentialBuilder::default()),
+            "fibonacci" => BackoffPolicy::Fibonacci(FibonacciBuilder::default()),
+            _ => match Preset::from_name(name) {
+                Some(preset) => BackoffPolicy::Exponential(preset.builder()),
+                None => {
+                    return Err(ParseBackoffError::new(
+                        name,
+                        "expected `constant`, `exponential`, `fibonacci` or a preset",
+                    ))
+                }
+            },
+        };
+        for token in tokens {
+            policy = policy.apply(token)?;
+        }
+        Ok(policy)
+    }
+}
+
+impl BackoffPolicy {
+    /// Build the backoff like [`BackoffBuilder::build`], unless the parameters make no sense,
+    /// see [`ExponentialBuilder::try_build`].
+    pub fn try_build(self) -> Result<Box<dyn Backoff>, BuildError> {
+        Ok(match self {
+            BackoffPolicy::Constant(builder) => Box::new(builder.try_build()?),
+            BackoffPolicy::Exponential(builder) => Box::new(builder.try_build()?),
+            BackoffPolicy::Fibonacci(builder) => Box::new(builder.try_build()?),
+        })
+    }
+
+    /// Returns the backoff without jitter, so its delays are the ones it plans.
+    pub fn planned(&self) -> Self {
+        match *self {
+            BackoffPolicy::Constant(b) => {
+                BackoffPolicy::Constant(b.with_jitter_policy(Jitter::none()))
+            }
+            BackoffPolicy::Exponential(b) => {
+                BackoffPolicy::Exponential(b.with_jitter_policy(Jitter::none()))
+            }
+            BackoffPolicy::Fibonacci(b) => {
+                BackoffPolicy::Fibonacci(b.with_jitter_policy(Jitter::none()))
+            }
+        }
+    }
+
+    /// Returns the least and the most delay of the backoff without jitter.
+    #[cfg(feature = "test-util")]
+    pub(crate) fn delay_range(&self) -> (Duration, Option<Duration>) {
+        match self {
+            BackoffPolicy::Constant(builder) => builder.delay_range(),
+            BackoffPolicy::Exponential(builder) => builder.delay_range(),
+            BackoffPolicy::Fibonacci(builder) => builder.delay_range(),
+        }
+    }
+
+    /// Apply a token of the description after the name of the backoff.
+    fn apply(self, token: &str) -> Result<Self, ParseBackoffError> {
+        let option = |key: &str, value: &str| self.set(token, key, value);
+        if token == "jitter" {
+            return option("jitter", "true");
+        }
+        if let Some((key, value)) = token.split_once('=') {
+            return option(if key == "max" { "max_times" } else { key }, value);
+        }
+        if let Some(n) = token.strip_prefix('x') {
+            return match self {
+                BackoffPolicy::Exponential(_) => option("factor", n),
+                _ => Err(ParseBackoffError::new(
+                    token,
+                    "only `exponential` has a factor, use `max_times=` or `max=` to set the retries",
+                )),
+            };
+        }
+        if let Some((min, max)) = token.split_once("..") {
+            if let BackoffPolicy::Constant(_) = self {
+                return Err(ParseBackoffError::new(
+                    token,
+                    "`constant` has a single delay",
+                ));
+            }
+            return option("min_delay", min)?.set(token, "max_delay", max);
+        }
+        match self {
+            BackoffPolicy::Constant(_) => option("delay", token),
+            _ => option("min_delay", token),
+        }
+    }
+
+    /// Set the parameter `key` of the backoff to `value`, reporting errors at `token`.
+    pub(crate) fn set(
+        self,
+        token: &str,
+        key: &str,
+        value: &str,
+    ) -> Result<Self, ParseBackoffError> {
+        let delay = || {
+            duration::parse(value)
+                .map_err(|_| ParseBackoffError::new(token, "invalid delay, expected like `500ms`"))
+        };
+        let number = || {
+            value
+                .parse::<usize>()
+                .map_err(|_| ParseBackoffError::new(token, "invalid number of retries"))
+        };
+        if key == "jitter" {
+            let jitter = Jitter::parse(value).ok_or_else(|| {
+                ParseBackoffError::new(token, "invalid jitter, expected a bool, a ratio or a range")
+            })?;
+            return Ok(match self {
+                BackoffPolicy::Constant(b) => BackoffPolicy::Constant(b.with_jitter_policy(jitter)),
+                BackoffPolicy::Exponential(b) => {
+                    BackoffPolicy::Exponential(b.with_jitter_policy(jitter))
+                }
+                BackoffPolicy::Fibonacci(b) => {
+                    BackoffPolicy::Fibonacci(b.with_jitter_policy(jitter))
+                }
+            });
+        }
+        Ok(match (self, key) {
+            (BackoffPolicy::Constant(b), "delay") => {
+                BackoffPolicy::Constant(b.with_delay(delay()?))
+            }
+            (BackoffPolicy::Constant(b), "max_times") => {
+                BackoffPolicy::Constant(b.with_max_times(number()?))
+            }
+            (BackoffPolicy::Exponential(b), "min_delay") => {
+                BackoffPolicy::Exponential(b.with_min_delay(delay()?))
+            }
+            (BackoffPolicy::Exponential(b), "max_delay") => {
+                BackoffPolicy::Exponential(b.with_max_delay(delay()?))
+            }
+            (BackoffPolicy::Exponential(b), "max_times") => {
+                BackoffPolicy::Exponential(b.with_max_times(number()?))
+            }
+            (BackoffPolicy::Exponential(b), "factor") => {
+                let factor = value
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|factor| *factor >= 1.0)
+                    .ok_or_else(|| {
+                        ParseBackoffError::new(token, "invalid factor, expected >= 1")
+                    })?;
+                BackoffPolicy::Exponential(b.with_factor(factor))
+            }
+            (BackoffPolicy::Fibonacci(b), "min_delay") => {
+                BackoffPolicy::Fibonacci(b.with_min_delay(delay()?))
+            }
+            (BackoffPolicy::Fibonacci(b), "max_delay") => {
+                BackoffPolicy::Fibonacci(b.with_max_delay(delay()?))
+            }
+            (BackoffPolicy::Fibonacci(b), "max_times") => {
+                BackoffPolicy::Fibonacci(b.with_max_times(number()?))
+            }
+            _ => {
+                return Err(ParseBackoffError::new(
+                    token,
+                    "unknown option for this backoff",
+                ))
+            }
+        })
+    }
+}
+
+/// The error returned when parsing an invalid [`BackoffPolicy`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseBackoffError {
+    token: String,
+    reason: &'static str,
+}
+
+impl ParseBackoffError {
+    fn new(token: &str, reason: &'static str) -> Self {
+        ParseBackoffError {
+            token: token.to_string(),
+            reason,
+        }
+    }
+
+    /// Returns why the description is invalid.
+    #[cfg(feature = "std")]
+    pub(crate) fn reason(&self) -> &'static str {
+        self.reason
+    }
+}
+
+impl fmt::Display for ParseBackoffError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid backoff policy at `{}`: {}",
+            self.token, self.reason
+        )
+    }
+}
+
+impl core::error::Error for ParseBackoffError {}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+    use alloc::vec::Vec;
+    use core::time::Duration;
+
+    use super::*;
+
+    fn delays(policy: &str) -> Vec<Duration> {
+        policy.parse::<BackoffPolicy>().unwrap().build().collect()
+    }
+
+    #[test]
+    fn test_parse_policy() {
+        assert_eq!(
+            delays("constant 500ms max=2"),
+            [Duration::from_millis(500); 2]
+        );
+        assert_eq!(
+            delays("exponential 100ms..300ms x3 max_times=4"),
+            [100, 300, 300, 300].map(Duration::from_millis)
+        );
+        assert_eq!(
+            delays("fibonacci 1s..3s max=5"),
+            [1, 1, 2, 3, 3].map(Duration::from_secs)
+        );
+        assert_eq!(delays("exponential max_delay=1s").len(), 3);
+        assert_eq!(delays("conservative max_times=2").len(), 2);
+    }
+
+    #[test]
+    fn test_display_policy() {
+        for policy in [
+            "constant 500ms max_times=2 jitter",
+            "exponential 100ms..10s x1.5 max_times=8",
+            "fibonacci 1s..1m max_times=4",
+        ] {
+            let parsed: BackoffPolicy = policy.parse().unwrap();
+            assert_eq!(parsed.to_string(), policy);
+            assert_eq!(parsed.to_string().parse::<BackoffPolicy>(), Ok(parsed));
+        }
+        assert_eq!(
+            ExponentialBuilder::standard().to_string(),
+            "exponential 100ms..10s x2 max_times=5 jitter"
+        );
+    }
+
+    #[test]
+    fn test_parse_policy_errors() {
+        let err = |policy: &str| policy.parse::<BackoffPolicy>().unwrap_err().to_string();
+
+        assert_eq!(
+            err(""),
+            "invalid backoff policy at ``: empty backoff policy"
+        );
+        assert_eq!(
+            err("linear 1s"),
+            "invalid backoff policy at `linear`: expected `constant`, `exponential`, `fibonacci` or a preset"
+        );
+        assert_eq!(
+            err("constant 1s..2s"),
+            "invalid backoff policy at `1s..2s`: `constant` has a single delay"
+        );
+        assert_eq!(
+            err("exponential x0.5"),
+            "invalid backoff policy at `x0.5`: invalid factor, expected >= 1"
+        );
+        assert_eq!(
+            err("constant 1s x3"),
+            "invalid backoff policy at `x3`: only `exponential` has a factor, use `max_times=` or \
+             `max=` to set the retries"
+        );
+        assert_eq!(
+            err("fibonacci factor=2"),
+            "invalid backoff policy at `factor=2`: unknown option for this backoff"
+        );
+        assert_eq!(
+            err("constant soon"),
+            "invalid backoff policy at `soon`: invalid delay, expected like `500ms`"
+        );
+    }
+}
@@ -0,0 +1,165 @@
+use core::time::Duration;
+
+use crate::backoff::BackoffBuilder;
+
+/// PrecomputedBuilder is used to construct a [`PrecomputedBackoff`] offering a schedule of `N`
+/// delays computed at compile time.
+///
+/// Its constructors are `const`, so the schedule is computed into a fixed array when the
+/// builder is defined as a constant: the backoff then only reads the next delay, without the
+/// float math of [`ExponentialBuilder`](crate::ExponentialBuilder) nor the branches of its
+/// jitter, for hot loops and targets without an FPU. As floats can't be used in `const`, its
+/// factor is an integer.
+///
+/// # Examples
+///
+/// ```
+/// use core::time::Duration;
+///
+/// use backon::BackoffBuilder;
+/// use backon::PrecomputedBuilder;
+///
+/// const SCHEDULE: PrecomputedBuilder<4> =
+///     PrecomputedBuilder::exponential(Duration::from_millis(100), 2, Duration::from_millis(500));
+///
+/// let delays: Vec<_> = SCHEDULE.build().collect();
+/// assert_eq!(delays, [100, 200, 400, 500].map(Duration::from_millis));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PrecomputedBuilder<const N: usize> {
+    delays: [Duration; N],
+}
+
+impl<const N: usize> PrecomputedBuilder<N> {
+    /// Create a builder offering `delays` in order, after which the backoff is exhausted.
+    pub const fn new(delays: [Duration; N]) -> Self {
+        PrecomputedBuilder { delays }
+    }
+
+    /// Create a builder waiting `delay` `N` times.
+    pub const fn constant(delay: Duration) -> Self {
+        Self::new([delay; N])
+    }
+
+    /// Create a builder waiting `min_delay` first, then `factor` times the delay before it,
+    /// up to `max_delay`, `N` times.
+    pub const fn exponential(min_delay: Duration, factor: u32, max_delay: Duration) -> Self {
+        let mut delays = [Duration::ZERO; N];
+        let mut delay = min(min_delay, max_delay);
+        let mut i = 0;
+        while i < N {
+            delays[i] = delay;
+            delay = min(delay.saturating_mul(factor), max_delay);
+            i += 1;
+        }
+        Self::new(delays)
+    }
+
+    /// Create a builder waiting `min_delay` twice first, then the sum of the two delays before
+    /// it, up to `max_delay`, `N` times.
+    pub const fn fibonacci(min_delay: Duration, max_delay: Duration) -> Self {
+        let mut delays = [Duration::ZERO; N];
+        let (mut prev, mut next) = (Duration::ZERO, min(min_delay, max_delay));
+        let mut i = 0;
+        while i < N {
+            delays[i] = next;
+            (prev, next) = (next, min(prev.saturating_add(next), max_delay));
+            i += 1;
+        }
+        Self::new(delays)
+    }
+
+    /// Returns the delays of the schedule.
+    pub const fn delays(&self) -> &[Duration; N] {
+        &self.delays
+    }
+}
+
+/// Returns the lower of `a` and `b`, as [`Ord::min`] isn't `const`.
+const fn min(a: Duration, b: Duration) -> Duration {
+    if a.as_nanos() < b.as_nanos() {
+        a
+    } else {
+        b
+    }
+}
+
+impl<const N: usize> BackoffBuilder for PrecomputedBuilder<N> {
+    type Backoff = PrecomputedBackoff<N>;
+
+    fn build(self) -> Self::Backoff {
+        PrecomputedBackoff {
+            delays: self.delays,
+            index: 0,
+        }
+    }
+}
+
+impl<const N: usize> BackoffBuilder for &PrecomputedBuilder<N> {
+    type Backoff = PrecomputedBackoff<N>;
+
+    fn build(self) -> Self::Backoff {
+        (*self).build()
+    }
+}
+
+/// PrecomputedBackoff provides the delays of a schedule computed at compile time.
+///
+/// This backoff strategy is constructed by [`PrecomputedBuilder`].
+#[doc(hidden)]
+#[derive(Debug, Clone, Copy)]
+pub struct PrecomputedBackoff<const N: usize> {
+    delays: [Duration; N],
+    index: usize,
+}
+
+impl<const N: usize> Iterator for PrecomputedBackoff<N> {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let delay = self.delays.get(self.index).copied();
+        self.index += usize::from(delay.is_some());
+        delay
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = N - self.index;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<const N: usize> ExactSizeIterator for PrecomputedBackoff<N> {}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+    use crate::FibonacciBuilder;
+
+    const EXPONENTIAL: PrecomputedBuilder<5> =
+        PrecomputedBuilder::exponential(Duration::from_secs(1), 3, Duration::from_secs(20));
+
+    #[test]
+    fn test_precomputed() {
+        let delays: Vec<_> = EXPONENTIAL.build().map(|d| d.as_secs()).collect();
+        assert_eq!(delays, [1, 3, 9, 20, 20]);
+        let mut backoff = PrecomputedBuilder::new([Duration::from_millis(3)]).build();
+        assert_eq!(backoff.len(), 1);
+        assert_eq!(backoff.next(), Some(Duration::from_millis(3)));
+        assert_eq!(backoff.next(), None);
+        assert_eq!(backoff.len(), 0);
+
+        // The schedule is the one of the backoff computing it at runtime.
+        let fibonacci = PrecomputedBuilder::<6>::fibonacci(Duration::ZERO, Duration::from_secs(5));
+        assert_eq!(fibonacci.delays(), &[Duration::ZERO; 6]);
+        let fibonacci =
+            PrecomputedBuilder::<8>::fibonacci(Duration::from_secs(1), Duration::from_secs(10));
+        let expected: Vec<_> = FibonacciBuilder::default()
+            .with_max_delay(Duration::from_secs(10))
+            .with_max_times(8)
+            .build()
+            .collect();
+        assert_eq!(fibonacci.build().collect::<Vec<_>>(), expected);
+    }
+}
@@ -0,0 +1,113 @@
+use core::fmt;
+
+use crate::backoff::BackoffBuilder;
+use crate::ExponentialBackoff;
+use crate::ExponentialBuilder;
+
+/// Preset is one of the vetted backoff policies of this crate, referenced by name.
+///
+/// Each preset is an [`ExponentialBuilder`] with jitter, see its documentation for the
+/// schedule:
+///
+/// - `aggressive`: [`ExponentialBuilder::aggressive`]
+/// - `standard`: [`ExponentialBuilder::standard`]
+/// - `conservative`: [`ExponentialBuilder::conservative`]
+///
+/// With the `serde` feature, it's loaded from configuration files by name.
+///
+/// # Examples
+///
+/// ```
+/// use backon::BlockingRetryable;
+/// use backon::Preset;
+///
+/// let preset = Preset::from_name("aggressive").expect("preset must exist");
+/// let result = (|| Ok::<_, &str>(42)).retry(preset).call();
+///
+/// assert_eq!(result, Ok(42));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Preset {
+    /// See [`ExponentialBuilder::aggressive`].
+    Aggressive,
+    /// See [`ExponentialBuilder::standard`].
+    Standard,
+    /// See [`ExponentialBuilder::conservative`].
+    Conservative,
+}
+
+impl Preset {
+    /// Returns the preset named `name`, like `standard`, if there is one.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "aggressive" => Some(Preset::Aggressive),
+            "standard" => Some(Preset::Standard),
+            "conservative" => Some(Preset::Conservative),
+            _ => None,
+        }
+    }
+
+    /// Returns the name of the preset.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Preset::Aggressive => "aggressive",
+            Preset::Standard => "standard",
+            Preset::Conservative => "conservative",
+        }
+    }
+
+    /// Returns the builder of the preset, to be tweaked further.
+    pub fn builder(&self) -> ExponentialBuilder {
+        match self {
+            Preset::Aggressive => ExponentialBuilder::aggressive(),
+            Preset::Standard => ExponentialBuilder::standard(),
+            Preset::Conservative => ExponentialBuilder::conservative(),
+        }
+    }
+}
+
+impl fmt::Display for Preset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl BackoffBuilder for Preset {
+    type Backoff = ExponentialBackoff;
+
+    fn build(self) -> Self::Backoff {
+        self.builder().build()
+    }
+}
+
+impl BackoffBuilder for &Preset {
+    type Backoff = ExponentialBackoff;
+
+    fn build(self) -> Self::Backoff {
+        (*self).build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+    use core::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_presets() {
+        for preset in [Preset::Aggressive, Preset::Standard, Preset::Conservative] {
+            assert_eq!(Preset::from_name(preset.name()), Some(preset));
+            assert!(preset.builder().try_build().is_ok());
+        }
+        assert_eq!(Preset::from_name("reckless"), None);
+
+        let delays: Vec<_> = Preset::Conservative.build().collect();
+        assert_eq!(delays.len(), 8);
+        assert!(delays[0] >= Duration::from_secs(1) && delays[0] < Duration::from_secs(2));
+        assert!(delays[7] >= Duration::from_secs(60));
+    }
+}
@@ -0,0 +1,94 @@
+use alloc::vec::Vec;
+use core::fmt;
+use core::time::Duration;
+
+use crate::duration::Humane;
+use crate::BackoffPolicy;
+
+/// SchedulePreview is the first delays of a backoff without jitter, returned by `preview`
+/// like [`ExponentialBuilder::preview`][crate::ExponentialBuilder::preview].
+///
+/// It's displayed like `1s, 2s, 4s (7s in total)`, so docs, CLIs and code reviews can show
+/// what a policy does instead of working it out from its parameters.
+///
+/// # Examples
+///
+/// ```
+/// use core::time::Duration;
+///
+/// use backon::ExponentialBuilder;
+///
+/// let preview = ExponentialBuilder::default()
+///     .with_min_delay(Duration::from_millis(500))
+///     .with_max_times(5)
+///     .preview(5);
+///
+/// assert_eq!(preview.to_string(), "500ms, 1s, 2s, 4s, 8s (15500ms in total)");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SchedulePreview {
+    delays: Vec<Duration>,
+}
+
+impl SchedulePreview {
+    pub(crate) fn new(delays: impl Iterator<Item = Duration>, n: usize) -> Self {
+        SchedulePreview {
+            delays: delays.take(n).collect(),
+        }
+    }
+
+    /// Returns the delays, fewer than asked if the backoff is exhausted before.
+    pub fn delays(&self) -> &[Duration] {
+        &self.delays
+    }
+
+    /// Returns the sum of the delays.
+    pub fn total(&self) -> Duration {
+        self.delays.iter().sum()
+    }
+}
+
+impl fmt::Display for SchedulePreview {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.delays.is_empty() {
+            return f.write_str("no retries");
+        }
+        for (i, delay) in self.delays.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{}", Humane(*delay))?;
+        }
+        write!(f, " ({} in total)", Humane(self.total()))
+    }
+}
+
+impl BackoffPolicy {
+    /// Returns the first `n` delays of the backoff without jitter, see [`SchedulePreview`].
+    pub fn preview(&self, n: usize) -> SchedulePreview {
+        match self {
+            BackoffPolicy::Constant(builder) => builder.preview(n),
+            BackoffPolicy::Exponential(builder) => builder.preview(n),
+            BackoffPolicy::Fibonacci(builder) => builder.preview(n),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+
+    use super::*;
+    use crate::ConstantBuilder;
+
+    #[test]
+    fn test_preview() {
+        let policy: BackoffPolicy = "fibonacci 1s..3s max=5 jitter".parse().unwrap();
+        let preview = policy.preview(10);
+        assert_eq!(preview.delays(), [1, 1, 2, 3, 3].map(Duration::from_secs));
+        assert_eq!(preview.to_string(), "1s, 1s, 2s, 3s, 3s (10s in total)");
+
+        let preview = ConstantBuilder::default().preview(0);
+        assert_eq!(preview.to_string(), "no retries");
+    }
+}
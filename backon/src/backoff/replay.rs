@@ -0,0 +1,107 @@
+use alloc::vec::Vec;
+use core::time::Duration;
+
+use crate::backoff::BackoffBuilder;
+
+/// ReplayBuilder is used to construct a [`ReplayBackoff`] that offers a recorded sequence of
+/// delays, like the jittered delays of a retry seen in production.
+///
+/// The delays planned by the backoff of a retry, jitter included, are recorded by
+/// [`RetryReport::planned_delays`](crate::RetryReport::planned_delays). Replaying them in a
+/// test reproduces the identical schedule.
+///
+/// # Examples
+///
+/// ```
+/// use core::time::Duration;
+///
+/// use backon::BlockingRetryable;
+/// use backon::ExponentialBuilder;
+/// use backon::ReplayBuilder;
+///
+/// let f = || Err::<(), _>("unavailable");
+/// let (_, report) = f
+///     .retry(
+///         ExponentialBuilder::default()
+///             .with_min_delay(Duration::from_millis(1))
+///             .with_jitter(),
+///     )
+///     .call_with_report();
+///
+/// let (_, replayed) = f
+///     .retry(ReplayBuilder::new(report.planned_delays().iter().copied()))
+///     .call_with_report();
+///
+/// assert_eq!(replayed.delays(), report.delays());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default, deny_unknown_fields))]
+pub struct ReplayBuilder {
+    #[cfg_attr(feature = "serde", serde(with = "crate::duration::serde::vec"))]
+    delays: Vec<Duration>,
+}
+
+impl ReplayBuilder {
+    /// Create a builder replaying `delays` in order, after which the backoff is exhausted.
+    pub fn new(delays: impl IntoIterator<Item = Duration>) -> Self {
+        ReplayBuilder {
+            delays: delays.into_iter().collect(),
+        }
+    }
+}
+
+impl BackoffBuilder for ReplayBuilder {
+    type Backoff = ReplayBackoff;
+
+    fn build(self) -> Self::Backoff {
+        ReplayBackoff {
+            delays: self.delays.into_iter(),
+        }
+    }
+}
+
+impl BackoffBuilder for &ReplayBuilder {
+    type Backoff = ReplayBackoff;
+
+    fn build(self) -> Self::Backoff {
+        self.clone().build()
+    }
+}
+
+/// ReplayBackoff provides the delays of a recorded sequence.
+///
+/// This backoff strategy is constructed by [`ReplayBuilder`].
+#[doc(hidden)]
+#[derive(Clone, Debug)]
+pub struct ReplayBackoff {
+    delays: alloc::vec::IntoIter<Duration>,
+}
+
+impl Iterator for ReplayBackoff {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.delays.next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+    use core::time::Duration;
+
+    use crate::BackoffBuilder;
+    use crate::ReplayBuilder;
+
+    #[test]
+    fn test_replay() {
+        let builder = ReplayBuilder::new(vec![Duration::from_millis(3), Duration::from_millis(1)]);
+        let mut it = (&builder).build();
+
+        assert_eq!(Some(Duration::from_millis(3)), it.next());
+        assert_eq!(Some(Duration::from_millis(1)), it.next());
+        assert_eq!(None, it.next());
+        assert_eq!(builder.build().count(), 2);
+    }
+}
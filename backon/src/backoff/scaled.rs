@@ -0,0 +1,96 @@
+use alloc::format;
+use core::time::Duration;
+
+use crate::backoff::BackoffBuilder;
+
+/// ScaledBuilder is used to construct a [`ScaledBackoff`], multiplying every delay of another
+/// backoff by a factor.
+///
+/// It's created by [`BackoffBuilder::scaled_by`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScaledBuilder<B> {
+    builder: B,
+    factor: f64,
+}
+
+impl<B> ScaledBuilder<B> {
+    pub(crate) fn new(builder: B, factor: f32) -> Self {
+        debug_assert!(factor >= 0.0, "invalid scale that lower than 0");
+
+        ScaledBuilder {
+            builder,
+            // Widen the factor as written, so `0.1` scales `1s` to exactly `100ms`.
+            factor: format!("{factor}").parse().unwrap_or(factor.into()),
+        }
+    }
+}
+
+impl<B: BackoffBuilder> BackoffBuilder for ScaledBuilder<B> {
+    type Backoff = ScaledBackoff<B::Backoff>;
+
+    fn build(self) -> Self::Backoff {
+        ScaledBackoff {
+            backoff: self.builder.build(),
+            factor: self.factor,
+        }
+    }
+}
+
+impl<'a, B: Sync> BackoffBuilder for &'a ScaledBuilder<B>
+where
+    &'a B: BackoffBuilder,
+{
+    type Backoff = ScaledBackoff<<&'a B as BackoffBuilder>::Backoff>;
+
+    fn build(self) -> Self::Backoff {
+        ScaledBackoff {
+            backoff: (&self.builder).build(),
+            factor: self.factor,
+        }
+    }
+}
+
+/// ScaledBackoff provides the delays of another backoff, multiplied by a factor.
+///
+/// This backoff strategy is constructed by [`ScaledBuilder`].
+#[doc(hidden)]
+#[derive(Clone, Debug)]
+pub struct ScaledBackoff<B> {
+    backoff: B,
+    factor: f64,
+}
+
+impl<B: Iterator<Item = Duration>> Iterator for ScaledBackoff<B> {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let dur = self.backoff.next()?;
+        // Saturate instead of panicking on overflow, like the other backoffs do.
+        Some(Duration::try_from_secs_f64(dur.as_secs_f64() * self.factor).unwrap_or(Duration::MAX))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+    use core::time::Duration;
+
+    use crate::BackoffBuilder;
+    use crate::ConstantBuilder;
+    use crate::ExponentialBuilder;
+
+    #[test]
+    fn test_scaled_by() {
+        let builder = ExponentialBuilder::default().scaled_by(0.5);
+        let delays: Vec<_> = (&builder).build().collect();
+        assert_eq!(delays, [500, 1000, 2000].map(Duration::from_millis));
+        assert_eq!(builder.build().count(), 3);
+
+        let delays: Vec<_> = ConstantBuilder::default()
+            .with_delay(Duration::MAX)
+            .scaled_by(10.0)
+            .build()
+            .collect();
+        assert_eq!(delays, [Duration::MAX; 3]);
+    }
+}
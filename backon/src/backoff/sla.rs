@@ -0,0 +1,139 @@
+use core::time::Duration;
+
+use crate::backoff::BackoffBuilder;
+use crate::ExponentialBuilder;
+
+/// SlaBuilder is used to construct a [`SlaBackoff`], keeping the retries of another backoff
+/// within a latency budget, like the p99 target of a request.
+///
+/// Every attempt is assumed to take about `attempt_latency`, so a retry is only made if it
+/// can finish within the budget, and the delays before it are shortened to fit in what's left
+/// of it. Static schedules don't know how much of the budget the attempts already used,
+/// and routinely exceed the deadline of the request.
+///
+/// It retries like [`ExponentialBuilder::standard`] unless set by `with_backoff`.
+///
+/// # Examples
+///
+/// ```
+/// use core::time::Duration;
+///
+/// use backon::BackoffBuilder;
+/// use backon::ConstantBuilder;
+/// use backon::SlaBuilder;
+///
+/// let builder = SlaBuilder::new(Duration::from_millis(800), Duration::from_millis(100))
+///     .with_backoff(ConstantBuilder::default().with_delay(Duration::from_millis(200)));
+///
+/// // 4 attempts of 100ms, sleeping 200ms, 200ms and then what's left between them.
+/// let delays: Vec<_> = builder.build().collect();
+/// assert_eq!(delays, [200, 200, 0].map(Duration::from_millis));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SlaBuilder<B = ExponentialBuilder> {
+    builder: B,
+    budget: Duration,
+    attempt_latency: Duration,
+}
+
+impl SlaBuilder {
+    /// Create a builder keeping the attempts and the delays between them within `budget`,
+    /// every attempt taking about `attempt_latency`.
+    pub const fn new(budget: Duration, attempt_latency: Duration) -> Self {
+        SlaBuilder {
+            builder: ExponentialBuilder::standard(),
+            budget,
+            attempt_latency,
+        }
+    }
+}
+
+impl<B> SlaBuilder<B> {
+    /// Set the backoff whose delays are kept within the budget.
+    pub fn with_backoff<B2: BackoffBuilder>(self, builder: B2) -> SlaBuilder<B2> {
+        SlaBuilder {
+            builder,
+            budget: self.budget,
+            attempt_latency: self.attempt_latency,
+        }
+    }
+}
+
+impl<B: BackoffBuilder> BackoffBuilder for SlaBuilder<B> {
+    type Backoff = SlaBackoff<B::Backoff>;
+
+    fn build(self) -> Self::Backoff {
+        SlaBackoff::new(self.builder.build(), self.budget, self.attempt_latency)
+    }
+}
+
+impl<'a, B: Sync> BackoffBuilder for &'a SlaBuilder<B>
+where
+    &'a B: BackoffBuilder,
+{
+    type Backoff = SlaBackoff<<&'a B as BackoffBuilder>::Backoff>;
+
+    fn build(self) -> Self::Backoff {
+        SlaBackoff::new((&self.builder).build(), self.budget, self.attempt_latency)
+    }
+}
+
+/// SlaBackoff provides the delays of another backoff, as long as the retries fit in a
+/// latency budget.
+///
+/// This backoff strategy is constructed by [`SlaBuilder`].
+#[doc(hidden)]
+#[derive(Clone, Debug)]
+pub struct SlaBackoff<B> {
+    backoff: B,
+    remaining: Duration,
+    attempt_latency: Duration,
+}
+
+impl<B> SlaBackoff<B> {
+    fn new(backoff: B, budget: Duration, attempt_latency: Duration) -> Self {
+        SlaBackoff {
+            backoff,
+            // The first attempt runs before any delay.
+            remaining: budget.saturating_sub(attempt_latency),
+            attempt_latency,
+        }
+    }
+}
+
+impl<B: Iterator<Item = Duration>> Iterator for SlaBackoff<B> {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let delay = self.backoff.next()?;
+        // The next attempt must finish within the budget after the delay.
+        let left = self.remaining.checked_sub(self.attempt_latency)?;
+        let delay = delay.min(left);
+        self.remaining = left - delay;
+        Some(delay)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+    use crate::FibonacciBuilder;
+
+    #[test]
+    fn test_sla_builder() {
+        let fibonacci = FibonacciBuilder::default()
+            .with_min_delay(Duration::from_millis(100))
+            .with_max_times(10);
+        let builder = SlaBuilder::new(Duration::from_millis(800), Duration::from_millis(100))
+            .with_backoff(fibonacci);
+        let delays: Vec<_> = (&builder).build().collect();
+        // 4 attempts of 100ms, and no time left for a fifth one.
+        assert_eq!(delays, [100, 100, 200].map(Duration::from_millis));
+
+        // Attempts slower than the budget aren't retried.
+        let builder = SlaBuilder::new(Duration::from_millis(100), Duration::from_millis(200));
+        assert_eq!(builder.build().next(), None);
+    }
+}
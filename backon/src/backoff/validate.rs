@@ -0,0 +1,98 @@
+use core::fmt;
+use core::time::Duration;
+
+/// The error returned by `try_build` when the parameters of a builder make no sense.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum BuildError {
+    /// The factor is lower than 1 or not a number, like one loaded from configuration, so the
+    /// delays would shrink.
+    InvalidFactor(f32),
+    /// The minimum delay is greater than the maximum delay.
+    MinDelayExceedsMaxDelay {
+        /// The minimum delay.
+        min_delay: Duration,
+        /// The maximum delay.
+        max_delay: Duration,
+    },
+    /// The maximum number of retries is zero, so the operation would never be retried.
+    ZeroMaxTimes,
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::InvalidFactor(factor) => {
+                write!(f, "invalid factor {factor}, expected at least 1")
+            }
+            BuildError::MinDelayExceedsMaxDelay {
+                min_delay,
+                max_delay,
+            } => write!(
+                f,
+                "min delay {min_delay:?} is greater than max delay {max_delay:?}"
+            ),
+            BuildError::ZeroMaxTimes => {
+                f.write_str("max times is zero, the operation is never retried")
+            }
+        }
+    }
+}
+
+impl core::error::Error for BuildError {}
+
+/// Check the parameters shared by the builders.
+pub(crate) fn validate(
+    min_delay: Duration,
+    max_delay: Option<Duration>,
+    max_times: Option<usize>,
+) -> Result<(), BuildError> {
+    if let Some(max_delay) = max_delay.filter(|max_delay| min_delay > *max_delay) {
+        return Err(BuildError::MinDelayExceedsMaxDelay {
+            min_delay,
+            max_delay,
+        });
+    }
+    if max_times == Some(0) {
+        return Err(BuildError::ZeroMaxTimes);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+    use core::time::Duration;
+
+    use super::*;
+    use crate::ConstantBuilder;
+    use crate::ExponentialBuilder;
+    use crate::FibonacciBuilder;
+
+    #[test]
+    fn test_try_build() {
+        assert!(ExponentialBuilder::default().try_build().is_ok());
+        assert_eq!(
+            FibonacciBuilder::default()
+                .with_min_delay(Duration::from_secs(10))
+                .with_max_delay(Duration::from_secs(1))
+                .try_build()
+                .unwrap_err(),
+            BuildError::MinDelayExceedsMaxDelay {
+                min_delay: Duration::from_secs(10),
+                max_delay: Duration::from_secs(1),
+            }
+        );
+        assert_eq!(
+            ConstantBuilder::default()
+                .with_max_times(0)
+                .try_build()
+                .unwrap_err(),
+            BuildError::ZeroMaxTimes
+        );
+        assert_eq!(
+            BuildError::ZeroMaxTimes.to_string(),
+            "max times is zero, the operation is never retried"
+        );
+    }
+}
@@ -0,0 +1,124 @@
+use alloc::boxed::Box;
+use core::fmt;
+use std::sync::PoisonError;
+
+use crate::backoff::BackoffBuilder;
+use crate::sync::Arc;
+use crate::sync::RwLock;
+use crate::Backoff;
+use crate::SharedBackoffBuilder;
+
+/// WatchedPolicy is a slot holding a backoff policy, updated at runtime.
+///
+/// Clones share the slot: retries are started with a clone of it, and every retry started
+/// after [`WatchedPolicy::set`] uses the new policy, like a policy tuned during an incident
+/// without restarting the service. Retries already started keep their backoff.
+///
+/// Only available with the `std` feature.
+///
+/// # Examples
+///
+/// ```
+/// use core::time::Duration;
+///
+/// use backon::BlockingRetryable;
+/// use backon::ConstantBuilder;
+/// use backon::WatchedPolicy;
+///
+/// let policy = WatchedPolicy::new(ConstantBuilder::default().with_max_times(3));
+///
+/// // Like from a config reload.
+/// let handle = policy.clone();
+/// handle.set(
+///     ConstantBuilder::default()
+///         .with_delay(Duration::from_millis(1))
+///         .with_max_times(1),
+/// );
+///
+/// let mut attempts = 0;
+/// let _ = (|| {
+///     attempts += 1;
+///     Err::<(), _>("unavailable")
+/// })
+/// .retry(&policy)
+/// .call();
+/// assert_eq!(attempts, 2);
+/// ```
+#[derive(Clone)]
+pub struct WatchedPolicy(Arc<RwLock<SharedBackoffBuilder>>);
+
+impl WatchedPolicy {
+    /// Create a slot holding `builder`.
+    pub fn new<B>(builder: B) -> Self
+    where
+        B: BackoffBuilder + Clone + 'static,
+    {
+        WatchedPolicy(Arc::new(RwLock::new(SharedBackoffBuilder::new(builder))))
+    }
+
+    /// Replace the policy of the slot, for every retry started afterward.
+    pub fn set<B>(&self, builder: B)
+    where
+        B: BackoffBuilder + Clone + 'static,
+    {
+        *self.0.write().unwrap_or_else(PoisonError::into_inner) =
+            SharedBackoffBuilder::new(builder);
+    }
+
+    /// Returns the current policy of the slot.
+    pub fn get(&self) -> SharedBackoffBuilder {
+        self.0
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
+
+impl fmt::Debug for WatchedPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("WatchedPolicy").field(&self.get()).finish()
+    }
+}
+
+impl BackoffBuilder for WatchedPolicy {
+    type Backoff = Box<dyn Backoff>;
+
+    fn build(self) -> Self::Backoff {
+        self.get().build()
+    }
+}
+
+impl BackoffBuilder for &WatchedPolicy {
+    type Backoff = Box<dyn Backoff>;
+
+    fn build(self) -> Self::Backoff {
+        self.get().build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use super::*;
+    use crate::ConstantBuilder;
+    use crate::ExponentialBuilder;
+
+    #[test]
+    fn test_watched_policy() {
+        let policy = WatchedPolicy::new(ExponentialBuilder::default());
+        let started = (&policy).build();
+
+        policy.clone().set(
+            ConstantBuilder::default()
+                .with_delay(Duration::from_millis(5))
+                .with_max_times(1),
+        );
+        assert_eq!(
+            (&policy).build().collect::<alloc::vec::Vec<_>>(),
+            [Duration::from_millis(5)]
+        );
+        // The backoff built before keeps the previous policy.
+        assert_eq!(started.count(), 3);
+    }
+}
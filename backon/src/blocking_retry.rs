@@ -1,8 +1,54 @@
+use alloc::borrow::Cow;
+#[cfg(feature = "std")]
+use alloc::boxed::Box;
+use core::any;
+use core::fmt;
+use core::ops::ControlFlow;
 use core::time::Duration;
 
 use crate::backoff::BackoffBuilder;
 use crate::blocking_sleep::MaybeBlockingSleeper;
-use crate::{Backoff, BlockingSleeper, DefaultBlockingSleeper};
+use crate::clock::SharedClock;
+#[cfg(feature = "anyhow")]
+use crate::error::WhenDowncast;
+use crate::error::{
+    BackoffBy, Classify, ErrorClassifier, ErrorCollector, LastError, MapErrBefore, MapError,
+    ReduceErrors, SelectError, Unmapped, UnwrapPermanent, WhenRetryableError, WhenWithState,
+    WrapRetryError,
+};
+#[cfg(feature = "defmt")]
+use crate::hooks::Defmt;
+#[cfg(feature = "logging")]
+use crate::hooks::Log;
+#[cfg(feature = "opentelemetry")]
+use crate::hooks::OpenTelemetry;
+#[cfg(feature = "sentry")]
+use crate::hooks::Sentry;
+#[cfg(feature = "slog")]
+use crate::hooks::Slog;
+#[cfg(feature = "tracing")]
+use crate::hooks::Trace;
+use crate::hooks::{
+    Chain, Hooks, Metrics, NoHooks, Observe, OnBackoffExhausted, OnFatal, OnStart, OnTransient,
+    Recover,
+};
+use crate::retry::{Notify, NotifyWithState};
+use crate::state::CurrentAttempt;
+use crate::BackoffChoice;
+use crate::BudgetWarning;
+use crate::Deadline;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+use crate::FailureBudget;
+use crate::FinalError;
+use crate::GiveUpReason;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+use crate::RetryBudget;
+use crate::RetryEvent;
+use crate::RetryMetrics;
+use crate::RetryReport;
+use crate::RetrySnapshot;
+use crate::ScheduleSummary;
+use crate::{Backoff, BlockingSleeper, Clock, DefaultBlockingSleeper, RetryState, RetryableError};
 
 /// BlockingRetryable adds retry support for blocking functions.
 ///
@@ -57,6 +103,27 @@ where
     }
 }
 
+/// BlockingRetryableDefault adds `retry_default` to the functions of [`BlockingRetryable`],
+/// starting a retry with the process-wide default backoff, see
+/// [`set_default_backoff`][crate::set_default_backoff].
+///
+/// Only available with the `std` feature.
+#[cfg(feature = "std")]
+pub trait BlockingRetryableDefault<T, E, F: FnMut() -> Result<T, E>> {
+    /// Generate a new retry with the default backoff.
+    fn retry_default(self) -> BlockingRetry<Box<dyn Backoff>, T, E, F>;
+}
+
+#[cfg(feature = "std")]
+impl<T, E, F> BlockingRetryableDefault<T, E, F> for F
+where
+    F: FnMut() -> Result<T, E>,
+{
+    fn retry_default(self) -> BlockingRetry<Box<dyn Backoff>, T, E, F> {
+        BlockingRetry::new(self, crate::default_backoff().build())
+    }
+}
+
 /// Retry structure generated by [`BlockingRetryable`].
 pub struct BlockingRetry<
     B: Backoff,
@@ -66,14 +133,65 @@ pub struct BlockingRetry<
     SF: MaybeBlockingSleeper = DefaultBlockingSleeper,
     RF = fn(&E) -> bool,
     NF = fn(&E, Duration),
+    EC = LastError,
+    ME = Unmapped,
+    HK = NoHooks,
 > {
     backoff: B,
     retryable: RF,
     notify: NF,
+    errors: EC,
+    map_err: ME,
+    hooks: HK,
+    retry_state: RetryState,
     f: F,
     sleep_fn: SF,
 }
 
+impl<B, T, E, F, SF, RF, NF, EC, ME, HK> BlockingRetry<B, T, E, F, SF, RF, NF, EC, ME, HK>
+where
+    B: Backoff,
+    F: FnMut() -> Result<T, E>,
+    SF: MaybeBlockingSleeper,
+{
+    /// Returns the index of the current attempt, starting from 1 for the first attempt, or 0
+    /// before the retry starts.
+    pub fn attempt(&self) -> usize {
+        self.retry_state.attempt()
+    }
+
+    /// Returns the time elapsed since the first attempt started.
+    pub fn elapsed(&self) -> Duration {
+        self.retry_state.elapsed()
+    }
+
+    /// Capture the progress of the retry and the state of its backoff, to be restored by
+    /// `resume_from`.
+    pub fn snapshot(&self) -> RetrySnapshot<B>
+    where
+        B: Clone,
+    {
+        RetrySnapshot::new(self.retry_state.attempt(), self.backoff.clone())
+            .with_elapsed(self.retry_state.elapsed())
+    }
+}
+
+impl<B, T, E, F, SF, RF, NF, EC, ME, HK> fmt::Debug
+    for BlockingRetry<B, T, E, F, SF, RF, NF, EC, ME, HK>
+where
+    B: Backoff,
+    F: FnMut() -> Result<T, E>,
+    SF: MaybeBlockingSleeper,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BlockingRetry")
+            .field("backoff", &any::type_name::<B>())
+            .field("attempt", &self.retry_state.attempt())
+            .field("next_delay", &self.retry_state.next_delay())
+            .finish_non_exhaustive()
+    }
+}
+
 impl<B, T, E, F> BlockingRetry<B, T, E, F>
 where
     B: Backoff,
@@ -85,19 +203,27 @@ where
             backoff,
             retryable: |_: &E| true,
             notify: |_: &E, _: Duration| {},
+            errors: LastError,
+            map_err: Unmapped,
+            hooks: NoHooks,
+            retry_state: RetryState::new(),
             sleep_fn: DefaultBlockingSleeper::default(),
             f,
         }
     }
 }
 
-impl<B, T, E, F, SF, RF, NF> BlockingRetry<B, T, E, F, SF, RF, NF>
+#[allow(clippy::type_complexity)]
+impl<B, T, E, F, SF, RF, NF, EC, ME, HK> BlockingRetry<B, T, E, F, SF, RF, NF, EC, ME, HK>
 where
     B: Backoff,
     F: FnMut() -> Result<T, E>,
     SF: MaybeBlockingSleeper,
-    RF: FnMut(&E) -> bool,
-    NF: FnMut(&E, Duration),
+    RF: ErrorClassifier<ME::Error>,
+    NF: Notify<ME::Error>,
+    HK: Hooks<T, ME::Error>,
+    ME: MapError<E>,
+    EC: ErrorCollector<ME::Error>,
 {
     /// Set the sleeper for retrying.
     ///
@@ -126,13 +252,52 @@ where
     ///     Ok(())
     /// }
     /// ```
-    pub fn sleep<SN: BlockingSleeper>(self, sleep_fn: SN) -> BlockingRetry<B, T, E, F, SN, RF, NF> {
+    pub fn sleep<SN: BlockingSleeper>(
+        self,
+        sleep_fn: SN,
+    ) -> BlockingRetry<B, T, E, F, SN, RF, NF, EC, ME, HK> {
         BlockingRetry {
             backoff: self.backoff,
             retryable: self.retryable,
             notify: self.notify,
             f: self.f,
             sleep_fn,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+        }
+    }
+    /// Set the function mapping the errors of the retried function before they are passed to
+    /// the conditions and notify.
+    ///
+    /// This allows reusing the same conditions for functions returning differently wrapped
+    /// errors. It resets the conditions, notify and error handling set before, so it must be
+    /// called first.
+    pub fn map_err_before<E2, MN: FnMut(E) -> E2>(
+        self,
+        map_err: MN,
+    ) -> BlockingRetry<
+        B,
+        T,
+        E,
+        F,
+        SF,
+        fn(&E2) -> bool,
+        fn(&E2, Duration),
+        LastError,
+        MapErrBefore<MN>,
+    > {
+        BlockingRetry {
+            backoff: self.backoff,
+            retryable: |_: &E2| true,
+            notify: |_: &E2, _: Duration| {},
+            f: self.f,
+            sleep_fn: self.sleep_fn,
+            errors: LastError,
+            retry_state: self.retry_state,
+            map_err: MapErrBefore(map_err),
+            hooks: NoHooks,
         }
     }
 
@@ -161,16 +326,250 @@ where
     ///     Ok(())
     /// }
     /// ```
-    pub fn when<RN: FnMut(&E) -> bool>(
+    pub fn when<RN: FnMut(&ME::Error) -> bool>(
         self,
         retryable: RN,
-    ) -> BlockingRetry<B, T, E, F, SF, RN, NF> {
+    ) -> BlockingRetry<B, T, E, F, SF, RN, NF, EC, ME, HK> {
         BlockingRetry {
             backoff: self.backoff,
             retryable,
             notify: self.notify,
             f: self.f,
             sleep_fn: self.sleep_fn,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+        }
+    }
+
+    /// Set the conditions for retrying with access to the [`RetryState`].
+    ///
+    /// The state tells the index of the failed attempt and the delay slept before it, the
+    /// backoff only proposing the next delay once the error is retried. This replaces the
+    /// conditions set by `when`.
+    pub fn when_with_state<RN: FnMut(&ME::Error, &RetryState) -> bool>(
+        self,
+        retryable: RN,
+    ) -> BlockingRetry<B, T, E, F, SF, WhenWithState<RN>, NF, EC, ME, HK> {
+        BlockingRetry {
+            backoff: self.backoff,
+            retryable: WhenWithState(retryable),
+            notify: self.notify,
+            f: self.f,
+            sleep_fn: self.sleep_fn,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+        }
+    }
+
+    /// Set the conditions for retrying with the error passed by value.
+    ///
+    /// The function returns [`ControlFlow::Continue`] with the error to retry, or
+    /// [`ControlFlow::Break`] with the error to end the retry with. So errors can be enriched
+    /// on every attempt, for example with the attempt index from [`RetryState`]. This replaces
+    /// the conditions set by `when`.
+    pub fn classify<RN: FnMut(ME::Error, &RetryState) -> ControlFlow<ME::Error, ME::Error>>(
+        self,
+        retryable: RN,
+    ) -> BlockingRetry<B, T, E, F, SF, Classify<RN>, NF, EC, ME, HK> {
+        BlockingRetry {
+            backoff: self.backoff,
+            retryable: Classify(retryable),
+            notify: self.notify,
+            f: self.f,
+            sleep_fn: self.sleep_fn,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+        }
+    }
+
+    /// Set the conditions for retrying by the error's [`RetryableError`] implementation.
+    ///
+    /// The delay suggested by [`RetryableError::retry_after`] is used instead of the backoff's
+    /// delay if present. This replaces the conditions set by `when`.
+    pub fn when_retryable(self) -> BlockingRetry<B, T, E, F, SF, WhenRetryableError, NF, EC, ME, HK>
+    where
+        ME::Error: RetryableError,
+    {
+        BlockingRetry {
+            backoff: self.backoff,
+            retryable: WhenRetryableError,
+            notify: self.notify,
+            f: self.f,
+            sleep_fn: self.sleep_fn,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+        }
+    }
+
+    /// Set the function choosing the backoff of every error, see [`BackoffChoice`].
+    ///
+    /// Errors of the class returned by the function are retried with its own backoff,
+    /// independently of the other classes, and those returning [`BackoffChoice::Default`] with
+    /// the backoff of the retry. It applies to the conditions set before, so it must be called
+    /// after `when` and the like.
+    pub fn backoff_by<BB: BackoffBuilder, BN: FnMut(&ME::Error) -> BackoffChoice<BB>>(
+        self,
+        choose: BN,
+    ) -> BlockingRetry<B, T, E, F, SF, BackoffBy<RF, BN, BB>, NF, EC, ME, HK> {
+        BlockingRetry {
+            backoff: self.backoff,
+            retryable: BackoffBy::new(self.retryable, choose),
+            notify: self.notify,
+            f: self.f,
+            sleep_fn: self.sleep_fn,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+        }
+    }
+
+    /// Set the conditions for retrying [`anyhow::Error`]s caused by `X`.
+    ///
+    /// The whole chain of the error is inspected, so errors with context attached are retried
+    /// as well. This replaces the conditions set by `when`.
+    #[cfg(feature = "anyhow")]
+    pub fn when_downcast<X: core::error::Error + 'static>(
+        self,
+    ) -> BlockingRetry<B, T, E, F, SF, WhenDowncast<X>, NF, EC, ME, HK>
+    where
+        ME: MapError<E, Error = anyhow::Error>,
+    {
+        BlockingRetry {
+            backoff: self.backoff,
+            retryable: WhenDowncast::new(),
+            notify: self.notify,
+            f: self.f,
+            sleep_fn: self.sleep_fn,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+        }
+    }
+
+    /// Set the function reducing the errors of all attempts into the returned one.
+    ///
+    /// The function is called with the reduced error so far and the error of the next attempt.
+    /// If not specified, the error of the last attempt is returned.
+    ///
+    /// This replaces the previous reducer, and must be set before `wrap_err`.
+    pub fn reduce_errors<RN: FnMut(ME::Error, ME::Error) -> ME::Error>(
+        self,
+        reduce: RN,
+    ) -> BlockingRetry<B, T, E, F, SF, RF, NF, ReduceErrors<ME::Error, RN>, ME, HK>
+    where
+        EC: ErrorCollector<ME::Error, Error = ME::Error>,
+    {
+        BlockingRetry {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            f: self.f,
+            sleep_fn: self.sleep_fn,
+            errors: ReduceErrors::new(reduce),
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+        }
+    }
+
+    /// Set the policy selecting the error returned when the retry ends unsuccessfully.
+    ///
+    /// If not specified, the error of the last attempt is returned. See [`FinalError`] for
+    /// the policies.
+    ///
+    /// This replaces the previous reducer, and must be set before `wrap_err`.
+    pub fn final_error(
+        self,
+        policy: FinalError<ME::Error>,
+    ) -> BlockingRetry<B, T, E, F, SF, RF, NF, SelectError<ME::Error>, ME, HK>
+    where
+        EC: ErrorCollector<ME::Error, Error = ME::Error>,
+    {
+        BlockingRetry {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            f: self.f,
+            sleep_fn: self.sleep_fn,
+            errors: SelectError::new(policy),
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+        }
+    }
+
+    /// End the retry at errors wrapped by [`non_retryable`](crate::non_retryable), and unwrap
+    /// the returned [`Permanent`](crate::Permanent) error.
+    ///
+    /// Permanent errors end the retry regardless of the conditions set by `when`.
+    pub fn unwrap_permanent(
+        self,
+    ) -> BlockingRetry<B, T, E, F, SF, RF, NF, UnwrapPermanent<EC>, ME, HK>
+    where
+        UnwrapPermanent<EC>: ErrorCollector<ME::Error>,
+    {
+        BlockingRetry {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            f: self.f,
+            sleep_fn: self.sleep_fn,
+            errors: UnwrapPermanent(self.errors),
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+        }
+    }
+
+    /// Wrap the error that ends the retry into a [`RetryError`](crate::RetryError).
+    ///
+    /// The wrapper records the number of attempts made and keeps the original error as its
+    /// [`source`](core::error::Error::source).
+    pub fn wrap_err(
+        self,
+    ) -> BlockingRetry<B, T, E, F, SF, RF, NF, WrapRetryError<EC, EC::Error>, ME, HK> {
+        BlockingRetry {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            f: self.f,
+            sleep_fn: self.sleep_fn,
+            errors: WrapRetryError::new(self.errors, 0),
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+        }
+    }
+
+    /// Wrap the error that ends the retry into a [`RetryError`](crate::RetryError), keeping
+    /// the history of up to `cap` most recent failed attempts.
+    ///
+    /// Every entry of the history records the error, the delay slept after it and when it
+    /// happened, so the failure timeline can be reported once the retry ends.
+    pub fn wrap_err_with_history(
+        self,
+        cap: usize,
+    ) -> BlockingRetry<B, T, E, F, SF, RF, NF, WrapRetryError<EC, EC::Error>, ME, HK> {
+        BlockingRetry {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            f: self.f,
+            sleep_fn: self.sleep_fn,
+            errors: WrapRetryError::new(self.errors, cap),
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
         }
     }
 
@@ -205,88 +604,622 @@ where
     ///     Ok(())
     /// }
     /// ```
-    pub fn notify<NN: FnMut(&E, Duration)>(
+    pub fn notify<NN: FnMut(&ME::Error, Duration)>(
         self,
         notify: NN,
-    ) -> BlockingRetry<B, T, E, F, SF, RF, NN> {
+    ) -> BlockingRetry<B, T, E, F, SF, RF, NN, EC, ME, HK> {
         BlockingRetry {
             backoff: self.backoff,
             retryable: self.retryable,
             notify,
             f: self.f,
             sleep_fn: self.sleep_fn,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
         }
     }
-}
 
-impl<B, T, E, F, SF, RF, NF> BlockingRetry<B, T, E, F, SF, RF, NF>
-where
-    B: Backoff,
-    F: FnMut() -> Result<T, E>,
-    SF: BlockingSleeper,
-    RF: FnMut(&E) -> bool,
-    NF: FnMut(&E, Duration),
-{
-    /// Call the retried function.
+    /// Set to notify for all retry attempts with access to the [`RetryState`].
     ///
-    /// TODO: implement [`FnOnce`] after it stable.
-    pub fn call(mut self) -> Result<T, E> {
-        loop {
-            let result = (self.f)();
-
-            match result {
-                Ok(v) => return Ok(v),
-                Err(err) => {
-                    if !(self.retryable)(&err) {
-                        return Err(err);
-                    }
+    /// The state tells the session ID of the retry and the index of the failed attempt, so
+    /// the notifications can be correlated with the logs of the retried operation. This
+    /// replaces the function set by `notify`.
+    pub fn notify_with_state<NN: FnMut(&ME::Error, Duration, &RetryState)>(
+        self,
+        notify: NN,
+    ) -> BlockingRetry<B, T, E, F, SF, RF, NotifyWithState<NN>, EC, ME, HK> {
+        BlockingRetry {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: NotifyWithState(notify),
+            f: self.f,
+            sleep_fn: self.sleep_fn,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+        }
+    }
 
-                    match self.backoff.next() {
-                        None => return Err(err),
-                        Some(dur) => {
-                            (self.notify)(&err, dur);
-                            self.sleep_fn.sleep(dur);
-                        }
-                    }
-                }
-            }
+    /// Set the function called once before the first attempt starts.
+    ///
+    /// It's called in addition to the functions set before, so per-call setup like starting
+    /// a timer or logging the operation can live in the retry.
+    pub fn on_start<ON: FnMut(&RetryState)>(
+        self,
+        f: ON,
+    ) -> BlockingRetry<B, T, E, F, SF, RF, NF, EC, ME, Chain<HK, OnStart<ON>>> {
+        BlockingRetry {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            f: self.f,
+            sleep_fn: self.sleep_fn,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, OnStart(f)),
         }
     }
-}
-#[cfg(test)]
-mod tests {
-    use alloc::string::ToString;
-    use alloc::vec;
-    use alloc::vec::Vec;
-    use core::time::Duration;
-    use spin::Mutex;
 
-    use super::*;
-    use crate::ExponentialBuilder;
+    /// Set the function called with every error that will be retried and the delay before
+    /// the next attempt.
+    ///
+    /// It's called after `notify`, and in addition to the functions set before.
+    pub fn on_transient_error<TN: FnMut(&ME::Error, Duration)>(
+        self,
+        f: TN,
+    ) -> BlockingRetry<B, T, E, F, SF, RF, NF, EC, ME, Chain<HK, OnTransient<TN>>> {
+        BlockingRetry {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            f: self.f,
+            sleep_fn: self.sleep_fn,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, OnTransient(f)),
+        }
+    }
 
-    fn always_error() -> anyhow::Result<()> {
-        Err(anyhow::anyhow!("test_query meets error"))
+    /// Set the function called with the error that ends the retry unsuccessfully and the
+    /// reason why.
+    ///
+    /// Both non-retryable errors and errors left when the backoff is exhausted end the retry.
+    /// It's called in addition to the functions set before.
+    pub fn on_fatal_error<FN: FnMut(&ME::Error, GiveUpReason)>(
+        self,
+        f: FN,
+    ) -> BlockingRetry<B, T, E, F, SF, RF, NF, EC, ME, Chain<HK, OnFatal<FN>>> {
+        BlockingRetry {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            f: self.f,
+            sleep_fn: self.sleep_fn,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, OnFatal(f)),
+        }
     }
 
-    #[test]
-    fn test_retry() -> anyhow::Result<()> {
-        let result = always_error
-            .retry(ExponentialBuilder::default().with_min_delay(Duration::from_millis(1)))
-            .call();
+    /// Set the function called when the retry gives up because the backoff is exhausted,
+    /// with the last error and the [`ScheduleSummary`] of the delays planned and slept.
+    ///
+    /// Unlike `on_fatal_error` it's not called for errors that are not retryable, so an
+    /// exhausted policy can be told apart from a fatal error.
+    pub fn on_backoff_exhausted<BN: FnMut(&ME::Error, &ScheduleSummary)>(
+        self,
+        f: BN,
+    ) -> BlockingRetry<B, T, E, F, SF, RF, NF, EC, ME, Chain<HK, OnBackoffExhausted<BN>>> {
+        BlockingRetry {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            f: self.f,
+            sleep_fn: self.sleep_fn,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, OnBackoffExhausted::new(f)),
+        }
+    }
 
-        assert!(result.is_err());
-        assert_eq!("test_query meets error", result.unwrap_err().to_string());
-        Ok(())
+    /// Set the function recovering from the error that ends the retry unsuccessfully.
+    ///
+    /// Both non-retryable errors and errors left when the backoff is exhausted are passed to
+    /// it, and the value it returns is returned as the result of the retry instead. Errors
+    /// that are not recovered are passed on to `on_fatal_error` and returned as usual.
+    ///
+    /// Recovered retries still gave up: their metrics, events and traces record them as
+    /// giving up, only `on_fatal_error` isn't called.
+    pub fn recover<RN: FnMut(&ME::Error) -> Option<T>>(
+        self,
+        f: RN,
+    ) -> BlockingRetry<B, T, E, F, SF, RF, NF, EC, ME, Chain<HK, Recover<RN>>> {
+        BlockingRetry {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            f: self.f,
+            sleep_fn: self.sleep_fn,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, Recover(f)),
+        }
     }
 
-    #[test]
-    fn test_retry_with_not_retryable_error() -> anyhow::Result<()> {
-        let error_times = Mutex::new(0);
+    /// Set the [`RetryMetrics`] observing the progress of the retry.
+    ///
+    /// It's called in addition to the metrics set before.
+    pub fn metrics<MN: RetryMetrics>(
+        self,
+        metrics: MN,
+    ) -> BlockingRetry<B, T, E, F, SF, RF, NF, EC, ME, Chain<HK, Metrics<MN>>> {
+        BlockingRetry {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            f: self.f,
+            sleep_fn: self.sleep_fn,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, Metrics(metrics)),
+        }
+    }
 
-        let f = || {
-            let mut x = error_times.lock();
-            *x += 1;
-            Err::<(), anyhow::Error>(anyhow::anyhow!("not retryable"))
+    /// Set the function observing every step of the retry as a [`RetryEvent`].
+    ///
+    /// It's called in addition to the other hooks and observers set before, so a single
+    /// function can follow the whole lifecycle of the retry. Its events are no longer delivered
+    /// to the observer set by `set_global_observer`.
+    pub fn observe<FN: FnMut(RetryEvent<'_, ME::Error>)>(
+        mut self,
+        f: FN,
+    ) -> BlockingRetry<B, T, E, F, SF, RF, NF, EC, ME, Chain<HK, Observe<FN>>> {
+        self.retry_state.set_observed();
+        BlockingRetry {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            f: self.f,
+            sleep_fn: self.sleep_fn,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, Observe(f)),
+        }
+    }
+
+    /// Record the retry in a `tracing` span named `retry`, with the `operation` and
+    /// `session_id` fields set.
+    ///
+    /// The retried function runs inside the span, and every sleep inside a child span named
+    /// `sleep` with the `operation`, `attempt` and `delay` fields. Failed attempts are emitted
+    /// as `WARN` events, sleeps, resumes and successes as `DEBUG` events and giving up as an
+    /// `ERROR` event, with the attempt number, delay, error and reason as fields.
+    #[cfg(feature = "tracing")]
+    pub fn trace(
+        self,
+        operation: &'static str,
+    ) -> BlockingRetry<B, T, E, F, SF, RF, NF, EC, ME, Chain<HK, Trace>>
+    where
+        ME::Error: fmt::Display,
+    {
+        BlockingRetry {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            f: self.f,
+            sleep_fn: self.sleep_fn,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, Trace::new(operation)),
+        }
+    }
+
+    /// Record the retry on the current OpenTelemetry span and in OpenTelemetry metrics, with
+    /// the `operation.name` attribute set to `operation`.
+    ///
+    /// Every failed attempt is added to the span active when this is called as a
+    /// `retry.attempt_failed` event and giving up as a `retry.gave_up` event, carrying the
+    /// `retry.attempt`, `retry.delay`, `error.type` and `exception.message` attributes. The
+    /// `backon.retry.attempts`, `backon.retry.retries` and `backon.retry.giveups` counters and
+    /// the `backon.retry.sleep.duration` histogram are reported to the global meter provider.
+    #[cfg(feature = "opentelemetry")]
+    pub fn opentelemetry(
+        self,
+        operation: &'static str,
+    ) -> BlockingRetry<B, T, E, F, SF, RF, NF, EC, ME, Chain<HK, OpenTelemetry>>
+    where
+        ME::Error: fmt::Display,
+    {
+        BlockingRetry {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            f: self.f,
+            sleep_fn: self.sleep_fn,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, OpenTelemetry::new(operation)),
+        }
+    }
+
+    /// Log the errors that will be retried as warnings and the error that ends the retry as
+    /// an error with `defmt`, for embedded targets logging over RTT.
+    #[cfg(feature = "defmt")]
+    pub fn defmt(self) -> BlockingRetry<B, T, E, F, SF, RF, NF, EC, ME, Chain<HK, Defmt>>
+    where
+        ME::Error: defmt::Format,
+    {
+        BlockingRetry {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            f: self.f,
+            sleep_fn: self.sleep_fn,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, Defmt),
+        }
+    }
+
+    /// Record every error that will be retried and the error that ends the retry as Sentry
+    /// breadcrumbs in the `retry` category, with the attempt, the delay and the error message.
+    ///
+    /// When the retry gives up, the number of attempts is set as the `retry.attempts` tag of
+    /// the current scope, so the error reported afterwards shows the history of the retry.
+    #[cfg(feature = "sentry")]
+    pub fn sentry(self) -> BlockingRetry<B, T, E, F, SF, RF, NF, EC, ME, Chain<HK, Sentry>>
+    where
+        ME::Error: fmt::Display,
+    {
+        BlockingRetry {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            f: self.f,
+            sleep_fn: self.sleep_fn,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, Sentry),
+        }
+    }
+
+    /// Log the errors that will be retried at `retry_level` and the error that ends the
+    /// retry at `give_up_level` with the `log` crate, under the `backon` target.
+    #[cfg(feature = "logging")]
+    pub fn log(
+        self,
+        retry_level: log::Level,
+        give_up_level: log::Level,
+    ) -> BlockingRetry<B, T, E, F, SF, RF, NF, EC, ME, Chain<HK, Log>>
+    where
+        ME::Error: fmt::Display,
+    {
+        BlockingRetry {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            f: self.f,
+            sleep_fn: self.sleep_fn,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, Log::new(retry_level, give_up_level)),
+        }
+    }
+
+    /// Log the errors that will be retried as warnings and the error that ends the retry as
+    /// an error to `logger`, with the attempt number, delay, error and reason as key-value
+    /// pairs.
+    #[cfg(feature = "slog")]
+    pub fn slog(
+        self,
+        logger: slog::Logger,
+    ) -> BlockingRetry<B, T, E, F, SF, RF, NF, EC, ME, Chain<HK, Slog>>
+    where
+        ME::Error: fmt::Display,
+    {
+        BlockingRetry {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            f: self.f,
+            sleep_fn: self.sleep_fn,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, Slog::new(logger)),
+        }
+    }
+
+    /// Set whether the retried operation is idempotent, `true` by default.
+    ///
+    /// Operations that are not idempotent, like writes, are only retried at errors telling
+    /// the operation had no effect by [`RetryableError::is_safe_to_retry`], so this works
+    /// together with `when_retryable`. Other errors end the retry with
+    /// [`GiveUpReason::Ambiguous`] instead of risking applying the operation twice.
+    pub fn idempotent(mut self, idempotent: bool) -> Self {
+        self.retry_state.set_idempotent(idempotent);
+        self
+    }
+
+    /// Set the name of the retried operation, like `s3_put_object`.
+    ///
+    /// It's included in the events passed to `observe`, the [`RetryReport`](crate::RetryReport)
+    /// and the output of the `tracing` and `opentelemetry` integrations, so they can be
+    /// aggregated per operation.
+    pub fn name(mut self, name: &'static str) -> Self {
+        self.retry_state.set_name(name);
+        self
+    }
+
+    /// Add a key-value tag describing the retried operation, included wherever the name set
+    /// by `name` is.
+    pub fn tag(mut self, key: &'static str, value: impl Into<Cow<'static, str>>) -> Self {
+        self.retry_state.add_tag(key, value.into());
+        self
+    }
+
+    /// Continue a retry from a [`RetrySnapshot`] taken by `snapshot`, like one persisted
+    /// before the process restarted.
+    ///
+    /// The backoff of the snapshot replaces the one passed to `retry`, so the delays pick up
+    /// where they were instead of starting over, and the attempts and elapsed time count from
+    /// the snapshot. The next attempt starts right away.
+    pub fn resume_from<S: Backoff>(
+        mut self,
+        snapshot: RetrySnapshot<S>,
+    ) -> BlockingRetry<S, T, E, F, SF, RF, NF, EC, ME, HK> {
+        self.retry_state.resume(&snapshot);
+        BlockingRetry {
+            backoff: snapshot.into_backoff(),
+            retryable: self.retryable,
+            notify: self.notify,
+            f: self.f,
+            sleep_fn: self.sleep_fn,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+        }
+    }
+
+    /// Set the [`Clock`] measuring the elapsed time and the latency of the attempts, reported
+    /// by [`RetryState`], the events passed to `observe` and the report.
+    ///
+    /// It defaults to [`StdClock`](crate::StdClock) with the `std` feature outside of
+    /// `wasm32`. Without it, the retry isn't timed unless a clock is set.
+    pub fn clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.retry_state.set_clock(SharedClock::new(clock));
+        self
+    }
+
+    /// Seed the random numbers of the retry, the jitter of its backoff and its ID, instead of
+    /// drawing them from the thread's RNG.
+    ///
+    /// Together with a [`Clock`] set by `clock`, the delays, the events and the report of the
+    /// retry are the same on every run, so tests can assert on them exactly.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.retry_state.set_seed(seed);
+        self
+    }
+
+    /// Panic if the retry makes more than `max_attempts` attempts or sleeps more than
+    /// `max_total_sleep` in total, so tests assert a code path never retries more than it
+    /// should, like "this call is retried at most twice".
+    ///
+    /// The panic happens before the retry over the budget sleeps. Only available with the
+    /// `test-util` feature.
+    #[cfg(feature = "test-util")]
+    pub fn with_test_budget(mut self, max_attempts: usize, max_total_sleep: Duration) -> Self {
+        self.retry_state
+            .set_test_budget(max_attempts, max_total_sleep);
+        self
+    }
+
+    /// Set the ID identifying this retry in logs and events, returned by
+    /// [`RetryState::session_id`].
+    ///
+    /// A random ID is generated when the retry is built if it's not set, the same for all its
+    /// attempts. The retried operation reads it with `current_attempt`, so its logs can be
+    /// correlated with the events of the retry.
+    pub fn session_id(mut self, id: u64) -> Self {
+        self.retry_state.set_session_id(id);
+        self
+    }
+
+    /// Set the [`BudgetWarning`] raised to the function set by `observe` once a fraction of
+    /// the attempts or time of the retry is consumed.
+    pub fn budget_warning(mut self, warning: BudgetWarning) -> Self {
+        self.retry_state.set_budget_warning(warning);
+        self
+    }
+
+    /// Set the most time the retry sleeps in total between attempts, combined with the
+    /// limits of the backoff like its maximum number of retries.
+    ///
+    /// The retry gives up with [`GiveUpReason::MaxTotalSleepReached`] instead of sleeping past
+    /// it, telling it apart from [`GiveUpReason::BackoffExhausted`] if the backoff runs out
+    /// first.
+    pub fn max_total_sleep(mut self, max: Duration) -> Self {
+        self.retry_state.set_max_total_sleep(max);
+        self
+    }
+
+    /// Attach the [`FailureBudget`] shared with other retries.
+    ///
+    /// Every attempt failed with a retryable error is recorded in the budget, and the retry
+    /// ends at the next one while the budget is exhausted.
+    #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+    pub fn failure_budget(mut self, budget: FailureBudget) -> Self {
+        self.retry_state.set_failure_budget(budget);
+        self
+    }
+
+    /// Attach the [`RetryBudget`] shared with other retries.
+    ///
+    /// Every successful attempt deposits in the budget, and every retry withdraws from it,
+    /// the retry ending with [`GiveUpReason::BudgetExhausted`] once it's empty.
+    #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+    pub fn retry_budget(mut self, budget: RetryBudget) -> Self {
+        self.retry_state.set_retry_budget(budget);
+        self
+    }
+
+    /// Set the [`Deadline`] the retry must be done by, inherited from the context of the call.
+    ///
+    /// The delays are shortened for every retry to start with the least time an attempt
+    /// needs left, the retry ending with [`GiveUpReason::DeadlineExceeded`] once it can't.
+    /// Blocking attempts aren't timed out, see [`Deadline`].
+    pub fn deadline(mut self, deadline: Deadline) -> Self {
+        self.retry_state.set_deadline(deadline);
+        self
+    }
+}
+
+impl<B, T, E, F, SF, RF, NF, EC, ME, HK> BlockingRetry<B, T, E, F, SF, RF, NF, EC, ME, HK>
+where
+    B: Backoff,
+    F: FnMut() -> Result<T, E>,
+    SF: BlockingSleeper,
+    RF: ErrorClassifier<ME::Error>,
+    NF: Notify<ME::Error>,
+    HK: Hooks<T, ME::Error>,
+    ME: MapError<E>,
+    EC: ErrorCollector<ME::Error>,
+{
+    /// Call the retried function.
+    ///
+    /// TODO: implement [`FnOnce`] after it stable.
+    pub fn call(self) -> Result<T, EC::Error> {
+        self.run().0
+    }
+
+    /// Call the retried function, returning the [`RetryReport`] of the retry alongside its
+    /// result.
+    pub fn call_with_report(mut self) -> (Result<T, EC::Error>, RetryReport) {
+        self.retry_state.start_report();
+        let (result, mut state) = self.run();
+        (result, state.take_report())
+    }
+
+    /// Run the retry, returning its result with the final state.
+    fn run(mut self) -> (Result<T, EC::Error>, RetryState) {
+        let result = loop {
+            self.retry_state.start_attempt();
+            self.hooks.on_attempt(&self.retry_state);
+            let result = {
+                let _current = CurrentAttempt::enter(&self.retry_state);
+                self.hooks.enter();
+                let res = (self.f)();
+                self.hooks.exit();
+                res
+            };
+
+            match result {
+                Ok(v) => {
+                    self.retry_state.succeed_attempt();
+                    self.hooks.on_success(&v, &self.retry_state);
+                    break Ok(v);
+                }
+                Err(err) => {
+                    let err = self.map_err.map_err(err);
+                    match self.retry_state.retry_delay(
+                        err,
+                        &mut self.backoff,
+                        &mut self.retryable,
+                        &mut self.errors,
+                    ) {
+                        Err((err, reason)) => {
+                            if let Some(v) = self.hooks.recover(&err) {
+                                self.hooks.on_recovered(&err, reason, &self.retry_state);
+                                break Ok(v);
+                            }
+                            self.hooks.on_fatal(&err, reason, &self.retry_state);
+                            break Err(self.errors.finish(err, reason));
+                        }
+                        Ok((err, dur)) => {
+                            self.notify.notify(&err, dur, &self.retry_state);
+                            self.hooks.on_transient(&err, dur, &self.retry_state);
+                            self.errors.collect(err, dur, &self.retry_state);
+                            self.hooks.enter_sleep();
+                            self.sleep_fn.sleep(dur);
+                            self.hooks.exit_sleep();
+                            self.hooks.on_resume(&self.retry_state);
+                        }
+                    }
+                }
+            }
+        };
+        (result, self.retry_state)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use alloc::format;
+    use alloc::string::String;
+    use alloc::string::ToString;
+    use alloc::vec;
+    use alloc::vec::Vec;
+    use core::time::Duration;
+    use spin::Mutex;
+
+    use super::*;
+    use crate::non_retryable;
+    use crate::ConstantBuilder;
+    use crate::ExponentialBuilder;
+    use crate::GiveUpReason;
+    use crate::Permanent;
+
+    fn always_error() -> anyhow::Result<()> {
+        Err(anyhow::anyhow!("test_query meets error"))
+    }
+
+    #[test]
+    fn test_retry() -> anyhow::Result<()> {
+        let result = always_error
+            .retry(ExponentialBuilder::default().with_min_delay(Duration::from_millis(1)))
+            .call();
+
+        assert!(result.is_err());
+        assert_eq!("test_query meets error", result.unwrap_err().to_string());
+        Ok(())
+    }
+
+    #[test]
+    fn test_retry_debug() -> anyhow::Result<()> {
+        let retry = always_error.retry(ExponentialBuilder::default());
+
+        assert_eq!(retry.attempt(), 0);
+        assert_eq!(
+            format!("{retry:?}"),
+            "BlockingRetry { backoff: \"backon::backoff::exponential::ExponentialBackoff\", \
+             attempt: 0, next_delay: None, .. }"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_retry_with_not_retryable_error() -> anyhow::Result<()> {
+        let error_times = Mutex::new(0);
+
+        let f = || {
+            let mut x = error_times.lock();
+            *x += 1;
+            Err::<(), anyhow::Error>(anyhow::anyhow!("not retryable"))
         };
 
         let backoff = ExponentialBuilder::default().with_min_delay(Duration::from_millis(1));
@@ -330,6 +1263,839 @@ mod tests {
         Ok(())
     }
 
+    #[derive(Debug)]
+    enum TestError {
+        Throttled,
+        Dropped,
+        NotFound,
+    }
+
+    impl RetryableError for TestError {
+        fn is_retryable(&self) -> bool {
+            matches!(self, TestError::Throttled | TestError::Dropped)
+        }
+
+        fn retry_after(&self) -> Option<Duration> {
+            Some(Duration::from_millis(2))
+        }
+
+        fn is_safe_to_retry(&self) -> bool {
+            matches!(self, TestError::Throttled)
+        }
+    }
+
+    #[test]
+    fn test_retry_when_retryable() -> anyhow::Result<()> {
+        let error_times = Mutex::new(0);
+        let mut sleeps: Vec<Duration> = vec![];
+
+        let f = || {
+            let mut x = error_times.lock();
+            *x += 1;
+            Err::<(), _>(if *x < 3 {
+                TestError::Throttled
+            } else {
+                TestError::NotFound
+            })
+        };
+
+        let backoff = ExponentialBuilder::default().with_min_delay(Duration::from_millis(1));
+        let result = f
+            .retry(backoff)
+            .when_retryable()
+            .notify(|_, dur| sleeps.push(dur))
+            .call();
+
+        assert!(matches!(result, Err(TestError::NotFound)));
+        // `f` stops retrying at the first non-retryable error.
+        assert_eq!(*error_times.lock(), 3);
+        // The delay suggested by the error replaces the backoff's delay.
+        assert_eq!(sleeps, vec![Duration::from_millis(2); 2]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_retry_not_idempotent() -> anyhow::Result<()> {
+        let error_times = Mutex::new(0);
+
+        let f = || {
+            let mut x = error_times.lock();
+            *x += 1;
+            Err::<(), _>(if *x < 3 {
+                TestError::Throttled
+            } else {
+                TestError::Dropped
+            })
+        };
+
+        let backoff = ExponentialBuilder::default().with_min_delay(Duration::from_millis(1));
+        let result = f
+            .retry(backoff)
+            .when_retryable()
+            .idempotent(false)
+            .wrap_err()
+            .call();
+
+        let err = result.unwrap_err();
+        // Throttled writes are retried, but a dropped connection is not.
+        assert!(matches!(err.get_ref(), TestError::Dropped));
+        assert_eq!(err.attempts(), 3);
+        assert_eq!(err.reason(), GiveUpReason::Ambiguous);
+        Ok(())
+    }
+
+    #[test]
+    fn test_retry_with_reduce_errors() -> anyhow::Result<()> {
+        let error_times = Mutex::new(0);
+
+        let f = || {
+            let mut x = error_times.lock();
+            *x += 1;
+            // Only the second attempt fails with the informative error.
+            Err::<(), _>(if *x == 2 { 403 } else { 504 })
+        };
+
+        let backoff = ExponentialBuilder::default().with_min_delay(Duration::from_millis(1));
+        let result = f
+            .retry(backoff)
+            .reduce_errors(|prev, next| if prev == 403 { prev } else { next })
+            .wrap_err()
+            .call();
+
+        let err = result.unwrap_err();
+        assert_eq!(err.attempts(), 4);
+        assert_eq!(*err.get_ref(), 403);
+        assert_eq!(err.reason(), GiveUpReason::BackoffExhausted);
+        Ok(())
+    }
+
+    #[test]
+    fn test_retry_with_give_up_reason() -> anyhow::Result<()> {
+        let f = || Err::<(), _>(anyhow::anyhow!("not retryable"));
+
+        let backoff = ExponentialBuilder::default().with_min_delay(Duration::from_millis(1));
+        let result = f.retry(backoff).when(|_| false).wrap_err().call();
+
+        let err = result.unwrap_err();
+        assert_eq!(err.attempts(), 1);
+        assert_eq!(err.reason(), GiveUpReason::NotRetryable);
+        Ok(())
+    }
+
+    #[test]
+    fn test_retry_with_error_hooks() -> anyhow::Result<()> {
+        let error_times = Mutex::new(0);
+        let transient = Mutex::new(Vec::new());
+        let fatal = Mutex::new(Vec::new());
+
+        let f = || {
+            let mut x = error_times.lock();
+            *x += 1;
+            Err::<(), _>(*x)
+        };
+
+        let backoff = ExponentialBuilder::default()
+            .with_min_delay(Duration::from_millis(1))
+            .with_max_times(2);
+        let result = f
+            .retry(backoff)
+            .on_transient_error(|err, _| transient.lock().push(*err))
+            .on_fatal_error(|err, reason| fatal.lock().push((*err, reason)))
+            .call();
+
+        assert_eq!(result, Err(3));
+        assert_eq!(*transient.lock(), vec![1, 2]);
+        assert_eq!(*fatal.lock(), vec![(3, GiveUpReason::BackoffExhausted)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_retry_with_backoff_exhausted() -> anyhow::Result<()> {
+        let exhausted = Mutex::new(Vec::new());
+        let backoff = ExponentialBuilder::default()
+            .with_min_delay(Duration::from_millis(1))
+            .with_max_times(2);
+        let retry = |status: u16| {
+            (|| Err::<(), _>(status))
+                .retry(backoff)
+                .when(|status| *status >= 500)
+                .on_backoff_exhausted(|status, schedule| {
+                    exhausted.lock().push((*status, schedule.clone()))
+                })
+                .call()
+        };
+
+        assert_eq!(retry(400), Err(400));
+        assert!(exhausted.lock().is_empty());
+        assert_eq!(retry(503), Err(503));
+        let exhausted = exhausted.lock();
+        assert_eq!(exhausted.len(), 1);
+        assert_eq!(exhausted[0].0, 503);
+        assert_eq!(
+            exhausted[0].1.planned(),
+            [Duration::from_millis(1), Duration::from_millis(2)]
+        );
+        assert_eq!(exhausted[0].1.total_actual(), Duration::from_millis(3));
+        Ok(())
+    }
+
+    #[test]
+    fn test_retry_with_max_total_sleep() -> anyhow::Result<()> {
+        let backoff = ConstantBuilder::default()
+            .with_delay(Duration::from_millis(2))
+            .with_max_times(3);
+        let f = || Err::<(), _>(anyhow::anyhow!("unavailable"));
+
+        // 2ms + 2ms fit, the third sleep doesn't.
+        let err = f
+            .retry(backoff)
+            .max_total_sleep(Duration::from_millis(5))
+            .wrap_err()
+            .call()
+            .unwrap_err();
+        assert_eq!(err.attempts(), 3);
+        assert_eq!(err.reason(), GiveUpReason::MaxTotalSleepReached);
+
+        // The backoff runs out first.
+        let err = f
+            .retry(backoff)
+            .max_total_sleep(Duration::from_millis(6))
+            .wrap_err()
+            .call()
+            .unwrap_err();
+        assert_eq!(err.attempts(), 4);
+        assert_eq!(err.reason(), GiveUpReason::BackoffExhausted);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "test-util")]
+    #[should_panic(expected = "fetch exceeded its test budget of 2 attempts")]
+    fn test_retry_with_test_budget() {
+        let f = || Err::<(), _>("unavailable");
+        // Within the budget, the retry ends as usual.
+        let result = f
+            .retry(ConstantBuilder::default().with_max_times(1))
+            .with_test_budget(2, Duration::from_secs(1))
+            .sleep(|_| {})
+            .call();
+        assert_eq!(result, Err("unavailable"));
+
+        let _ = f
+            .retry(ConstantBuilder::default())
+            .name("fetch")
+            .with_test_budget(2, Duration::from_secs(10))
+            .sleep(|_| {})
+            .call();
+    }
+
+    #[test]
+    #[cfg(feature = "jitter")]
+    fn test_retry_with_seed() -> anyhow::Result<()> {
+        let backoff = ExponentialBuilder::default()
+            .with_jitter()
+            .with_max_times(5);
+        let f = || Err::<(), _>(anyhow::anyhow!("unavailable"));
+        let run = |seed| {
+            let mut delays = vec![];
+            let _ = f
+                .retry(backoff)
+                .seed(seed)
+                .sleep(|_| {})
+                .notify(|_, dur| delays.push(dur))
+                .call();
+            delays
+        };
+
+        crate::seed_jitter_rng(7);
+        let next = crate::rng::u64();
+        crate::seed_jitter_rng(7);
+        assert_eq!(run(42), run(42));
+        assert_ne!(run(42), run(43));
+        // The thread's RNG isn't consumed by seeded retries.
+        assert_eq!(crate::rng::u64(), next);
+        Ok(())
+    }
+
+    #[test]
+    fn test_retry_session_id() -> anyhow::Result<()> {
+        let ids = Mutex::new(Vec::new());
+        let attempts = Mutex::new(Vec::new());
+        let backoff = ConstantBuilder::default()
+            .with_delay(Duration::from_millis(1))
+            .with_max_times(2);
+        let result = (|| {
+            let (id, attempt) = crate::current_attempt().unwrap();
+            ids.lock().push(id);
+            attempts.lock().push(attempt);
+            Err::<(), _>(anyhow::anyhow!("unavailable"))
+        })
+        .retry(backoff)
+        .sleep(|_| {})
+        .on_start(|state| ids.lock().push(state.session_id()))
+        .when_with_state(|_, state| {
+            ids.lock().push(state.session_id());
+            true
+        })
+        .notify_with_state(|_, _, state| ids.lock().push(state.session_id()))
+        .call();
+
+        assert!(result.is_err());
+        assert_eq!(*attempts.lock(), vec![1, 2, 3]);
+        assert_eq!(crate::current_attempt(), None);
+        // The ID is known before the first attempt, and stays the same across attempts.
+        let ids = ids.lock();
+        assert_eq!(ids.len(), 9);
+        assert!(ids.iter().all(|id| *id == ids[0]));
+
+        let session_id = |retry: BlockingRetry<_, _, _, _, _, _, _, _, _, _>| {
+            let mut id = None;
+            let _ = retry.on_start(|state| id = Some(state.session_id())).call();
+            id.unwrap()
+        };
+        let f = || Ok::<(), anyhow::Error>(());
+        assert_ne!(
+            session_id(f.retry(ConstantBuilder::default())),
+            session_id(f.retry(ConstantBuilder::default()))
+        );
+        assert_eq!(
+            session_id(f.retry(ConstantBuilder::default()).session_id(7).seed(42)),
+            7
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_retry_with_backoff_by() -> anyhow::Result<()> {
+        let throttled = ConstantBuilder::default()
+            .with_delay(Duration::from_millis(5))
+            .with_max_times(2);
+        let backoff = ExponentialBuilder::default().with_min_delay(Duration::from_millis(1));
+        let mut attempt = 0;
+        let f = || {
+            attempt += 1;
+            match attempt % 2 {
+                1 => Err::<(), _>(anyhow::anyhow!("throttled")),
+                _ => Err(anyhow::anyhow!("network")),
+            }
+        };
+
+        let mut delays = vec![];
+        let err = f
+            .retry(backoff)
+            .backoff_by(|e| match e.to_string().as_str() {
+                "throttled" => BackoffChoice::Class("throttled", throttled),
+                _ => BackoffChoice::Default,
+            })
+            .notify(|_, dur| delays.push(dur))
+            .wrap_err()
+            .call()
+            .unwrap_err();
+        // Each class keeps its own state, until the third throttling error exhausts its
+        // backoff.
+        assert_eq!(delays, [5, 1, 5, 2].map(Duration::from_millis));
+        assert_eq!(err.attempts(), 5);
+        assert_eq!(err.reason(), GiveUpReason::BackoffExhausted);
+        Ok(())
+    }
+
+    #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+    #[test]
+    fn test_retry_with_failure_budget() -> anyhow::Result<()> {
+        let budget = FailureBudget::new(3, Duration::from_secs(60));
+        let backoff = ExponentialBuilder::default().with_min_delay(Duration::from_millis(1));
+
+        let f = || Err::<(), _>(anyhow::anyhow!("unavailable"));
+        let result = f
+            .retry(backoff)
+            .failure_budget(budget.clone())
+            .wrap_err()
+            .call();
+        let err = result.unwrap_err();
+        assert_eq!(err.attempts(), 4);
+        assert_eq!(err.reason(), GiveUpReason::BudgetExhausted);
+
+        // Other retries sharing the budget end at their first failure.
+        let error_times = Mutex::new(0);
+        let f = || {
+            *error_times.lock() += 1;
+            Err::<(), _>(anyhow::anyhow!("unavailable"))
+        };
+        let result = f.retry(backoff).failure_budget(budget).wrap_err().call();
+        assert_eq!(result.unwrap_err().reason(), GiveUpReason::BudgetExhausted);
+        assert_eq!(*error_times.lock(), 1);
+        Ok(())
+    }
+
+    #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+    #[test]
+    fn test_retry_not_retryable_before_backoff() -> anyhow::Result<()> {
+        let budget = FailureBudget::new(0, Duration::from_secs(60));
+        let mut backoff = ExponentialBuilder::default()
+            .with_min_delay(Duration::from_millis(1))
+            .build();
+
+        let result = (|| Err::<(), _>(anyhow::anyhow!("not found")))
+            .retry(&mut backoff)
+            .when(|_| false)
+            .failure_budget(budget.clone())
+            .call();
+        assert!(result.is_err());
+        // The error isn't retried, so it takes no delay and doesn't count as a failure.
+        assert_eq!(backoff.next(), Some(Duration::from_millis(1)));
+        assert!(!budget.is_exhausted());
+        Ok(())
+    }
+
+    #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+    #[test]
+    fn test_retry_with_retry_budget() -> anyhow::Result<()> {
+        let budget = RetryBudget::new(1.0).with_min_retries_per_sec(0);
+        let backoff = ExponentialBuilder::default().with_min_delay(Duration::from_millis(1));
+
+        // A success deposits a retry, taken by the next retry before the budget runs out.
+        let result = (|| Ok::<_, anyhow::Error>(()))
+            .retry(backoff)
+            .retry_budget(budget.clone())
+            .call();
+        assert!(result.is_ok());
+        let f = || Err::<(), _>(anyhow::anyhow!("unavailable"));
+        let result = f
+            .retry(backoff)
+            .retry_budget(budget.clone())
+            .wrap_err()
+            .call();
+        let err = result.unwrap_err();
+        assert_eq!(err.attempts(), 2);
+        assert_eq!(err.reason(), GiveUpReason::BudgetExhausted);
+        assert_eq!(err.reason().to_string(), "budget exhausted");
+        assert_eq!(budget.balance(), 0.0);
+        Ok(())
+    }
+
+    #[cfg(feature = "test-util")]
+    #[test]
+    fn test_retry_with_deadline() -> anyhow::Result<()> {
+        use crate::test::ManualSleeper;
+
+        #[derive(Debug)]
+        struct Throttled(Duration);
+
+        impl RetryableError for Throttled {
+            fn is_retryable(&self) -> bool {
+                true
+            }
+
+            fn retry_after(&self) -> Option<Duration> {
+                Some(self.0)
+            }
+        }
+
+        let backoff = ConstantBuilder::default()
+            .with_delay(Duration::from_secs(10))
+            .with_max_times(5);
+        let f = || Err::<(), _>(anyhow::anyhow!("unavailable"));
+
+        // The time is told by the clock of the retry, moved forward by its sleeps: the last
+        // delay is shortened for the attempt to start with 2s left.
+        let sleeper = ManualSleeper::new();
+        let mut delays = Vec::new();
+        let deadline =
+            Deadline::after(Duration::from_secs(25)).with_min_attempt(Duration::from_secs(2));
+        let result = f
+            .retry(backoff)
+            .sleep(sleeper.clone())
+            .clock(sleeper.clone())
+            .deadline(deadline)
+            .notify(|_, dur| delays.push(dur))
+            .wrap_err()
+            .call();
+        let err = result.unwrap_err();
+        assert_eq!(err.attempts(), 4);
+        assert_eq!(err.reason(), GiveUpReason::DeadlineExceeded);
+        assert_eq!(delays, [10, 10, 3].map(Duration::from_secs));
+        assert_eq!(sleeper.now(), Duration::from_secs(23));
+
+        // No retry starts with less time left than an attempt needs.
+        let deadline = deadline.with_min_attempt(Duration::from_secs(26));
+        let result = f.retry(backoff).deadline(deadline).wrap_err().call();
+        let err = result.unwrap_err();
+        assert_eq!(err.attempts(), 1);
+        assert_eq!(err.reason(), GiveUpReason::DeadlineExceeded);
+
+        // The delays asked for by the errors are shortened too.
+        let sleeper = ManualSleeper::new();
+        let mut delays = Vec::new();
+        let result = (|| Err::<(), _>(Throttled(Duration::from_secs(60))))
+            .retry(backoff)
+            .when_retryable()
+            .sleep(sleeper.clone())
+            .clock(sleeper.clone())
+            .deadline(deadline.with_min_attempt(Duration::from_secs(2)))
+            .notify(|_, dur| delays.push(dur))
+            .call();
+        assert!(result.is_err());
+        assert_eq!(delays, [Duration::from_secs(23)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_retry_with_recover() -> anyhow::Result<()> {
+        let fatal = Mutex::new(Vec::new());
+        let backoff = ExponentialBuilder::default().with_min_delay(Duration::from_millis(1));
+        let retry = |status: u16| {
+            (|| Err::<Vec<u8>, _>(status))
+                .retry(backoff)
+                .when(|status| *status >= 500)
+                .recover(|status| (*status == 404).then(Vec::new))
+                .on_fatal_error(|status, _| fatal.lock().push(*status))
+                .call()
+        };
+
+        // Not found is recovered as an empty list.
+        assert_eq!(retry(404), Ok(vec![]));
+        assert_eq!(retry(403), Err(403));
+        assert_eq!(retry(503), Err(503));
+        assert_eq!(*fatal.lock(), vec![403, 503]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_retry_with_recover_observed() -> anyhow::Result<()> {
+        #[derive(Default)]
+        struct GiveUps(Mutex<Vec<GiveUpReason>>);
+
+        impl RetryMetrics for GiveUps {
+            fn gave_up(&self, reason: GiveUpReason, _: &RetryState) {
+                self.0.lock().push(reason);
+            }
+        }
+
+        let metrics = GiveUps::default();
+        let events = Mutex::new(Vec::new());
+        let result = (|| Err::<Vec<u8>, _>(404))
+            .retry(ExponentialBuilder::default())
+            .when(|status| *status >= 500)
+            .recover(|status| (*status == 404).then(Vec::new))
+            .metrics(&metrics)
+            .observe(|event| {
+                if let RetryEvent::GaveUp { attempts, reason } = event {
+                    events.lock().push((attempts, reason));
+                }
+            })
+            .call();
+
+        // The recovered retry is seen giving up, the error not being retryable.
+        assert_eq!(result, Ok(vec![]));
+        assert_eq!(*metrics.0.lock(), vec![GiveUpReason::NotRetryable]);
+        assert_eq!(*events.lock(), vec![(1, GiveUpReason::NotRetryable)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_retry_with_on_start() -> anyhow::Result<()> {
+        let started = Mutex::new(Vec::new());
+        let backoff = ExponentialBuilder::default()
+            .with_min_delay(Duration::from_millis(1))
+            .with_max_times(2);
+        let result = always_error
+            .retry(backoff)
+            .on_start(|state| started.lock().push(state.attempt()))
+            .call();
+
+        assert!(result.is_err());
+        assert_eq!(*started.lock(), vec![1]);
+        Ok(())
+    }
+
+    #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+    #[test]
+    fn test_retry_with_report() -> anyhow::Result<()> {
+        let error_times = Mutex::new(0);
+        let f = || {
+            let mut x = error_times.lock();
+            *x += 1;
+            if *x < 3 {
+                Err(*x)
+            } else {
+                Ok(())
+            }
+        };
+
+        let backoff = ExponentialBuilder::default().with_min_delay(Duration::from_millis(1));
+        let (result, report) = f
+            .retry(backoff)
+            .name("fetch")
+            .tag("region", "eu-west-1")
+            .call_with_report();
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(report.name(), Some("fetch"));
+        assert_eq!(report.tags(), [("region", "eu-west-1".into())]);
+        assert_eq!(report.attempts(), 3);
+        assert_eq!(report.slept(), Duration::from_millis(3));
+        if cfg!(feature = "timing") {
+            assert!(report.elapsed() >= report.slept());
+            assert_eq!(report.latencies().len(), 3);
+        } else {
+            // Without the `timing` feature, the attempts aren't timed.
+            assert_eq!(report.elapsed(), Duration::ZERO);
+            assert!(report.latencies().is_empty());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_retry_immediately() -> anyhow::Result<()> {
+        let immediate = Mutex::new(Vec::new());
+        let backoff = ConstantBuilder::default()
+            .with_delay(Duration::ZERO)
+            .with_max_times(2);
+        let (result, report) = always_error
+            .retry(backoff)
+            .observe(|event| {
+                if let RetryEvent::Immediate { attempt } = event {
+                    immediate.lock().push(attempt);
+                }
+            })
+            .call_with_report();
+
+        assert!(result.is_err());
+        assert_eq!(*immediate.lock(), vec![1, 2]);
+        assert_eq!(report.immediate_retries(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_retry_with_observe() -> anyhow::Result<()> {
+        let events = Mutex::new(Vec::new());
+        let error_times = Mutex::new(0);
+        let f = || {
+            let mut x = error_times.lock();
+            *x += 1;
+            if *x < 2 {
+                Err(*x)
+            } else {
+                Ok(())
+            }
+        };
+
+        let backoff = ExponentialBuilder::default().with_min_delay(Duration::from_millis(1));
+        let result = f
+            .retry(backoff)
+            .name("fetch")
+            .session_id(7)
+            .budget_warning(BudgetWarning::new(0.5).with_max_attempts(2))
+            .observe(|event| {
+                events.lock().push(match event {
+                    RetryEvent::Started {
+                        name,
+                        session_id,
+                        attempt,
+                        ..
+                    } => format!("started {} {session_id} {attempt}", name.unwrap()),
+                    RetryEvent::AttemptFinished { attempt, .. } => format!("finished {attempt}"),
+                    RetryEvent::AttemptFailed { attempt, err } => {
+                        format!("attempt {attempt} failed: {err}")
+                    }
+                    RetryEvent::BudgetWarning { attempt, consumed } => {
+                        format!("budget warning {attempt} {consumed}")
+                    }
+                    RetryEvent::Sleeping { dur } => format!("sleeping {dur:?}"),
+                    RetryEvent::Immediate { attempt } => format!("immediate {attempt}"),
+                    RetryEvent::Resumed { attempt, slept } => {
+                        format!("resumed {attempt} after {slept:?}")
+                    }
+                    RetryEvent::Succeeded { attempts } => format!("succeeded {attempts}"),
+                    RetryEvent::GaveUp { attempts, reason } => {
+                        format!("gave up {attempts}: {reason}")
+                    }
+                })
+            })
+            .call();
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            *events.lock(),
+            vec![
+                "started fetch 7 1",
+                "finished 1",
+                "attempt 1 failed: 1",
+                "budget warning 1 0.5",
+                "sleeping 1ms",
+                "resumed 2 after 1ms",
+                "started fetch 7 2",
+                "finished 2",
+                "succeeded 2"
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_retry_with_first_error() -> anyhow::Result<()> {
+        let error_times = Mutex::new(0);
+
+        let f = || {
+            let mut x = error_times.lock();
+            *x += 1;
+            // Later attempts only see the token used by the first one.
+            Err::<(), _>(if *x == 1 {
+                "conflict"
+            } else {
+                "token already used"
+            })
+        };
+
+        let backoff = ExponentialBuilder::default().with_min_delay(Duration::from_millis(1));
+        let result = f
+            .retry(backoff)
+            .final_error(FinalError::First)
+            .wrap_err_with_history(3)
+            .call();
+
+        let err = result.unwrap_err();
+        assert_eq!(err.attempts(), 4);
+        assert_eq!(*err.get_ref(), "conflict");
+        // The first error is returned instead of being kept in the history.
+        assert!(err.history()[0].error().is_none());
+        assert_eq!(err.history()[1].error(), Some(&"token already used"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_retry_with_non_retryable() -> anyhow::Result<()> {
+        let error_times = Mutex::new(0);
+
+        let f = || -> Result<(), Permanent<anyhow::Error>> {
+            let mut x = error_times.lock();
+            *x += 1;
+            if *x == 2 {
+                return Err(non_retryable(anyhow::anyhow!("fatal")));
+            }
+            Err(anyhow::anyhow!("retryable"))?
+        };
+
+        let backoff = ExponentialBuilder::default().with_min_delay(Duration::from_millis(1));
+        let result: Result<(), anyhow::Error> = f
+            .retry(backoff)
+            // Permanent errors end the retry even if `when` says otherwise.
+            .when(|_| true)
+            .unwrap_permanent()
+            .call();
+
+        assert_eq!("fatal", result.unwrap_err().to_string());
+        assert_eq!(*error_times.lock(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_retry_when_with_state() -> anyhow::Result<()> {
+        let error_times = Mutex::new(0);
+        let mut attempts = vec![];
+
+        let f = || {
+            let mut x = error_times.lock();
+            *x += 1;
+            Err::<(), anyhow::Error>(anyhow::anyhow!("retryable"))
+        };
+
+        let backoff = ExponentialBuilder::default()
+            .with_min_delay(Duration::from_millis(1))
+            .with_max_times(2);
+        let result = f
+            .retry(backoff)
+            .when_with_state(|_, state| {
+                attempts.push((state.attempt(), state.next_delay().is_some()));
+                true
+            })
+            .call();
+
+        assert!(result.is_err());
+        assert_eq!(*error_times.lock(), 3);
+        // The backoff only proposes a delay once the error is retried, so the first attempt
+        // has none yet.
+        assert_eq!(attempts, vec![(1, false), (2, true), (3, true)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_retry_with_map_err_before() -> anyhow::Result<()> {
+        let error_times = Mutex::new(0);
+        let mut notified: Vec<String> = vec![];
+
+        let f = || {
+            let mut x = error_times.lock();
+            *x += 1;
+            Err::<(), _>(anyhow::anyhow!("retryable {}", *x))
+        };
+
+        let backoff = ExponentialBuilder::default().with_min_delay(Duration::from_millis(1));
+        let result: Result<(), String> = f
+            .retry(backoff)
+            .map_err_before(|e| e.to_string())
+            .when(|e| e.starts_with("retryable"))
+            .notify(|e, _| notified.push(e.clone()))
+            .call();
+
+        assert_eq!(result.unwrap_err(), "retryable 4");
+        assert_eq!(notified, vec!["retryable 1", "retryable 2", "retryable 3"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_retry_with_classify() -> anyhow::Result<()> {
+        let f = || Err::<(), anyhow::Error>(anyhow::anyhow!("retryable"));
+
+        let backoff = ExponentialBuilder::default().with_min_delay(Duration::from_millis(1));
+        let result = f
+            .retry(backoff)
+            .classify(|e, state| {
+                let e = e.context(format!("attempt {}", state.attempt()));
+                if state.attempt() < 2 {
+                    ControlFlow::Continue(e)
+                } else {
+                    ControlFlow::Break(e)
+                }
+            })
+            .call();
+
+        let err = result.unwrap_err();
+        assert_eq!("attempt 2", err.to_string());
+        assert_eq!("retryable", err.root_cause().to_string());
+        Ok(())
+    }
+
+    #[test]
+    fn test_retry_with_error_history() -> anyhow::Result<()> {
+        let error_times = Mutex::new(0);
+
+        let f = || {
+            let mut x = error_times.lock();
+            *x += 1;
+            Err::<(), _>(anyhow::anyhow!("error {}", *x))
+        };
+
+        let backoff = ExponentialBuilder::default().with_min_delay(Duration::from_millis(1));
+        let result = f.retry(backoff).wrap_err_with_history(2).call();
+
+        let err = result.unwrap_err();
+        assert_eq!(err.attempts(), 4);
+        assert_eq!("error 4", err.get_ref().to_string());
+        // Only the 2 most recent failed attempts are kept.
+        let history: Vec<_> = err
+            .history()
+            .iter()
+            .map(|v| (v.attempt(), v.error().unwrap().to_string()))
+            .collect();
+        assert_eq!(
+            history,
+            vec![(2, "error 2".to_string()), (3, "error 3".to_string())]
+        );
+        assert!(err.history()[0].delay() < err.history()[1].delay());
+        Ok(())
+    }
+
     #[test]
     fn test_fn_mut_when_and_notify() -> anyhow::Result<()> {
         let mut calls_retryable: Vec<()> = vec![];
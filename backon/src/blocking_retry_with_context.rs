@@ -1,8 +1,59 @@
+use alloc::borrow::Cow;
+use core::any;
+use core::convert::Infallible;
+use core::fmt;
+use core::marker::PhantomData;
+use core::ops::ControlFlow;
 use core::time::Duration;
 
 use crate::backoff::BackoffBuilder;
 use crate::blocking_sleep::MaybeBlockingSleeper;
-use crate::{Backoff, BlockingSleeper, DefaultBlockingSleeper};
+use crate::clock::SharedClock;
+#[cfg(feature = "anyhow")]
+use crate::error::WhenDowncast;
+use crate::error::{
+    BackoffBy, Classify, ErrorClassifier, ErrorCollector, LastError, MapErrBefore, MapError,
+    ReduceErrors, SelectError, Unmapped, UnwrapPermanent, WhenRetryableError, WhenWithState,
+    WrapRetryError,
+};
+#[cfg(feature = "defmt")]
+use crate::hooks::Defmt;
+#[cfg(feature = "logging")]
+use crate::hooks::Log;
+#[cfg(feature = "opentelemetry")]
+use crate::hooks::OpenTelemetry;
+#[cfg(feature = "sentry")]
+use crate::hooks::Sentry;
+#[cfg(feature = "slog")]
+use crate::hooks::Slog;
+#[cfg(feature = "tracing")]
+use crate::hooks::Trace;
+use crate::hooks::{
+    Chain, Hooks, Metrics, NoHooks, Observe, OnBackoffExhausted, OnFatal, OnStart, OnTransient,
+    Recover,
+};
+use crate::retry::NotifyWithState;
+use crate::retry_with_context::{
+    ContextSource, ContextValue, NotifyFnWithContext, NotifyWithContext, PleaseProvideAContext,
+};
+use crate::state::CurrentAttempt;
+use crate::BackoffChoice;
+use crate::BudgetWarning;
+use crate::Deadline;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+use crate::FailureBudget;
+use crate::FinalError;
+use crate::GiveUpReason;
+#[cfg(feature = "std")]
+use crate::PanicOrError;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+use crate::RetryBudget;
+use crate::RetryEvent;
+use crate::RetryMetrics;
+use crate::RetryReport;
+use crate::RetrySnapshot;
+use crate::ScheduleSummary;
+use crate::{Backoff, BlockingSleeper, Clock, DefaultBlockingSleeper, RetryState, RetryableError};
 
 /// BlockingRetryableWithContext adds retry support for blocking functions.
 pub trait BlockingRetryableWithContext<
@@ -37,13 +88,67 @@ pub struct BlockingRetryWithContext<
     SF: MaybeBlockingSleeper = DefaultBlockingSleeper,
     RF = fn(&E) -> bool,
     NF = fn(&E, Duration),
+    EC = LastError,
+    ME = Unmapped,
+    HK = NoHooks,
+    CS = PleaseProvideAContext,
 > {
     backoff: B,
     retryable: RF,
     notify: NF,
+    errors: EC,
+    map_err: ME,
+    hooks: HK,
+    retry_state: RetryState,
     f: F,
     sleep_fn: SF,
-    ctx: Option<Ctx>,
+    ctx: CS,
+    _ctx: PhantomData<Ctx>,
+}
+
+impl<B, T, E, Ctx, F, SF, RF, NF, EC, ME, HK, CS>
+    BlockingRetryWithContext<B, T, E, Ctx, F, SF, RF, NF, EC, ME, HK, CS>
+where
+    B: Backoff,
+    F: FnMut(Ctx) -> (Ctx, Result<T, E>),
+    SF: MaybeBlockingSleeper,
+{
+    /// Returns the index of the current attempt, starting from 1 for the first attempt, or 0
+    /// before the retry starts.
+    pub fn attempt(&self) -> usize {
+        self.retry_state.attempt()
+    }
+
+    /// Returns the time elapsed since the first attempt started.
+    pub fn elapsed(&self) -> Duration {
+        self.retry_state.elapsed()
+    }
+
+    /// Capture the progress of the retry and the state of its backoff, to be restored by
+    /// `resume_from`.
+    pub fn snapshot(&self) -> RetrySnapshot<B>
+    where
+        B: Clone,
+    {
+        RetrySnapshot::new(self.retry_state.attempt(), self.backoff.clone())
+            .with_elapsed(self.retry_state.elapsed())
+    }
+}
+
+impl<B, T, E, Ctx, F, SF, RF, NF, EC, ME, HK, CS> fmt::Debug
+    for BlockingRetryWithContext<B, T, E, Ctx, F, SF, RF, NF, EC, ME, HK, CS>
+where
+    B: Backoff,
+    F: FnMut(Ctx) -> (Ctx, Result<T, E>),
+    SF: MaybeBlockingSleeper,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BlockingRetryWithContext")
+            .field("backoff", &any::type_name::<B>())
+            .field("attempt", &self.retry_state.attempt())
+            .field("next_delay", &self.retry_state.next_delay())
+            .finish_non_exhaustive()
+    }
 }
 
 impl<B, T, E, Ctx, F> BlockingRetryWithContext<B, T, E, Ctx, F>
@@ -57,32 +162,103 @@ where
             backoff,
             retryable: |_: &E| true,
             notify: |_: &E, _: Duration| {},
+            errors: LastError,
+            map_err: Unmapped,
+            hooks: NoHooks,
+            retry_state: RetryState::new(),
             sleep_fn: DefaultBlockingSleeper::default(),
             f,
-            ctx: None,
+            ctx: PleaseProvideAContext,
+            _ctx: PhantomData,
         }
     }
 }
 
-impl<B, T, E, Ctx, F, SF, RF, NF> BlockingRetryWithContext<B, T, E, Ctx, F, SF, RF, NF>
+#[allow(clippy::type_complexity)]
+impl<B, T, E, Ctx, F, SF, RF, NF, EC, ME, HK, CS>
+    BlockingRetryWithContext<B, T, E, Ctx, F, SF, RF, NF, EC, ME, HK, CS>
 where
     B: Backoff,
     F: FnMut(Ctx) -> (Ctx, Result<T, E>),
     SF: MaybeBlockingSleeper,
-    RF: FnMut(&E) -> bool,
-    NF: FnMut(&E, Duration),
+    RF: ErrorClassifier<ME::Error>,
+    NF: NotifyWithContext<ME::Error, Ctx>,
+    HK: Hooks<T, ME::Error>,
+    ME: MapError<E>,
+    EC: ErrorCollector<ME::Error>,
 {
     /// Set the context for retrying.
     ///
     /// Context is used to capture ownership manually to prevent lifetime issues.
-    pub fn context(self, context: Ctx) -> BlockingRetryWithContext<B, T, E, Ctx, F, SF, RF, NF> {
+    pub fn context(
+        self,
+        context: Ctx,
+    ) -> BlockingRetryWithContext<B, T, E, Ctx, F, SF, RF, NF, EC, ME, HK, ContextValue<Ctx>> {
         BlockingRetryWithContext {
             backoff: self.backoff,
             retryable: self.retryable,
             notify: self.notify,
             f: self.f,
             sleep_fn: self.sleep_fn,
-            ctx: Some(context),
+            ctx: ContextValue(context),
+            _ctx: PhantomData,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+        }
+    }
+
+    /// Set a function to build the context for retrying.
+    ///
+    /// The function is only invoked once the retry is started by [`BlockingRetryWithContext::call`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use anyhow::anyhow;
+    /// use anyhow::Result;
+    /// use backon::BlockingRetryableWithContext;
+    /// use backon::ExponentialBuilder;
+    ///
+    /// struct Test;
+    ///
+    /// impl Test {
+    ///     fn hello(&mut self) -> Result<usize> {
+    ///         Err(anyhow!("not retryable"))
+    ///     }
+    /// }
+    ///
+    /// fn main() -> Result<()> {
+    ///     let (_, result) = {
+    ///         |mut v: Test| {
+    ///             let res = v.hello();
+    ///             (v, res)
+    ///         }
+    ///     }
+    ///     .retry(ExponentialBuilder::default())
+    ///     .context_with(|| Test)
+    ///     .call();
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn context_with<CN: FnOnce() -> Ctx>(
+        self,
+        context: CN,
+    ) -> BlockingRetryWithContext<B, T, E, Ctx, F, SF, RF, NF, EC, ME, HK, CN> {
+        BlockingRetryWithContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            f: self.f,
+            sleep_fn: self.sleep_fn,
+            ctx: context,
+            _ctx: PhantomData,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
         }
     }
 
@@ -94,7 +270,7 @@ where
     pub fn sleep<SN: BlockingSleeper>(
         self,
         sleep_fn: SN,
-    ) -> BlockingRetryWithContext<B, T, E, Ctx, F, SN, RF, NF> {
+    ) -> BlockingRetryWithContext<B, T, E, Ctx, F, SN, RF, NF, EC, ME, HK, CS> {
         BlockingRetryWithContext {
             backoff: self.backoff,
             retryable: self.retryable,
@@ -102,16 +278,58 @@ where
             f: self.f,
             sleep_fn,
             ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+        }
+    }
+    /// Set the function mapping the errors of the retried function before they are passed to
+    /// the conditions and notify.
+    ///
+    /// This allows reusing the same conditions for functions returning differently wrapped
+    /// errors. It resets the conditions, notify and error handling set before, so it must be
+    /// called first.
+    pub fn map_err_before<E2, MN: FnMut(E) -> E2>(
+        self,
+        map_err: MN,
+    ) -> BlockingRetryWithContext<
+        B,
+        T,
+        E,
+        Ctx,
+        F,
+        SF,
+        fn(&E2) -> bool,
+        fn(&E2, Duration),
+        LastError,
+        MapErrBefore<MN>,
+        NoHooks,
+        CS,
+    > {
+        BlockingRetryWithContext {
+            backoff: self.backoff,
+            retryable: |_: &E2| true,
+            notify: |_: &E2, _: Duration| {},
+            f: self.f,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: LastError,
+            retry_state: self.retry_state,
+            map_err: MapErrBefore(map_err),
+            hooks: NoHooks,
         }
     }
 
     /// Set the conditions for retrying.
     ///
     /// If not specified, all errors are considered retryable.
-    pub fn when<RN: FnMut(&E) -> bool>(
+    pub fn when<RN: FnMut(&ME::Error) -> bool>(
         self,
         retryable: RN,
-    ) -> BlockingRetryWithContext<B, T, E, Ctx, F, SF, RN, NF> {
+    ) -> BlockingRetryWithContext<B, T, E, Ctx, F, SF, RN, NF, EC, ME, HK, CS> {
         BlockingRetryWithContext {
             backoff: self.backoff,
             retryable,
@@ -119,6 +337,303 @@ where
             f: self.f,
             sleep_fn: self.sleep_fn,
             ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+        }
+    }
+
+    /// Set the conditions for retrying with access to the [`RetryState`].
+    ///
+    /// The state tells the index of the failed attempt and the delay slept before it, the
+    /// backoff only proposing the next delay once the error is retried. This replaces the
+    /// conditions set by `when`.
+    pub fn when_with_state<RN: FnMut(&ME::Error, &RetryState) -> bool>(
+        self,
+        retryable: RN,
+    ) -> BlockingRetryWithContext<B, T, E, Ctx, F, SF, WhenWithState<RN>, NF, EC, ME, HK, CS> {
+        BlockingRetryWithContext {
+            backoff: self.backoff,
+            retryable: WhenWithState(retryable),
+            notify: self.notify,
+            f: self.f,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+        }
+    }
+
+    /// Set the conditions for retrying with the error passed by value.
+    ///
+    /// The function returns [`ControlFlow::Continue`] with the error to retry, or
+    /// [`ControlFlow::Break`] with the error to end the retry with. So errors can be enriched
+    /// on every attempt, for example with the attempt index from [`RetryState`]. This replaces
+    /// the conditions set by `when`.
+    pub fn classify<RN: FnMut(ME::Error, &RetryState) -> ControlFlow<ME::Error, ME::Error>>(
+        self,
+        retryable: RN,
+    ) -> BlockingRetryWithContext<B, T, E, Ctx, F, SF, Classify<RN>, NF, EC, ME, HK, CS> {
+        BlockingRetryWithContext {
+            backoff: self.backoff,
+            retryable: Classify(retryable),
+            notify: self.notify,
+            f: self.f,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+        }
+    }
+
+    /// Set the conditions for retrying by the error's [`RetryableError`] implementation.
+    ///
+    /// The delay suggested by [`RetryableError::retry_after`] is used instead of the backoff's
+    /// delay if present. This replaces the conditions set by `when`.
+    pub fn when_retryable(
+        self,
+    ) -> BlockingRetryWithContext<B, T, E, Ctx, F, SF, WhenRetryableError, NF, EC, ME, HK, CS>
+    where
+        ME::Error: RetryableError,
+    {
+        BlockingRetryWithContext {
+            backoff: self.backoff,
+            retryable: WhenRetryableError,
+            notify: self.notify,
+            f: self.f,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+        }
+    }
+
+    /// Set the function choosing the backoff of every error, see [`BackoffChoice`].
+    ///
+    /// Errors of the class returned by the function are retried with its own backoff,
+    /// independently of the other classes, and those returning [`BackoffChoice::Default`] with
+    /// the backoff of the retry. It applies to the conditions set before, so it must be called
+    /// after `when` and the like.
+    pub fn backoff_by<BB: BackoffBuilder, BN: FnMut(&ME::Error) -> BackoffChoice<BB>>(
+        self,
+        choose: BN,
+    ) -> BlockingRetryWithContext<B, T, E, Ctx, F, SF, BackoffBy<RF, BN, BB>, NF, EC, ME, HK, CS>
+    {
+        BlockingRetryWithContext {
+            backoff: self.backoff,
+            retryable: BackoffBy::new(self.retryable, choose),
+            notify: self.notify,
+            f: self.f,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+        }
+    }
+
+    /// Set the conditions for retrying [`anyhow::Error`]s caused by `X`.
+    ///
+    /// The whole chain of the error is inspected, so errors with context attached are retried
+    /// as well. This replaces the conditions set by `when`.
+    #[cfg(feature = "anyhow")]
+    pub fn when_downcast<X: core::error::Error + 'static>(
+        self,
+    ) -> BlockingRetryWithContext<B, T, E, Ctx, F, SF, WhenDowncast<X>, NF, EC, ME, HK, CS>
+    where
+        ME: MapError<E, Error = anyhow::Error>,
+    {
+        BlockingRetryWithContext {
+            backoff: self.backoff,
+            retryable: WhenDowncast::new(),
+            notify: self.notify,
+            f: self.f,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+        }
+    }
+
+    /// Set the function reducing the errors of all attempts into the returned one.
+    ///
+    /// The function is called with the reduced error so far and the error of the next attempt.
+    /// If not specified, the error of the last attempt is returned.
+    ///
+    /// This replaces the previous reducer, and must be set before `wrap_err`.
+    pub fn reduce_errors<RN: FnMut(ME::Error, ME::Error) -> ME::Error>(
+        self,
+        reduce: RN,
+    ) -> BlockingRetryWithContext<
+        B,
+        T,
+        E,
+        Ctx,
+        F,
+        SF,
+        RF,
+        NF,
+        ReduceErrors<ME::Error, RN>,
+        ME,
+        HK,
+        CS,
+    >
+    where
+        EC: ErrorCollector<ME::Error, Error = ME::Error>,
+    {
+        BlockingRetryWithContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            f: self.f,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: ReduceErrors::new(reduce),
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+        }
+    }
+
+    /// Set the policy selecting the error returned when the retry ends unsuccessfully.
+    ///
+    /// If not specified, the error of the last attempt is returned. See [`FinalError`] for
+    /// the policies.
+    ///
+    /// This replaces the previous reducer, and must be set before `wrap_err`.
+    pub fn final_error(
+        self,
+        policy: FinalError<ME::Error>,
+    ) -> BlockingRetryWithContext<B, T, E, Ctx, F, SF, RF, NF, SelectError<ME::Error>, ME, HK, CS>
+    where
+        EC: ErrorCollector<ME::Error, Error = ME::Error>,
+    {
+        BlockingRetryWithContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            f: self.f,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: SelectError::new(policy),
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+        }
+    }
+
+    /// End the retry at errors wrapped by [`non_retryable`](crate::non_retryable), and unwrap
+    /// the returned [`Permanent`](crate::Permanent) error.
+    ///
+    /// Permanent errors end the retry regardless of the conditions set by `when`.
+    pub fn unwrap_permanent(
+        self,
+    ) -> BlockingRetryWithContext<B, T, E, Ctx, F, SF, RF, NF, UnwrapPermanent<EC>, ME, HK, CS>
+    where
+        UnwrapPermanent<EC>: ErrorCollector<ME::Error>,
+    {
+        BlockingRetryWithContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            f: self.f,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: UnwrapPermanent(self.errors),
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+        }
+    }
+
+    /// Wrap the error that ends the retry into a [`RetryError`](crate::RetryError).
+    ///
+    /// The wrapper records the number of attempts made and keeps the original error as its
+    /// [`source`](core::error::Error::source).
+    pub fn wrap_err(
+        self,
+    ) -> BlockingRetryWithContext<
+        B,
+        T,
+        E,
+        Ctx,
+        F,
+        SF,
+        RF,
+        NF,
+        WrapRetryError<EC, EC::Error>,
+        ME,
+        HK,
+        CS,
+    > {
+        BlockingRetryWithContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            f: self.f,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: WrapRetryError::new(self.errors, 0),
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+        }
+    }
+
+    /// Wrap the error that ends the retry into a [`RetryError`](crate::RetryError), keeping
+    /// the history of up to `cap` most recent failed attempts.
+    ///
+    /// Every entry of the history records the error, the delay slept after it and when it
+    /// happened, so the failure timeline can be reported once the retry ends.
+    pub fn wrap_err_with_history(
+        self,
+        cap: usize,
+    ) -> BlockingRetryWithContext<
+        B,
+        T,
+        E,
+        Ctx,
+        F,
+        SF,
+        RF,
+        NF,
+        WrapRetryError<EC, EC::Error>,
+        ME,
+        HK,
+        CS,
+    > {
+        BlockingRetryWithContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            f: self.f,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: WrapRetryError::new(self.errors, cap),
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
         }
     }
 
@@ -127,10 +642,10 @@ where
     /// When a retry happens, the input function will be invoked with the error and the sleep duration before pausing.
     ///
     /// If not specified, this operation does nothing.
-    pub fn notify<NN: FnMut(&E, Duration)>(
+    pub fn notify<NN: FnMut(&ME::Error, Duration)>(
         self,
         notify: NN,
-    ) -> BlockingRetryWithContext<B, T, E, Ctx, F, SF, RF, NN> {
+    ) -> BlockingRetryWithContext<B, T, E, Ctx, F, SF, RF, NN, EC, ME, HK, CS> {
         BlockingRetryWithContext {
             backoff: self.backoff,
             retryable: self.retryable,
@@ -138,45 +653,707 @@ where
             f: self.f,
             sleep_fn: self.sleep_fn,
             ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
         }
     }
+
+    /// Set the function called once before the first attempt starts.
+    ///
+    /// It's called in addition to the functions set before, so per-call setup like starting
+    /// a timer or logging the operation can live in the retry.
+    pub fn on_start<ON: FnMut(&RetryState)>(
+        self,
+        f: ON,
+    ) -> BlockingRetryWithContext<B, T, E, Ctx, F, SF, RF, NF, EC, ME, Chain<HK, OnStart<ON>>, CS>
+    {
+        BlockingRetryWithContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            f: self.f,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, OnStart(f)),
+        }
+    }
+
+    /// Set to notify for all retry attempts with access to the context.
+    ///
+    /// When a retry happens, the input function will be invoked with the error, the sleep duration
+    /// and the context before pausing. This replaces the function set by [`BlockingRetryWithContext::notify`].
+    ///
+    /// If not specified, this operation does nothing.
+    pub fn notify_with_ctx<NN: FnMut(&ME::Error, Duration, &Ctx)>(
+        self,
+        notify: NN,
+    ) -> BlockingRetryWithContext<B, T, E, Ctx, F, SF, RF, NotifyFnWithContext<NN>, EC, ME, HK, CS>
+    {
+        BlockingRetryWithContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: NotifyFnWithContext(notify),
+            f: self.f,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+        }
+    }
+
+    /// Set to notify for all retry attempts with access to the [`RetryState`].
+    ///
+    /// The state tells the session ID of the retry and the index of the failed attempt, so
+    /// the notifications can be correlated with the logs of the retried operation. This
+    /// replaces the function set by `notify`.
+    pub fn notify_with_state<NN: FnMut(&ME::Error, Duration, &RetryState)>(
+        self,
+        notify: NN,
+    ) -> BlockingRetryWithContext<B, T, E, Ctx, F, SF, RF, NotifyWithState<NN>, EC, ME, HK, CS>
+    {
+        BlockingRetryWithContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: NotifyWithState(notify),
+            f: self.f,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+        }
+    }
+
+    /// Set the function called with every error that will be retried and the delay before
+    /// the next attempt.
+    ///
+    /// It's called after `notify`, and in addition to the functions set before.
+    pub fn on_transient_error<TN: FnMut(&ME::Error, Duration)>(
+        self,
+        f: TN,
+    ) -> BlockingRetryWithContext<B, T, E, Ctx, F, SF, RF, NF, EC, ME, Chain<HK, OnTransient<TN>>, CS>
+    {
+        BlockingRetryWithContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            f: self.f,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, OnTransient(f)),
+        }
+    }
+
+    /// Set the function called with the error that ends the retry unsuccessfully and the
+    /// reason why.
+    ///
+    /// Both non-retryable errors and errors left when the backoff is exhausted end the retry.
+    /// It's called in addition to the functions set before.
+    pub fn on_fatal_error<FN: FnMut(&ME::Error, GiveUpReason)>(
+        self,
+        f: FN,
+    ) -> BlockingRetryWithContext<B, T, E, Ctx, F, SF, RF, NF, EC, ME, Chain<HK, OnFatal<FN>>, CS>
+    {
+        BlockingRetryWithContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            f: self.f,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, OnFatal(f)),
+        }
+    }
+
+    /// Set the function called when the retry gives up because the backoff is exhausted,
+    /// with the last error and the [`ScheduleSummary`] of the delays planned and slept.
+    ///
+    /// Unlike `on_fatal_error` it's not called for errors that are not retryable, so an
+    /// exhausted policy can be told apart from a fatal error.
+    pub fn on_backoff_exhausted<BN: FnMut(&ME::Error, &ScheduleSummary)>(
+        self,
+        f: BN,
+    ) -> BlockingRetryWithContext<
+        B,
+        T,
+        E,
+        Ctx,
+        F,
+        SF,
+        RF,
+        NF,
+        EC,
+        ME,
+        Chain<HK, OnBackoffExhausted<BN>>,
+        CS,
+    > {
+        BlockingRetryWithContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            f: self.f,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, OnBackoffExhausted::new(f)),
+        }
+    }
+
+    /// Set the function recovering from the error that ends the retry unsuccessfully.
+    ///
+    /// Both non-retryable errors and errors left when the backoff is exhausted are passed to
+    /// it, and the value it returns is returned as the result of the retry instead. Errors
+    /// that are not recovered are passed on to `on_fatal_error` and returned as usual.
+    ///
+    /// Recovered retries still gave up: their metrics, events and traces record them as
+    /// giving up, only `on_fatal_error` isn't called.
+    pub fn recover<RN: FnMut(&ME::Error) -> Option<T>>(
+        self,
+        f: RN,
+    ) -> BlockingRetryWithContext<B, T, E, Ctx, F, SF, RF, NF, EC, ME, Chain<HK, Recover<RN>>, CS>
+    {
+        BlockingRetryWithContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            f: self.f,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, Recover(f)),
+        }
+    }
+
+    /// Set the [`RetryMetrics`] observing the progress of the retry.
+    ///
+    /// It's called in addition to the metrics set before.
+    pub fn metrics<MN: RetryMetrics>(
+        self,
+        metrics: MN,
+    ) -> BlockingRetryWithContext<B, T, E, Ctx, F, SF, RF, NF, EC, ME, Chain<HK, Metrics<MN>>, CS>
+    {
+        BlockingRetryWithContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            f: self.f,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, Metrics(metrics)),
+        }
+    }
+
+    /// Set the function observing every step of the retry as a [`RetryEvent`].
+    ///
+    /// It's called in addition to the other hooks and observers set before, so a single
+    /// function can follow the whole lifecycle of the retry. Its events are no longer delivered
+    /// to the observer set by `set_global_observer`.
+    pub fn observe<FN: FnMut(RetryEvent<'_, ME::Error>)>(
+        mut self,
+        f: FN,
+    ) -> BlockingRetryWithContext<B, T, E, Ctx, F, SF, RF, NF, EC, ME, Chain<HK, Observe<FN>>, CS>
+    {
+        self.retry_state.set_observed();
+        BlockingRetryWithContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            f: self.f,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, Observe(f)),
+        }
+    }
+
+    /// Record the retry in a `tracing` span named `retry`, with the `operation` and
+    /// `session_id` fields set.
+    ///
+    /// The retried function runs inside the span, and every sleep inside a child span named
+    /// `sleep` with the `operation`, `attempt` and `delay` fields. Failed attempts are emitted
+    /// as `WARN` events, sleeps, resumes and successes as `DEBUG` events and giving up as an
+    /// `ERROR` event, with the attempt number, delay, error and reason as fields.
+    #[cfg(feature = "tracing")]
+    pub fn trace(
+        self,
+        operation: &'static str,
+    ) -> BlockingRetryWithContext<B, T, E, Ctx, F, SF, RF, NF, EC, ME, Chain<HK, Trace>, CS>
+    where
+        ME::Error: fmt::Display,
+    {
+        BlockingRetryWithContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            f: self.f,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, Trace::new(operation)),
+        }
+    }
+
+    /// Record the retry on the current OpenTelemetry span and in OpenTelemetry metrics, with
+    /// the `operation.name` attribute set to `operation`.
+    ///
+    /// Every failed attempt is added to the span active when this is called as a
+    /// `retry.attempt_failed` event and giving up as a `retry.gave_up` event, carrying the
+    /// `retry.attempt`, `retry.delay`, `error.type` and `exception.message` attributes. The
+    /// `backon.retry.attempts`, `backon.retry.retries` and `backon.retry.giveups` counters and
+    /// the `backon.retry.sleep.duration` histogram are reported to the global meter provider.
+    #[cfg(feature = "opentelemetry")]
+    pub fn opentelemetry(
+        self,
+        operation: &'static str,
+    ) -> BlockingRetryWithContext<B, T, E, Ctx, F, SF, RF, NF, EC, ME, Chain<HK, OpenTelemetry>, CS>
+    where
+        ME::Error: fmt::Display,
+    {
+        BlockingRetryWithContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            f: self.f,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, OpenTelemetry::new(operation)),
+        }
+    }
+
+    /// Log the errors that will be retried as warnings and the error that ends the retry as
+    /// an error with `defmt`, for embedded targets logging over RTT.
+    #[cfg(feature = "defmt")]
+    pub fn defmt(
+        self,
+    ) -> BlockingRetryWithContext<B, T, E, Ctx, F, SF, RF, NF, EC, ME, Chain<HK, Defmt>, CS>
+    where
+        ME::Error: defmt::Format,
+    {
+        BlockingRetryWithContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            f: self.f,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, Defmt),
+        }
+    }
+
+    /// Record every error that will be retried and the error that ends the retry as Sentry
+    /// breadcrumbs in the `retry` category, with the attempt, the delay and the error message.
+    ///
+    /// When the retry gives up, the number of attempts is set as the `retry.attempts` tag of
+    /// the current scope, so the error reported afterwards shows the history of the retry.
+    #[cfg(feature = "sentry")]
+    pub fn sentry(
+        self,
+    ) -> BlockingRetryWithContext<B, T, E, Ctx, F, SF, RF, NF, EC, ME, Chain<HK, Sentry>, CS>
+    where
+        ME::Error: fmt::Display,
+    {
+        BlockingRetryWithContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            f: self.f,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, Sentry),
+        }
+    }
+
+    /// Log the errors that will be retried at `retry_level` and the error that ends the
+    /// retry at `give_up_level` with the `log` crate, under the `backon` target.
+    #[cfg(feature = "logging")]
+    pub fn log(
+        self,
+        retry_level: log::Level,
+        give_up_level: log::Level,
+    ) -> BlockingRetryWithContext<B, T, E, Ctx, F, SF, RF, NF, EC, ME, Chain<HK, Log>, CS>
+    where
+        ME::Error: fmt::Display,
+    {
+        BlockingRetryWithContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            f: self.f,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, Log::new(retry_level, give_up_level)),
+        }
+    }
+
+    /// Log the errors that will be retried as warnings and the error that ends the retry as
+    /// an error to `logger`, with the attempt number, delay, error and reason as key-value
+    /// pairs.
+    #[cfg(feature = "slog")]
+    pub fn slog(
+        self,
+        logger: slog::Logger,
+    ) -> BlockingRetryWithContext<B, T, E, Ctx, F, SF, RF, NF, EC, ME, Chain<HK, Slog>, CS>
+    where
+        ME::Error: fmt::Display,
+    {
+        BlockingRetryWithContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            f: self.f,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, Slog::new(logger)),
+        }
+    }
+
+    /// Set whether the retried operation is idempotent, `true` by default.
+    ///
+    /// Operations that are not idempotent, like writes, are only retried at errors telling
+    /// the operation had no effect by [`RetryableError::is_safe_to_retry`], so this works
+    /// together with `when_retryable`. Other errors end the retry with
+    /// [`GiveUpReason::Ambiguous`] instead of risking applying the operation twice.
+    pub fn idempotent(mut self, idempotent: bool) -> Self {
+        self.retry_state.set_idempotent(idempotent);
+        self
+    }
+
+    /// Set the name of the retried operation, like `s3_put_object`.
+    ///
+    /// It's included in the events passed to `observe`, the [`RetryReport`](crate::RetryReport)
+    /// and the output of the `tracing` and `opentelemetry` integrations, so they can be
+    /// aggregated per operation.
+    pub fn name(mut self, name: &'static str) -> Self {
+        self.retry_state.set_name(name);
+        self
+    }
+
+    /// Add a key-value tag describing the retried operation, included wherever the name set
+    /// by `name` is.
+    pub fn tag(mut self, key: &'static str, value: impl Into<Cow<'static, str>>) -> Self {
+        self.retry_state.add_tag(key, value.into());
+        self
+    }
+
+    /// Continue a retry from a [`RetrySnapshot`] taken by `snapshot`, like one persisted
+    /// before the process restarted.
+    ///
+    /// The backoff of the snapshot replaces the one passed to `retry`, so the delays pick up
+    /// where they were instead of starting over, and the attempts and elapsed time count from
+    /// the snapshot. The next attempt starts right away.
+    pub fn resume_from<S: Backoff>(
+        mut self,
+        snapshot: RetrySnapshot<S>,
+    ) -> BlockingRetryWithContext<S, T, E, Ctx, F, SF, RF, NF, EC, ME, HK, CS> {
+        self.retry_state.resume(&snapshot);
+        BlockingRetryWithContext {
+            backoff: snapshot.into_backoff(),
+            retryable: self.retryable,
+            notify: self.notify,
+            f: self.f,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+        }
+    }
+
+    /// Set the [`Clock`] measuring the elapsed time and the latency of the attempts, reported
+    /// by [`RetryState`], the events passed to `observe` and the report.
+    ///
+    /// It defaults to [`StdClock`](crate::StdClock) with the `std` feature outside of
+    /// `wasm32`. Without it, the retry isn't timed unless a clock is set.
+    pub fn clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.retry_state.set_clock(SharedClock::new(clock));
+        self
+    }
+
+    /// Seed the random numbers of the retry, the jitter of its backoff and its ID, instead of
+    /// drawing them from the thread's RNG.
+    ///
+    /// Together with a [`Clock`] set by `clock`, the delays, the events and the report of the
+    /// retry are the same on every run, so tests can assert on them exactly.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.retry_state.set_seed(seed);
+        self
+    }
+
+    /// Panic if the retry makes more than `max_attempts` attempts or sleeps more than
+    /// `max_total_sleep` in total, so tests assert a code path never retries more than it
+    /// should, like "this call is retried at most twice".
+    ///
+    /// The panic happens before the retry over the budget sleeps. Only available with the
+    /// `test-util` feature.
+    #[cfg(feature = "test-util")]
+    pub fn with_test_budget(mut self, max_attempts: usize, max_total_sleep: Duration) -> Self {
+        self.retry_state
+            .set_test_budget(max_attempts, max_total_sleep);
+        self
+    }
+
+    /// Set the ID identifying this retry in logs and events, returned by
+    /// [`RetryState::session_id`].
+    ///
+    /// A random ID is generated when the retry is built if it's not set, the same for all its
+    /// attempts. The retried operation reads it with `current_attempt`, so its logs can be
+    /// correlated with the events of the retry.
+    pub fn session_id(mut self, id: u64) -> Self {
+        self.retry_state.set_session_id(id);
+        self
+    }
+
+    /// Set the [`BudgetWarning`] raised to the function set by `observe` once a fraction of
+    /// the attempts or time of the retry is consumed.
+    pub fn budget_warning(mut self, warning: BudgetWarning) -> Self {
+        self.retry_state.set_budget_warning(warning);
+        self
+    }
+
+    /// Set the most time the retry sleeps in total between attempts, combined with the
+    /// limits of the backoff like its maximum number of retries.
+    ///
+    /// The retry gives up with [`GiveUpReason::MaxTotalSleepReached`] instead of sleeping past
+    /// it, telling it apart from [`GiveUpReason::BackoffExhausted`] if the backoff runs out
+    /// first.
+    pub fn max_total_sleep(mut self, max: Duration) -> Self {
+        self.retry_state.set_max_total_sleep(max);
+        self
+    }
+
+    /// Attach the [`FailureBudget`] shared with other retries.
+    ///
+    /// Every attempt failed with a retryable error is recorded in the budget, and the retry
+    /// ends at the next one while the budget is exhausted.
+    #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+    pub fn failure_budget(mut self, budget: FailureBudget) -> Self {
+        self.retry_state.set_failure_budget(budget);
+        self
+    }
+
+    /// Attach the [`RetryBudget`] shared with other retries.
+    ///
+    /// Every successful attempt deposits in the budget, and every retry withdraws from it,
+    /// the retry ending with [`GiveUpReason::BudgetExhausted`] once it's empty.
+    #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+    pub fn retry_budget(mut self, budget: RetryBudget) -> Self {
+        self.retry_state.set_retry_budget(budget);
+        self
+    }
+
+    /// Set the [`Deadline`] the retry must be done by, inherited from the context of the call.
+    ///
+    /// The delays are shortened for every retry to start with the least time an attempt
+    /// needs left, the retry ending with [`GiveUpReason::DeadlineExceeded`] once it can't.
+    /// Blocking attempts aren't timed out, see [`Deadline`].
+    pub fn deadline(mut self, deadline: Deadline) -> Self {
+        self.retry_state.set_deadline(deadline);
+        self
+    }
 }
 
-impl<B, T, E, Ctx, F, SF, RF, NF> BlockingRetryWithContext<B, T, E, Ctx, F, SF, RF, NF>
+impl<B, T, E, Ctx, F, SF, RF, NF, EC, ME, HK, CS>
+    BlockingRetryWithContext<B, T, E, Ctx, F, SF, RF, NF, EC, ME, HK, CS>
 where
     B: Backoff,
     F: FnMut(Ctx) -> (Ctx, Result<T, E>),
     SF: BlockingSleeper,
-    RF: FnMut(&E) -> bool,
-    NF: FnMut(&E, Duration),
+    RF: ErrorClassifier<ME::Error>,
+    NF: NotifyWithContext<ME::Error, Ctx>,
+    HK: Hooks<T, ME::Error>,
+    ME: MapError<E>,
+    EC: ErrorCollector<ME::Error>,
+    CS: ContextSource<Ctx>,
 {
     /// Call the retried function.
     ///
+    /// The context is moved into the function, so it's lost if the function panics. Use
+    /// `call_catch_unwind` to catch the panic instead.
+    ///
     /// TODO: implement [`FnOnce`] after it stable.
-    pub fn call(mut self) -> (Ctx, Result<T, E>) {
-        let mut ctx = self.ctx.take().expect("context must be valid");
-        loop {
-            let (xctx, result) = (self.f)(ctx);
+    pub fn call(self) -> (Ctx, Result<T, EC::Error>) {
+        self.run().0
+    }
+
+    /// Call the retried function, returning the [`RetryReport`] of the retry alongside its
+    /// result.
+    pub fn call_with_report(mut self) -> ((Ctx, Result<T, EC::Error>), RetryReport) {
+        self.retry_state.start_report();
+        let (result, mut state) = self.run();
+        (result, state.take_report())
+    }
+
+    /// Call the retried function, catching panics raised by it.
+    ///
+    /// A panicking attempt stops the retry, which fails with [`PanicOrError::Panic`]. The
+    /// context is returned like by `call`, except after a panic: the context moved into the
+    /// panicking attempt is dropped by the unwinding, so `None` is returned in its place. A
+    /// pooled connection dropped this way goes back to its pool by its own `Drop`, use
+    /// `BlockingRetryableWithMutContext` to keep the context after a panic instead.
+    ///
+    /// The function and the context are asserted unwind safe, like by
+    /// [`std::panic::AssertUnwindSafe`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use backon::BlockingRetryableWithContext;
+    /// use backon::ExponentialBuilder;
+    ///
+    /// struct Conn {
+    ///     id: usize,
+    /// }
+    ///
+    /// let (conn, result) = (|conn: Conn| -> (Conn, Result<(), &str>) {
+    ///     panic!("connection {} is broken", conn.id)
+    /// })
+    /// .retry(ExponentialBuilder::default())
+    /// .context(Conn { id: 7 })
+    /// .call_catch_unwind();
+    ///
+    /// assert!(result.unwrap_err().is_panic());
+    /// // The connection was dropped with the panicking attempt.
+    /// assert!(conn.is_none());
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn call_catch_unwind(self) -> (Option<Ctx>, Result<T, PanicOrError<EC::Error>>) {
+        let (result, _) = self.run_attempts(|f, ctx| {
+            std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| f(ctx)))
+        });
+        match result {
+            Ok((ctx, result)) => (Some(ctx), result.map_err(PanicOrError::Error)),
+            Err(payload) => (None, Err(PanicOrError::Panic(payload))),
+        }
+    }
+
+    /// Run the retry, returning its result with the final state.
+    fn run(self) -> ((Ctx, Result<T, EC::Error>), RetryState) {
+        let (result, state) = self.run_attempts(|f, ctx| Ok::<_, Infallible>(f(ctx)));
+        match result {
+            Ok(result) => (result, state),
+            Err(never) => match never {},
+        }
+    }
+
+    /// Run the retry with every attempt made by `attempt`, which stops the retry with the
+    /// error it returns.
+    #[allow(clippy::type_complexity)]
+    fn run_attempts<P>(
+        mut self,
+        mut attempt: impl FnMut(&mut F, Ctx) -> Result<(Ctx, Result<T, E>), P>,
+    ) -> (Result<(Ctx, Result<T, EC::Error>), P>, RetryState) {
+        let mut ctx = self.ctx.into_context();
+        let result = loop {
+            self.retry_state.start_attempt();
+            self.hooks.on_attempt(&self.retry_state);
+            let res = {
+                let _current = CurrentAttempt::enter(&self.retry_state);
+                self.hooks.enter();
+                let res = attempt(&mut self.f, ctx);
+                self.hooks.exit();
+                res
+            };
+            let (xctx, result) = match res {
+                Ok(res) => res,
+                Err(stop) => break Err(stop),
+            };
             // return ctx ownership back
             ctx = xctx;
 
             match result {
-                Ok(v) => return (ctx, Ok(v)),
+                Ok(v) => {
+                    self.retry_state.succeed_attempt();
+                    self.hooks.on_success(&v, &self.retry_state);
+                    break Ok((ctx, Ok(v)));
+                }
                 Err(err) => {
-                    if !(self.retryable)(&err) {
-                        return (ctx, Err(err));
-                    }
-
-                    match self.backoff.next() {
-                        None => return (ctx, Err(err)),
-                        Some(dur) => {
-                            (self.notify)(&err, dur);
+                    let err = self.map_err.map_err(err);
+                    match self.retry_state.retry_delay(
+                        err,
+                        &mut self.backoff,
+                        &mut self.retryable,
+                        &mut self.errors,
+                    ) {
+                        Err((err, reason)) => {
+                            if let Some(v) = self.hooks.recover(&err) {
+                                self.hooks.on_recovered(&err, reason, &self.retry_state);
+                                break Ok((ctx, Ok(v)));
+                            }
+                            self.hooks.on_fatal(&err, reason, &self.retry_state);
+                            break Ok((ctx, Err(self.errors.finish(err, reason))));
+                        }
+                        Ok((err, dur)) => {
+                            self.notify.notify(&err, dur, &ctx, &self.retry_state);
+                            self.hooks.on_transient(&err, dur, &self.retry_state);
+                            self.errors.collect(err, dur, &self.retry_state);
+                            self.hooks.enter_sleep();
                             self.sleep_fn.sleep(dur);
+                            self.hooks.exit_sleep();
+                            self.hooks.on_resume(&self.retry_state);
                         }
                     }
                 }
             }
-        }
+        };
+        (result, self.retry_state)
     }
 }
 
@@ -185,6 +1362,8 @@ mod tests {
     use super::*;
     use crate::ExponentialBuilder;
     use alloc::string::ToString;
+    use alloc::vec;
+    use alloc::vec::Vec;
     use anyhow::anyhow;
     use anyhow::Result;
     use core::time::Duration;
@@ -228,4 +1407,95 @@ mod tests {
         assert_eq!(*error_times.lock(), 1);
         Ok(())
     }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_retry_with_context_catch_unwind() -> Result<()> {
+        /// A connection lent by a pool, which can't be cloned.
+        struct Pooled {
+            id: usize,
+            attempts: usize,
+        }
+
+        let backoff = ExponentialBuilder::default().with_min_delay(Duration::from_millis(1));
+        let mut attempts = 0;
+        let (conn, result) = {
+            |mut conn: Pooled| {
+                conn.attempts += 1;
+                attempts = conn.attempts;
+                if conn.attempts > 1 {
+                    panic!("attempt panicked");
+                }
+                (conn, Err::<(), _>(anyhow!("retryable")))
+            }
+        }
+        .retry(backoff)
+        .context(Pooled { id: 7, attempts: 0 })
+        .call_catch_unwind();
+
+        let err = result.unwrap_err();
+        assert_eq!(err.panic_message(), Some("attempt panicked"));
+        // The context moved into the panicking attempt is dropped with it.
+        assert!(conn.is_none());
+        assert_eq!(attempts, 2);
+
+        // Without a panic, the context and the error are returned like by `call`.
+        let (conn, result) = (|conn: Pooled| (conn, Err::<(), _>(anyhow!("retryable"))))
+            .retry(backoff)
+            .context(Pooled { id: 8, attempts: 0 })
+            .call_catch_unwind();
+        assert!(!result.unwrap_err().is_panic());
+        assert_eq!(conn.map(|conn| conn.id), Some(8));
+        Ok(())
+    }
+
+    #[test]
+    fn test_retry_with_notify_with_ctx() -> Result<()> {
+        let mut notified: Vec<usize> = vec![];
+
+        let backoff = ExponentialBuilder::default().with_min_delay(Duration::from_millis(1));
+
+        let (_, result) = {
+            |v: usize| {
+                let v = v + 1;
+                (v, Err::<(), anyhow::Error>(anyhow!("retryable")))
+            }
+        }
+        .retry(backoff)
+        .context(0)
+        .notify_with_ctx(|_, _, v: &usize| notified.push(*v))
+        .call();
+
+        assert!(result.is_err());
+        // The context is passed to notify after each failed attempt.
+        assert_eq!(notified, vec![1, 2, 3]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_retry_with_context_with() -> Result<()> {
+        let built = Mutex::new(0);
+
+        let backoff = ExponentialBuilder::default().with_min_delay(Duration::from_millis(1));
+
+        let retry = {
+            |v: usize| {
+                let v = v + 1;
+                (v, Err::<(), anyhow::Error>(anyhow!("retryable")))
+            }
+        }
+        .retry(backoff)
+        .context_with(|| {
+            *built.lock() += 1;
+            0
+        });
+        // The context is not built until the retry is called.
+        assert_eq!(*built.lock(), 0);
+
+        let (ctx, result) = retry.call();
+        assert!(result.is_err());
+        assert_eq!(ctx, 4);
+        assert_eq!(*built.lock(), 1);
+        Ok(())
+    }
 }
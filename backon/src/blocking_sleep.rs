@@ -35,11 +35,13 @@ pub type DefaultBlockingSleeper = StdSleeper;
 /// A placeholder type that does not implement [`Sleeper`] and will therefore fail to compile if used as one.
 ///
 /// Users should enable a feature of this crate that provides a valid [`Sleeper`] implementation when this type appears in compilation errors. Alternatively, a custom [`Sleeper`] implementation should be provided where necessary, such as in [`crate::Retry::sleeper`].
+#[cfg(not(feature = "std-blocking-sleep"))]
 #[doc(hidden)]
 #[derive(Clone, Copy, Debug, Default)]
 pub struct PleaseEnableAFeatureOrProvideACustomSleeper;
 
 /// Implement `MaybeSleeper` but not `Sleeper`.
+#[cfg(not(feature = "std-blocking-sleep"))]
 impl MaybeBlockingSleeper for PleaseEnableAFeatureOrProvideACustomSleeper {}
 
 /// The implementation of `StdSleeper` uses [`std::thread::sleep`].
@@ -0,0 +1,521 @@
+use core::fmt;
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::Ordering;
+use core::task::ready;
+use core::task::Context;
+use core::task::Poll;
+use core::time::Duration;
+use std::time::Instant;
+
+use crate::sync::Arc;
+use crate::sync::AtomicU64;
+use crate::sync::AtomicUsize;
+use crate::RetryableError;
+
+/// CircuitBreaker stops calling an operation failing too often, for a while, instead of
+/// retrying it again and again.
+///
+/// The breaker starts closed, letting every call through and counting the failures within a
+/// window. Once at least `min_calls` calls were made within the window and the share of
+/// them that failed reaches `failure_rate`, it opens: calls are rejected at once with
+/// [`CircuitError::Open`], without calling the operation. After `open_duration`, it's
+/// half-open, letting up to `probes` calls through: the first one succeeding closes it
+/// again, the first one failing opens it for another `open_duration`.
+///
+/// Functions are guarded with [`guard`](CircuitBreaker::guard), or
+/// [`guard_blocking`](CircuitBreaker::guard_blocking), before being retried: every attempt
+/// is a call. [`CircuitError`] implements [`RetryableError`] when the errors of the function
+/// do, the rejections not being retryable, so `when_retryable` ends a retry as soon as the
+/// breaker opens.
+///
+/// Cloning a `CircuitBreaker` shares the same breaker. Its state is kept in atomics, so the
+/// calls sharing it don't take a lock. The counts of a window are reset by the first call
+/// after it ends, and calls racing the reset may be counted in either window.
+///
+/// # Examples
+///
+/// ```
+/// use core::time::Duration;
+///
+/// use backon::BlockingRetryable;
+/// use backon::CircuitBreaker;
+/// use backon::CircuitError;
+/// use backon::ConstantBuilder;
+///
+/// let breaker = CircuitBreaker::new()
+///     .with_min_calls(2)
+///     .with_open_duration(Duration::from_secs(30));
+///
+/// let result = breaker
+///     .guard_blocking(|| Err::<(), _>("unavailable"))
+///     .retry(ConstantBuilder::default().with_delay(Duration::from_millis(1)))
+///     .when(|e| !e.is_open())
+///     .call();
+///
+/// // The breaker opened after two failures, rejecting the third attempt.
+/// assert_eq!(result, Err(CircuitError::Open));
+/// assert!(breaker.is_open());
+/// ```
+#[derive(Clone, Debug)]
+pub struct CircuitBreaker {
+    failure_rate: f32,
+    min_calls: usize,
+    window: Duration,
+    open_duration: Duration,
+    probes: usize,
+    shared: Arc<Shared>,
+}
+
+/// The state of a [`CircuitBreaker`], returned by [`CircuitBreaker::state`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CircuitState {
+    /// Every call is let through.
+    Closed,
+    /// Every call is rejected.
+    Open,
+    /// Calls probing whether the operation recovered are let through.
+    HalfOpen,
+}
+
+const CLOSED: usize = 0;
+const OPEN: usize = 1;
+const HALF_OPEN: usize = 2;
+
+/// The state shared by the clones of a breaker.
+///
+/// `state` is written with `Release` after the time it opened or the window it started, and
+/// read with `Acquire` before them, so a call seeing the breaker open sees when it opened.
+/// The counts of the window are incremented with `AcqRel` and loaded with `Acquire` after,
+/// so of the calls failing at once, the last one counted sees the others.
+#[derive(Debug)]
+struct Shared {
+    /// The time the breaker was created, the times being nanoseconds after it.
+    origin: Instant,
+    state: AtomicUsize,
+    opened_at: AtomicU64,
+    window_start: AtomicU64,
+    calls: AtomicUsize,
+    failures: AtomicUsize,
+    /// The probes in flight while half-open.
+    probing: AtomicUsize,
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CircuitBreaker {
+    /// Create a closed breaker opening once half of the calls failed, over at least 10 calls
+    /// within 10 seconds, for 30 seconds, then letting one probe through.
+    pub fn new() -> Self {
+        CircuitBreaker {
+            failure_rate: 0.5,
+            min_calls: 10,
+            window: Duration::from_secs(10),
+            open_duration: Duration::from_secs(30),
+            probes: 1,
+            shared: Arc::new(Shared {
+                origin: Instant::now(),
+                state: AtomicUsize::new(CLOSED),
+                opened_at: AtomicU64::new(0),
+                window_start: AtomicU64::new(0),
+                calls: AtomicUsize::new(0),
+                failures: AtomicUsize::new(0),
+                probing: AtomicUsize::new(0),
+            }),
+        }
+    }
+
+    /// Set the share of failed calls, between 0 and 1, opening the breaker.
+    pub fn with_failure_rate(mut self, failure_rate: f32) -> Self {
+        self.failure_rate = failure_rate.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Set the number of calls within the window needed before the breaker opens.
+    pub fn with_min_calls(mut self, min_calls: usize) -> Self {
+        self.min_calls = min_calls.max(1);
+        self
+    }
+
+    /// Set the window over which the failure rate is computed.
+    pub fn with_window(mut self, window: Duration) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Set how long the breaker stays open before letting probes through.
+    pub fn with_open_duration(mut self, open_duration: Duration) -> Self {
+        self.open_duration = open_duration;
+        self
+    }
+
+    /// Set the number of probes let through at once while half-open.
+    pub fn with_probes(mut self, probes: usize) -> Self {
+        self.probes = probes.max(1);
+        self
+    }
+
+    /// Returns the state of the breaker, half-open once it's been open for `open_duration`
+    /// even if no call probed it yet.
+    pub fn state(&self) -> CircuitState {
+        match self.shared.state.load(Ordering::Acquire) {
+            CLOSED => CircuitState::Closed,
+            OPEN if !self.open_elapsed() => CircuitState::Open,
+            _ => CircuitState::HalfOpen,
+        }
+    }
+
+    /// Returns whether the breaker rejects calls.
+    pub fn is_open(&self) -> bool {
+        self.state() == CircuitState::Open
+    }
+
+    /// Guard the async `f` with the breaker, for it to be retried.
+    ///
+    /// Every call of the returned function is a call of the breaker: it's rejected with
+    /// [`CircuitError::Open`] while it's open, otherwise it calls `f` and records whether its
+    /// future failed.
+    pub fn guard<T, E, Fut, F>(&self, mut f: F) -> impl FnMut() -> CircuitFuture<Fut>
+    where
+        Fut: Future<Output = Result<T, E>>,
+        F: FnMut() -> Fut,
+    {
+        let breaker = self.clone();
+        move || {
+            let permit = breaker.acquire();
+            CircuitFuture {
+                fut: permit.map(|_| f()),
+                permit: permit.map(|probe| Permit {
+                    breaker: breaker.clone(),
+                    probe,
+                }),
+            }
+        }
+    }
+
+    /// Guard the blocking `f` with the breaker, for it to be retried, like `guard`.
+    pub fn guard_blocking<T, E, F>(&self, mut f: F) -> impl FnMut() -> Result<T, CircuitError<E>>
+    where
+        F: FnMut() -> Result<T, E>,
+    {
+        let breaker = self.clone();
+        move || {
+            let probe = breaker.acquire().ok_or(CircuitError::Open)?;
+            let permit = Permit {
+                breaker: breaker.clone(),
+                probe,
+            };
+            let res = f();
+            permit.record(res.is_ok());
+            res.map_err(CircuitError::Failed)
+        }
+    }
+
+    /// Returns the nanoseconds after the origin of the breaker, plus one.
+    fn now(&self) -> u64 {
+        let nanos = self.shared.origin.elapsed().as_nanos();
+        u64::try_from(nanos).unwrap_or(u64::MAX - 1) + 1
+    }
+
+    fn open_elapsed(&self) -> bool {
+        let opened_at = self.shared.opened_at.load(Ordering::Acquire);
+        let open = u64::try_from(self.open_duration.as_nanos()).unwrap_or(u64::MAX);
+        self.now().saturating_sub(opened_at) >= open
+    }
+
+    /// Let a call through, returning whether it's a probe, or `None` if it's rejected.
+    fn acquire(&self) -> Option<bool> {
+        let shared = &self.shared;
+        loop {
+            match shared.state.load(Ordering::Acquire) {
+                CLOSED => return Some(false),
+                OPEN => {
+                    if !self.open_elapsed() {
+                        return None;
+                    }
+                    // The first call after the breaker was open long enough half-opens it.
+                    if shared
+                        .state
+                        .compare_exchange(OPEN, HALF_OPEN, Ordering::AcqRel, Ordering::Acquire)
+                        .is_ok()
+                    {
+                        shared.probing.store(0, Ordering::Relaxed);
+                    }
+                }
+                _ => {
+                    if shared.probing.fetch_add(1, Ordering::Relaxed) < self.probes {
+                        return Some(true);
+                    }
+                    shared.probing.fetch_sub(1, Ordering::Relaxed);
+                    return None;
+                }
+            }
+        }
+    }
+
+    /// Record the outcome of a call let through.
+    fn record(&self, probe: bool, ok: bool) {
+        let shared = &self.shared;
+        if probe {
+            shared.probing.fetch_sub(1, Ordering::Relaxed);
+            if ok {
+                self.reset_window(self.now());
+                let _ = shared.state.compare_exchange(
+                    HALF_OPEN,
+                    CLOSED,
+                    Ordering::AcqRel,
+                    Ordering::Relaxed,
+                );
+            } else {
+                self.trip(HALF_OPEN);
+            }
+            return;
+        }
+
+        let now = self.now();
+        let start = shared.window_start.load(Ordering::Acquire);
+        let window = u64::try_from(self.window.as_nanos()).unwrap_or(u64::MAX);
+        if now.saturating_sub(start) > window
+            && shared
+                .window_start
+                .compare_exchange(start, now, Ordering::AcqRel, Ordering::Relaxed)
+                .is_ok()
+        {
+            shared.calls.store(0, Ordering::Relaxed);
+            shared.failures.store(0, Ordering::Relaxed);
+        }
+        shared.calls.fetch_add(1, Ordering::AcqRel);
+        if ok {
+            return;
+        }
+        shared.failures.fetch_add(1, Ordering::AcqRel);
+        // Loaded after counting, so the last of the calls failing at once sees them all.
+        let calls = shared.calls.load(Ordering::Acquire);
+        let failures = shared.failures.load(Ordering::Acquire);
+        if calls >= self.min_calls && failures as f32 >= self.failure_rate * calls as f32 {
+            self.trip(CLOSED);
+        }
+    }
+
+    fn reset_window(&self, now: u64) {
+        let shared = &self.shared;
+        shared.calls.store(0, Ordering::Relaxed);
+        shared.failures.store(0, Ordering::Relaxed);
+        shared.window_start.store(now, Ordering::Release);
+    }
+
+    /// Open the breaker if it's still in the state `from`.
+    fn trip(&self, from: usize) {
+        let shared = &self.shared;
+        if shared.state.load(Ordering::Acquire) != from {
+            return;
+        }
+        shared.opened_at.store(self.now(), Ordering::Release);
+        let _ = shared
+            .state
+            .compare_exchange(from, OPEN, Ordering::AcqRel, Ordering::Relaxed);
+    }
+}
+
+/// A call let through by the breaker, released without an outcome if it's dropped before.
+struct Permit {
+    breaker: CircuitBreaker,
+    probe: bool,
+}
+
+impl Permit {
+    fn record(mut self, ok: bool) {
+        self.breaker.record(self.probe, ok);
+        // The probe was released by `record`.
+        self.probe = false;
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        if self.probe {
+            self.breaker.shared.probing.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// The future of a function guarded by [`CircuitBreaker::guard`].
+#[doc(hidden)]
+pub struct CircuitFuture<Fut> {
+    fut: Option<Fut>,
+    permit: Option<Permit>,
+}
+
+impl<Fut> fmt::Debug for CircuitFuture<Fut> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CircuitFuture")
+            .field("rejected", &self.fut.is_none())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T, E, Fut: Future<Output = Result<T, E>>> Future for CircuitFuture<Fut> {
+    type Output = Result<T, CircuitError<E>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Safety: the future is pinned with this one, only the permit is moved out.
+        let this = unsafe { self.get_unchecked_mut() };
+        let Some(fut) = &mut this.fut else {
+            return Poll::Ready(Err(CircuitError::Open));
+        };
+        let res = ready!(unsafe { Pin::new_unchecked(fut) }.poll(cx));
+        if let Some(permit) = this.permit.take() {
+            permit.record(res.is_ok());
+        }
+        Poll::Ready(res.map_err(CircuitError::Failed))
+    }
+}
+
+/// CircuitError is the error of a function guarded by a [`CircuitBreaker`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CircuitError<E> {
+    /// The breaker is open, the function wasn't called.
+    Open,
+    /// The function failed.
+    Failed(E),
+}
+
+impl<E> CircuitError<E> {
+    /// Returns whether the call was rejected by the breaker.
+    pub fn is_open(&self) -> bool {
+        matches!(self, CircuitError::Open)
+    }
+
+    /// Returns the error of the function, or `None` if the call was rejected.
+    pub fn into_inner(self) -> Option<E> {
+        match self {
+            CircuitError::Open => None,
+            CircuitError::Failed(err) => Some(err),
+        }
+    }
+
+    fn failed(&self) -> Option<&E> {
+        match self {
+            CircuitError::Open => None,
+            CircuitError::Failed(err) => Some(err),
+        }
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for CircuitError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CircuitError::Open => write!(f, "circuit breaker is open"),
+            CircuitError::Failed(err) => err.fmt(f),
+        }
+    }
+}
+
+impl<E: core::error::Error> core::error::Error for CircuitError<E> {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            CircuitError::Open => None,
+            CircuitError::Failed(err) => err.source(),
+        }
+    }
+}
+
+/// Rejected calls aren't retryable, and aren't safe to retry either, the others are as the
+/// error of the function is.
+impl<E: RetryableError> RetryableError for CircuitError<E> {
+    fn is_retryable(&self) -> bool {
+        self.failed().is_some_and(E::is_retryable)
+    }
+
+    fn retry_after(&self) -> Option<Duration> {
+        self.failed()?.retry_after()
+    }
+
+    fn is_safe_to_retry(&self) -> bool {
+        self.failed().is_some_and(E::is_safe_to_retry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(breaker: &CircuitBreaker, ok: bool) -> Result<(), CircuitError<()>> {
+        breaker.guard_blocking(|| if ok { Ok(()) } else { Err(()) })()
+    }
+
+    #[test]
+    fn test_circuit_breaker() {
+        let breaker = CircuitBreaker::new()
+            .with_failure_rate(0.5)
+            .with_min_calls(4)
+            .with_open_duration(Duration::from_millis(50));
+
+        assert_eq!(call(&breaker, true), Ok(()));
+        assert_eq!(call(&breaker, false), Err(CircuitError::Failed(())));
+        assert_eq!(call(&breaker, true), Ok(()));
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        // Two calls out of four failed.
+        assert_eq!(call(&breaker, false), Err(CircuitError::Failed(())));
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert_eq!(call(&breaker, true), Err(CircuitError::Open));
+
+        // A failed probe opens it again, a successful one closes it.
+        std::thread::sleep(Duration::from_millis(60));
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        assert_eq!(call(&breaker, false), Err(CircuitError::Failed(())));
+        assert_eq!(breaker.state(), CircuitState::Open);
+        std::thread::sleep(Duration::from_millis(60));
+        assert_eq!(call(&breaker, true), Ok(()));
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+
+    #[tokio::test]
+    #[cfg(not(backon_loom))]
+    async fn test_circuit_breaker_guard() {
+        use crate::ConstantBuilder;
+        use crate::Retryable;
+
+        let breaker = CircuitBreaker::new()
+            .with_min_calls(1)
+            .with_open_duration(Duration::from_millis(20));
+        let mut attempts = 0;
+        let result = breaker
+            .guard(|| {
+                attempts += 1;
+                core::future::ready(Err::<(), _>("unavailable"))
+            })
+            .retry(ConstantBuilder::default().with_delay(Duration::from_millis(1)))
+            .when(|e| !e.is_open())
+            .await;
+        assert_eq!(result, Err(CircuitError::Open));
+        assert_eq!(attempts, 1);
+
+        // The probe let through once the breaker half-opened closes it.
+        let result = breaker
+            .guard(|| core::future::ready(Ok::<_, &str>(())))
+            .retry(ConstantBuilder::default().with_delay(Duration::from_millis(15)))
+            .await;
+        assert_eq!(result, Ok(()));
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+
+    #[test]
+    #[cfg(backon_loom)]
+    fn test_circuit_breaker_loom() {
+        loom::model(|| {
+            let breaker = CircuitBreaker::new()
+                .with_min_calls(2)
+                .with_failure_rate(1.0);
+            let shared = breaker.clone();
+            let thread = loom::thread::spawn(move || call(&shared, false));
+            let _ = call(&breaker, false);
+            thread.join().unwrap().ok();
+            assert!(breaker.is_open());
+        });
+    }
+}
@@ -0,0 +1,348 @@
+use alloc::boxed::Box;
+use core::sync::atomic::Ordering;
+use core::time::Duration;
+use std::time::Instant;
+
+use crate::sync::Arc;
+use crate::sync::AtomicU64;
+use crate::sync::AtomicUsize;
+
+/// FailureBudget limits the failures of all retries sharing it within a sliding window.
+///
+/// Once more than `max_failures` attempts failed within `window`, the attached retries stop
+/// retrying and end at the next failure, until older failures move out of the window. This
+/// keeps retries from piling onto a failing downstream during an incident.
+///
+/// Cloning a `FailureBudget` shares the same budget. Recording failures and checking the
+/// budget take no lock, so sharing it between the retries of every request doesn't serialize
+/// them.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use backon::BlockingRetryable;
+/// use backon::ConstantBuilder;
+/// use backon::FailureBudget;
+///
+/// let budget = FailureBudget::new(3, Duration::from_secs(60));
+///
+/// let fetch = || Err::<(), _>("unavailable");
+/// let result = fetch
+///     .retry(ConstantBuilder::default().with_delay(Duration::from_millis(1)))
+///     .failure_budget(budget.clone())
+///     .call();
+///
+/// assert!(result.is_err());
+/// assert!(budget.is_exhausted());
+/// ```
+#[derive(Clone, Debug)]
+pub struct FailureBudget {
+    window: Duration,
+    failures: Arc<Failures>,
+}
+
+/// The times of the last `max_failures + 1` failures, in a ring.
+///
+/// A failure claims the next slot of the ring and stores its time there, so recording one is
+/// two atomic operations and no lock, as the budget is updated by every failed attempt of
+/// every retry sharing it. The budget is exhausted once every slot holds a failure within the
+/// window.
+#[derive(Debug)]
+struct Failures {
+    /// The time the budget was created, so a failure fits in a `u64` of nanoseconds after it.
+    origin: Instant,
+    next: AtomicUsize,
+    /// The nanoseconds after `origin` of the failures plus one, or 0 for an empty slot.
+    slots: Box<[AtomicU64]>,
+}
+
+impl FailureBudget {
+    /// Create a new budget allowing `max_failures` failures within `window`.
+    pub fn new(max_failures: usize, window: Duration) -> Self {
+        let len = max_failures.saturating_add(1);
+        FailureBudget {
+            window,
+            failures: Arc::new(Failures {
+                origin: Instant::now(),
+                next: AtomicUsize::new(0),
+                slots: (0..len).map(|_| AtomicU64::new(0)).collect(),
+            }),
+        }
+    }
+
+    /// Returns the nanoseconds after the origin of the budget, plus one.
+    fn now(&self) -> u64 {
+        let nanos = self.failures.origin.elapsed().as_nanos();
+        u64::try_from(nanos).unwrap_or(u64::MAX - 1) + 1
+    }
+
+    /// Record a failed attempt.
+    ///
+    /// The failure is visible to every [`is_exhausted`](Self::is_exhausted) of a thread
+    /// synchronized with this one after it, as it's stored with `Release` and loaded with
+    /// `Acquire`. Failures recorded concurrently claim distinct slots, and the oldest one is
+    /// overwritten, so the budget stays exact as long as fewer than `max_failures + 1`
+    /// failures race each other.
+    pub fn record_failure(&self) {
+        let failures = &self.failures;
+        let slot = failures.next.fetch_add(1, Ordering::Relaxed) % failures.slots.len();
+        failures.slots[slot].store(self.now(), Ordering::Release);
+    }
+
+    /// Returns `true` if more than `max_failures` failures happened within the window.
+    ///
+    /// The slots are read one by one without a lock, so a failure recorded while it reads
+    /// may or may not be counted.
+    pub fn is_exhausted(&self) -> bool {
+        let now = self.now();
+        let window = u64::try_from(self.window.as_nanos()).unwrap_or(u64::MAX);
+        self.failures.slots.iter().all(|slot| {
+            let at = slot.load(Ordering::Acquire);
+            at != 0 && now.saturating_sub(at) <= window
+        })
+    }
+}
+
+/// RetryBudget bounds the retries of all retries sharing it to a share of their successes.
+///
+/// Every successful attempt deposits `ratio` of a retry in the budget, and every retry
+/// withdraws one before sleeping, ending with
+/// [`GiveUpReason::BudgetExhausted`](crate::GiveUpReason::BudgetExhausted) if none is left.
+/// So during an outage, when attempts stop succeeding, retries add at most `ratio` to the
+/// load of the calls that succeeded before it, instead of multiplying it by the number of
+/// attempts of every retry. It's the retry budget of Finagle and linkerd.
+///
+/// The budget also earns `min_retries_per_sec` retries every second whatever the successes,
+/// so services with little traffic can still retry, and holds at most `max_retries`, so the
+/// retries earned while healthy don't all burst at once. It starts with one second of
+/// `min_retries_per_sec`.
+///
+/// Cloning a `RetryBudget` shares the same budget. Its balance is an atomic updated by
+/// compare-and-swap loops without a lock: a retry may fail to withdraw while a concurrent
+/// deposit lands, but a budget never lends more than it holds.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use backon::BlockingRetryable;
+/// use backon::ConstantBuilder;
+/// use backon::RetryBudget;
+///
+/// // Retry at most 20% of the successful calls, without a minimum.
+/// let budget = RetryBudget::new(0.2).with_min_retries_per_sec(0);
+///
+/// let fetch = || Err::<(), _>("unavailable");
+/// let result = fetch
+///     .retry(ConstantBuilder::default().with_delay(Duration::from_millis(1)))
+///     .retry_budget(budget.clone())
+///     .call();
+///
+/// // The budget had nothing to lend.
+/// assert!(result.is_err());
+/// assert!(budget.balance() < 1.0);
+/// ```
+#[derive(Clone, Debug)]
+pub struct RetryBudget {
+    /// The thousandths of a retry deposited by a success.
+    ratio: u64,
+    min_retries_per_sec: u64,
+    /// The most thousandths of a retry held.
+    max: u64,
+    tokens: Arc<Tokens>,
+}
+
+/// The balance of a retry budget, in thousandths of a retry.
+#[derive(Debug)]
+struct Tokens {
+    /// The time the budget was created, the refills being nanoseconds after it.
+    origin: Instant,
+    balance: AtomicU64,
+    refilled_at: AtomicU64,
+}
+
+impl Tokens {
+    fn new(balance: u64) -> Arc<Self> {
+        Arc::new(Tokens {
+            origin: Instant::now(),
+            balance: AtomicU64::new(balance),
+            refilled_at: AtomicU64::new(0),
+        })
+    }
+}
+
+impl RetryBudget {
+    /// Create a budget depositing `ratio` of a retry for every success, earning 10 retries
+    /// every second and holding at most 100.
+    pub fn new(ratio: f32) -> Self {
+        RetryBudget {
+            ratio: (ratio.max(0.0) * 1000.0) as u64,
+            min_retries_per_sec: 10,
+            max: 100_000,
+            tokens: Tokens::new(10_000),
+        }
+    }
+
+    /// Set the retries earned every second whatever the successes.
+    ///
+    /// The budget starts over with a balance of its own, not shared with the clones made
+    /// before.
+    pub fn with_min_retries_per_sec(mut self, min_retries_per_sec: u64) -> Self {
+        self.min_retries_per_sec = min_retries_per_sec;
+        self.tokens = Tokens::new(self.initial_balance());
+        self
+    }
+
+    /// Set the most retries the budget holds.
+    ///
+    /// The budget starts over like with `with_min_retries_per_sec`.
+    pub fn with_max_retries(mut self, max_retries: u64) -> Self {
+        self.max = max_retries.saturating_mul(1000);
+        self.tokens = Tokens::new(self.initial_balance());
+        self
+    }
+
+    /// Returns the balance the budget starts with, one second of `min_retries_per_sec`.
+    fn initial_balance(&self) -> u64 {
+        self.min_retries_per_sec.saturating_mul(1000).min(self.max)
+    }
+
+    /// Record a successful attempt, depositing `ratio` of a retry.
+    pub fn record_success(&self) {
+        self.deposit(self.ratio);
+    }
+
+    /// Withdraw a retry, returning `false` if the budget has none left.
+    pub fn try_retry(&self) -> bool {
+        self.refill();
+        self.tokens
+            .balance
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |balance| {
+                balance.checked_sub(1000)
+            })
+            .is_ok()
+    }
+
+    /// Returns the retries held by the budget.
+    pub fn balance(&self) -> f32 {
+        self.refill();
+        self.tokens.balance.load(Ordering::Acquire) as f32 / 1000.0
+    }
+
+    fn deposit(&self, amount: u64) {
+        let max = self.max;
+        let _ = self
+            .tokens
+            .balance
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |balance| {
+                Some(balance.saturating_add(amount).min(max))
+            });
+    }
+
+    /// Deposit the retries earned since the last refill.
+    fn refill(&self) {
+        if self.min_retries_per_sec == 0 {
+            return;
+        }
+        let tokens = &self.tokens;
+        let now = u64::try_from(tokens.origin.elapsed().as_micros()).unwrap_or(u64::MAX);
+        let last = tokens.refilled_at.load(Ordering::Acquire);
+        // Thousandths of a retry earned in the microseconds since the last refill.
+        let earned = now
+            .saturating_sub(last)
+            .saturating_mul(self.min_retries_per_sec)
+            / 1000;
+        // Only the call claiming the time since the last refill deposits it.
+        if earned > 0
+            && tokens
+                .refilled_at
+                .compare_exchange(last, now, Ordering::AcqRel, Ordering::Relaxed)
+                .is_ok()
+        {
+            self.deposit(earned);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failure_budget() {
+        let budget = FailureBudget::new(2, Duration::from_millis(50));
+        let shared = budget.clone();
+
+        budget.record_failure();
+        shared.record_failure();
+        assert!(!budget.is_exhausted());
+
+        shared.record_failure();
+        assert!(budget.is_exhausted());
+
+        // The failures move out of the window.
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(!budget.is_exhausted());
+    }
+
+    #[test]
+    fn test_retry_budget() {
+        let budget = RetryBudget::new(0.5)
+            .with_min_retries_per_sec(0)
+            .with_max_retries(1);
+        assert!(!budget.try_retry());
+
+        // Two successes earn a retry.
+        budget.record_success();
+        assert!(!budget.try_retry());
+        budget.record_success();
+        assert!(budget.try_retry());
+        assert!(!budget.try_retry());
+
+        // The budget holds at most one retry.
+        for _ in 0..10 {
+            budget.record_success();
+        }
+        assert_eq!(budget.balance(), 1.0);
+
+        // Setting up a clone leaves the budget it was cloned from alone.
+        let configured = budget.clone().with_max_retries(0);
+        assert_eq!(configured.balance(), 0.0);
+        assert_eq!(budget.balance(), 1.0);
+
+        let budget = RetryBudget::new(0.0).with_min_retries_per_sec(1000);
+        while budget.try_retry() {}
+        std::thread::sleep(Duration::from_millis(5));
+        assert!(budget.try_retry());
+    }
+
+    #[test]
+    #[cfg(backon_loom)]
+    fn test_failure_budget_loom() {
+        loom::model(|| {
+            let budget = FailureBudget::new(1, Duration::from_secs(60));
+            let shared = budget.clone();
+            let thread = loom::thread::spawn(move || shared.record_failure());
+            budget.record_failure();
+            thread.join().unwrap();
+            assert!(budget.is_exhausted());
+        });
+    }
+
+    #[test]
+    #[cfg(backon_loom)]
+    fn test_retry_budget_loom() {
+        loom::model(|| {
+            let budget = RetryBudget::new(1.0).with_min_retries_per_sec(0);
+            budget.record_success();
+            let shared = budget.clone();
+            let thread = loom::thread::spawn(move || shared.try_retry());
+            let retried = budget.try_retry();
+            // Only one of the retries gets the deposit.
+            assert!(retried ^ thread.join().unwrap());
+        });
+    }
+}
@@ -0,0 +1,511 @@
+use alloc::collections::VecDeque;
+use core::fmt;
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::Ordering;
+use core::task::ready;
+use core::task::Context;
+use core::task::Poll;
+use core::task::Waker;
+
+use crate::sync;
+use crate::sync::Arc;
+use crate::sync::AtomicU64;
+use crate::sync::Condvar;
+use crate::sync::Mutex;
+use crate::RetryableError;
+
+/// Bulkhead bounds the attempts of a class of operations running at once, across every
+/// retry sharing it.
+///
+/// During a partial outage, slow attempts pile up and their retries add more, until they take
+/// every connection of a pool shared with healthy operations. Attempts of functions guarded
+/// by a bulkhead with [`guard`](Bulkhead::guard) or [`guard_blocking`](Bulkhead::guard_blocking)
+/// first take one of its `max_in_flight` permits, released once they finish. Attempts finding
+/// none left wait in a queue, in order, unless `max_queued` attempts already wait: they're
+/// then rejected at once with [`BulkheadError::Full`], not retryable as a [`RetryableError`],
+/// like [`CircuitError::Open`](crate::CircuitError::Open).
+///
+/// Cloning a `Bulkhead` shares the same permits. Taking and releasing a permit is an atomic
+/// operation while some are left; the queue takes a lock, only once they all are taken.
+///
+/// # Examples
+///
+/// ```
+/// use core::time::Duration;
+///
+/// use backon::Bulkhead;
+/// use backon::ExponentialBuilder;
+/// use backon::Retryable;
+///
+/// async fn query() -> Result<u32, std::io::Error> {
+///     Ok(42)
+/// }
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// // At most 8 queries at once, 32 more waiting.
+/// let database = Bulkhead::new(8).with_max_queued(32);
+///
+/// let result = database
+///     .guard(query)
+///     .retry(ExponentialBuilder::default())
+///     .when(|e| !e.is_full())
+///     .await;
+/// assert_eq!(result.unwrap(), 42);
+/// assert_eq!(database.in_flight(), 0);
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Bulkhead {
+    max_in_flight: usize,
+    max_queued: usize,
+    shared: Arc<Shared>,
+}
+
+/// The permits shared by the clones of a bulkhead.
+///
+/// The attempts waiting are counted in the high half of `counts`, the ones running in the low
+/// half, so an attempt queues itself in the same atomic operation finding no permit left, and
+/// a release sees the attempts waiting in the one giving back its permit: either the waiting
+/// attempt sees the permit, or the release sees it waiting and wakes it. The queue is only
+/// changed under the lock of `waiters`, which the waiting attempt holds until its waker is in
+/// the queue.
+#[derive(Debug)]
+struct Shared {
+    counts: AtomicU64,
+    waiters: Mutex<Waiters>,
+    /// Notified when a blocking attempt is at the front of the queue.
+    freed: Condvar,
+}
+
+/// The attempts waiting for a permit, in order, with the waker of the async ones.
+#[derive(Debug, Default)]
+struct Waiters {
+    queue: VecDeque<(u64, Option<Waker>)>,
+    next_id: u64,
+}
+
+impl Waiters {
+    fn remove(&mut self, id: u64) -> bool {
+        match self.queue.iter().position(|(i, _)| *i == id) {
+            Some(pos) => {
+                self.queue.remove(pos);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// One attempt running, in [`Shared::counts`].
+const RUNNING: u64 = 1;
+/// One attempt waiting, in [`Shared::counts`].
+const WAITING: u64 = 1 << 32;
+
+fn running(counts: u64) -> u64 {
+    counts & (WAITING - 1)
+}
+
+fn waiting(counts: u64) -> u64 {
+    counts >> 32
+}
+
+impl Bulkhead {
+    /// Create a bulkhead letting `max_in_flight` attempts run at once, the others waiting
+    /// without a bound.
+    pub fn new(max_in_flight: usize) -> Self {
+        Bulkhead {
+            max_in_flight: max_in_flight.clamp(1, u32::MAX as usize),
+            max_queued: u32::MAX as usize,
+            shared: Arc::new(Shared {
+                counts: AtomicU64::new(0),
+                waiters: Mutex::new(Waiters::default()),
+                freed: Condvar::new(),
+            }),
+        }
+    }
+
+    /// Set the most attempts waiting for a permit, the others being rejected. With 0, every
+    /// attempt finding no permit left is rejected.
+    pub fn with_max_queued(mut self, max_queued: usize) -> Self {
+        self.max_queued = max_queued.min(u32::MAX as usize);
+        self
+    }
+
+    /// Returns the number of attempts running.
+    pub fn in_flight(&self) -> usize {
+        running(self.shared.counts.load(Ordering::Acquire)) as usize
+    }
+
+    /// Returns the number of attempts waiting for a permit.
+    pub fn queued(&self) -> usize {
+        waiting(self.shared.counts.load(Ordering::Acquire)) as usize
+    }
+
+    /// Guard the async `f` with the bulkhead, for it to be retried.
+    ///
+    /// The future of every call waits for a permit, or is rejected with
+    /// [`BulkheadError::Full`], before polling the future of `f`.
+    pub fn guard<T, E, Fut, F>(&self, mut f: F) -> impl FnMut() -> BulkheadFuture<Fut>
+    where
+        Fut: Future<Output = Result<T, E>>,
+        F: FnMut() -> Fut,
+    {
+        let bulkhead = self.clone();
+        move || BulkheadFuture {
+            bulkhead: bulkhead.clone(),
+            waiting: None,
+            permit: None,
+            // Futures do nothing until polled, so it's only created early.
+            fut: f(),
+        }
+    }
+
+    /// Guard the blocking `f` with the bulkhead, for it to be retried, like `guard`, the
+    /// thread waiting for a permit.
+    pub fn guard_blocking<T, E, F>(&self, mut f: F) -> impl FnMut() -> Result<T, BulkheadError<E>>
+    where
+        F: FnMut() -> Result<T, E>,
+    {
+        let bulkhead = self.clone();
+        move || {
+            let _permit = bulkhead.acquire_blocking().ok_or(BulkheadError::Full)?;
+            f().map_err(BulkheadError::Failed)
+        }
+    }
+
+    /// Take a permit if one is left, or else wait for one if `queue` and the queue isn't
+    /// full. Returns the counts before.
+    ///
+    /// The counts are only updated with read-modify-writes, `AcqRel` to order the attempts
+    /// with the ones whose permits they take.
+    fn update(&self, queue: bool) -> Result<u64, u64> {
+        let max_in_flight = self.max_in_flight as u64;
+        let max_queued = self.max_queued as u64;
+        self.shared
+            .counts
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |counts| {
+                if running(counts) < max_in_flight {
+                    Some(counts + RUNNING)
+                } else if queue && waiting(counts) < max_queued {
+                    Some(counts + WAITING)
+                } else {
+                    None
+                }
+            })
+    }
+
+    fn permit(&self) -> Permit {
+        Permit {
+            bulkhead: self.clone(),
+        }
+    }
+
+    /// Take a permit if one is left, without queueing.
+    fn try_acquire(&self) -> Option<Permit> {
+        self.update(false).ok().map(|_| self.permit())
+    }
+
+    /// Take a permit for an attempt waiting, if one is left, no longer counting it waiting.
+    fn try_acquire_waiting(&self) -> Option<Permit> {
+        let max_in_flight = self.max_in_flight as u64;
+        self.shared
+            .counts
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |counts| {
+                (running(counts) < max_in_flight).then(|| counts + RUNNING - WAITING)
+            })
+            .ok()
+            .map(|_| self.permit())
+    }
+
+    /// Take a permit if one is left, or else count a new attempt waiting and return its ID,
+    /// or `Err(None)` if the queue is full.
+    fn acquire_or_enqueue(&self, waiters: &mut Waiters) -> Result<Permit, Option<u64>> {
+        let counts = self.update(true).map_err(|_| None)?;
+        if running(counts) < self.max_in_flight as u64 {
+            return Ok(self.permit());
+        }
+        let id = waiters.next_id;
+        waiters.next_id += 1;
+        Err(Some(id))
+    }
+
+    /// Take a permit, waiting for one in the queue, or `None` if the queue is full.
+    fn acquire_blocking(&self) -> Option<Permit> {
+        if let Some(permit) = self.try_acquire() {
+            return Some(permit);
+        }
+        let shared = &self.shared;
+        let mut waiters = sync::lock(&shared.waiters);
+        let id = match self.acquire_or_enqueue(&mut waiters) {
+            Ok(permit) => return Some(permit),
+            Err(id) => id?,
+        };
+        waiters.queue.push_back((id, None));
+        loop {
+            waiters = sync::wait(&shared.freed, waiters);
+            if let Some(permit) = self.try_acquire_waiting() {
+                waiters.remove(id);
+                return Some(permit);
+            }
+            // Woken at the front of the queue but beaten to the permit, it stays in front.
+            if !waiters.queue.iter().any(|(i, _)| *i == id) {
+                waiters.queue.push_front((id, None));
+            }
+        }
+    }
+
+    /// Poll for a permit, queueing the attempt `waiting` until one is left.
+    fn poll_acquire(
+        &self,
+        waiting: &mut Option<u64>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Permit>> {
+        if waiting.is_none() {
+            if let Some(permit) = self.try_acquire() {
+                return Poll::Ready(Some(permit));
+            }
+        }
+        let shared = &self.shared;
+        let mut waiters = sync::lock(&shared.waiters);
+        let id = match *waiting {
+            Some(id) => match self.try_acquire_waiting() {
+                Some(permit) => {
+                    waiters.remove(id);
+                    *waiting = None;
+                    return Poll::Ready(Some(permit));
+                }
+                None => id,
+            },
+            None => match self.acquire_or_enqueue(&mut waiters) {
+                Ok(permit) => return Poll::Ready(Some(permit)),
+                Err(Some(id)) => *waiting.insert(id),
+                Err(None) => return Poll::Ready(None),
+            },
+        };
+        let waker = Some(cx.waker().clone());
+        match waiters.queue.iter().position(|(i, _)| *i == id) {
+            Some(pos) => waiters.queue[pos].1 = waker,
+            // Woken at the front of the queue but beaten to the permit, it goes back in front,
+            // older than the others. New attempts go at the back.
+            None if waiters.queue.front().map_or(true, |(front, _)| id < *front) => {
+                waiters.queue.push_front((id, waker))
+            }
+            None => waiters.queue.push_back((id, waker)),
+        }
+        Poll::Pending
+    }
+
+    /// Wake the attempt at the front of the queue, if any.
+    fn wake_next(&self) {
+        let shared = &self.shared;
+        let next = sync::lock(&shared.waiters).queue.pop_front();
+        match next {
+            Some((_, Some(waker))) => waker.wake(),
+            Some((_, None)) => shared.freed.notify_all(),
+            None => {}
+        }
+    }
+
+    /// Stop waiting for a permit.
+    fn dequeue(&self, id: u64) {
+        let shared = &self.shared;
+        let removed = sync::lock(&shared.waiters).remove(id);
+        let counts = shared.counts.fetch_sub(WAITING, Ordering::AcqRel);
+        // The attempt was woken for a permit it won't take.
+        if !removed && waiting(counts) > 1 {
+            self.wake_next();
+        }
+    }
+}
+
+/// A permit of a bulkhead, released when dropped.
+#[derive(Debug)]
+struct Permit {
+    bulkhead: Bulkhead,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        let counts = self
+            .bulkhead
+            .shared
+            .counts
+            .fetch_sub(RUNNING, Ordering::AcqRel);
+        if waiting(counts) > 0 {
+            self.bulkhead.wake_next();
+        }
+    }
+}
+
+/// The future of a function guarded by [`Bulkhead::guard`].
+#[doc(hidden)]
+pub struct BulkheadFuture<Fut> {
+    bulkhead: Bulkhead,
+    /// The ID of the attempt in the queue, while it waits.
+    waiting: Option<u64>,
+    permit: Option<Permit>,
+    fut: Fut,
+}
+
+impl<Fut> fmt::Debug for BulkheadFuture<Fut> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BulkheadFuture")
+            .field("waiting", &self.waiting.is_some())
+            .field("running", &self.permit.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T, E, Fut: Future<Output = Result<T, E>>> Future for BulkheadFuture<Fut> {
+    type Output = Result<T, BulkheadError<E>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Safety: the future is pinned with this one, only the permit is moved out.
+        let this = unsafe { self.get_unchecked_mut() };
+        if this.permit.is_none() {
+            match ready!(this.bulkhead.poll_acquire(&mut this.waiting, cx)) {
+                Some(permit) => this.permit = Some(permit),
+                None => return Poll::Ready(Err(BulkheadError::Full)),
+            }
+        }
+        let res = ready!(unsafe { Pin::new_unchecked(&mut this.fut) }.poll(cx));
+        // Released as soon as the attempt finishes, not when the future is dropped.
+        this.permit = None;
+        Poll::Ready(res.map_err(BulkheadError::Failed))
+    }
+}
+
+impl<Fut> Drop for BulkheadFuture<Fut> {
+    fn drop(&mut self) {
+        if let Some(id) = self.waiting.take() {
+            self.bulkhead.dequeue(id);
+        }
+    }
+}
+
+/// BulkheadError is the error of a function guarded by a [`Bulkhead`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BulkheadError<E> {
+    /// The bulkhead had no permit left, nor room in its queue, the function wasn't called.
+    Full,
+    /// The function failed.
+    Failed(E),
+}
+
+impl<E> BulkheadError<E> {
+    /// Returns whether the call was rejected by the bulkhead.
+    pub fn is_full(&self) -> bool {
+        matches!(self, BulkheadError::Full)
+    }
+
+    /// Returns the error of the function, or `None` if the call was rejected.
+    pub fn into_inner(self) -> Option<E> {
+        match self {
+            BulkheadError::Full => None,
+            BulkheadError::Failed(err) => Some(err),
+        }
+    }
+
+    fn failed(&self) -> Option<&E> {
+        match self {
+            BulkheadError::Full => None,
+            BulkheadError::Failed(err) => Some(err),
+        }
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for BulkheadError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BulkheadError::Full => write!(f, "bulkhead is full"),
+            BulkheadError::Failed(err) => err.fmt(f),
+        }
+    }
+}
+
+impl<E: core::error::Error> core::error::Error for BulkheadError<E> {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        self.failed()?.source()
+    }
+}
+
+/// Rejected calls aren't retryable, as retrying them would only add to the load the bulkhead
+/// sheds, but they're safe to retry, the function not having been called.
+impl<E: RetryableError> RetryableError for BulkheadError<E> {
+    fn is_retryable(&self) -> bool {
+        self.failed().is_some_and(E::is_retryable)
+    }
+
+    fn retry_after(&self) -> Option<core::time::Duration> {
+        self.failed()?.retry_after()
+    }
+
+    fn is_safe_to_retry(&self) -> bool {
+        self.failed().map_or(true, E::is_safe_to_retry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    #[cfg(not(backon_loom))]
+    async fn test_bulkhead() {
+        use core::time::Duration;
+
+        let bulkhead = Bulkhead::new(2).with_max_queued(1);
+        let slow = || async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            Ok::<_, ()>(())
+        };
+        let mut guarded = bulkhead.guard(slow);
+        let mut first = core::pin::pin!(guarded());
+        let mut second = core::pin::pin!(guarded());
+        let mut third = core::pin::pin!(guarded());
+        let mut fourth = core::pin::pin!(guarded());
+
+        // Two attempts run, a third one waits and a fourth one is rejected.
+        let waker = Waker::from(Arc::new(Noop));
+        let mut cx = Context::from_waker(&waker);
+        assert!(first.as_mut().poll(&mut cx).is_pending());
+        assert!(second.as_mut().poll(&mut cx).is_pending());
+        assert!(third.as_mut().poll(&mut cx).is_pending());
+        assert_eq!(bulkhead.in_flight(), 2);
+        assert_eq!(bulkhead.queued(), 1);
+        assert_eq!(
+            fourth.as_mut().poll(&mut cx),
+            Poll::Ready(Err(BulkheadError::Full))
+        );
+
+        assert_eq!(first.await, Ok(()));
+        assert_eq!(third.await, Ok(()));
+        assert_eq!(second.await, Ok(()));
+        assert_eq!((bulkhead.in_flight(), bulkhead.queued()), (0, 0));
+    }
+
+    #[cfg(not(backon_loom))]
+    struct Noop;
+
+    #[cfg(not(backon_loom))]
+    impl std::task::Wake for Noop {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    #[test]
+    #[cfg(backon_loom)]
+    fn test_bulkhead_loom() {
+        loom::model(|| {
+            let bulkhead = Bulkhead::new(1);
+            let shared = bulkhead.clone();
+            let mut guarded = shared.guard_blocking(|| Ok::<_, ()>(()));
+            let thread = loom::thread::spawn(move || guarded());
+            // The permit is released to the other thread if it's waiting for it.
+            assert_eq!(bulkhead.guard_blocking(|| Ok::<_, ()>(()))(), Ok(()));
+            assert_eq!(thread.join().unwrap(), Ok(()));
+            assert_eq!(bulkhead.in_flight(), 0);
+        });
+    }
+}
@@ -0,0 +1,144 @@
+//! Retry flags for command line tools built with [`clap`].
+//!
+//! [`RetryArgs`] is flattened into the arguments of a tool, so every tool accepts the same
+//! retry flags:
+//!
+//! - `--retry <POLICY>`, the backoff described like a [`BackoffPolicy`], like
+//!   `--retry "exponential 1s..30s x2 max=5"`.
+//! - `--retry-delay`, `--retry-min-delay`, `--retry-max-delay`, `--retry-factor`,
+//!   `--retry-max-times` and `--retry-jitter`, overriding the options of the backoff like
+//!   [`BackoffOverrides`].
+//!
+//! [`BackoffPolicy`] is also parsed by clap on its own, for tools with their own flags.
+//!
+//! # Examples
+//!
+//! ```
+//! use backon::cli::RetryArgs;
+//! use backon::ExponentialBuilder;
+//! use clap::Parser;
+//!
+//! #[derive(Parser)]
+//! struct Cli {
+//!     #[command(flatten)]
+//!     retry: RetryArgs,
+//! }
+//!
+//! let cli = Cli::parse_from(["tool", "--retry", "fibonacci 1s..30s", "--retry-max-times", "5"]);
+//! let policy = cli.retry.policy(ExponentialBuilder::default());
+//!
+//! assert_eq!(policy.to_string(), "fibonacci 1s..30s max_times=5");
+//! ```
+
+use alloc::string::String;
+use alloc::string::ToString;
+use core::time::Duration;
+
+use crate::duration;
+use crate::BackoffOverrides;
+use crate::BackoffPolicy;
+use crate::Jitter;
+
+/// RetryArgs is the retry flags of a command line tool, see the [module](self) docs.
+#[derive(Debug, Clone, Default, PartialEq, ::clap::Args)]
+pub struct RetryArgs {
+    /// The backoff of the retries, like `exponential 1s..30s x2 max_times=5`.
+    #[arg(long = "retry", value_name = "POLICY")]
+    pub policy: Option<BackoffPolicy>,
+    /// The delay of a constant backoff, like `500ms`.
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub retry_delay: Option<Duration>,
+    /// The minimum delay between retries.
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub retry_min_delay: Option<Duration>,
+    /// The maximum delay between retries.
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub retry_max_delay: Option<Duration>,
+    /// The factor of an exponential backoff.
+    #[arg(long, value_name = "FACTOR")]
+    pub retry_factor: Option<f32>,
+    /// The maximum number of retries.
+    #[arg(long, value_name = "N")]
+    pub retry_max_times: Option<usize>,
+    /// The jitter added to every delay: `true`, `false`, a ratio like `0.2` or a range like
+    /// `10ms..50ms`.
+    #[arg(long, value_name = "JITTER", value_parser = parse_jitter)]
+    pub retry_jitter: Option<Jitter>,
+}
+
+impl RetryArgs {
+    /// Returns the backoff given by `--retry`, or `default` without it, with the options
+    /// given by the other flags.
+    pub fn policy(&self, default: impl Into<BackoffPolicy>) -> BackoffPolicy {
+        self.policy
+            .unwrap_or_else(|| default.into())
+            .merge(self.overrides())
+    }
+
+    /// Returns the options given by the flags other than `--retry`.
+    pub fn overrides(&self) -> BackoffOverrides {
+        BackoffOverrides {
+            delay: self.retry_delay,
+            min_delay: self.retry_min_delay,
+            max_delay: self.retry_max_delay,
+            factor: self.retry_factor,
+            max_times: self.retry_max_times,
+            jitter: self.retry_jitter,
+        }
+    }
+}
+
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    duration::parse(s).map_err(|err| err.to_string())
+}
+
+fn parse_jitter(s: &str) -> Result<Jitter, String> {
+    Jitter::parse(s).ok_or_else(|| "expected a bool, a ratio or a range".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use ::clap::Parser;
+
+    use super::*;
+    use crate::ConstantBuilder;
+    use crate::ExponentialBuilder;
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(flatten)]
+        retry: RetryArgs,
+    }
+
+    #[test]
+    fn test_retry_args() {
+        let cli = Cli::try_parse_from(["tool"]).unwrap();
+        assert_eq!(
+            cli.retry.policy(ConstantBuilder::default()),
+            ConstantBuilder::default().into()
+        );
+
+        let cli = Cli::try_parse_from([
+            "tool",
+            "--retry",
+            "exponential 1s..30s x2 max=5",
+            "--retry-min-delay",
+            "500ms",
+            "--retry-jitter",
+            "0.2",
+        ])
+        .unwrap();
+        let expected = ExponentialBuilder::default()
+            .with_min_delay(Duration::from_millis(500))
+            .with_max_delay(Duration::from_secs(30))
+            .with_max_times(5)
+            .with_jitter_policy(Jitter::ratio(0.2));
+        assert_eq!(
+            cli.retry.policy(ConstantBuilder::default()),
+            expected.into()
+        );
+
+        assert!(Cli::try_parse_from(["tool", "--retry", "linear 1s"]).is_err());
+        assert!(Cli::try_parse_from(["tool", "--retry-max-delay", "soon"]).is_err());
+    }
+}
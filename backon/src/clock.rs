@@ -0,0 +1,94 @@
+use alloc::sync::Arc;
+use core::fmt;
+use core::time::Duration;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+use std::sync::OnceLock;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+use std::time::Instant;
+
+/// Clock tells the time at which attempts of a retry start and finish, to measure the
+/// elapsed times and latencies in its events and report.
+///
+/// The time is the duration since an arbitrary fixed point, like the start of the process.
+/// Retries use [`StdClock`] by default with the `std` feature outside of `wasm32`. Set another
+/// one with `clock` to get timing data on targets without `Instant`, or to assert on exact
+/// timelines in tests.
+///
+/// Without the `timing` feature, enabled by default, retries have no clock unless one is set:
+/// they never read the time, and their elapsed times and latencies are zero, so retries
+/// that don't report them don't pay for reading the time twice per attempt. Deadlines like
+/// the one of a [`BudgetWarning`](crate::BudgetWarning) then need a clock set by `clock`.
+///
+/// It's implemented by all `Fn() -> Duration + Send + Sync`.
+///
+/// # Examples
+///
+/// ```
+/// use core::sync::atomic::AtomicU64;
+/// use core::sync::atomic::Ordering;
+/// use core::time::Duration;
+///
+/// use backon::BlockingRetryable;
+/// use backon::ConstantBuilder;
+///
+/// // Every reading of the clock moves it 10ms forward.
+/// static NOW: AtomicU64 = AtomicU64::new(0);
+/// let clock = || Duration::from_millis(NOW.fetch_add(10, Ordering::Relaxed));
+///
+/// let (result, report) = (|| Err::<(), _>("unavailable"))
+///     .retry(ConstantBuilder::default().with_delay(Duration::ZERO).with_max_times(1))
+///     .clock(clock)
+///     .call_with_report();
+///
+/// assert!(result.is_err());
+/// assert_eq!(
+///     report.latencies(),
+///     [Duration::from_millis(10), Duration::from_millis(10)]
+/// );
+/// ```
+pub trait Clock: Send + Sync {
+    /// Returns the current time.
+    fn now(&self) -> Duration;
+}
+
+impl<F: Fn() -> Duration + Send + Sync> Clock for F {
+    fn now(&self) -> Duration {
+        self()
+    }
+}
+
+/// The default clock, reading [`Instant::now`] relative to the first time it's read in the
+/// process.
+///
+/// Only available with the `std` feature outside of `wasm32`.
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StdClock;
+
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+impl Clock for StdClock {
+    fn now(&self) -> Duration {
+        static ORIGIN: OnceLock<Instant> = OnceLock::new();
+        ORIGIN.get_or_init(Instant::now).elapsed()
+    }
+}
+
+/// The clock set by `clock`, shared by the clones of the state of a retry.
+#[derive(Clone)]
+pub(crate) struct SharedClock(Arc<dyn Clock>);
+
+impl SharedClock {
+    pub(crate) fn new(clock: impl Clock + 'static) -> Self {
+        SharedClock(Arc::new(clock))
+    }
+
+    pub(crate) fn now(&self) -> Duration {
+        self.0.now()
+    }
+}
+
+impl fmt::Debug for SharedClock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SharedClock").finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,397 @@
+//! Retry policies loaded from configuration files.
+//!
+//! A [`RetryConfig`] is a map of named [`RetryPolicy`], so services can share one retry
+//! policy file format and look policies up by name:
+//!
+//! ```toml
+//! [fetch]
+//! backoff = "exponential"
+//! min_delay = "100ms"
+//! max_delay = "10s"
+//! max_times = 5
+//! jitter = true
+//!
+//! [upload]
+//! backoff = "conservative"
+//! max_times = 3
+//!
+//! [local]
+//! backoff = "constant 1s max=3"
+//! sleeper = "immediate"
+//! ```
+//!
+//! `backoff` is described like a [`BackoffPolicy`], from its name or a [`Preset`][crate::Preset]
+//! to its whole description. The other keys of a policy are the options of its backoff,
+//! `delay`, `min_delay`, `max_delay`, `factor`, `max_times` and `jitter`, overriding the
+//! description, and `sleeper`, see [`ConfigSleeper`].
+//!
+//! `RetryConfig` is deserialized from any format supported by serde, and directly from TOML
+//! with the `toml` feature and YAML with the `yaml` feature.
+
+use alloc::boxed::Box;
+use alloc::collections::btree_map;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::string::ToString;
+use core::fmt;
+use core::time::Duration;
+
+use serde::Deserialize;
+
+use crate::duration::Humane;
+use crate::Backoff;
+use crate::BackoffBuilder;
+use crate::BackoffPolicy;
+use crate::Jitter;
+use crate::ParseBackoffError;
+
+/// RetryConfig is a set of named retry policies, loaded from configuration.
+///
+/// # Examples
+///
+/// ```
+/// use backon::config::RetryConfig;
+/// use backon::BlockingRetryable;
+///
+/// let config: RetryConfig = serde_json::from_str(
+///     r#"{ "fetch": { "backoff": "exponential", "max_times": 3, "sleeper": "immediate" } }"#,
+/// )?;
+///
+/// let policy = config.get("fetch").expect("policy must exist");
+/// let result = (|| Err::<(), _>("unavailable"))
+///     .retry(policy)
+///     .sleep(policy.sleeper())
+///     .call();
+/// assert_eq!(result, Err("unavailable"));
+/// # Ok::<(), serde_json::Error>(())
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(transparent)]
+pub struct RetryConfig {
+    policies: BTreeMap<String, RetryPolicy>,
+}
+
+impl RetryConfig {
+    /// Load the retry policies from a TOML document, one table per policy.
+    #[cfg(feature = "toml")]
+    pub fn from_toml(s: &str) -> Result<Self, ConfigError> {
+        toml::from_str(s).map_err(|err| ConfigError::new(&err.to_string()))
+    }
+
+    /// Load the retry policies from a YAML document, one mapping per policy.
+    #[cfg(feature = "yaml")]
+    pub fn from_yaml(s: &str) -> Result<Self, ConfigError> {
+        serde_yaml::from_str(s).map_err(|err| ConfigError::new(&err.to_string()))
+    }
+
+    /// Returns the policy named `name`, if there is one.
+    pub fn get(&self, name: &str) -> Option<&RetryPolicy> {
+        self.policies.get(name)
+    }
+
+    /// Returns the names of the policies and the policies, sorted by name.
+    pub fn iter(&self) -> btree_map::Iter<'_, String, RetryPolicy> {
+        self.policies.iter()
+    }
+
+    /// Add the policy `name`, replacing the one of the same name.
+    pub fn insert(&mut self, name: impl Into<String>, policy: RetryPolicy) -> Option<RetryPolicy> {
+        self.policies.insert(name.into(), policy)
+    }
+}
+
+/// RetryPolicy is a policy of a [`RetryConfig`]: how long to wait between retries, and how.
+///
+/// It's a [`BackoffBuilder`] itself, and its sleeper is passed to `sleep`, like
+/// [`Retry::sleep`][crate::Retry::sleep].
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(try_from = "RawPolicy")]
+pub struct RetryPolicy {
+    backoff: BackoffPolicy,
+    sleeper: ConfigSleeper,
+}
+
+impl RetryPolicy {
+    /// Create a policy sleeping between retries of `backoff`.
+    pub fn new(backoff: impl Into<BackoffPolicy>) -> Self {
+        RetryPolicy {
+            backoff: backoff.into(),
+            sleeper: ConfigSleeper::Default,
+        }
+    }
+
+    /// Set the sleeper of the policy.
+    pub fn with_sleeper(mut self, sleeper: ConfigSleeper) -> Self {
+        self.sleeper = sleeper;
+        self
+    }
+
+    /// Returns the backoff of the policy.
+    pub fn backoff(&self) -> BackoffPolicy {
+        self.backoff
+    }
+
+    /// Returns the sleeper of the policy.
+    pub fn sleeper(&self) -> ConfigSleeper {
+        self.sleeper
+    }
+}
+
+impl BackoffBuilder for RetryPolicy {
+    type Backoff = Box<dyn Backoff>;
+
+    fn build(self) -> Self::Backoff {
+        self.backoff.build()
+    }
+}
+
+impl BackoffBuilder for &RetryPolicy {
+    type Backoff = Box<dyn Backoff>;
+
+    fn build(self) -> Self::Backoff {
+        self.backoff.build()
+    }
+}
+
+/// A policy as written in configuration, before it's checked.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawPolicy {
+    backoff: String,
+    #[serde(default, with = "crate::duration::serde::option")]
+    delay: Option<Duration>,
+    #[serde(default, with = "crate::duration::serde::option")]
+    min_delay: Option<Duration>,
+    #[serde(default, with = "crate::duration::serde::option")]
+    max_delay: Option<Duration>,
+    factor: Option<f32>,
+    max_times: Option<usize>,
+    jitter: Option<Jitter>,
+    #[serde(default)]
+    sleeper: ConfigSleeper,
+}
+
+impl TryFrom<RawPolicy> for RetryPolicy {
+    type Error = ConfigError;
+
+    fn try_from(raw: RawPolicy) -> Result<Self, Self::Error> {
+        let mut backoff: BackoffPolicy = raw.backoff.parse().map_err(ConfigError::from)?;
+        let options = [
+            ("delay", raw.delay.map(|dur| Humane(dur).to_string())),
+            (
+                "min_delay",
+                raw.min_delay.map(|dur| Humane(dur).to_string()),
+            ),
+            (
+                "max_delay",
+                raw.max_delay.map(|dur| Humane(dur).to_string()),
+            ),
+            ("factor", raw.factor.map(|factor| factor.to_string())),
+            ("max_times", raw.max_times.map(|n| n.to_string())),
+            ("jitter", raw.jitter.map(|jitter| jitter.to_string())),
+        ];
+        for (key, value) in options {
+            if let Some(value) = value {
+                backoff = backoff.set(key, key, &value)?;
+            }
+        }
+        if let Err(err) = backoff.try_build() {
+            return Err(ConfigError::new(&err.to_string()));
+        }
+        Ok(RetryPolicy::new(backoff).with_sleeper(raw.sleeper))
+    }
+}
+
+/// ConfigSleeper is the sleeper of a [`RetryPolicy`], set by its `sleeper` key.
+///
+/// It's a [`Sleeper`][crate::Sleeper] when the [`DefaultSleeper`][crate::DefaultSleeper] is
+/// one, and a [`BlockingSleeper`][crate::BlockingSleeper] when the
+/// [`DefaultBlockingSleeper`][crate::DefaultBlockingSleeper] is one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfigSleeper {
+    /// `default`: sleep with the default sleeper.
+    #[default]
+    Default,
+    /// `immediate`: retry without sleeping, like in tests and local development.
+    Immediate,
+}
+
+#[cfg(any(
+    all(not(target_arch = "wasm32"), feature = "tokio-sleep"),
+    all(target_arch = "wasm32", feature = "gloo-timers-sleep"),
+))]
+mod sleep {
+    use alloc::boxed::Box;
+    use core::future::Future;
+    use core::pin::Pin;
+    use core::task::Context;
+    use core::task::Poll;
+    use core::time::Duration;
+
+    use super::ConfigSleeper;
+    use crate::DefaultSleeper;
+    use crate::Sleeper;
+
+    /// The future returned by [`ConfigSleeper`], completed right away by
+    /// [`ConfigSleeper::Immediate`].
+    pub struct ConfigSleep(Option<Pin<Box<<DefaultSleeper as Sleeper>::Sleep>>>);
+
+    impl Future for ConfigSleep {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            match &mut self.0 {
+                Some(sleep) => sleep.as_mut().poll(cx),
+                None => Poll::Ready(()),
+            }
+        }
+    }
+
+    impl Sleeper for ConfigSleeper {
+        type Sleep = ConfigSleep;
+
+        fn sleep(&self, dur: Duration) -> Self::Sleep {
+            match self {
+                ConfigSleeper::Default => {
+                    ConfigSleep(Some(Box::pin(DefaultSleeper::default().sleep(dur))))
+                }
+                ConfigSleeper::Immediate => ConfigSleep(None),
+            }
+        }
+    }
+}
+
+#[cfg(any(
+    all(not(target_arch = "wasm32"), feature = "tokio-sleep"),
+    all(target_arch = "wasm32", feature = "gloo-timers-sleep"),
+))]
+pub use sleep::ConfigSleep;
+
+#[cfg(feature = "std-blocking-sleep")]
+impl crate::BlockingSleeper for ConfigSleeper {
+    fn sleep(&self, dur: Duration) {
+        match self {
+            ConfigSleeper::Default => crate::StdSleeper.sleep(dur),
+            ConfigSleeper::Immediate => {}
+        }
+    }
+}
+
+/// The error returned when loading an invalid [`RetryConfig`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConfigError {
+    message: String,
+}
+
+impl ConfigError {
+    fn new(message: &str) -> Self {
+        ConfigError {
+            message: message.trim_end().to_string(),
+        }
+    }
+}
+
+impl From<ParseBackoffError> for ConfigError {
+    fn from(err: ParseBackoffError) -> Self {
+        ConfigError::new(&err.to_string())
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl core::error::Error for ConfigError {}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+    use crate::ConstantBuilder;
+
+    const CONFIG: &str = r#"{
+        "fetch": {
+            "backoff": "exponential",
+            "min_delay": "1s",
+            "max_delay": 4,
+            "factor": 2,
+            "max_times": 4
+        },
+        "upload": { "backoff": "constant 500ms max=2", "sleeper": "immediate" }
+    }"#;
+
+    #[test]
+    fn test_retry_config() {
+        let config: RetryConfig = serde_json::from_str(CONFIG).unwrap();
+
+        let fetch = config.get("fetch").unwrap();
+        assert_eq!(fetch.sleeper(), ConfigSleeper::Default);
+        let delays: Vec<_> = fetch.build().collect();
+        assert_eq!(delays, [1, 2, 4, 4].map(Duration::from_secs));
+
+        let upload = config.get("upload").unwrap();
+        assert_eq!(
+            *upload,
+            RetryPolicy::new(
+                ConstantBuilder::default()
+                    .with_delay(Duration::from_millis(500))
+                    .with_max_times(2)
+            )
+            .with_sleeper(ConfigSleeper::Immediate)
+        );
+        assert!(config.get("download").is_none());
+        assert_eq!(config.iter().count(), 2);
+    }
+
+    #[test]
+    fn test_retry_config_errors() {
+        let err = |policy: &str| {
+            serde_json::from_str::<RetryConfig>(&alloc::format!(r#"{{ "p": {policy} }}"#))
+                .unwrap_err()
+                .to_string()
+        };
+
+        assert!(err(r#"{ "backoff": "constant", "min_delay": "1s" }"#)
+            .starts_with("invalid backoff policy at `min_delay`: unknown option for this backoff"));
+        assert!(err(r#"{ "backoff": "fibonacci", "max_times": 0 }"#)
+            .starts_with("max times is zero, the operation is never retried"));
+        assert!(
+            err(r#"{ "backoff": "constant", "retries": 3 }"#).contains("unknown field `retries`")
+        );
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_from_toml() {
+        let config = RetryConfig::from_toml(
+            r#"
+            [fetch]
+            backoff = "standard"
+            max_times = 2
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.get("fetch").unwrap().build().count(), 2);
+
+        let err = RetryConfig::from_toml("[fetch]\nbackoff = \"linear\"").unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("invalid backoff policy at `linear`"));
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_from_yaml() {
+        let config = RetryConfig::from_yaml(
+            "fetch:\n  backoff: fibonacci\n  min_delay: 1s\n  sleeper: immediate\n",
+        )
+        .unwrap();
+        let fetch = config.get("fetch").unwrap();
+        assert_eq!(fetch.sleeper(), ConfigSleeper::Immediate);
+        assert_eq!(fetch.build().next(), Some(Duration::from_secs(1)));
+    }
+}
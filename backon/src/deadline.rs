@@ -0,0 +1,189 @@
+use core::fmt;
+use core::time::Duration;
+
+use crate::RetryableError;
+
+/// Deadline is the time by which a retry must be done, inherited from the context of the
+/// call, like the timeout of the request being served.
+///
+/// Set on a retry with `deadline`, it bounds the whole retry, measured from the start of its
+/// first attempt by the [`Clock`](crate::Clock) of the retry, like
+/// [`RetryState::elapsed`](crate::RetryState::elapsed). The delays of the backoff and the
+/// ones asked for by errors are shortened so a retry still starts with at least
+/// `min_attempt` left, and once it can't, the retry gives up with
+/// [`GiveUpReason::DeadlineExceeded`](crate::GiveUpReason) instead of starting an attempt
+/// bound to run out of time. The first attempt always starts, there's no error to end the
+/// retry with before it.
+///
+/// Async retries also time out their attempts: an attempt still running after the time left
+/// until the deadline, or after the timeout set by `with_attempt_timeout` if it's shorter,
+/// is dropped and fails with [`AttemptTimedOut`], converted into the error of the retry.
+/// Blocking retries, and retries moving their context into the attempts, can't stop an
+/// attempt without losing it: their function bounds its attempts itself, with the timeout
+/// set by `with_attempt_timeout`.
+///
+/// Like the deadline of a [`BudgetWarning`](crate::BudgetWarning), it's never reached
+/// without a clock, see [`Clock`](crate::Clock).
+///
+/// # Examples
+///
+/// ```
+/// use core::time::Duration;
+///
+/// use backon::Deadline;
+/// use backon::ExponentialBuilder;
+/// use backon::Retryable;
+///
+/// async fn fetch() -> Result<String, std::io::Error> {
+///     Ok("hello, world!".to_string())
+/// }
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> Result<(), std::io::Error> {
+/// // The request being served must be answered within 2s, attempts running for over 500ms
+/// // are retried.
+/// let deadline = Deadline::after(Duration::from_secs(2))
+///     .with_attempt_timeout(Duration::from_millis(500))
+///     .with_min_attempt(Duration::from_millis(100));
+///
+/// let content = fetch
+///     .retry(ExponentialBuilder::default())
+///     .deadline(deadline)
+///     .await?;
+/// assert_eq!(content, "hello, world!");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Deadline {
+    after: Duration,
+    attempt_timeout: Option<Duration>,
+    min_attempt: Duration,
+}
+
+impl Deadline {
+    /// Create a deadline `after` the start of the first attempt of the retry.
+    pub fn after(after: Duration) -> Self {
+        Deadline {
+            after,
+            attempt_timeout: None,
+            min_attempt: Duration::ZERO,
+        }
+    }
+
+    /// Set the timeout of the attempts while the deadline is further away.
+    pub fn with_attempt_timeout(mut self, timeout: Duration) -> Self {
+        self.attempt_timeout = Some(timeout);
+        self
+    }
+
+    /// Set the least time an attempt needs to finish, no retry starting with less left.
+    pub fn with_min_attempt(mut self, min_attempt: Duration) -> Self {
+        self.min_attempt = min_attempt;
+        self
+    }
+
+    /// Returns the time left until the deadline once `elapsed` since the first attempt.
+    pub(crate) fn remaining(&self, elapsed: Duration) -> Duration {
+        self.after.saturating_sub(elapsed)
+    }
+
+    /// Returns the timeout of an attempt starting once `elapsed` since the first attempt.
+    pub(crate) fn attempt_timeout(&self, elapsed: Duration) -> Duration {
+        let remaining = self.remaining(elapsed);
+        self.attempt_timeout
+            .map_or(remaining, |timeout| timeout.min(remaining))
+    }
+
+    /// Returns the delay to sleep before the next attempt instead of `delay`, shortened for
+    /// the attempt to start with at least `min_attempt` left, or `None` if it can't.
+    pub(crate) fn fit_delay(&self, elapsed: Duration, delay: Duration) -> Option<Duration> {
+        let slack = self
+            .remaining(elapsed)
+            .checked_sub(self.min_attempt)
+            .filter(|slack| !slack.is_zero())?;
+        // Without `min_attempt`, an attempt starting at the deadline still has no time left.
+        if self.min_attempt.is_zero() && delay >= slack {
+            return None;
+        }
+        Some(delay.min(slack))
+    }
+}
+
+/// AttemptTimedOut is the error of an attempt of an async retry still running once the
+/// [`Deadline`] of the retry leaves it no more time.
+///
+/// Retries with a deadline convert it into their error with [`From`], implemented for
+/// [`std::io::Error`] with the `std` feature, and by errors like `anyhow::Error` accepting
+/// any error. It's retryable, every attempt gets a timeout of its own.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct AttemptTimedOut;
+
+impl fmt::Display for AttemptTimedOut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "attempt timed out")
+    }
+}
+
+impl core::error::Error for AttemptTimedOut {}
+
+impl RetryableError for AttemptTimedOut {
+    fn is_retryable(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<AttemptTimedOut> for std::io::Error {
+    fn from(err: AttemptTimedOut) -> Self {
+        std::io::Error::new(std::io::ErrorKind::TimedOut, err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deadline() {
+        let deadline = Deadline::after(Duration::from_secs(60))
+            .with_attempt_timeout(Duration::from_secs(1))
+            .with_min_attempt(Duration::from_secs(10));
+        assert_eq!(
+            deadline.attempt_timeout(Duration::ZERO),
+            Duration::from_secs(1)
+        );
+        assert_eq!(
+            deadline.attempt_timeout(Duration::from_millis(59_500)),
+            Duration::from_millis(500)
+        );
+
+        // Delays are shortened for the next attempt to start with 10s left.
+        assert_eq!(
+            deadline.fit_delay(Duration::ZERO, Duration::from_secs(1)),
+            Some(Duration::from_secs(1))
+        );
+        assert_eq!(
+            deadline.fit_delay(Duration::from_secs(5), Duration::from_secs(120)),
+            Some(Duration::from_secs(45))
+        );
+        assert_eq!(
+            deadline.fit_delay(Duration::from_secs(50), Duration::from_secs(1)),
+            None
+        );
+
+        let deadline = Deadline::after(Duration::from_secs(1));
+        assert_eq!(
+            deadline.attempt_timeout(Duration::from_secs(2)),
+            Duration::ZERO
+        );
+        assert_eq!(
+            deadline.fit_delay(Duration::ZERO, Duration::from_millis(500)),
+            Some(Duration::from_millis(500))
+        );
+        assert_eq!(
+            deadline.fit_delay(Duration::ZERO, Duration::from_secs(1)),
+            None
+        );
+    }
+}
@@ -0,0 +1,215 @@
+use core::fmt;
+use core::time::Duration;
+
+/// The units of human-readable durations, from the largest.
+const UNITS: [(&str, Duration); 7] = [
+    ("h", Duration::from_secs(3600)),
+    ("m", Duration::from_secs(60)),
+    ("s", Duration::from_secs(1)),
+    ("ms", Duration::from_millis(1)),
+    ("us", Duration::from_micros(1)),
+    ("µs", Duration::from_micros(1)),
+    ("ns", Duration::from_nanos(1)),
+];
+
+/// Humane formats a duration in the largest unit representing it exactly, like `250ms` or
+/// `2m`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Humane(pub(crate) Duration);
+
+impl fmt::Display for Humane {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_zero() {
+            return f.write_str("0s");
+        }
+        let nanos = self.0.as_nanos();
+        let (unit, size) = UNITS
+            .iter()
+            .find(|(_, size)| nanos % size.as_nanos() == 0)
+            .expect("every duration is a whole number of nanoseconds");
+        write!(f, "{}{unit}", nanos / size.as_nanos())
+    }
+}
+
+/// The error returned when parsing an invalid human-readable duration, like by
+/// `with_min_delay_str`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseDurationError;
+
+impl fmt::Display for ParseDurationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(
+            "invalid duration, expected a number followed by a unit like `250ms` or `1m30s`",
+        )
+    }
+}
+
+/// Parse a human-readable duration, made of numbers each followed by one of the units
+/// `h`, `m`, `s`, `ms`, `us` and `ns`, like `250ms`, `1.5s` or `1m30s`.
+pub(crate) fn parse(s: &str) -> Result<Duration, ParseDurationError> {
+    let mut rest = s.trim();
+    if rest.is_empty() {
+        return Err(ParseDurationError);
+    }
+    let mut total = Duration::ZERO;
+    while !rest.is_empty() {
+        let number_len = rest
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .ok_or(ParseDurationError)?;
+        let (number, tail) = rest.split_at(number_len);
+        let unit_len = tail
+            .find(|c: char| !c.is_alphabetic())
+            .unwrap_or(tail.len());
+        let (unit, tail) = tail.split_at(unit_len);
+        let (_, size) = UNITS
+            .iter()
+            .find(|(name, _)| *name == unit)
+            .ok_or(ParseDurationError)?;
+        let dur = match number.parse::<u32>() {
+            Ok(number) => size.checked_mul(number).ok_or(ParseDurationError)?,
+            Err(_) => {
+                let number: f64 = number.parse().map_err(|_| ParseDurationError)?;
+                Duration::try_from_secs_f64(number * size.as_secs_f64())
+                    .map_err(|_| ParseDurationError)?
+            }
+        };
+        total = total.checked_add(dur).ok_or(ParseDurationError)?;
+        rest = tail;
+    }
+    Ok(total)
+}
+
+/// Serialize durations in the human-readable form, and deserialize them from it or from a
+/// number of seconds.
+#[cfg(feature = "serde")]
+pub(crate) mod serde {
+    use alloc::vec::Vec;
+    use core::fmt;
+    use core::time::Duration;
+
+    use ::serde::de;
+    use ::serde::Deserialize;
+    use ::serde::Deserializer;
+    use ::serde::Serialize;
+    use ::serde::Serializer;
+
+    use super::Humane;
+
+    impl Serialize for Humane {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_str(self)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Humane {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            deserializer.deserialize_any(HumaneVisitor)
+        }
+    }
+
+    struct HumaneVisitor;
+
+    impl de::Visitor<'_> for HumaneVisitor {
+        type Value = Humane;
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("a duration like `250ms` or a number of seconds")
+        }
+
+        fn visit_str<E: de::Error>(self, v: &str) -> Result<Humane, E> {
+            super::parse(v).map(Humane).map_err(E::custom)
+        }
+
+        fn visit_u64<E: de::Error>(self, v: u64) -> Result<Humane, E> {
+            Ok(Humane(Duration::from_secs(v)))
+        }
+
+        fn visit_i64<E: de::Error>(self, v: i64) -> Result<Humane, E> {
+            let secs = u64::try_from(v).map_err(|_| E::custom("negative duration"))?;
+            self.visit_u64(secs)
+        }
+
+        fn visit_f64<E: de::Error>(self, v: f64) -> Result<Humane, E> {
+            Duration::try_from_secs_f64(v)
+                .map(Humane)
+                .map_err(E::custom)
+        }
+    }
+
+    pub(crate) fn serialize<S: Serializer>(
+        dur: &Duration,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        Humane(*dur).serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Duration, D::Error> {
+        Humane::deserialize(deserializer).map(|dur| dur.0)
+    }
+
+    pub(crate) mod option {
+        use super::*;
+
+        pub(crate) fn serialize<S: Serializer>(
+            dur: &Option<Duration>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            dur.map(Humane).serialize(serializer)
+        }
+
+        pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Option<Duration>, D::Error> {
+            Option::<Humane>::deserialize(deserializer).map(|dur| dur.map(|dur| dur.0))
+        }
+    }
+
+    pub(crate) mod vec {
+        use super::*;
+
+        pub(crate) fn serialize<S: Serializer>(
+            durs: &[Duration],
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq(durs.iter().copied().map(Humane))
+        }
+
+        pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Vec<Duration>, D::Error> {
+            Vec::<Humane>::deserialize(deserializer)
+                .map(|durs| durs.into_iter().map(|dur| dur.0).collect())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse("250ms"), Ok(Duration::from_millis(250)));
+        assert_eq!(parse("1.5s"), Ok(Duration::from_millis(1500)));
+        assert_eq!(parse("1m30s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse(" 2h "), Ok(Duration::from_secs(7200)));
+        assert_eq!(parse("10us"), Ok(Duration::from_micros(10)));
+        assert_eq!(parse(""), Err(ParseDurationError));
+        assert_eq!(parse("10"), Err(ParseDurationError));
+        assert_eq!(parse("10 parsecs"), Err(ParseDurationError));
+        assert_eq!(parse("-1s"), Err(ParseDurationError));
+    }
+
+    #[test]
+    fn test_humane() {
+        assert_eq!(Humane(Duration::ZERO).to_string(), "0s");
+        assert_eq!(Humane(Duration::from_millis(250)).to_string(), "250ms");
+        assert_eq!(Humane(Duration::from_secs(120)).to_string(), "2m");
+        assert_eq!(Humane(Duration::from_millis(1500)).to_string(), "1500ms");
+        assert_eq!(Humane(Duration::from_nanos(7)).to_string(), "7ns");
+    }
+}
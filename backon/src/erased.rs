@@ -0,0 +1,390 @@
+use alloc::boxed::Box;
+use core::fmt;
+use core::future::Future;
+use core::marker::PhantomData;
+use core::pin::Pin;
+use core::task::ready;
+use core::task::Context;
+use core::task::Poll;
+use core::time::Duration;
+
+use crate::error::ErrorClassifier;
+use crate::error::ErrorCollector;
+use crate::error::MapError;
+use crate::hooks::Hooks;
+use crate::report::Reported;
+use crate::retry::Notify;
+use crate::state::CurrentAttempt;
+use crate::Backoff;
+use crate::RetryState;
+use crate::Sleeper;
+use crate::WithReport;
+
+/// DynRetry is a [`Retry`](crate::Retry) whose parts are type-erased, returned by
+/// [`Retry::erase`](crate::Retry::erase).
+///
+/// [`Retry`](crate::Retry) is generic over its function, its backoff, its sleeper and every
+/// option set on it, so its retry loop is compiled again for every call site. The loop of
+/// `DynRetry` calls its parts through trait objects instead, so it's compiled once for every
+/// `T`, `E` and output error, and only the thin `erase` is compiled for every call site. It
+/// retries exactly like the retry it was erased from.
+///
+/// Erasing boxes the parts of the retry once, and polling then doesn't allocate either. The
+/// price is a virtual call for every part of an attempt, negligible next to the operations
+/// worth retrying, see the `erased` benchmark. The parts must be `Send`, so the retry can be
+/// spawned.
+///
+/// # Examples
+///
+/// ```no_run
+/// use backon::DynRetry;
+/// use backon::ExponentialBuilder;
+/// use backon::Retryable;
+///
+/// async fn fetch() -> Result<String, std::io::Error> {
+///     Ok("hello, world!".to_string())
+/// }
+///
+/// fn retried() -> DynRetry<'static, String, std::io::Error> {
+///     fetch
+///         .retry(ExponentialBuilder::default())
+///         .when(|e| e.kind() == std::io::ErrorKind::TimedOut)
+///         .erase()
+/// }
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> Result<(), std::io::Error> {
+/// let content = retried().await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct DynRetry<'a, T, E, Out = E> {
+    backoff: Box<dyn Backoff + 'a>,
+    retryable: Box<dyn ErrorClassifier<E> + Send + 'a>,
+    notify: DynNotify<'a, E>,
+    errors: Box<dyn ErrorCollector<E, Error = Out> + Send + 'a>,
+    hooks: Box<dyn Hooks<T, E> + Send + 'a>,
+    retry_state: RetryState,
+    attempts: Pin<Box<dyn Attempts<T, E> + Send + 'a>>,
+    sleeps: Pin<Box<dyn Sleeps + Send + 'a>>,
+    sleeping: bool,
+}
+
+/// The function set by `notify`, erased.
+pub(crate) type DynNotify<'a, E> = Box<dyn Notify<E> + Send + 'a>;
+
+/// The parts of a [`Retry`](crate::Retry) erased by [`DynRetry`].
+pub(crate) struct Parts<'a, T, E, Out> {
+    pub(crate) backoff: Box<dyn Backoff + 'a>,
+    pub(crate) retryable: Box<dyn ErrorClassifier<E> + Send + 'a>,
+    pub(crate) notify: DynNotify<'a, E>,
+    pub(crate) errors: Box<dyn ErrorCollector<E, Error = Out> + Send + 'a>,
+    pub(crate) hooks: Box<dyn Hooks<T, E> + Send + 'a>,
+    pub(crate) retry_state: RetryState,
+}
+
+impl<'a, T, E, Out> DynRetry<'a, T, E, Out> {
+    /// Erase the function and the sleeper of a retry, with its other parts.
+    pub(crate) fn new<E0, Fut, FutureFn, ME, SF>(
+        parts: Parts<'a, T, E, Out>,
+        future_fn: FutureFn,
+        map_err: ME,
+        sleeper: SF,
+    ) -> Self
+    where
+        T: 'a,
+        E0: 'a,
+        E: 'a,
+        Fut: Future<Output = Result<T, E0>> + Send + 'a,
+        FutureFn: FnMut() -> Fut + Send + 'a,
+        ME: MapError<E0, Error = E> + Send + 'a,
+        SF: Sleeper + Send,
+        SF::Sleep: Send + 'a,
+    {
+        DynRetry {
+            backoff: parts.backoff,
+            retryable: parts.retryable,
+            notify: parts.notify,
+            errors: parts.errors,
+            hooks: parts.hooks,
+            retry_state: parts.retry_state,
+            attempts: Box::pin(ErasedAttempts {
+                future_fn,
+                map_err,
+                fut: None,
+                _error: PhantomData,
+            }),
+            sleeps: Box::pin(ErasedSleeps {
+                sleeper,
+                sleep: None,
+                timer: None,
+            }),
+            sleeping: false,
+        }
+    }
+
+    /// Returns the index of the current attempt, starting from 1 for the first attempt, or 0
+    /// before the retry starts.
+    pub fn attempt(&self) -> usize {
+        self.retry_state.attempt()
+    }
+
+    /// Returns the time elapsed since the first attempt started.
+    pub fn elapsed(&self) -> Duration {
+        self.retry_state.elapsed()
+    }
+
+    /// Return a future yielding the [`RetryReport`](crate::RetryReport) of the retry alongside its output.
+    pub fn await_with_report(mut self) -> WithReport<Self> {
+        self.retry_state.start_report();
+        WithReport::new(self)
+    }
+}
+
+impl<T, E, Out> fmt::Debug for DynRetry<'_, T, E, Out> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DynRetry")
+            .field("retry_state", &self.retry_state)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T, E, Out> Reported for DynRetry<'_, T, E, Out> {
+    fn retry_state(&mut self) -> &mut RetryState {
+        &mut self.retry_state
+    }
+}
+
+impl<T, E, Out> Future for DynRetry<'_, T, E, Out> {
+    type Output = Result<T, Out>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // The futures are pinned in their boxes, so the retry itself can move.
+        let this = self.get_mut();
+
+        loop {
+            if this.sleeping {
+                this.hooks.enter_sleep();
+                let res = this.sleeps.as_mut().poll(cx);
+                this.hooks.exit_sleep();
+                ready!(res);
+
+                this.sleeping = false;
+                this.hooks.on_resume(&this.retry_state);
+            }
+            if !this.attempts.is_started() {
+                this.retry_state.start_attempt();
+                this.hooks.on_attempt(&this.retry_state);
+                {
+                    let _current = CurrentAttempt::enter(&this.retry_state);
+                    this.hooks.enter();
+                    this.attempts.as_mut().start();
+                    this.hooks.exit();
+                }
+                let timeout = this.retry_state.attempt_timeout();
+                this.sleeps.as_mut().start_timer(timeout);
+            }
+
+            let res = {
+                let _current = CurrentAttempt::enter(&this.retry_state);
+                this.hooks.enter();
+                let res = this.attempts.as_mut().poll(cx);
+                this.hooks.exit();
+                res
+            };
+            let res = match res {
+                Poll::Ready(res) => res,
+                Poll::Pending => match this.sleeps.as_mut().poll_timer(cx) {
+                    Poll::Ready(()) => match this.hooks.timed_out() {
+                        Some(err) => {
+                            this.attempts.as_mut().cancel();
+                            Err(err)
+                        }
+                        None => return Poll::Pending,
+                    },
+                    Poll::Pending => return Poll::Pending,
+                },
+            };
+            match res {
+                Ok(v) => {
+                    this.retry_state.succeed_attempt();
+                    this.hooks.on_success(&v, &this.retry_state);
+                    return Poll::Ready(Ok(v));
+                }
+                Err(err) => match this.retry_state.retry_delay(
+                    err,
+                    &mut this.backoff,
+                    &mut *this.retryable,
+                    &mut *this.errors,
+                ) {
+                    Err((err, reason)) => {
+                        if let Some(v) = this.hooks.recover(&err) {
+                            this.hooks.on_recovered(&err, reason, &this.retry_state);
+                            return Poll::Ready(Ok(v));
+                        }
+                        this.hooks.on_fatal(&err, reason, &this.retry_state);
+                        return Poll::Ready(Err(this.errors.finish(err, reason)));
+                    }
+                    Ok((err, dur)) => {
+                        this.notify.notify(&err, dur, &this.retry_state);
+                        this.hooks.on_transient(&err, dur, &this.retry_state);
+                        this.errors.collect(err, dur, &this.retry_state);
+                        this.sleeps.as_mut().start(dur);
+                        this.sleeping = true;
+                    }
+                },
+            }
+        }
+    }
+}
+
+/// The attempts of a retry, the future of the current one mapped to the error of the retry.
+trait Attempts<T, E> {
+    /// Returns whether an attempt is running.
+    fn is_started(&self) -> bool;
+
+    /// Start an attempt.
+    fn start(self: Pin<&mut Self>);
+
+    /// Poll the running attempt, finishing it once it's ready.
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<T, E>>;
+
+    /// Drop the running attempt, timed out.
+    fn cancel(self: Pin<&mut Self>);
+}
+
+struct ErasedAttempts<FutureFn, ME, Fut, E0> {
+    future_fn: FutureFn,
+    map_err: ME,
+    fut: Option<Fut>,
+    _error: PhantomData<fn(E0)>,
+}
+
+impl<T, E0, FutureFn, ME, Fut> Attempts<T, ME::Error> for ErasedAttempts<FutureFn, ME, Fut, E0>
+where
+    Fut: Future<Output = Result<T, E0>>,
+    FutureFn: FnMut() -> Fut,
+    ME: MapError<E0>,
+{
+    fn is_started(&self) -> bool {
+        self.fut.is_some()
+    }
+
+    fn start(self: Pin<&mut Self>) {
+        // Safety: the future is only moved in place, it's never moved out once pinned.
+        let this = unsafe { self.get_unchecked_mut() };
+        this.fut = Some((this.future_fn)());
+    }
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<T, ME::Error>> {
+        // Safety: the future is pinned with the attempts, and dropped in place when ready.
+        let this = unsafe { self.get_unchecked_mut() };
+        let fut = this.fut.as_mut().expect("no attempt is running");
+        let res = ready!(unsafe { Pin::new_unchecked(fut) }.poll(cx));
+        this.fut = None;
+        Poll::Ready(res.map_err(|err| this.map_err.map_err(err)))
+    }
+
+    fn cancel(self: Pin<&mut Self>) {
+        // Safety: the future is dropped in place.
+        let this = unsafe { self.get_unchecked_mut() };
+        this.fut = None;
+    }
+}
+
+/// The sleeps of a retry between its attempts.
+trait Sleeps {
+    /// Start sleeping for `dur`.
+    fn start(self: Pin<&mut Self>, dur: Duration);
+
+    /// Poll the running sleep.
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()>;
+
+    /// Start the timer timing out the attempt after `timeout`, if any.
+    fn start_timer(self: Pin<&mut Self>, timeout: Option<Duration>);
+
+    /// Poll the timer of the attempt, never ready without one, dropping it once it's done.
+    fn poll_timer(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()>;
+}
+
+struct ErasedSleeps<SF: Sleeper> {
+    sleeper: SF,
+    sleep: Option<SF::Sleep>,
+    timer: Option<SF::Sleep>,
+}
+
+impl<SF: Sleeper> Sleeps for ErasedSleeps<SF> {
+    fn start(self: Pin<&mut Self>, dur: Duration) {
+        // Safety: the sleep is only replaced in place, it's never moved out once pinned.
+        let this = unsafe { self.get_unchecked_mut() };
+        this.sleep = Some(this.sleeper.sleep(dur));
+    }
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        // Safety: the sleep is pinned with the sleeps.
+        let this = unsafe { self.get_unchecked_mut() };
+        match &mut this.sleep {
+            Some(sleep) => unsafe { Pin::new_unchecked(sleep) }.poll(cx),
+            None => Poll::Ready(()),
+        }
+    }
+
+    fn start_timer(self: Pin<&mut Self>, timeout: Option<Duration>) {
+        // Safety: the timer is only replaced in place, it's never moved out once pinned.
+        let this = unsafe { self.get_unchecked_mut() };
+        this.timer = timeout.map(|timeout| this.sleeper.sleep(timeout));
+    }
+
+    fn poll_timer(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        // Safety: the timer is pinned with the sleeps.
+        let this = unsafe { self.get_unchecked_mut() };
+        let res = match &mut this.timer {
+            Some(timer) => unsafe { Pin::new_unchecked(timer) }.poll(cx),
+            None => Poll::Pending,
+        };
+        // The timer is done, so it's never polled again: every attempt starts a new one.
+        if res.is_ready() {
+            this.timer = None;
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::future::ready;
+
+    use super::*;
+    use crate::ConstantBuilder;
+    use crate::Retryable;
+
+    #[tokio::test]
+    async fn test_dyn_retry() {
+        let mut attempts = 0;
+        let mut notified = 0;
+        let retry: DynRetry<'_, usize, &str> = (|| {
+            attempts += 1;
+            ready(if attempts < 3 {
+                Err("unavailable")
+            } else {
+                Ok(attempts)
+            })
+        })
+        .retry(ConstantBuilder::default().with_delay(Duration::from_millis(1)))
+        .notify(|_, _| notified += 1)
+        .sleep(|_| ready(()))
+        .erase();
+        let (result, report) = retry.await_with_report().await;
+        assert_eq!(result, Ok(3));
+        assert_eq!(report.attempts(), 3);
+        assert_eq!(notified, 2);
+
+        let result = (|| ready(Err::<(), _>("unavailable")))
+            .retry(ConstantBuilder::default().with_delay(Duration::from_millis(1)))
+            .when(|e| *e != "unavailable")
+            .sleep(|_| ready(()))
+            .erase()
+            .await;
+        assert_eq!(result, Err("unavailable"));
+    }
+}
@@ -0,0 +1,119 @@
+use core::marker::PhantomData;
+use core::ops::ControlFlow;
+
+use super::retry_if;
+use super::ErrorClassifier;
+use crate::RetryState;
+
+/// Conditions set by `when_downcast` that retry errors caused by `X`.
+#[doc(hidden)]
+pub struct WhenDowncast<X>(PhantomData<fn() -> X>);
+
+impl<X> WhenDowncast<X> {
+    pub(crate) fn new() -> Self {
+        WhenDowncast(PhantomData)
+    }
+}
+
+impl<X: core::error::Error + 'static> ErrorClassifier<::anyhow::Error> for WhenDowncast<X> {
+    fn classify(
+        &mut self,
+        err: ::anyhow::Error,
+        _: &RetryState,
+    ) -> ControlFlow<::anyhow::Error, ::anyhow::Error> {
+        retry_if(err.chain().any(|e| e.is::<X>()), err)
+    }
+}
+
+/// Build conditions for `when` that retry [`anyhow::Error`]s caused by any of the given
+/// error types.
+///
+/// The whole chain of the error is inspected, so errors with context attached are matched
+/// as well.
+///
+/// # Examples
+///
+/// ```no_run
+/// use anyhow::Result;
+/// use backon::when_any_downcast;
+/// use backon::ExponentialBuilder;
+/// use backon::Retryable;
+///
+/// async fn fetch() -> Result<String> {
+///     Ok(reqwest::get("https://www.rust-lang.org")
+///         .await?
+///         .text()
+///         .await?)
+/// }
+///
+/// #[tokio::main(flavor = "current_thread")]
+/// async fn main() -> Result<()> {
+///     let content = fetch
+///         .retry(ExponentialBuilder::default())
+///         .when(when_any_downcast!(std::io::Error, reqwest::Error))
+///         .await?;
+///     println!("fetch succeeded: {}", content);
+///
+///     Ok(())
+/// }
+/// ```
+#[macro_export]
+macro_rules! when_any_downcast {
+    ($($ty:ty),+ $(,)?) => {
+        |err: &::anyhow::Error| err.chain().any(|e| $(e.is::<$ty>())||+)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+    use anyhow::Context;
+    use core::fmt;
+    use core::time::Duration;
+    use spin::Mutex;
+
+    use crate::BlockingRetryable;
+    use crate::ExponentialBuilder;
+
+    #[derive(Debug)]
+    struct Transient;
+
+    impl fmt::Display for Transient {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("transient")
+        }
+    }
+
+    impl core::error::Error for Transient {}
+
+    #[test]
+    fn test_when_downcast() -> anyhow::Result<()> {
+        let error_times = Mutex::new(0);
+
+        let f = || {
+            let mut x = error_times.lock();
+            *x += 1;
+            if *x < 3 {
+                Err::<(), _>(anyhow::Error::new(Transient)).context("fetch")
+            } else {
+                Err(anyhow::anyhow!("fatal"))
+            }
+        };
+
+        let backoff = ExponentialBuilder::default().with_min_delay(Duration::from_millis(1));
+        let result = f.retry(backoff).when_downcast::<Transient>().call();
+
+        assert_eq!("fatal", result.unwrap_err().to_string());
+        assert_eq!(*error_times.lock(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_when_any_downcast() {
+        let when = when_any_downcast!(Transient, fmt::Error);
+
+        assert!(when(&anyhow::Error::new(Transient)));
+        assert!(when(&anyhow::Error::new(fmt::Error).context("format")));
+        assert!(!when(&anyhow::anyhow!("fatal")));
+    }
+}
@@ -0,0 +1,85 @@
+use std::error::Error;
+use std::io;
+
+use ::h2::Reason;
+
+use super::RetryableError;
+
+/// Classifies [`hyper::Error`] as retryable if the request can be safely sent again.
+///
+/// Connections closed before the message completed, canceled requests that were never
+/// dispatched and timeouts are retryable. Otherwise the error's sources are inspected for
+/// a retryable [`h2::Error`] or [`std::io::Error`].
+impl RetryableError for ::hyper::Error {
+    fn is_retryable(&self) -> bool {
+        if self.is_incomplete_message() || self.is_canceled() || self.is_timeout() {
+            return true;
+        }
+
+        let mut source = self.source();
+        while let Some(err) = source {
+            if let Some(err) = err.downcast_ref::<::h2::Error>() {
+                return err.is_retryable();
+            }
+            if let Some(err) = err.downcast_ref::<io::Error>() {
+                return err.is_retryable();
+            }
+            source = err.source();
+        }
+        false
+    }
+
+    /// Canceled requests were never dispatched, otherwise the error's sources are inspected
+    /// for a [`h2::Error`] telling the request was not processed.
+    fn is_safe_to_retry(&self) -> bool {
+        if self.is_canceled() {
+            return true;
+        }
+
+        let mut source = self.source();
+        while let Some(err) = source {
+            if let Some(err) = err.downcast_ref::<::h2::Error>() {
+                return err.is_safe_to_retry();
+            }
+            source = err.source();
+        }
+        false
+    }
+}
+
+/// Classifies [`h2::Error`] as retryable if the request definitely has not been processed.
+///
+/// Streams reset with `REFUSED_STREAM` and streams refused by a graceful `GOAWAY` with
+/// `NO_ERROR` are retryable, as well as retryable [`std::io::Error`]s.
+impl RetryableError for ::h2::Error {
+    fn is_retryable(&self) -> bool {
+        if let Some(err) = self.get_io() {
+            return err.is_retryable();
+        }
+
+        match self.reason() {
+            Some(Reason::REFUSED_STREAM) => true,
+            Some(Reason::NO_ERROR) => self.is_go_away(),
+            _ => false,
+        }
+    }
+
+    /// Both retryable reasons guarantee the stream was not processed by the server.
+    fn is_safe_to_retry(&self) -> bool {
+        self.get_io().is_none() && self.is_retryable()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_h2_error_is_retryable() {
+        assert!(::h2::Error::from(Reason::REFUSED_STREAM).is_retryable());
+        assert!(::h2::Error::from(Reason::REFUSED_STREAM).is_safe_to_retry());
+        assert!(!::h2::Error::from(Reason::NO_ERROR).is_retryable());
+        assert!(!::h2::Error::from(Reason::PROTOCOL_ERROR).is_retryable());
+        assert!(!::h2::Error::from(Reason::INTERNAL_ERROR).is_retryable());
+    }
+}
@@ -0,0 +1,992 @@
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use core::fmt;
+use core::mem;
+use core::ops::ControlFlow;
+use core::ops::Deref;
+use core::time::Duration;
+
+use crate::BackoffBuilder;
+use crate::BackoffChoice;
+use crate::RetryState;
+
+#[cfg(feature = "anyhow")]
+mod anyhow;
+#[cfg(feature = "anyhow")]
+pub(crate) use self::anyhow::WhenDowncast;
+
+#[cfg(feature = "hyper")]
+mod hyper;
+
+#[cfg(feature = "reqwest")]
+mod reqwest;
+#[cfg(feature = "reqwest")]
+pub use self::reqwest::ReqwestError;
+
+/// RetryableError is implemented by errors that know whether the operation producing them
+/// should be retried.
+///
+/// Library authors can encode retryability on their error types once, and users can retry
+/// with it by calling `when_retryable` instead of `when` on the retry.
+///
+/// # Examples
+///
+/// ```no_run
+/// use core::time::Duration;
+///
+/// use backon::ExponentialBuilder;
+/// use backon::Retryable;
+/// use backon::RetryableError;
+///
+/// #[derive(Debug)]
+/// enum Error {
+///     Throttled,
+///     NotFound,
+/// }
+///
+/// impl RetryableError for Error {
+///     fn is_retryable(&self) -> bool {
+///         matches!(self, Error::Throttled)
+///     }
+///
+///     fn retry_after(&self) -> Option<Duration> {
+///         match self {
+///             Error::Throttled => Some(Duration::from_secs(5)),
+///             Error::NotFound => None,
+///         }
+///     }
+/// }
+///
+/// async fn fetch() -> Result<String, Error> {
+///     Err(Error::Throttled)
+/// }
+///
+/// #[tokio::main(flavor = "current_thread")]
+/// async fn main() -> Result<(), Error> {
+///     let content = fetch
+///         .retry(ExponentialBuilder::default())
+///         .when_retryable()
+///         .await?;
+///     println!("fetch succeeded: {}", content);
+///
+///     Ok(())
+/// }
+/// ```
+pub trait RetryableError {
+    /// Returns `true` if the operation producing this error should be retried.
+    fn is_retryable(&self) -> bool;
+
+    /// Returns the delay suggested by this error before retrying, like the `Retry-After`
+    /// header of a HTTP response.
+    ///
+    /// The suggested delay is used instead of the delay produced by the backoff. The retry
+    /// still counts towards the backoff's limits.
+    ///
+    /// Returns `None` by default.
+    fn retry_after(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Returns `true` if the operation definitely had no effect, so that it's safe to retry
+    /// even if it's not idempotent, like a request refused before being processed.
+    ///
+    /// Only consulted by retries configured with `idempotent(false)`. Returns `false` by
+    /// default, since errors like a connection dropped after sending leave the outcome
+    /// ambiguous.
+    fn is_safe_to_retry(&self) -> bool {
+        false
+    }
+}
+
+/// A stub trait allowing both `FnMut(&E) -> bool` and errors implementing [`RetryableError`]
+/// to decide whether to retry.
+#[doc(hidden)]
+pub trait ErrorClassifier<E> {
+    /// Returns `Continue` with the error to retry, or `Break` with the error to end the
+    /// retry with.
+    fn classify(&mut self, err: E, state: &RetryState) -> ControlFlow<E, E>;
+
+    fn retry_after(&mut self, _: &E) -> Option<Duration> {
+        None
+    }
+
+    fn is_safe_to_retry(&mut self, _: &E) -> bool {
+        false
+    }
+
+    /// Returns the next delay of the backoff chosen for the error, or `None` to use the
+    /// backoff of the retry.
+    fn backoff(&mut self, _: &E) -> Option<Option<Duration>> {
+        None
+    }
+}
+
+pub(crate) fn retry_if<E>(retryable: bool, err: E) -> ControlFlow<E, E> {
+    if retryable {
+        ControlFlow::Continue(err)
+    } else {
+        ControlFlow::Break(err)
+    }
+}
+
+/// All `FnMut(&E) -> bool` implements `ErrorClassifier`.
+impl<E, F: FnMut(&E) -> bool> ErrorClassifier<E> for F {
+    fn classify(&mut self, err: E, _: &RetryState) -> ControlFlow<E, E> {
+        retry_if(self(&err), err)
+    }
+}
+
+/// Conditions that accept the retry state, set by `when_with_state`.
+#[doc(hidden)]
+pub struct WhenWithState<F>(pub(crate) F);
+
+impl<E, F: FnMut(&E, &RetryState) -> bool> ErrorClassifier<E> for WhenWithState<F> {
+    fn classify(&mut self, err: E, state: &RetryState) -> ControlFlow<E, E> {
+        retry_if((self.0)(&err, state), err)
+    }
+}
+
+/// Classifier receiving the errors by value, set by `classify`.
+#[doc(hidden)]
+pub struct Classify<F>(pub(crate) F);
+
+impl<E, F: FnMut(E, &RetryState) -> ControlFlow<E, E>> ErrorClassifier<E> for Classify<F> {
+    fn classify(&mut self, err: E, state: &RetryState) -> ControlFlow<E, E> {
+        (self.0)(err, state)
+    }
+}
+
+/// Classifier set by `when_retryable` that delegates to [`RetryableError`].
+#[doc(hidden)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct WhenRetryableError;
+
+impl<E: RetryableError> ErrorClassifier<E> for WhenRetryableError {
+    fn classify(&mut self, err: E, _: &RetryState) -> ControlFlow<E, E> {
+        retry_if(err.is_retryable(), err)
+    }
+
+    fn retry_after(&mut self, err: &E) -> Option<Duration> {
+        err.retry_after()
+    }
+
+    fn is_safe_to_retry(&mut self, err: &E) -> bool {
+        err.is_safe_to_retry()
+    }
+}
+
+/// Classifier set by `backoff_by` that picks the backoff of every error.
+#[doc(hidden)]
+pub struct BackoffBy<RF, F, B: BackoffBuilder> {
+    inner: RF,
+    choose: F,
+    classes: Vec<(&'static str, B::Backoff)>,
+}
+
+impl<RF, F, B: BackoffBuilder> BackoffBy<RF, F, B> {
+    pub(crate) fn new(inner: RF, choose: F) -> Self {
+        BackoffBy {
+            inner,
+            choose,
+            classes: Vec::new(),
+        }
+    }
+}
+
+impl<E, RF, F, B> ErrorClassifier<E> for BackoffBy<RF, F, B>
+where
+    RF: ErrorClassifier<E>,
+    F: FnMut(&E) -> BackoffChoice<B>,
+    B: BackoffBuilder,
+{
+    fn classify(&mut self, err: E, state: &RetryState) -> ControlFlow<E, E> {
+        self.inner.classify(err, state)
+    }
+
+    fn retry_after(&mut self, err: &E) -> Option<Duration> {
+        self.inner.retry_after(err)
+    }
+
+    fn is_safe_to_retry(&mut self, err: &E) -> bool {
+        self.inner.is_safe_to_retry(err)
+    }
+
+    fn backoff(&mut self, err: &E) -> Option<Option<Duration>> {
+        let BackoffChoice::Class(class, builder) = (self.choose)(err) else {
+            return self.inner.backoff(err);
+        };
+        let index = match self.classes.iter().position(|(name, _)| *name == class) {
+            Some(index) => index,
+            None => {
+                self.classes.push((class, builder.build()));
+                self.classes.len() - 1
+            }
+        };
+        Some(self.classes[index].1.next())
+    }
+}
+
+/// A stub trait mapping the errors of the retried function before they are classified.
+#[doc(hidden)]
+pub trait MapError<E> {
+    type Error;
+
+    fn map_err(&mut self, err: E) -> Self::Error;
+}
+
+/// Default mapping that keeps the errors as is.
+#[doc(hidden)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Unmapped;
+
+impl<E> MapError<E> for Unmapped {
+    type Error = E;
+
+    fn map_err(&mut self, err: E) -> E {
+        err
+    }
+}
+
+/// Mapping set by `map_err_before`.
+#[doc(hidden)]
+pub struct MapErrBefore<F>(pub(crate) F);
+
+impl<E, E2, F: FnMut(E) -> E2> MapError<E> for MapErrBefore<F> {
+    type Error = E2;
+
+    fn map_err(&mut self, err: E) -> E2 {
+        (self.0)(err)
+    }
+}
+
+/// RetryError wraps the error that ended a retry.
+///
+/// It's returned by retries configured with `wrap_err`. Its [`source`](core::error::Error::source)
+/// is the original error, so error reporters like `anyhow` keep the cause chain intact.
+///
+/// # Examples
+///
+/// ```no_run
+/// use anyhow::Result;
+/// use backon::ExponentialBuilder;
+/// use backon::Retryable;
+///
+/// async fn fetch() -> Result<String, std::io::Error> {
+///     Err(std::io::ErrorKind::TimedOut.into())
+/// }
+///
+/// #[tokio::main(flavor = "current_thread")]
+/// async fn main() -> Result<()> {
+///     let content = fetch
+///         .retry(ExponentialBuilder::default())
+///         .wrap_err()
+///         .await?;
+///     println!("fetch succeeded: {}", content);
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug)]
+pub struct RetryError<E> {
+    error: E,
+    attempts: usize,
+    reason: GiveUpReason,
+    history: Vec<FailedAttempt<E>>,
+}
+
+impl<E> RetryError<E> {
+    /// Returns the number of attempts made, including the first one.
+    pub fn attempts(&self) -> usize {
+        self.attempts
+    }
+
+    /// Returns the reason why the retry gave up.
+    pub fn reason(&self) -> GiveUpReason {
+        self.reason
+    }
+
+    /// Returns the attempts that failed before the last one, from the oldest to the newest.
+    ///
+    /// It's empty unless the retry is configured with `wrap_err_with_history`, and only keeps
+    /// the configured number of most recent attempts.
+    pub fn history(&self) -> &[FailedAttempt<E>] {
+        &self.history
+    }
+
+    /// Returns a reference to the error that ended the retry.
+    pub fn get_ref(&self) -> &E {
+        &self.error
+    }
+
+    /// Consumes the `RetryError`, returning the error that ended the retry.
+    pub fn into_inner(self) -> E {
+        self.error
+    }
+}
+
+impl<E> fmt::Display for RetryError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.attempts {
+            1 => write!(f, "retry failed after 1 attempt"),
+            n => write!(f, "retry failed after {n} attempts"),
+        }
+    }
+}
+
+impl<E: core::error::Error + 'static> core::error::Error for RetryError<E> {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// GiveUpReason tells why a retry ended without success.
+///
+/// It's kept in [`RetryError`] returned by retries configured with `wrap_err`, so that
+/// giving up can be told apart from being told not to try again. A retry that's dropped
+/// before it ends, like when the task awaiting it is cancelled, doesn't end with a reason.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum GiveUpReason {
+    /// The error is not retryable, either by the conditions set by `when` or by being
+    /// [`Permanent`].
+    NotRetryable,
+    /// The backoff has no delay left for another attempt, like after its maximum number of
+    /// retries.
+    BackoffExhausted,
+    /// The outcome of a non-idempotent operation is ambiguous, so it's not safe to retry.
+    Ambiguous,
+    /// The failure budget or the retry budget shared with other retries is exhausted.
+    BudgetExhausted,
+    /// The time allowed for the retry has run out, or what's left of the time until the
+    /// deadline set by `deadline` is too short for another attempt.
+    DeadlineExceeded,
+    /// Sleeping before another attempt would exceed the total sleep allowed by
+    /// `max_total_sleep`.
+    MaxTotalSleepReached,
+}
+
+impl GiveUpReason {
+    /// Returns the reason in `snake_case`, suitable as a label of metrics.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GiveUpReason::NotRetryable => "not_retryable",
+            GiveUpReason::BackoffExhausted => "backoff_exhausted",
+            GiveUpReason::Ambiguous => "ambiguous",
+            GiveUpReason::BudgetExhausted => "budget_exhausted",
+            GiveUpReason::DeadlineExceeded => "deadline_exceeded",
+            GiveUpReason::MaxTotalSleepReached => "max_total_sleep_reached",
+        }
+    }
+}
+
+impl fmt::Display for GiveUpReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GiveUpReason::NotRetryable => write!(f, "error is not retryable"),
+            GiveUpReason::BackoffExhausted => write!(f, "backoff exhausted"),
+            GiveUpReason::Ambiguous => write!(f, "outcome is ambiguous"),
+            GiveUpReason::BudgetExhausted => write!(f, "budget exhausted"),
+            GiveUpReason::DeadlineExceeded => write!(f, "deadline exceeded"),
+            GiveUpReason::MaxTotalSleepReached => write!(f, "maximum total sleep reached"),
+        }
+    }
+}
+
+/// A stub trait deciding the error returned when a retry ends.
+#[doc(hidden)]
+pub trait ErrorCollector<E> {
+    type Error;
+
+    /// Returns `true` if the error ends the retry regardless of the conditions set by `when`.
+    fn is_permanent(&mut self, _: &E) -> bool {
+        false
+    }
+
+    /// Called with every error that will be retried, returning it back if it's not kept.
+    fn collect(&mut self, err: E, delay: Duration, state: &RetryState) -> Option<Self::Error>;
+
+    /// Called with the error that ends the retry and the reason why the retry ends.
+    fn finish(&mut self, err: E, reason: GiveUpReason) -> Self::Error;
+}
+
+/// Default collector that returns the error that ended the retry as is.
+#[doc(hidden)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LastError;
+
+impl<E> ErrorCollector<E> for LastError {
+    type Error = E;
+
+    fn collect(&mut self, err: E, _: Duration, _: &RetryState) -> Option<E> {
+        Some(err)
+    }
+
+    fn finish(&mut self, err: E, _: GiveUpReason) -> E {
+        err
+    }
+}
+
+/// Collector set by `reduce_errors` that reduces all errors into one.
+#[doc(hidden)]
+pub struct ReduceErrors<E, F> {
+    reduce: F,
+    prev: Option<E>,
+}
+
+impl<E, F: FnMut(E, E) -> E> ReduceErrors<E, F> {
+    pub(crate) fn new(reduce: F) -> Self {
+        ReduceErrors { reduce, prev: None }
+    }
+
+    fn reduce(&mut self, err: E) -> E {
+        match self.prev.take() {
+            Some(prev) => (self.reduce)(prev, err),
+            None => err,
+        }
+    }
+}
+
+impl<E, F: FnMut(E, E) -> E> ErrorCollector<E> for ReduceErrors<E, F> {
+    type Error = E;
+
+    fn collect(&mut self, err: E, _: Duration, _: &RetryState) -> Option<E> {
+        self.prev = Some(self.reduce(err));
+        None
+    }
+
+    fn finish(&mut self, err: E, _: GiveUpReason) -> E {
+        self.reduce(err)
+    }
+}
+
+/// FinalError selects the error returned when a retry ends unsuccessfully.
+///
+/// It's set by `final_error`. By default, the error of the last attempt is returned.
+///
+/// # Examples
+///
+/// ```
+/// use backon::BlockingRetryable;
+/// use backon::ConstantBuilder;
+/// use backon::FinalError;
+///
+/// let mut attempts = 0;
+/// let result = (|| {
+///     attempts += 1;
+///     Err::<(), _>(attempts)
+/// })
+/// .retry(ConstantBuilder::default().with_max_times(2))
+/// .final_error(FinalError::First)
+/// .call();
+///
+/// assert_eq!(result, Err(1));
+/// ```
+#[derive(Debug, Default)]
+pub enum FinalError<E> {
+    /// Return the error of the first attempt.
+    First,
+    /// Return the error of the last attempt.
+    #[default]
+    Last,
+    /// Reduce the errors of all attempts into one, see `reduce_errors`.
+    Reduced(fn(E, E) -> E),
+}
+
+impl<E> Clone for FinalError<E> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<E> Copy for FinalError<E> {}
+
+/// Collector set by `final_error` that keeps the error selected by [`FinalError`].
+#[doc(hidden)]
+pub struct SelectError<E> {
+    policy: FinalError<E>,
+    kept: Option<E>,
+}
+
+impl<E> SelectError<E> {
+    pub(crate) fn new(policy: FinalError<E>) -> Self {
+        SelectError { policy, kept: None }
+    }
+}
+
+impl<E> ErrorCollector<E> for SelectError<E> {
+    type Error = E;
+
+    fn collect(&mut self, err: E, _: Duration, _: &RetryState) -> Option<E> {
+        match self.policy {
+            FinalError::First if self.kept.is_some() => Some(err),
+            FinalError::First => {
+                self.kept = Some(err);
+                None
+            }
+            FinalError::Last => Some(err),
+            FinalError::Reduced(_) => {
+                self.kept = Some(self.select(err));
+                None
+            }
+        }
+    }
+
+    fn finish(&mut self, err: E, _: GiveUpReason) -> E {
+        self.select(err)
+    }
+}
+
+impl<E> SelectError<E> {
+    fn select(&mut self, err: E) -> E {
+        match (self.policy, self.kept.take()) {
+            (FinalError::First, Some(first)) => first,
+            (FinalError::Reduced(reduce), Some(prev)) => reduce(prev, err),
+            _ => err,
+        }
+    }
+}
+
+/// Collector set by `wrap_err` that wraps the error into a [`RetryError`].
+#[doc(hidden)]
+pub struct WrapRetryError<EC, E> {
+    inner: EC,
+    retries: usize,
+    history: VecDeque<FailedAttempt<E>>,
+    history_cap: usize,
+}
+
+impl<EC, E> WrapRetryError<EC, E> {
+    pub(crate) fn new(inner: EC, history_cap: usize) -> Self {
+        WrapRetryError {
+            inner,
+            retries: 0,
+            history: VecDeque::new(),
+            history_cap,
+        }
+    }
+}
+
+impl<E, EC: ErrorCollector<E>> ErrorCollector<E> for WrapRetryError<EC, EC::Error> {
+    type Error = RetryError<EC::Error>;
+
+    fn is_permanent(&mut self, err: &E) -> bool {
+        self.inner.is_permanent(err)
+    }
+
+    fn collect(&mut self, err: E, delay: Duration, state: &RetryState) -> Option<Self::Error> {
+        self.retries += 1;
+        let error = self.inner.collect(err, delay, state);
+        if self.history_cap > 0 {
+            if self.history.len() == self.history_cap {
+                self.history.pop_front();
+            }
+            self.history.push_back(FailedAttempt {
+                error,
+                attempt: state.attempt(),
+                delay,
+                elapsed: state.elapsed(),
+            });
+        }
+        None
+    }
+
+    fn finish(&mut self, err: E, reason: GiveUpReason) -> RetryError<EC::Error> {
+        RetryError {
+            error: self.inner.finish(err, reason),
+            attempts: self.retries + 1,
+            reason,
+            history: mem::take(&mut self.history).into(),
+        }
+    }
+}
+
+/// FailedAttempt records an attempt that failed and was retried.
+///
+/// It's kept in the history of [`RetryError`] set by `wrap_err_with_history`.
+#[derive(Debug)]
+pub struct FailedAttempt<E> {
+    error: Option<E>,
+    attempt: usize,
+    delay: Duration,
+    elapsed: Duration,
+}
+
+impl<E> FailedAttempt<E> {
+    /// Returns the error of the attempt.
+    ///
+    /// Returns `None` if the error was consumed by `reduce_errors`.
+    pub fn error(&self) -> Option<&E> {
+        self.error.as_ref()
+    }
+
+    /// Returns the index of the attempt, starting from 1 for the first attempt.
+    pub fn attempt(&self) -> usize {
+        self.attempt
+    }
+
+    /// Returns the delay slept after the attempt failed.
+    pub fn delay(&self) -> Duration {
+        self.delay
+    }
+
+    /// Returns the time elapsed since the first attempt started when the attempt failed.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+}
+
+/// Permanent wraps the error of a retried function, telling whether it must end the retry.
+///
+/// Retried functions returning `Permanent<E>` can stop the retry immediately from inside by
+/// returning [`non_retryable`], regardless of the conditions set by `when`. Other errors are
+/// converted by `?`, directly or marked by [`Transient`], and retried as usual. Retries
+/// configured with `unwrap_permanent` return `E`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use anyhow::anyhow;
+/// use anyhow::Result;
+/// use backon::non_retryable;
+/// use backon::ExponentialBuilder;
+/// use backon::Permanent;
+/// use backon::Retryable;
+///
+/// async fn fetch() -> Result<String, Permanent<anyhow::Error>> {
+///     let resp = reqwest::get("https://www.rust-lang.org")
+///         .await
+///         .map_err(anyhow::Error::from)?;
+///     if resp.status().as_u16() == 404 {
+///         return Err(non_retryable(anyhow!("not found")));
+///     }
+///     Ok(resp.text().await.map_err(anyhow::Error::from)?)
+/// }
+///
+/// #[tokio::main(flavor = "current_thread")]
+/// async fn main() -> Result<()> {
+///     let content = fetch
+///         .retry(ExponentialBuilder::default())
+///         .unwrap_permanent()
+///         .await?;
+///     println!("fetch succeeded: {}", content);
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Permanent<E> {
+    error: E,
+    permanent: bool,
+}
+
+/// Wrap the error so that it ends the retry immediately.
+pub fn non_retryable<E>(error: E) -> Permanent<E> {
+    Permanent {
+        error,
+        permanent: true,
+    }
+}
+
+impl<E> Permanent<E> {
+    /// Returns `true` if the error must end the retry.
+    pub fn is_permanent(&self) -> bool {
+        self.permanent
+    }
+
+    /// Consumes the `Permanent`, returning the wrapped error.
+    pub fn into_inner(self) -> E {
+        self.error
+    }
+}
+
+impl<E> From<E> for Permanent<E> {
+    fn from(error: E) -> Self {
+        Permanent {
+            error,
+            permanent: false,
+        }
+    }
+}
+
+impl<E> From<Transient<E>> for Permanent<E> {
+    fn from(error: Transient<E>) -> Self {
+        error.0.into()
+    }
+}
+
+impl<E> Deref for Permanent<E> {
+    type Target = E;
+
+    fn deref(&self) -> &E {
+        &self.error
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for Permanent<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.error.fmt(f)
+    }
+}
+
+impl<E: core::error::Error> core::error::Error for Permanent<E> {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        self.error.source()
+    }
+}
+
+impl<E: RetryableError> RetryableError for Permanent<E> {
+    fn is_retryable(&self) -> bool {
+        !self.permanent && self.error.is_retryable()
+    }
+
+    fn retry_after(&self) -> Option<Duration> {
+        self.error.retry_after()
+    }
+
+    fn is_safe_to_retry(&self) -> bool {
+        self.error.is_safe_to_retry()
+    }
+}
+
+/// Transient marks the error of a retried function as retryable.
+///
+/// Converting it by `?` into [`Permanent`] is the same as converting the error itself, it
+/// only spells out the intent next to errors returned by [`non_retryable`]. Together they
+/// mirror `Error::Transient` and `Error::Permanent` of the `backoff` crate.
+///
+/// # Examples
+///
+/// ```
+/// use backon::non_retryable;
+/// use backon::BlockingRetryable;
+/// use backon::ConstantBuilder;
+/// use backon::Permanent;
+/// use backon::Transient;
+///
+/// let mut attempts = 0;
+/// let result = (|| -> Result<(), Permanent<&str>> {
+///     attempts += 1;
+///     if attempts < 3 {
+///         Err(Transient("busy"))?
+///     }
+///     Err(non_retryable("denied"))
+/// })
+/// .retry(ConstantBuilder::default())
+/// .unwrap_permanent()
+/// .call();
+///
+/// assert_eq!(result, Err("denied"));
+/// assert_eq!(attempts, 3);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Transient<E>(pub E);
+
+impl<E: fmt::Display> fmt::Display for Transient<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl<E: core::error::Error> core::error::Error for Transient<E> {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        self.0.source()
+    }
+}
+
+/// PanicOrError is the error of a retry run by `call_catch_unwind`: either an attempt
+/// panicked, or the retry failed.
+///
+/// Only available with the `std` feature.
+#[cfg(feature = "std")]
+pub enum PanicOrError<E> {
+    /// An attempt panicked, stopping the retry, with the payload of the panic.
+    Panic(alloc::boxed::Box<dyn core::any::Any + Send + 'static>),
+    /// The retry failed.
+    Error(E),
+}
+
+#[cfg(feature = "std")]
+impl<E> PanicOrError<E> {
+    /// Returns whether an attempt panicked.
+    pub fn is_panic(&self) -> bool {
+        matches!(self, PanicOrError::Panic(_))
+    }
+
+    /// Returns the message of the panic, if it's a string.
+    pub fn panic_message(&self) -> Option<&str> {
+        match self {
+            PanicOrError::Panic(payload) => payload.downcast_ref::<&str>().copied().or_else(|| {
+                payload
+                    .downcast_ref::<alloc::string::String>()
+                    .map(|s| s.as_str())
+            }),
+            PanicOrError::Error(_) => None,
+        }
+    }
+
+    /// Returns the error of the retry, or `None` if an attempt panicked.
+    pub fn into_error(self) -> Option<E> {
+        match self {
+            PanicOrError::Panic(_) => None,
+            PanicOrError::Error(err) => Some(err),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E: fmt::Debug> fmt::Debug for PanicOrError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PanicOrError::Panic(_) => f
+                .debug_tuple("Panic")
+                .field(&self.panic_message().unwrap_or("Box<dyn Any>"))
+                .finish(),
+            PanicOrError::Error(err) => f.debug_tuple("Error").field(err).finish(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E: fmt::Display> fmt::Display for PanicOrError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PanicOrError::Panic(_) => match self.panic_message() {
+                Some(message) => write!(f, "attempt panicked: {message}"),
+                None => write!(f, "attempt panicked"),
+            },
+            PanicOrError::Error(err) => err.fmt(f),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E: core::error::Error> core::error::Error for PanicOrError<E> {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            PanicOrError::Panic(_) => None,
+            PanicOrError::Error(err) => err.source(),
+        }
+    }
+}
+
+/// Collector set by `unwrap_permanent` that ends the retry at [`Permanent`] errors and
+/// unwraps them.
+#[doc(hidden)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct UnwrapPermanent<EC>(pub(crate) EC);
+
+impl<E, EC: ErrorCollector<E>> ErrorCollector<Permanent<E>> for UnwrapPermanent<EC> {
+    type Error = EC::Error;
+
+    fn is_permanent(&mut self, err: &Permanent<E>) -> bool {
+        err.permanent
+    }
+
+    fn collect(
+        &mut self,
+        err: Permanent<E>,
+        delay: Duration,
+        state: &RetryState,
+    ) -> Option<EC::Error> {
+        self.0.collect(err.error, delay, state)
+    }
+
+    fn finish(&mut self, err: Permanent<E>, reason: GiveUpReason) -> EC::Error {
+        self.0.finish(err.error, reason)
+    }
+}
+
+/// Classifies [`std::io::Error`] by its [`ErrorKind`](std::io::ErrorKind).
+///
+/// `Interrupted`, `WouldBlock`, `TimedOut`, `ConnectionReset`, `ConnectionAborted` and
+/// `BrokenPipe` are retryable, all other kinds like `PermissionDenied` and `NotFound` are not.
+///
+/// Use `when` instead of `when_retryable` to classify the errors differently for a call.
+#[cfg(feature = "std")]
+impl RetryableError for std::io::Error {
+    fn is_retryable(&self) -> bool {
+        use std::io::ErrorKind;
+
+        matches!(
+            self.kind(),
+            ErrorKind::Interrupted
+                | ErrorKind::WouldBlock
+                | ErrorKind::TimedOut
+                | ErrorKind::ConnectionReset
+                | ErrorKind::ConnectionAborted
+                | ErrorKind::BrokenPipe
+        )
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "std")]
+mod tests {
+    use alloc::string::ToString;
+    use core::time::Duration;
+    use spin::Mutex;
+    use std::io;
+
+    use super::*;
+    use crate::BlockingRetryable;
+    use crate::ExponentialBuilder;
+
+    #[test]
+    fn test_io_error_is_retryable() {
+        for kind in [
+            io::ErrorKind::Interrupted,
+            io::ErrorKind::WouldBlock,
+            io::ErrorKind::TimedOut,
+            io::ErrorKind::ConnectionReset,
+        ] {
+            assert!(io::Error::from(kind).is_retryable(), "{kind:?}");
+        }
+
+        for kind in [
+            io::ErrorKind::PermissionDenied,
+            io::ErrorKind::NotFound,
+            io::ErrorKind::InvalidInput,
+        ] {
+            assert!(!io::Error::from(kind).is_retryable(), "{kind:?}");
+        }
+    }
+
+    #[test]
+    fn test_retry_error_source() {
+        let backoff = ExponentialBuilder::default().with_min_delay(Duration::from_millis(1));
+        let result = (|| Err::<(), _>(io::Error::from(io::ErrorKind::TimedOut)))
+            .retry(backoff)
+            .wrap_err()
+            .call();
+
+        let err = result.unwrap_err();
+        assert_eq!(err.attempts(), 4);
+        assert_eq!(err.to_string(), "retry failed after 4 attempts");
+
+        let source = core::error::Error::source(&err).unwrap();
+        let source = source.downcast_ref::<io::Error>().unwrap();
+        assert_eq!(source.kind(), io::ErrorKind::TimedOut);
+    }
+
+    #[test]
+    fn test_retry_io_error_when_retryable() {
+        let error_times = Mutex::new(0);
+
+        let f = || {
+            let mut x = error_times.lock();
+            *x += 1;
+            Err::<(), _>(io::Error::from(if *x < 2 {
+                io::ErrorKind::ConnectionReset
+            } else {
+                io::ErrorKind::NotFound
+            }))
+        };
+
+        let backoff = ExponentialBuilder::default().with_min_delay(Duration::from_millis(1));
+        let result = f.retry(backoff).when_retryable().call();
+
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::NotFound);
+        assert_eq!(*error_times.lock(), 2);
+    }
+}
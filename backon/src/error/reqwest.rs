@@ -0,0 +1,234 @@
+use alloc::boxed::Box;
+use core::fmt;
+use core::time::Duration;
+
+use ::reqwest::header::{HeaderMap, RETRY_AFTER};
+use ::reqwest::{Response, StatusCode};
+
+use super::RetryableError;
+
+/// Returns `true` if a response with this status should be retried.
+///
+/// `429 Too Many Requests`, `502 Bad Gateway`, `503 Service Unavailable` and
+/// `504 Gateway Timeout` are retryable.
+fn is_retryable_status(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::TOO_MANY_REQUESTS
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+/// Returns `true` if a response with this status tells the request was not processed.
+///
+/// Unlike `502 Bad Gateway` and `504 Gateway Timeout`, `429 Too Many Requests` and
+/// `503 Service Unavailable` are returned before the request is processed.
+fn is_refused_status(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE
+    )
+}
+
+/// Parses the `Retry-After` header, which is either a number of seconds or a HTTP date.
+fn parse_retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+
+    // `SystemTime::now` is not available on `wasm32-unknown-unknown`.
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let at = httpdate::parse_http_date(value).ok()?;
+        // A date in the past means the request can be retried right now.
+        Some(
+            at.duration_since(std::time::SystemTime::now())
+                .unwrap_or_default(),
+        )
+    }
+    #[cfg(target_arch = "wasm32")]
+    {
+        None
+    }
+}
+
+/// Classifies [`reqwest::Error`] as retryable if it's caused by connecting, a timeout,
+/// reading the body, or a retryable status returned by `error_for_status`.
+impl RetryableError for ::reqwest::Error {
+    fn is_retryable(&self) -> bool {
+        self.is_connect()
+            || self.is_timeout()
+            || self.is_body()
+            || self.status().is_some_and(is_retryable_status)
+    }
+
+    /// Requests failed to connect were never sent, and responses with a retryable status
+    /// other than a gateway error were refused by the server.
+    fn is_safe_to_retry(&self) -> bool {
+        self.is_connect() || self.status().is_some_and(is_refused_status)
+    }
+}
+
+/// ReqwestError is the error of a request whose response status is also inspected.
+///
+/// Unlike [`reqwest::Response::error_for_status`], [`ReqwestError::error_for_status`] keeps
+/// the response, so the delay from its `Retry-After` header is used before retrying.
+///
+/// # Examples
+///
+/// ```no_run
+/// use backon::ExponentialBuilder;
+/// use backon::ReqwestError;
+/// use backon::Retryable;
+///
+/// async fn fetch() -> Result<String, ReqwestError> {
+///     let resp = reqwest::get("https://www.rust-lang.org").await?;
+///     let resp = ReqwestError::error_for_status(resp)?;
+///     Ok(resp.text().await?)
+/// }
+///
+/// #[tokio::main(flavor = "current_thread")]
+/// async fn main() -> Result<(), ReqwestError> {
+///     let content = fetch
+///         .retry(ExponentialBuilder::default())
+///         .when_retryable()
+///         .await?;
+///     println!("fetch succeeded: {}", content);
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug)]
+pub enum ReqwestError {
+    /// The request failed.
+    Request(::reqwest::Error),
+    /// The response has a client or server error status.
+    Status {
+        /// The status of the response.
+        status: StatusCode,
+        /// The delay parsed from the `Retry-After` header of the response.
+        retry_after: Option<Duration>,
+        /// The response.
+        response: Box<Response>,
+    },
+}
+
+impl ReqwestError {
+    /// Turn a response with a client or server error status into an error.
+    pub fn error_for_status(response: Response) -> Result<Response, ReqwestError> {
+        let status = response.status();
+        if !status.is_client_error() && !status.is_server_error() {
+            return Ok(response);
+        }
+
+        Err(ReqwestError::Status {
+            status,
+            retry_after: parse_retry_after(response.headers()),
+            response: Box::new(response),
+        })
+    }
+
+    /// Returns the status of the response, if any.
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            ReqwestError::Request(err) => err.status(),
+            ReqwestError::Status { status, .. } => Some(*status),
+        }
+    }
+}
+
+impl RetryableError for ReqwestError {
+    fn is_retryable(&self) -> bool {
+        match self {
+            ReqwestError::Request(err) => err.is_retryable(),
+            ReqwestError::Status { status, .. } => is_retryable_status(*status),
+        }
+    }
+
+    fn retry_after(&self) -> Option<Duration> {
+        match self {
+            ReqwestError::Request(_) => None,
+            ReqwestError::Status { retry_after, .. } => *retry_after,
+        }
+    }
+
+    fn is_safe_to_retry(&self) -> bool {
+        match self {
+            ReqwestError::Request(err) => err.is_safe_to_retry(),
+            ReqwestError::Status { status, .. } => is_refused_status(*status),
+        }
+    }
+}
+
+impl From<::reqwest::Error> for ReqwestError {
+    fn from(err: ::reqwest::Error) -> Self {
+        ReqwestError::Request(err)
+    }
+}
+
+impl fmt::Display for ReqwestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReqwestError::Request(err) => write!(f, "{err}"),
+            ReqwestError::Status { status, .. } => write!(f, "HTTP status {status}"),
+        }
+    }
+}
+
+impl std::error::Error for ReqwestError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ReqwestError::Request(err) => Some(err),
+            ReqwestError::Status { .. } => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(status: u16, retry_after: Option<&str>) -> Response {
+        let mut builder = http::Response::builder().status(status);
+        if let Some(v) = retry_after {
+            builder = builder.header(RETRY_AFTER, v);
+        }
+        builder.body("").unwrap().into()
+    }
+
+    #[test]
+    fn test_error_for_status() {
+        assert!(ReqwestError::error_for_status(response(200, None)).is_ok());
+
+        let err = ReqwestError::error_for_status(response(404, None)).unwrap_err();
+        assert_eq!(err.status(), Some(StatusCode::NOT_FOUND));
+        assert!(!err.is_retryable());
+
+        let err = ReqwestError::error_for_status(response(503, None)).unwrap_err();
+        assert!(err.is_retryable());
+        assert!(err.is_safe_to_retry());
+        assert_eq!(err.retry_after(), None);
+
+        let err = ReqwestError::error_for_status(response(504, None)).unwrap_err();
+        assert!(err.is_retryable());
+        assert!(!err.is_safe_to_retry());
+    }
+
+    #[test]
+    fn test_retry_after() {
+        let err = ReqwestError::error_for_status(response(429, Some("3"))).unwrap_err();
+        assert!(err.is_retryable());
+        assert_eq!(err.retry_after(), Some(Duration::from_secs(3)));
+
+        let err =
+            ReqwestError::error_for_status(response(503, Some("Wed, 21 Oct 2015 07:28:00 GMT")))
+                .unwrap_err();
+        assert_eq!(err.retry_after(), Some(Duration::ZERO));
+
+        let err = ReqwestError::error_for_status(response(503, Some("soon"))).unwrap_err();
+        assert_eq!(err.retry_after(), None);
+    }
+}
@@ -0,0 +1,104 @@
+use alloc::borrow::Cow;
+use core::time::Duration;
+
+use crate::GiveUpReason;
+
+/// RetryEvent is a step in the lifecycle of a retry, passed to the function set by `observe`.
+///
+/// A failed attempt is always reported by `AttemptFailed`, followed by `Sleeping` (or
+/// `Immediate` without delay) and then `Resumed` if it will be retried, or by `GaveUp` if it
+/// ends the retry.
+///
+/// # Examples
+///
+/// ```
+/// use core::time::Duration;
+///
+/// use backon::BlockingRetryable;
+/// use backon::ConstantBuilder;
+/// use backon::RetryEvent;
+///
+/// let result = (|| Err::<(), _>("unavailable"))
+///     .retry(ConstantBuilder::default().with_delay(Duration::from_millis(1)))
+///     .observe(|event: RetryEvent<'_, &str>| match event {
+///         RetryEvent::AttemptFailed { attempt, err } => {
+///             println!("attempt {attempt} failed: {err}")
+///         }
+///         RetryEvent::GaveUp { attempts, reason } => {
+///             println!("gave up after {attempts} attempts: {reason}")
+///         }
+///         _ => {}
+///     })
+///     .call();
+///
+/// assert!(result.is_err());
+/// ```
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum RetryEvent<'a, E> {
+    /// An attempt is about to start, with its index starting from 1.
+    Started {
+        /// The name of the retried operation set by `name`.
+        name: Option<&'static str>,
+        /// The key-value tags of the retried operation set by `tag`.
+        tags: &'a [(&'static str, Cow<'static, str>)],
+        /// The ID of the retry, see [`RetryState::session_id`](crate::RetryState::session_id).
+        session_id: u64,
+        /// The index of the attempt.
+        attempt: usize,
+    },
+    /// An attempt finished, before it's reported as failed or succeeded.
+    AttemptFinished {
+        /// The index of the attempt.
+        attempt: usize,
+        /// How long the attempt took to run.
+        latency: Duration,
+    },
+    /// An attempt failed, whether it will be retried or not.
+    AttemptFailed {
+        /// The index of the attempt.
+        attempt: usize,
+        /// The error returned by the attempt.
+        err: &'a E,
+    },
+    /// A fraction of the budget set by `budget_warning` is consumed, raised once per retry
+    /// before sleeping.
+    BudgetWarning {
+        /// The index of the attempt that failed.
+        attempt: usize,
+        /// The fraction of the budget consumed so far.
+        consumed: f32,
+    },
+    /// The retry is sleeping before the next attempt.
+    ///
+    /// It's replaced by `Immediate` if the delay before the next attempt is zero.
+    Sleeping {
+        /// How long the retry sleeps.
+        dur: Duration,
+    },
+    /// The retry starts the next attempt immediately, as the delay before it is zero.
+    Immediate {
+        /// The index of the attempt that failed.
+        attempt: usize,
+    },
+    /// The retry finished sleeping and is about to start the next attempt.
+    Resumed {
+        /// The index of the attempt about to start.
+        attempt: usize,
+        /// How long the retry slept.
+        slept: Duration,
+    },
+    /// An attempt succeeded.
+    Succeeded {
+        /// The number of attempts made, including the one that succeeded.
+        attempts: usize,
+    },
+    /// The retry gave up, returning the error of the last attempt.
+    GaveUp {
+        /// The number of attempts made.
+        attempts: usize,
+        /// Why the retry gave up.
+        reason: GiveUpReason,
+    },
+}
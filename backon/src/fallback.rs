@@ -0,0 +1,247 @@
+use alloc::vec::Vec;
+use core::fmt;
+use core::future::Future;
+use core::marker::PhantomData;
+
+use crate::BackoffBuilder;
+use crate::DefaultSleeper;
+use crate::Retryable;
+use crate::SharedBackoffBuilder;
+use crate::Sleeper;
+
+/// FallbackChain calls a list of targets in order, like the regions of a service, each one
+/// retried with its own backoff.
+///
+/// Once the backoff of a target is exhausted, the chain falls back to the next one, and the
+/// call resolves to the [`Served`] value of the first target succeeding, telling which one it
+/// is. Errors not retried, set by `when`, end the call at once instead: they'd fail the same
+/// on any target. The call fails with the last error of the last target once they all failed.
+///
+/// # Examples
+///
+/// ```
+/// use core::time::Duration;
+///
+/// use backon::ConstantBuilder;
+/// use backon::ExponentialBuilder;
+/// use backon::FallbackChain;
+///
+/// async fn fetch(region: &str) -> Result<String, std::io::Error> {
+///     match region {
+///         "us-east-1" => Err(std::io::ErrorKind::ConnectionRefused.into()),
+///         region => Ok(format!("served by {region}")),
+///     }
+/// }
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> Result<(), std::io::Error> {
+/// let mut chain = FallbackChain::new(
+///     "us-east-1",
+///     ExponentialBuilder::default().with_min_delay(Duration::from_millis(1)),
+/// )
+/// .fallback("eu-west-1", ConstantBuilder::default().with_max_times(1))
+/// .when(|e: &std::io::Error| e.kind() != std::io::ErrorKind::NotFound);
+///
+/// let served = chain.call(|region| fetch(region)).await?;
+/// assert_eq!(served.value, "served by eu-west-1");
+/// assert_eq!(*served.target, "eu-west-1");
+/// assert_eq!(served.index, 1);
+/// # Ok(())
+/// # }
+/// ```
+pub struct FallbackChain<Tg, E, SF = DefaultSleeper, RF = fn(&E) -> bool> {
+    targets: Vec<(Tg, SharedBackoffBuilder)>,
+    retryable: RF,
+    sleeper: SF,
+    _error: PhantomData<fn(&E)>,
+}
+
+/// Served is the output of a call through a [`FallbackChain`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Served<'a, Tg, T> {
+    /// The value returned by the target.
+    pub value: T,
+    /// The target serving the call.
+    pub target: &'a Tg,
+    /// The index of the target in the chain, 0 for the primary one.
+    pub index: usize,
+}
+
+impl<Tg, E> FallbackChain<Tg, E> {
+    /// Create a chain calling the `primary` target first, retried with the backoff of
+    /// `builder`, retrying every error and sleeping with the [`DefaultSleeper`].
+    pub fn new(primary: Tg, builder: impl BackoffBuilder + Clone + 'static) -> Self {
+        FallbackChain {
+            targets: Vec::from([(primary, SharedBackoffBuilder::new(builder))]),
+            retryable: |_| true,
+            sleeper: DefaultSleeper::default(),
+            _error: PhantomData,
+        }
+    }
+}
+
+impl<Tg, E, SF, RF: FnMut(&E) -> bool> FallbackChain<Tg, E, SF, RF> {
+    /// Add the `target` called once the ones before it failed, retried with the backoff of
+    /// `builder`.
+    pub fn fallback(mut self, target: Tg, builder: impl BackoffBuilder + Clone + 'static) -> Self {
+        self.targets
+            .push((target, SharedBackoffBuilder::new(builder)));
+        self
+    }
+
+    /// Set the sleeper of the retries.
+    pub fn sleep<SN: Sleeper>(self, sleeper: SN) -> FallbackChain<Tg, E, SN, RF> {
+        FallbackChain {
+            targets: self.targets,
+            retryable: self.retryable,
+            sleeper,
+            _error: PhantomData,
+        }
+    }
+
+    /// Set the errors retried, those for which `retryable` returns `true`, like
+    /// [`Retry::when`](crate::Retry::when). The others end the call without falling back.
+    pub fn when<RN: FnMut(&E) -> bool>(self, retryable: RN) -> FallbackChain<Tg, E, SF, RN> {
+        FallbackChain {
+            targets: self.targets,
+            retryable,
+            sleeper: self.sleeper,
+            _error: PhantomData,
+        }
+    }
+
+    /// Returns the targets of the chain, in order.
+    pub fn targets(&self) -> impl Iterator<Item = &Tg> {
+        self.targets.iter().map(|(target, _)| target)
+    }
+
+    /// Call `f` with the targets of the chain until one succeeds, retrying each one with its
+    /// backoff.
+    pub async fn call<T, Fut, F>(&mut self, mut f: F) -> Result<Served<'_, Tg, T>, E>
+    where
+        SF: Sleeper + Clone,
+        Fut: Future<Output = Result<T, E>>,
+        F: FnMut(&Tg) -> Fut,
+    {
+        let FallbackChain {
+            targets,
+            retryable,
+            sleeper,
+            ..
+        } = self;
+        let mut targets = targets.iter().enumerate().peekable();
+        loop {
+            let (index, (target, builder)) = targets.next().expect("the chain has a target");
+            let result = (|| f(target))
+                .retry(builder.clone())
+                .sleep(sleeper.clone())
+                .when(&mut *retryable)
+                .await;
+            match result {
+                Ok(value) => {
+                    return Ok(Served {
+                        value,
+                        target,
+                        index,
+                    })
+                }
+                Err(err) if targets.peek().is_none() || !retryable(&err) => return Err(err),
+                Err(_) => {}
+            }
+        }
+    }
+}
+
+impl<Tg: Clone, E, SF: Clone, RF: Clone> Clone for FallbackChain<Tg, E, SF, RF> {
+    fn clone(&self) -> Self {
+        FallbackChain {
+            targets: self.targets.clone(),
+            retryable: self.retryable.clone(),
+            sleeper: self.sleeper.clone(),
+            _error: PhantomData,
+        }
+    }
+}
+
+impl<Tg: fmt::Debug, E, SF: fmt::Debug, RF> fmt::Debug for FallbackChain<Tg, E, SF, RF> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let targets: Vec<_> = self.targets.iter().map(|(target, _)| target).collect();
+        f.debug_struct("FallbackChain")
+            .field("targets", &targets)
+            .field("sleeper", &self.sleeper)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::future::ready;
+    use core::future::Ready;
+    use core::time::Duration;
+
+    use super::*;
+    use crate::ConstantBuilder;
+
+    type NoSleep = fn(Duration) -> Ready<()>;
+
+    fn no_sleep(_: Duration) -> Ready<()> {
+        ready(())
+    }
+
+    #[tokio::test]
+    async fn test_fallback_chain() {
+        // The errors not retried are captured by the predicate.
+        let invalid = "invalid";
+        let mut chain = FallbackChain::new("primary", ConstantBuilder::default().with_max_times(2))
+            .fallback("secondary", ConstantBuilder::default().with_max_times(1))
+            .fallback("tertiary", ConstantBuilder::default().with_max_times(0))
+            .when(move |e: &&str| *e != invalid)
+            .sleep(no_sleep as NoSleep);
+        assert!(chain
+            .targets()
+            .eq(["primary", "secondary", "tertiary"].iter()));
+
+        let mut calls = Vec::new();
+        let served = chain
+            .call(|target| {
+                calls.push(*target);
+                ready(match *target {
+                    "tertiary" => Ok(42),
+                    _ => Err("unavailable"),
+                })
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            (served.value, *served.target, served.index),
+            (42, "tertiary", 2)
+        );
+        assert_eq!(
+            calls,
+            [
+                "primary",
+                "primary",
+                "primary",
+                "secondary",
+                "secondary",
+                "tertiary"
+            ]
+        );
+
+        // Errors not retried don't fall back.
+        let mut calls = 0;
+        let result = chain
+            .call(|_| {
+                calls += 1;
+                ready(Err::<(), _>("invalid"))
+            })
+            .await;
+        assert_eq!(result.unwrap_err(), "invalid");
+        assert_eq!(calls, 1);
+
+        // Once every target failed, the last error is returned.
+        let result = chain.call(|target| ready(Err::<(), _>(*target))).await;
+        assert_eq!(result.unwrap_err(), "tertiary");
+    }
+}
@@ -0,0 +1,81 @@
+//! Entry points for fuzzing the schedules of backoffs, with
+//! [cargo-fuzz](https://github.com/rust-fuzz/cargo-fuzz) or OSS-Fuzz.
+//!
+//! The builders and [`BackoffPolicy`] implement [`arbitrary::Arbitrary`], generating any of
+//! the parameters a configuration file could load, like huge factors and maximum delays, so
+//! the fuzzer hunts for panics and overflows in the arithmetic of the delays. [`schedule`] is
+//! the pure function under test, and [`exercise`] runs everything computing delays, like the
+//! `schedule` target in the `fuzz` directory of the repository, run by `cargo fuzz run schedule`:
+//!
+//! ```
+//! use backon::fuzz;
+//! use backon::BackoffPolicy;
+//!
+//! fn fuzz_target(data: &[u8]) {
+//!     let mut u = arbitrary::Unstructured::new(data);
+//!     if let Ok((policy, limit)) = u.arbitrary::<(BackoffPolicy, u16)>() {
+//!         fuzz::exercise(policy, limit.into());
+//!     }
+//! }
+//! # fuzz_target(&[7; 64]);
+//! ```
+//!
+//! Only available with the `arbitrary` feature.
+
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use core::time::Duration;
+
+use crate::BackoffBuilder;
+use crate::BackoffPolicy;
+
+/// Returns the first `limit` delays of `policy` without jitter, the same for the same
+/// inputs.
+pub fn schedule(policy: BackoffPolicy, limit: usize) -> Vec<Duration> {
+    policy.planned().build().take(limit).collect()
+}
+
+/// Run everything computing delays from `policy`, up to `limit` delays: its [`schedule`], the
+/// delays with jitter, its [`ScheduleBounds`](crate::ScheduleBounds) and its description
+/// parsed back.
+///
+/// Panics if they panic, or if the schedule without jitter isn't within its bounds.
+pub fn exercise(policy: BackoffPolicy, limit: usize) {
+    let delays = schedule(policy, limit);
+    let _ = policy.build().take(limit).count();
+    let _ = policy.to_string().parse::<BackoffPolicy>();
+    if let Some(bounds) = policy.schedule_bounds() {
+        if limit >= bounds.retries() {
+            let total = delays
+                .iter()
+                .fold(Duration::ZERO, |total, dur| total.saturating_add(*dur));
+            assert_eq!(
+                total,
+                bounds.min(),
+                "the schedule of {policy:?} isn't its bounds"
+            );
+            assert!(bounds.min() <= bounds.max());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exercise() {
+        let mut data = [0; 256];
+        let mut seed = 7u64;
+        for _ in 0..2000 {
+            data.fill_with(|| {
+                seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+                (seed >> 56) as u8
+            });
+            let mut u = arbitrary::Unstructured::new(&data);
+            if let Ok((policy, limit)) = u.arbitrary::<(BackoffPolicy, u8)>() {
+                exercise(policy, limit.into());
+            }
+        }
+    }
+}
@@ -0,0 +1,243 @@
+use alloc::boxed::Box;
+use core::any;
+use core::fmt;
+use std::sync::OnceLock;
+use std::sync::PoisonError;
+use std::sync::RwLock;
+
+use crate::hooks::Observe;
+use crate::BackoffBuilder;
+use crate::ExponentialBuilder;
+use crate::RetryEvent;
+use crate::RetryState;
+use crate::SharedBackoffBuilder;
+
+static OBSERVER: OnceLock<Box<dyn GlobalObserver>> = OnceLock::new();
+
+static DEFAULT_BACKOFF: RwLock<Option<SharedBackoffBuilder>> = RwLock::new(None);
+
+/// GlobalObserver receives the events of every retry in the process that doesn't set its
+/// own observer with `observe`.
+///
+/// As retries have errors of different types, the errors are passed as an [`ErasedError`].
+/// It's implemented by all `Fn(RetryEvent<'_, ErasedError>) + Send + Sync`.
+pub trait GlobalObserver: Send + Sync {
+    /// Called with every event of a retry.
+    fn observe(&self, event: RetryEvent<'_, ErasedError>);
+}
+
+impl<F: Fn(RetryEvent<'_, ErasedError>) + Send + Sync> GlobalObserver for F {
+    fn observe(&self, event: RetryEvent<'_, ErasedError>) {
+        self(event)
+    }
+}
+
+/// ErasedError stands for the error of a retry passed to the [`GlobalObserver`], which only
+/// knows the name of its type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ErasedError {
+    type_name: &'static str,
+}
+
+impl ErasedError {
+    fn new<E>() -> Self {
+        ErasedError {
+            type_name: any::type_name::<E>(),
+        }
+    }
+
+    /// Returns the name of the type of the error.
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+}
+
+impl fmt::Display for ErasedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.type_name)
+    }
+}
+
+/// The error returned by [`set_global_observer`] if a global observer has already been set.
+#[derive(Debug)]
+pub struct SetGlobalObserverError(());
+
+impl fmt::Display for SetGlobalObserverError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a global observer has already been set")
+    }
+}
+
+impl core::error::Error for SetGlobalObserverError {}
+
+/// Set the [`GlobalObserver`] receiving the events of every retry that doesn't set its own
+/// observer.
+///
+/// It can only be set once per process, later calls return an error.
+///
+/// Only available with the `std` feature.
+///
+/// # Examples
+///
+/// ```
+/// use backon::ErasedError;
+/// use backon::RetryEvent;
+///
+/// backon::set_global_observer(|event: RetryEvent<'_, ErasedError>| {
+///     if let RetryEvent::GaveUp { attempts, reason } = event {
+///         eprintln!("retry gave up after {attempts} attempts: {reason}");
+///     }
+/// })
+/// .expect("global observer must not be set yet");
+/// ```
+pub fn set_global_observer(
+    observer: impl GlobalObserver + 'static,
+) -> Result<(), SetGlobalObserverError> {
+    OBSERVER
+        .set(Box::new(observer))
+        .map_err(|_| SetGlobalObserverError(()))
+}
+
+/// Set the backoff of every retry started with `retry_default`, like
+/// [`RetryableDefault::retry_default`][crate::RetryableDefault::retry_default], replacing the
+/// one set before.
+///
+/// It lets applications change the policy of the libraries they use that don't expose their
+/// own. Until it's set, the default is [`ExponentialBuilder::default`].
+///
+/// Only available with the `std` feature.
+///
+/// # Examples
+///
+/// ```
+/// use core::time::Duration;
+///
+/// use backon::BlockingRetryableDefault;
+/// use backon::ConstantBuilder;
+///
+/// backon::set_default_backoff(
+///     ConstantBuilder::default()
+///         .with_delay(Duration::from_millis(1))
+///         .with_max_times(1),
+/// );
+///
+/// let result = (|| Err::<(), _>("unavailable")).retry_default().call();
+/// assert_eq!(result, Err("unavailable"));
+/// ```
+pub fn set_default_backoff(builder: impl BackoffBuilder + Clone + 'static) {
+    *DEFAULT_BACKOFF
+        .write()
+        .unwrap_or_else(PoisonError::into_inner) = Some(SharedBackoffBuilder::new(builder));
+}
+
+/// Returns the backoff set by [`set_default_backoff`], or [`ExponentialBuilder::default`] if
+/// it hasn't been set.
+///
+/// Only available with the `std` feature.
+pub fn default_backoff() -> SharedBackoffBuilder {
+    DEFAULT_BACKOFF
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+        .unwrap_or_else(|| SharedBackoffBuilder::new(ExponentialBuilder::default()))
+}
+
+/// Returns the hook delivering the events of the retry to the global observer, unless the
+/// retry sets its own.
+pub(crate) fn hook<E>(state: &RetryState) -> Option<Observe<impl FnMut(RetryEvent<'_, E>)>> {
+    let observer = OBSERVER.get().filter(|_| !state.is_observed())?;
+    Some(Observe(move |event: RetryEvent<'_, E>| {
+        let err = ErasedError::new::<E>();
+        observer.observe(match event {
+            RetryEvent::Started {
+                name,
+                tags,
+                session_id,
+                attempt,
+            } => RetryEvent::Started {
+                name,
+                tags,
+                session_id,
+                attempt,
+            },
+            RetryEvent::AttemptFinished { attempt, latency } => {
+                RetryEvent::AttemptFinished { attempt, latency }
+            }
+            RetryEvent::AttemptFailed { attempt, .. } => {
+                RetryEvent::AttemptFailed { attempt, err: &err }
+            }
+            RetryEvent::BudgetWarning { attempt, consumed } => {
+                RetryEvent::BudgetWarning { attempt, consumed }
+            }
+            RetryEvent::Sleeping { dur } => RetryEvent::Sleeping { dur },
+            RetryEvent::Immediate { attempt } => RetryEvent::Immediate { attempt },
+            RetryEvent::Resumed { attempt, slept } => RetryEvent::Resumed { attempt, slept },
+            RetryEvent::Succeeded { attempts } => RetryEvent::Succeeded { attempts },
+            RetryEvent::GaveUp { attempts, reason } => RetryEvent::GaveUp { attempts, reason },
+        })
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+    use alloc::vec::Vec;
+    use core::time::Duration;
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::BlockingRetryable;
+    use crate::BlockingRetryableDefault;
+    use crate::ConstantBuilder;
+
+    #[derive(Debug)]
+    struct GlobalTestError;
+
+    static FAILED: Mutex<Vec<usize>> = Mutex::new(Vec::new());
+
+    #[test]
+    fn test_global_observer() {
+        set_global_observer(|event: RetryEvent<'_, ErasedError>| {
+            // Retries of other tests running at the same time are observed too.
+            if let RetryEvent::AttemptFailed { attempt, err } = event {
+                if err.type_name().ends_with("GlobalTestError") {
+                    FAILED.lock().unwrap().push(attempt);
+                }
+            }
+        })
+        .unwrap();
+        assert!(set_global_observer(|_: RetryEvent<'_, ErasedError>| {}).is_err());
+
+        let backoff = ConstantBuilder::default()
+            .with_delay(Duration::from_millis(1))
+            .with_max_times(1);
+        let f = || Err::<(), _>(GlobalTestError);
+
+        // Retries setting their own observer are not observed globally.
+        let _ = f.retry(backoff).observe(|_| {}).call();
+        assert!(FAILED.lock().unwrap().is_empty());
+
+        let _ = f.retry(backoff).when(|_| false).call();
+        let _ = f.retry(backoff).on_fatal_error(|_, _| {}).call();
+        assert_eq!(*FAILED.lock().unwrap(), vec![1, 1, 2]);
+    }
+
+    #[test]
+    fn test_default_backoff() {
+        // Other tests don't start retries with `retry_default`, setting the default is safe.
+        assert_eq!(default_backoff().build().count(), 3);
+
+        set_default_backoff(ConstantBuilder::default().with_max_times(1));
+        assert_eq!(default_backoff().build().count(), 1);
+
+        let mut attempts = 0;
+        let _ = (|| {
+            attempts += 1;
+            Err::<(), _>(GlobalTestError)
+        })
+        .retry_default()
+        .sleep(|_| {})
+        .call();
+        assert_eq!(attempts, 2);
+    }
+}
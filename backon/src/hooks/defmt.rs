@@ -0,0 +1,31 @@
+use core::time::Duration;
+
+use ::defmt::Format;
+
+use super::Hooks;
+use crate::GiveUpReason;
+use crate::RetryState;
+
+/// Hook set by `defmt` that logs retries and giving up with `defmt`.
+#[doc(hidden)]
+pub struct Defmt;
+
+impl<T, E: Format> Hooks<T, E> for Defmt {
+    fn on_transient(&mut self, err: &E, dur: Duration, state: &RetryState) {
+        ::defmt::warn!(
+            "attempt {=usize} failed, retrying after {}: {}",
+            state.attempt(),
+            dur,
+            err
+        );
+    }
+
+    fn on_fatal(&mut self, err: &E, reason: GiveUpReason, state: &RetryState) {
+        ::defmt::error!(
+            "giving up after {=usize} attempts, {}: {}",
+            state.attempt(),
+            reason,
+            err
+        );
+    }
+}
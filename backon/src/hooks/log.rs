@@ -0,0 +1,171 @@
+use core::fmt;
+use core::time::Duration;
+
+use ::log::kv::{self, Key, Source, Value, VisitSource};
+use ::log::Level;
+
+use super::Hooks;
+use crate::GiveUpReason;
+use crate::RetryState;
+
+/// Hook set by `log` that logs retries and giving up with the `log` crate.
+///
+/// The name, the session ID and the tags of the retry are attached to the records as
+/// key-values.
+#[doc(hidden)]
+pub struct Log {
+    retry: Level,
+    give_up: Level,
+}
+
+impl Log {
+    pub(crate) fn new(retry: Level, give_up: Level) -> Self {
+        Log { retry, give_up }
+    }
+
+    /// Log a record of the retry at `level`.
+    fn log(&self, level: Level, state: &RetryState, args: fmt::Arguments<'_>) {
+        if level > ::log::max_level() {
+            return;
+        }
+        ::log::logger().log(
+            &::log::Record::builder()
+                .args(args)
+                .level(level)
+                .target("backon")
+                .module_path_static(Some(module_path!()))
+                .file_static(Some(file!()))
+                .line(Some(line!()))
+                .key_values(&Fields(state))
+                .build(),
+        );
+    }
+}
+
+/// The key-values of the records of a retry: its name as `operation`, its `session_id`, then
+/// its tags.
+struct Fields<'a>(&'a RetryState);
+
+impl Source for Fields<'_> {
+    fn visit<'kvs>(&'kvs self, visitor: &mut dyn VisitSource<'kvs>) -> Result<(), kv::Error> {
+        if let Some(name) = self.0.name() {
+            visitor.visit_pair(Key::from("operation"), Value::from(name))?;
+        }
+        visitor.visit_pair(Key::from("session_id"), Value::from(self.0.session_id()))?;
+        for (key, value) in self.0.tags() {
+            visitor.visit_pair(Key::from(*key), Value::from(value.as_ref()))?;
+        }
+        Ok(())
+    }
+}
+
+impl<T, E: fmt::Display> Hooks<T, E> for Log {
+    fn on_transient(&mut self, err: &E, dur: Duration, state: &RetryState) {
+        self.log(
+            self.retry,
+            state,
+            format_args!(
+                "attempt {} failed, retrying after {:?}: {}",
+                state.attempt(),
+                dur,
+                err
+            ),
+        );
+    }
+
+    fn on_fatal(&mut self, err: &E, reason: GiveUpReason, state: &RetryState) {
+        self.log(
+            self.give_up,
+            state,
+            format_args!(
+                "giving up after {} attempts, {}: {}",
+                state.attempt(),
+                reason,
+                err
+            ),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::format;
+    use alloc::string::String;
+    use alloc::vec;
+    use alloc::vec::Vec;
+    use log::{Metadata, Record};
+    use spin::Mutex;
+
+    use super::*;
+    use crate::BlockingRetryable;
+    use crate::ExponentialBuilder;
+
+    static LINES: Mutex<Vec<(Level, String)>> = Mutex::new(Vec::new());
+
+    struct Logger;
+
+    /// Formats the key-values of a record as ` key=value` suffixes.
+    struct KeyValues(String);
+
+    impl<'kvs> VisitSource<'kvs> for KeyValues {
+        fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+            self.0 += &format!(" {key}={value}");
+            Ok(())
+        }
+    }
+
+    impl log::Log for Logger {
+        fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+            metadata.target() == "backon"
+        }
+
+        fn log(&self, record: &Record<'_>) {
+            if self.enabled(record.metadata()) {
+                let mut kvs = KeyValues(String::new());
+                record.key_values().visit(&mut kvs).unwrap();
+                LINES
+                    .lock()
+                    .push((record.level(), format!("{}{}", record.args(), kvs.0)));
+            }
+        }
+
+        fn flush(&self) {}
+    }
+
+    #[test]
+    fn test_log() {
+        log::set_logger(&Logger).unwrap();
+        log::set_max_level(log::LevelFilter::Trace);
+
+        let backoff = ExponentialBuilder::default()
+            .with_min_delay(Duration::from_millis(1))
+            .with_max_times(1);
+        let result = (|| Err::<(), _>("unavailable"))
+            .retry(backoff)
+            .when(|_| true)
+            .name("fetch")
+            .session_id(7)
+            .tag("bucket", "photos")
+            .log(Level::Debug, Level::Error)
+            .call();
+
+        assert!(result.is_err());
+        let lines = LINES.lock();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].0, Level::Debug);
+        assert!(lines[0].1.starts_with("attempt 1 failed, retrying after"));
+        assert!(lines[0]
+            .1
+            .ends_with(": unavailable operation=fetch session_id=7 bucket=photos"));
+        assert_eq!(
+            lines[1..],
+            vec![(
+                Level::Error,
+                String::from(
+                    "giving up after 2 attempts, backoff exhausted: unavailable \
+                     operation=fetch session_id=7 bucket=photos"
+                )
+            )]
+        );
+    }
+}
@@ -0,0 +1,381 @@
+use core::time::Duration;
+
+use crate::AttemptTimedOut;
+use crate::GiveUpReason;
+use crate::RetryEvent;
+use crate::RetryMetrics;
+use crate::RetryState;
+use crate::ScheduleSummary;
+
+#[cfg(feature = "defmt")]
+mod defmt;
+#[cfg(feature = "defmt")]
+pub(crate) use self::defmt::Defmt;
+
+#[cfg(feature = "logging")]
+mod log;
+#[cfg(feature = "logging")]
+pub(crate) use self::log::Log;
+
+#[cfg(feature = "opentelemetry")]
+mod opentelemetry;
+#[cfg(feature = "opentelemetry")]
+pub(crate) use self::opentelemetry::OpenTelemetry;
+
+#[cfg(feature = "sentry")]
+mod sentry;
+#[cfg(feature = "sentry")]
+pub(crate) use self::sentry::Sentry;
+#[cfg(feature = "slog")]
+mod slog;
+#[cfg(feature = "slog")]
+pub(crate) use self::slog::Slog;
+
+#[cfg(feature = "tracing")]
+mod tracing;
+#[cfg(feature = "tracing")]
+pub(crate) use self::tracing::Trace;
+
+/// A stub trait observing the errors of a retry besides `notify`.
+///
+/// Every hook set on a retry is chained after the ones set before by [`Chain`], so each hook
+/// only implements the methods it's interested in.
+#[doc(hidden)]
+pub trait Hooks<T, E> {
+    /// Called before the retried function is called or polled.
+    fn enter(&mut self) {}
+
+    /// Called after the retried function returns or is polled.
+    fn exit(&mut self) {}
+
+    /// Called before the sleep after a failed attempt is polled, or slept for by blocking
+    /// retries.
+    fn enter_sleep(&mut self) {}
+
+    /// Called after the sleep after a failed attempt is polled, or slept for by blocking
+    /// retries.
+    fn exit_sleep(&mut self) {}
+
+    /// Called when an attempt starts.
+    fn on_attempt(&mut self, _: &RetryState) {}
+
+    /// Called with the value of the attempt that succeeded.
+    fn on_success(&mut self, _: &T, _: &RetryState) {}
+
+    /// Called with every error that will be retried, before sleeping.
+    fn on_transient(&mut self, _: &E, _: Duration, _: &RetryState) {}
+
+    /// Called when the sleep after a failed attempt finishes, before the next attempt starts.
+    fn on_resume(&mut self, _: &RetryState) {}
+
+    /// Called with the error that ends the retry, returning the value to recover with.
+    fn recover(&mut self, _: &E) -> Option<T> {
+        None
+    }
+
+    /// Called with the error that ends the retry if it's not recovered.
+    fn on_fatal(&mut self, _: &E, _: GiveUpReason, _: &RetryState) {}
+
+    /// Called with the error that ends the retry if it's recovered by `recover`.
+    ///
+    /// The retry gave up all the same, so it's passed to `on_fatal` unless a hook tells them
+    /// apart.
+    fn on_recovered(&mut self, err: &E, reason: GiveUpReason, state: &RetryState) {
+        self.on_fatal(err, reason, state);
+    }
+
+    /// Called when an attempt runs out of time, returning the error it fails with, or `None`
+    /// to keep it running.
+    fn timed_out(&mut self) -> Option<E> {
+        None
+    }
+}
+
+/// Default hooks that do nothing, besides delivering events to the global observer with the
+/// `std` feature.
+#[doc(hidden)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoHooks;
+
+impl<T, E> Hooks<T, E> for NoHooks {
+    #[cfg(feature = "std")]
+    fn on_attempt(&mut self, state: &RetryState) {
+        if let Some(mut global) = crate::global::hook::<E>(state) {
+            Hooks::<T, E>::on_attempt(&mut global, state);
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn on_success(&mut self, value: &T, state: &RetryState) {
+        if let Some(mut global) = crate::global::hook::<E>(state) {
+            Hooks::<T, E>::on_success(&mut global, value, state);
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn on_transient(&mut self, err: &E, dur: Duration, state: &RetryState) {
+        if let Some(mut global) = crate::global::hook::<E>(state) {
+            Hooks::<T, E>::on_transient(&mut global, err, dur, state);
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn on_resume(&mut self, state: &RetryState) {
+        if let Some(mut global) = crate::global::hook::<E>(state) {
+            Hooks::<T, E>::on_resume(&mut global, state);
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn on_fatal(&mut self, err: &E, reason: GiveUpReason, state: &RetryState) {
+        if let Some(mut global) = crate::global::hook::<E>(state) {
+            Hooks::<T, E>::on_fatal(&mut global, err, reason, state);
+        }
+    }
+}
+
+/// Hooks calling the hooks set before, then the one set last.
+#[doc(hidden)]
+pub struct Chain<H, N>(pub(crate) H, pub(crate) N);
+
+impl<T, E, H: Hooks<T, E>, N: Hooks<T, E>> Hooks<T, E> for Chain<H, N> {
+    fn enter(&mut self) {
+        self.0.enter();
+        self.1.enter();
+    }
+
+    fn exit(&mut self) {
+        self.1.exit();
+        self.0.exit();
+    }
+
+    fn enter_sleep(&mut self) {
+        self.0.enter_sleep();
+        self.1.enter_sleep();
+    }
+
+    fn exit_sleep(&mut self) {
+        self.1.exit_sleep();
+        self.0.exit_sleep();
+    }
+
+    fn on_attempt(&mut self, state: &RetryState) {
+        self.0.on_attempt(state);
+        self.1.on_attempt(state);
+    }
+
+    fn on_success(&mut self, value: &T, state: &RetryState) {
+        self.0.on_success(value, state);
+        self.1.on_success(value, state);
+    }
+
+    fn on_transient(&mut self, err: &E, dur: Duration, state: &RetryState) {
+        self.0.on_transient(err, dur, state);
+        self.1.on_transient(err, dur, state);
+    }
+
+    fn on_resume(&mut self, state: &RetryState) {
+        self.0.on_resume(state);
+        self.1.on_resume(state);
+    }
+
+    fn recover(&mut self, err: &E) -> Option<T> {
+        self.0.recover(err).or_else(|| self.1.recover(err))
+    }
+
+    fn on_fatal(&mut self, err: &E, reason: GiveUpReason, state: &RetryState) {
+        self.0.on_fatal(err, reason, state);
+        self.1.on_fatal(err, reason, state);
+    }
+
+    fn on_recovered(&mut self, err: &E, reason: GiveUpReason, state: &RetryState) {
+        self.0.on_recovered(err, reason, state);
+        self.1.on_recovered(err, reason, state);
+    }
+
+    fn timed_out(&mut self) -> Option<E> {
+        self.0.timed_out().or_else(|| self.1.timed_out())
+    }
+}
+
+/// Hook set by `on_start`.
+#[doc(hidden)]
+pub struct OnStart<F>(pub(crate) F);
+
+impl<T, E, F: FnMut(&RetryState)> Hooks<T, E> for OnStart<F> {
+    fn on_attempt(&mut self, state: &RetryState) {
+        if state.attempt() == 1 {
+            (self.0)(state);
+        }
+    }
+}
+
+/// Hook set by `on_transient_error`.
+#[doc(hidden)]
+pub struct OnTransient<F>(pub(crate) F);
+
+impl<T, E, F: FnMut(&E, Duration)> Hooks<T, E> for OnTransient<F> {
+    fn on_transient(&mut self, err: &E, dur: Duration, _: &RetryState) {
+        (self.0)(err, dur);
+    }
+}
+
+/// Hook set by `on_fatal_error`.
+#[doc(hidden)]
+pub struct OnFatal<F>(pub(crate) F);
+
+impl<T, E, F: FnMut(&E, GiveUpReason)> Hooks<T, E> for OnFatal<F> {
+    fn on_fatal(&mut self, err: &E, reason: GiveUpReason, _: &RetryState) {
+        (self.0)(err, reason);
+    }
+
+    // Only the errors that aren't recovered are passed to `on_fatal_error`.
+    fn on_recovered(&mut self, _: &E, _: GiveUpReason, _: &RetryState) {}
+}
+
+/// Hook set by `on_backoff_exhausted`.
+#[doc(hidden)]
+pub struct OnBackoffExhausted<F> {
+    f: F,
+    schedule: ScheduleSummary,
+}
+
+impl<F> OnBackoffExhausted<F> {
+    pub(crate) fn new(f: F) -> Self {
+        OnBackoffExhausted {
+            f,
+            schedule: ScheduleSummary::default(),
+        }
+    }
+}
+
+impl<T, E, F: FnMut(&E, &ScheduleSummary)> Hooks<T, E> for OnBackoffExhausted<F> {
+    fn on_transient(&mut self, _: &E, dur: Duration, state: &RetryState) {
+        self.schedule.record(state.next_delay().unwrap_or(dur), dur);
+    }
+
+    fn on_fatal(&mut self, err: &E, reason: GiveUpReason, _: &RetryState) {
+        if reason == GiveUpReason::BackoffExhausted {
+            (self.f)(err, &self.schedule);
+        }
+    }
+}
+
+/// Hook set by `recover`.
+#[doc(hidden)]
+pub struct Recover<F>(pub(crate) F);
+
+impl<T, E, F: FnMut(&E) -> Option<T>> Hooks<T, E> for Recover<F> {
+    fn recover(&mut self, err: &E) -> Option<T> {
+        (self.0)(err)
+    }
+}
+
+/// Hook set by `deadline` on async retries, failing the attempts that run out of time.
+#[doc(hidden)]
+pub struct AttemptTimeout;
+
+impl<T, E: From<AttemptTimedOut>> Hooks<T, E> for AttemptTimeout {
+    fn timed_out(&mut self) -> Option<E> {
+        Some(AttemptTimedOut.into())
+    }
+}
+
+/// Hook set by `metrics`.
+#[doc(hidden)]
+pub struct Metrics<M>(pub(crate) M);
+
+impl<T, E, M: RetryMetrics> Hooks<T, E> for Metrics<M> {
+    fn on_attempt(&mut self, state: &RetryState) {
+        self.0.attempt_started(state);
+    }
+
+    fn on_success(&mut self, _: &T, state: &RetryState) {
+        self.0.succeeded(state);
+    }
+
+    fn on_transient(&mut self, _: &E, dur: Duration, state: &RetryState) {
+        self.0.attempt_failed(state);
+        self.0.slept(dur, state);
+    }
+
+    fn on_fatal(&mut self, _: &E, reason: GiveUpReason, state: &RetryState) {
+        self.0.attempt_failed(state);
+        self.0.gave_up(reason, state);
+    }
+}
+
+/// Hook set by `observe`.
+#[doc(hidden)]
+pub struct Observe<F>(pub(crate) F);
+
+impl<F> Observe<F> {
+    /// Report the latency of the attempt that just finished.
+    fn finished<E>(&mut self, state: &RetryState)
+    where
+        F: FnMut(RetryEvent<'_, E>),
+    {
+        (self.0)(RetryEvent::AttemptFinished {
+            attempt: state.attempt(),
+            latency: state.attempt_latency(),
+        });
+    }
+}
+
+impl<T, E, F: FnMut(RetryEvent<'_, E>)> Hooks<T, E> for Observe<F> {
+    fn on_attempt(&mut self, state: &RetryState) {
+        (self.0)(RetryEvent::Started {
+            name: state.name(),
+            tags: state.tags(),
+            session_id: state.session_id(),
+            attempt: state.attempt(),
+        });
+    }
+
+    fn on_success(&mut self, _: &T, state: &RetryState) {
+        self.finished(state);
+        (self.0)(RetryEvent::Succeeded {
+            attempts: state.attempt(),
+        });
+    }
+
+    fn on_transient(&mut self, err: &E, dur: Duration, state: &RetryState) {
+        self.finished(state);
+        (self.0)(RetryEvent::AttemptFailed {
+            attempt: state.attempt(),
+            err,
+        });
+        if let Some(consumed) = state.budget_warning_due() {
+            (self.0)(RetryEvent::BudgetWarning {
+                attempt: state.attempt(),
+                consumed,
+            });
+        }
+        if dur.is_zero() {
+            (self.0)(RetryEvent::Immediate {
+                attempt: state.attempt(),
+            });
+        } else {
+            (self.0)(RetryEvent::Sleeping { dur });
+        }
+    }
+
+    fn on_resume(&mut self, state: &RetryState) {
+        (self.0)(RetryEvent::Resumed {
+            attempt: state.attempt() + 1,
+            slept: state.last_delay(),
+        });
+    }
+
+    fn on_fatal(&mut self, err: &E, reason: GiveUpReason, state: &RetryState) {
+        self.finished(state);
+        (self.0)(RetryEvent::AttemptFailed {
+            attempt: state.attempt(),
+            err,
+        });
+        (self.0)(RetryEvent::GaveUp {
+            attempts: state.attempt(),
+            reason,
+        });
+    }
+}
@@ -0,0 +1,242 @@
+use core::fmt;
+use core::time::Duration;
+use std::string::ToString;
+use std::vec;
+use std::vec::Vec;
+
+use ::opentelemetry::metrics::{Counter, Histogram};
+use ::opentelemetry::trace::TraceContextExt;
+use ::opentelemetry::{global, Context, KeyValue};
+
+use super::Hooks;
+use crate::GiveUpReason;
+use crate::RetryState;
+
+/// Hook set by `opentelemetry` that records the retry on the current OpenTelemetry span and
+/// in OpenTelemetry metrics.
+///
+/// The span is the one active when the retry is built. Every failed attempt and giving up is
+/// added to it as an event, while the metrics are reported to the `backon` meter of the
+/// global meter provider, with the operation and the tags as attributes.
+#[doc(hidden)]
+pub struct OpenTelemetry {
+    cx: Context,
+    operation: KeyValue,
+    tags: Vec<KeyValue>,
+    /// The attributes of the metrics, the operation then the tags.
+    attributes: Vec<KeyValue>,
+    attempts: Counter<u64>,
+    retries: Counter<u64>,
+    giveups: Counter<u64>,
+    sleep: Histogram<f64>,
+}
+
+impl OpenTelemetry {
+    pub(crate) fn new(operation: &'static str) -> Self {
+        let meter = global::meter("backon");
+        OpenTelemetry {
+            cx: Context::current(),
+            operation: KeyValue::new("operation.name", operation),
+            tags: Vec::new(),
+            attributes: vec![KeyValue::new("operation.name", operation)],
+            attempts: meter
+                .u64_counter("backon.retry.attempts")
+                .with_description("Number of attempts made, including the first one")
+                .build(),
+            retries: meter
+                .u64_counter("backon.retry.retries")
+                .with_description("Number of failed attempts that were retried")
+                .build(),
+            giveups: meter
+                .u64_counter("backon.retry.giveups")
+                .with_description("Number of retries that gave up")
+                .build(),
+            sleep: meter
+                .f64_histogram("backon.retry.sleep.duration")
+                .with_description("Time slept before retrying")
+                .with_unit("s")
+                .build(),
+        }
+    }
+}
+
+impl<T, E: fmt::Display> Hooks<T, E> for OpenTelemetry {
+    fn on_attempt(&mut self, state: &RetryState) {
+        if state.attempt() == 1 {
+            if let Some(name) = state.name() {
+                self.operation = KeyValue::new("operation.name", name);
+            }
+            self.tags = state
+                .tags()
+                .iter()
+                .map(|(key, value)| KeyValue::new(*key, value.clone()))
+                .collect();
+            self.attributes = core::iter::once(self.operation.clone())
+                .chain(self.tags.iter().cloned())
+                .collect();
+        }
+        self.attempts.add(1, &self.attributes);
+    }
+
+    fn on_transient(&mut self, err: &E, dur: Duration, state: &RetryState) {
+        self.retries.add(1, &self.attributes);
+        self.sleep.record(dur.as_secs_f64(), &self.attributes);
+        self.cx.span().add_event(
+            "retry.attempt_failed",
+            vec![
+                self.operation.clone(),
+                KeyValue::new("retry.attempt", state.attempt() as i64),
+                KeyValue::new("retry.delay", dur.as_secs_f64()),
+                KeyValue::new("exception.message", err.to_string()),
+            ]
+            .into_iter()
+            .chain(self.tags.iter().cloned())
+            .collect(),
+        );
+    }
+
+    fn on_fatal(&mut self, err: &E, reason: GiveUpReason, state: &RetryState) {
+        let reason = KeyValue::new("error.type", reason.as_str());
+        let attributes: Vec<_> = self
+            .attributes
+            .iter()
+            .cloned()
+            .chain([reason.clone()])
+            .collect();
+        self.giveups.add(1, &attributes);
+        self.cx.span().add_event(
+            "retry.gave_up",
+            vec![
+                self.operation.clone(),
+                KeyValue::new("retry.attempt", state.attempt() as i64),
+                reason,
+                KeyValue::new("exception.message", err.to_string()),
+            ]
+            .into_iter()
+            .chain(self.tags.iter().cloned())
+            .collect(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use opentelemetry::trace::{Tracer, TracerProvider};
+    use opentelemetry::Value;
+    use opentelemetry_sdk::metrics::data::{AggregatedMetrics, MetricData};
+    use opentelemetry_sdk::metrics::{InMemoryMetricExporter, SdkMeterProvider};
+    use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider};
+    use std::format;
+    use std::string::String;
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::BlockingRetryable;
+    use crate::ExponentialBuilder;
+
+    #[test]
+    fn test_opentelemetry() {
+        let exporter = InMemorySpanExporter::default();
+        let provider = SdkTracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+
+        let backoff = ExponentialBuilder::default()
+            .with_min_delay(Duration::from_millis(1))
+            .with_max_times(1);
+        let result = provider.tracer("test").in_span("request", |_| {
+            (|| Err::<(), _>("unavailable"))
+                .retry(backoff)
+                .when(|_| true)
+                .opentelemetry("fetch")
+                .call()
+        });
+        assert!(result.is_err());
+
+        let spans = exporter.get_finished_spans().unwrap();
+        assert_eq!(spans.len(), 1);
+        let events: Vec<_> = spans[0]
+            .events
+            .iter()
+            .map(|event| {
+                let reason = event
+                    .attributes
+                    .iter()
+                    .find(|kv| kv.key.as_str() == "error.type")
+                    .map(|kv| kv.value.clone());
+                (String::from(event.name.as_ref()), reason)
+            })
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                (String::from("retry.attempt_failed"), None),
+                (
+                    String::from("retry.gave_up"),
+                    Some(Value::from("backoff_exhausted"))
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_opentelemetry_metrics() {
+        let exporter = InMemoryMetricExporter::default();
+        let provider = SdkMeterProvider::builder()
+            .with_periodic_exporter(exporter.clone())
+            .build();
+        global::set_meter_provider(provider.clone());
+
+        let backoff = ExponentialBuilder::default()
+            .with_min_delay(Duration::from_millis(1))
+            .with_max_times(1);
+        let result = (|| Err::<(), _>("unavailable"))
+            .retry(backoff)
+            .name("upload")
+            .tag("bucket", "photos")
+            .opentelemetry("fetch")
+            .call();
+        assert!(result.is_err());
+        provider.force_flush().unwrap();
+
+        // The counters have the name and the tags of the retry as attributes.
+        let mut counts: Vec<(String, Vec<String>, u64)> = Vec::new();
+        for metrics in exporter.get_finished_metrics().unwrap() {
+            for metric in metrics.scope_metrics().flat_map(|scope| scope.metrics()) {
+                let AggregatedMetrics::U64(MetricData::Sum(sum)) = metric.data() else {
+                    continue;
+                };
+                for point in sum.data_points() {
+                    let mut attributes: Vec<_> = point
+                        .attributes()
+                        .map(|kv| format!("{}={}", kv.key, kv.value))
+                        .collect();
+                    attributes.sort();
+                    counts.push((metric.name().to_string(), attributes, point.value()));
+                }
+            }
+        }
+        counts.sort();
+        let attributes = |extra: &[&str]| {
+            let mut attributes: Vec<String> = ["bucket=photos", "operation.name=upload"]
+                .iter()
+                .chain(extra)
+                .map(|kv| kv.to_string())
+                .collect();
+            attributes.sort();
+            attributes
+        };
+        assert_eq!(
+            counts,
+            vec![
+                (String::from("backon.retry.attempts"), attributes(&[]), 2),
+                (
+                    String::from("backon.retry.giveups"),
+                    attributes(&["error.type=backoff_exhausted"]),
+                    1
+                ),
+                (String::from("backon.retry.retries"), attributes(&[]), 1),
+            ]
+        );
+    }
+}
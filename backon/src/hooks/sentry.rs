@@ -0,0 +1,113 @@
+use alloc::collections::BTreeMap;
+use alloc::string::ToString;
+use core::fmt;
+use core::time::Duration;
+
+use ::sentry_core::protocol::{Breadcrumb, Level, Value};
+
+use super::Hooks;
+use crate::GiveUpReason;
+use crate::RetryState;
+
+/// Hook set by `sentry` that records every failed attempt as a Sentry breadcrumb.
+///
+/// When the retry gives up, the number of attempts is set as a tag on the current scope, so
+/// the error captured afterwards carries it.
+#[doc(hidden)]
+pub struct Sentry;
+
+impl Sentry {
+    fn breadcrumb<E: fmt::Display>(
+        level: Level,
+        message: &str,
+        err: &E,
+        state: &RetryState,
+        data: impl IntoIterator<Item = (&'static str, Value)>,
+    ) -> Breadcrumb {
+        let mut map = BTreeMap::new();
+        if let Some(name) = state.name() {
+            map.insert("operation".to_string(), name.into());
+        }
+        map.insert("attempt".to_string(), state.attempt().into());
+        map.insert("error".to_string(), err.to_string().into());
+        map.extend(data.into_iter().map(|(k, v)| (k.to_string(), v)));
+        Breadcrumb {
+            category: Some("retry".to_string()),
+            message: Some(message.to_string()),
+            level,
+            data: map,
+            ..Default::default()
+        }
+    }
+}
+
+impl<T, E: fmt::Display> Hooks<T, E> for Sentry {
+    fn on_transient(&mut self, err: &E, dur: Duration, state: &RetryState) {
+        let delay = ("delay_ms", (dur.as_millis() as u64).into());
+        ::sentry_core::add_breadcrumb(Self::breadcrumb(
+            Level::Warning,
+            "attempt failed, retrying",
+            err,
+            state,
+            [delay],
+        ));
+    }
+
+    fn on_fatal(&mut self, err: &E, reason: GiveUpReason, state: &RetryState) {
+        let reason = ("reason", reason.as_str().into());
+        ::sentry_core::add_breadcrumb(Self::breadcrumb(
+            Level::Error,
+            "retry gave up",
+            err,
+            state,
+            [reason],
+        ));
+        ::sentry_core::configure_scope(|scope| {
+            scope.set_tag("retry.attempts", state.attempt());
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+    use crate::BlockingRetryable;
+    use crate::ExponentialBuilder;
+
+    #[test]
+    fn test_sentry() {
+        let backoff = ExponentialBuilder::default()
+            .with_min_delay(Duration::from_millis(1))
+            .with_max_times(2);
+
+        let events = ::sentry_core::test::with_captured_events(|| {
+            let result = (|| Err::<(), _>("unavailable"))
+                .retry(backoff)
+                .name("fetch")
+                .sentry()
+                .call();
+            ::sentry_core::capture_message(result.unwrap_err(), Level::Error);
+        });
+
+        let event = &events[0];
+        assert_eq!(event.tags["retry.attempts"], "3");
+        let breadcrumbs: Vec<_> = event
+            .breadcrumbs
+            .iter()
+            .map(|b| (b.category.as_deref(), b.level, b.data["attempt"].clone()))
+            .collect();
+        assert_eq!(
+            breadcrumbs,
+            [
+                (Some("retry"), Level::Warning, Value::from(1)),
+                (Some("retry"), Level::Warning, Value::from(2)),
+                (Some("retry"), Level::Error, Value::from(3)),
+            ]
+        );
+        assert_eq!(event.breadcrumbs[0].data["delay_ms"], 1);
+        assert_eq!(event.breadcrumbs[2].data["reason"], "backoff_exhausted");
+        assert_eq!(event.breadcrumbs[2].data["operation"], "fetch");
+    }
+}
@@ -0,0 +1,146 @@
+use core::fmt;
+use core::time::Duration;
+
+use ::slog::{Logger, Record, Serializer, KV};
+
+use super::Hooks;
+use crate::GiveUpReason;
+use crate::RetryState;
+
+/// Hook set by `slog` that logs retries and giving up to a `slog::Logger`.
+///
+/// The name, the session ID and the tags of the retry are attached to the records as
+/// key-values.
+#[doc(hidden)]
+pub struct Slog {
+    logger: Logger,
+}
+
+impl Slog {
+    pub(crate) fn new(logger: Logger) -> Self {
+        Slog { logger }
+    }
+}
+
+/// The key-values of the records of a retry: its name as `operation`, its `session_id`, then
+/// its tags.
+struct Fields<'a>(&'a RetryState);
+
+impl KV for Fields<'_> {
+    fn serialize(&self, _: &Record<'_>, serializer: &mut dyn Serializer) -> ::slog::Result {
+        // Serialized in reverse, like the key-values listed in the macros of `slog`.
+        for (key, value) in self.0.tags().iter().rev() {
+            serializer.emit_str(key, value)?;
+        }
+        serializer.emit_u64("session_id", self.0.session_id())?;
+        if let Some(name) = self.0.name() {
+            serializer.emit_str("operation", name)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T, E: fmt::Display> Hooks<T, E> for Slog {
+    fn on_transient(&mut self, err: &E, dur: Duration, state: &RetryState) {
+        ::slog::warn!(
+            self.logger,
+            "attempt failed, retrying";
+            "attempt" => state.attempt(),
+            "delay_ms" => dur.as_millis() as u64,
+            "error" => %err,
+            Fields(state),
+        );
+    }
+
+    fn on_fatal(&mut self, err: &E, reason: GiveUpReason, state: &RetryState) {
+        ::slog::error!(
+            self.logger,
+            "retry gave up";
+            "attempts" => state.attempt(),
+            "reason" => reason.as_str(),
+            "error" => %err,
+            Fields(state),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::format;
+    use alloc::string::String;
+    use alloc::sync::Arc;
+    use alloc::vec;
+    use alloc::vec::Vec;
+    use slog::{Drain, Key, Level, OwnedKVList};
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::BlockingRetryable;
+    use crate::ExponentialBuilder;
+
+    #[derive(Clone, Default)]
+    struct Lines(Arc<Mutex<Vec<(Level, String)>>>);
+
+    struct Fields(String);
+
+    impl Serializer for Fields {
+        fn emit_arguments(&mut self, key: Key, val: &fmt::Arguments<'_>) -> slog::Result {
+            self.0 = format!(" {key}={val}{}", self.0);
+            Ok(())
+        }
+    }
+
+    impl Drain for Lines {
+        type Ok = ();
+        type Err = slog::Never;
+
+        fn log(&self, record: &Record<'_>, _: &OwnedKVList) -> Result<(), slog::Never> {
+            let mut fields = Fields(String::new());
+            record.kv().serialize(record, &mut fields).unwrap();
+            self.0
+                .lock()
+                .unwrap()
+                .push((record.level(), format!("{}{}", record.msg(), fields.0)));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_slog() {
+        let lines = Lines::default();
+        let logger = Logger::root(lines.clone().fuse(), slog::o!());
+
+        let backoff = ExponentialBuilder::default()
+            .with_min_delay(Duration::from_millis(1))
+            .with_max_times(1);
+        let result = (|| Err::<(), _>("unavailable"))
+            .retry(backoff)
+            .when(|_| true)
+            .name("fetch")
+            .session_id(7)
+            .tag("bucket", "photos")
+            .slog(logger)
+            .call();
+
+        assert!(result.is_err());
+        assert_eq!(
+            *lines.0.lock().unwrap(),
+            vec![
+                (
+                    Level::Warning,
+                    String::from(
+                        "attempt failed, retrying attempt=1 delay_ms=1 error=unavailable \
+                         operation=fetch session_id=7 bucket=photos"
+                    )
+                ),
+                (
+                    Level::Error,
+                    String::from(
+                        "retry gave up attempts=2 reason=backoff_exhausted error=unavailable \
+                         operation=fetch session_id=7 bucket=photos"
+                    )
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,271 @@
+use core::fmt;
+use core::time::Duration;
+
+use ::tracing::field::Empty;
+use ::tracing::Span;
+
+use super::Hooks;
+use crate::GiveUpReason;
+use crate::RetryState;
+
+/// Hook set by `trace` that records the retry in a span.
+///
+/// The retried function runs inside the span, and every failure, sleep and the final outcome
+/// is emitted as an event within it. Every sleep runs inside a span of its own, like a
+/// future instrumented with [`Instrument`](::tracing::Instrument).
+#[doc(hidden)]
+pub struct Trace {
+    span: Span,
+    operation: &'static str,
+    sleep: Option<Span>,
+}
+
+impl Trace {
+    pub(crate) fn new(operation: &'static str) -> Self {
+        Trace {
+            span: ::tracing::info_span!(
+                "retry",
+                operation,
+                session_id = Empty,
+                attempts = Empty,
+                tags = Empty
+            ),
+            operation,
+            sleep: None,
+        }
+    }
+}
+
+impl<T, E: fmt::Display> Hooks<T, E> for Trace {
+    fn enter(&mut self) {
+        self.span
+            .with_subscriber(|(id, dispatch)| dispatch.enter(id));
+    }
+
+    fn exit(&mut self) {
+        self.span
+            .with_subscriber(|(id, dispatch)| dispatch.exit(id));
+    }
+
+    fn enter_sleep(&mut self) {
+        if let Some(sleep) = &self.sleep {
+            sleep.with_subscriber(|(id, dispatch)| dispatch.enter(id));
+        }
+    }
+
+    fn exit_sleep(&mut self) {
+        if let Some(sleep) = &self.sleep {
+            sleep.with_subscriber(|(id, dispatch)| dispatch.exit(id));
+        }
+    }
+
+    fn on_attempt(&mut self, state: &RetryState) {
+        if state.attempt() == 1 {
+            if let Some(name) = state.name() {
+                self.span.record("operation", name);
+                self.operation = name;
+            }
+            if !state.tags().is_empty() {
+                self.span
+                    .record("tags", ::tracing::field::debug(state.tags()));
+            }
+            self.span.record("session_id", state.session_id());
+        }
+        self.span.record("attempts", state.attempt());
+    }
+
+    fn on_success(&mut self, _: &T, state: &RetryState) {
+        ::tracing::debug!(parent: &self.span, attempt = state.attempt(), "retry succeeded");
+    }
+
+    fn on_transient(&mut self, err: &E, dur: Duration, state: &RetryState) {
+        ::tracing::warn!(
+            parent: &self.span,
+            attempt = state.attempt(),
+            error = %err,
+            "attempt failed"
+        );
+        ::tracing::debug!(
+            parent: &self.span,
+            attempt = state.attempt(),
+            delay = ?dur,
+            "sleeping before the next attempt"
+        );
+        self.sleep = Some(::tracing::info_span!(
+            parent: &self.span,
+            "sleep",
+            operation = self.operation,
+            attempt = state.attempt(),
+            delay = ?dur
+        ));
+    }
+
+    fn on_resume(&mut self, state: &RetryState) {
+        // The span of the sleep closes once it's over.
+        self.sleep = None;
+        ::tracing::debug!(
+            parent: &self.span,
+            attempt = state.attempt() + 1,
+            slept = ?state.last_delay(),
+            "resumed after backoff"
+        );
+    }
+
+    fn on_fatal(&mut self, err: &E, reason: GiveUpReason, state: &RetryState) {
+        ::tracing::error!(
+            parent: &self.span,
+            attempt = state.attempt(),
+            error = %err,
+            reason = %reason,
+            "retry gave up"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::format;
+    use alloc::string::String;
+    use alloc::string::ToString;
+    use alloc::vec;
+    use alloc::vec::Vec;
+    use core::future::ready;
+    use spin::Mutex;
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Level, Metadata, Subscriber};
+
+    use super::*;
+    use crate::BlockingRetryable;
+    use crate::ConstantBuilder;
+    use crate::ExponentialBuilder;
+    use crate::Retryable;
+
+    /// Records the level of every event, and whether it's emitted within a span, and the
+    /// name and fields of every span.
+    #[derive(Default)]
+    struct Events {
+        entered: Mutex<Vec<u64>>,
+        spans: Mutex<Vec<(&'static str, String)>>,
+        events: Mutex<Vec<(Level, bool)>>,
+    }
+
+    impl Events {
+        /// Returns the name and fields of the span entered last.
+        fn current(&self) -> Option<(&'static str, String)> {
+            let id = *self.entered.lock().last()?;
+            Some(self.spans.lock()[id as usize - 1].clone())
+        }
+    }
+
+    /// Formats the fields of a span as `name=value` pairs.
+    struct Fields(String);
+
+    impl Visit for Fields {
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            if !self.0.is_empty() {
+                self.0.push(' ');
+            }
+            self.0 += &format!("{}={value:?}", field.name());
+        }
+    }
+
+    impl Subscriber for &'static Events {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, attrs: &Attributes<'_>) -> Id {
+            let mut fields = Fields(String::new());
+            attrs.record(&mut fields);
+            let mut spans = self.spans.lock();
+            spans.push((attrs.metadata().name(), fields.0));
+            Id::from_u64(spans.len() as u64)
+        }
+
+        fn record(&self, _: &Id, _: &Record<'_>) {}
+
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            let within_span = !self.entered.lock().is_empty() || event.parent().is_some();
+            self.events
+                .lock()
+                .push((*event.metadata().level(), within_span));
+        }
+
+        fn enter(&self, id: &Id) {
+            self.entered.lock().push(id.into_u64());
+        }
+
+        fn exit(&self, _: &Id) {
+            self.entered.lock().pop();
+        }
+    }
+
+    #[test]
+    fn test_trace() {
+        let events: &'static Events = alloc::boxed::Box::leak(Default::default());
+        let error_times = Mutex::new(0);
+
+        let f = || {
+            let mut x = error_times.lock();
+            *x += 1;
+            // Events emitted by the retried function are within the span.
+            tracing::info!("fetching");
+            Err::<(), _>(String::from("unavailable"))
+        };
+
+        let backoff = ExponentialBuilder::default()
+            .with_min_delay(Duration::from_millis(1))
+            .with_max_times(1);
+        let result =
+            tracing::subscriber::with_default(events, || f.retry(backoff).trace("fetch").call());
+
+        assert_eq!(result.unwrap_err().to_string(), "unavailable");
+        assert_eq!(
+            *events.events.lock(),
+            vec![
+                (Level::INFO, true),
+                (Level::WARN, true),
+                (Level::DEBUG, true),
+                (Level::DEBUG, true),
+                (Level::INFO, true),
+                (Level::ERROR, true),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_trace_sleep() {
+        static EVENTS: Mutex<Vec<Option<(&'static str, String)>>> = Mutex::new(Vec::new());
+        let events: &'static Events = alloc::boxed::Box::leak(Default::default());
+        let _guard = tracing::subscriber::set_default(events);
+
+        // Every sleep is polled inside a span naming the operation and the attempt.
+        let backoff = ConstantBuilder::default()
+            .with_delay(Duration::from_millis(1))
+            .with_max_times(2);
+        let result = (|| ready(Err::<(), _>("unavailable")))
+            .retry(backoff)
+            .sleep(|_| async { EVENTS.lock().push(events.current()) })
+            .trace("fetch")
+            .await;
+
+        assert_eq!(result, Err("unavailable"));
+        assert_eq!(
+            *EVENTS.lock(),
+            vec![
+                Some((
+                    "sleep",
+                    "operation=\"fetch\" attempt=1 delay=1ms".to_string()
+                )),
+                Some((
+                    "sleep",
+                    "operation=\"fetch\" attempt=2 delay=1ms".to_string()
+                )),
+            ]
+        );
+        assert!(events.entered.lock().is_empty());
+    }
+}
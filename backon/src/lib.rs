@@ -28,6 +28,7 @@
 //! - [`ConstantBuilder`]: backoff with a constant delay, limited to a specific number of attempts.
 //! - [`ExponentialBuilder`]: backoff with an exponential delay, also supports jitter.
 //! - [`FibonacciBuilder`]: backoff with a fibonacci delay, also supports jitter.
+//! - [`PrecomputedBuilder`]: backoff with a schedule computed at compile time.
 //!
 //! # Sleep
 //!
@@ -79,6 +80,14 @@
 //! BlockingSleeper` will be raised to remind you to choose or bring a real Sleeper
 //! implementation.
 //!
+//! ## Loom
+//!
+//! The state shared between retries, like the one of [`FailureBudget`] and
+//! [`WatchedPolicy`], is synchronized with the primitives of [loom](https://docs.rs/loom)
+//! when built with `RUSTFLAGS="--cfg backon_loom"`, so loom tests exploring the interleavings
+//! of code sharing them explore the ones of backon as well. It's not `--cfg loom`, which
+//! changes the internals of crates like tokio that can't be built with it.
+//!
 //! # Retry
 //!
 //! For additional examples, please visit [`docs::examples`].
@@ -150,7 +159,7 @@
 #![deny(unused_qualifications)]
 #![no_std]
 
-#[cfg(feature = "std-blocking-sleep")]
+#[cfg(feature = "std")]
 extern crate std;
 
 extern crate alloc;
@@ -158,14 +167,108 @@ extern crate alloc;
 mod backoff;
 pub use backoff::*;
 
+#[cfg(feature = "clap")]
+pub mod cli;
+
+#[cfg(feature = "config")]
+pub mod config;
+
+mod clock;
+pub use clock::Clock;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+pub use clock::StdClock;
+
+mod duration;
+#[cfg(feature = "duration-str")]
+pub use duration::ParseDurationError;
+
+mod error;
+pub use error::non_retryable;
+pub use error::FailedAttempt;
+pub use error::FinalError;
+pub use error::GiveUpReason;
+#[cfg(feature = "std")]
+pub use error::PanicOrError;
+pub use error::Permanent;
+#[cfg(feature = "reqwest")]
+pub use error::ReqwestError;
+pub use error::RetryError;
+pub use error::RetryableError;
+pub use error::Transient;
+
+mod erased;
+pub use erased::DynRetry;
+
 mod retry;
 pub use retry::Retry;
 pub use retry::Retryable;
+#[cfg(feature = "std")]
+pub use retry::RetryableDefault;
 
 mod retry_with_context;
 pub use retry_with_context::RetryWithContext;
 pub use retry_with_context::RetryableWithContext;
 
+mod event;
+pub use event::RetryEvent;
+
+mod fallback;
+pub use fallback::{FallbackChain, Served};
+
+#[cfg(feature = "std")]
+mod global;
+#[cfg(feature = "std")]
+pub use global::{
+    default_backoff, set_default_backoff, set_global_observer, ErasedError, GlobalObserver,
+    SetGlobalObserverError,
+};
+
+mod hooks;
+
+mod macros;
+
+mod metrics;
+#[cfg(feature = "prometheus")]
+pub use metrics::PrometheusMetrics;
+pub use metrics::RetryMetrics;
+
+mod registry;
+pub use registry::PolicyRegistry;
+
+mod template;
+pub use template::RetryTemplate;
+
+mod report;
+pub use report::RetryReport;
+pub use report::ScheduleSummary;
+pub use report::WithReport;
+
+mod retry_with_mut_context;
+pub use retry_with_mut_context::RetryWithMutContext;
+pub use retry_with_mut_context::RetryableWithMutContext;
+
+mod snapshot;
+pub use snapshot::RetrySnapshot;
+
+mod speculative;
+pub use speculative::{Speculated, Speculative, SpeculativeRetryable};
+
+mod state;
+#[cfg(feature = "std")]
+pub use state::current_attempt;
+pub use state::RetryState;
+
+#[cfg(feature = "arbitrary")]
+pub mod fuzz;
+
+#[cfg(feature = "test-util")]
+pub mod test;
+
+#[cfg(feature = "jitter")]
+mod rng;
+#[cfg(feature = "jitter")]
+pub use rng::{seed_jitter_rng, set_jitter_rng};
+
 mod sleep;
 pub use sleep::DefaultSleeper;
 #[cfg(all(target_arch = "wasm32", feature = "gloo-timers-sleep"))]
@@ -173,13 +276,51 @@ pub use sleep::GlooTimersSleep;
 pub use sleep::Sleeper;
 #[cfg(all(not(target_arch = "wasm32"), feature = "tokio-sleep"))]
 pub use sleep::TokioSleeper;
+#[cfg(all(not(target_arch = "wasm32"), feature = "turmoil"))]
+pub use sleep::TurmoilSleeper;
+
+#[cfg(feature = "std")]
+mod sync;
+
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+mod breaker;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+pub use breaker::{CircuitBreaker, CircuitError, CircuitFuture, CircuitState};
+
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+mod bulkhead;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+pub use bulkhead::{Bulkhead, BulkheadError, BulkheadFuture};
+
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+mod budget;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+pub use budget::{FailureBudget, RetryBudget};
+
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+mod set;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+pub use set::{RetrySet, RetrySetStats, SetSleep, SetSleeper};
+
+mod warning;
+pub use warning::BudgetWarning;
+
+mod deadline;
+pub use deadline::{AttemptTimedOut, Deadline};
 
 mod blocking_retry;
+#[cfg(feature = "std")]
+pub use blocking_retry::BlockingRetryableDefault;
 pub use blocking_retry::{BlockingRetry, BlockingRetryable};
 
 mod blocking_retry_with_context;
 pub use blocking_retry_with_context::{BlockingRetryWithContext, BlockingRetryableWithContext};
 
+mod blocking_retry_with_mut_context;
+pub use blocking_retry_with_mut_context::{
+    BlockingRetryWithMutContext, BlockingRetryableWithMutContext,
+};
+
 mod blocking_sleep;
 pub use blocking_sleep::BlockingSleeper;
 pub use blocking_sleep::DefaultBlockingSleeper;
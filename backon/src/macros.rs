@@ -0,0 +1,191 @@
+/// Build a function for [`RetryableWithContext`][crate::RetryableWithContext] or
+/// [`BlockingRetryableWithContext`][crate::BlockingRetryableWithContext] whose context is a
+/// tuple of several values.
+///
+/// Every value of the tuple is bound to its own mutable variable before the body runs, and
+/// the tuple is packed again together with the result of the body afterwards. So the body
+/// only needs to evaluate to `Result<T, E>`.
+///
+/// Types can be annotated like closure arguments. Prefix the arguments with `async` to build
+/// an async function.
+///
+/// # Examples
+///
+/// ```no_run
+/// use anyhow::Result;
+/// use backon::ctx;
+/// use backon::ExponentialBuilder;
+/// use backon::RetryableWithContext;
+///
+/// struct Client;
+///
+/// impl Client {
+///     async fn get(&mut self, key: &str) -> Result<String> {
+///         Ok(key.to_string())
+///     }
+/// }
+///
+/// #[tokio::main(flavor = "current_thread")]
+/// async fn main() -> Result<()> {
+///     let ((_client, _key), content) = ctx!(async |client: Client, key: String| {
+///         client.get(&key).await
+///     })
+///     .retry(ExponentialBuilder::default())
+///     .context((Client, "hello".to_string()))
+///     .await;
+///     println!("fetch succeeded: {}", content?);
+///
+///     Ok(())
+/// }
+/// ```
+#[macro_export]
+macro_rules! ctx {
+    (async |$($ctx:ident $(: $ty:ty)?),+ $(,)?| $body:expr) => {
+        (|ctx| {
+            // `Cell` is never `Copy`, so the async block always captures the context
+            // by value even if all of its values are `Copy`.
+            let ctx = ::core::cell::Cell::new(ctx);
+            async {
+                let ($($ctx,)+) = ctx.into_inner();
+                $(
+                    #[allow(unused_mut)]
+                    let mut $ctx $(: $ty)? = $ctx;
+                )+
+                let res = $body;
+                (($($ctx,)+), res)
+            }
+        })
+    };
+    (|$($ctx:ident $(: $ty:ty)?),+ $(,)?| $body:expr) => {
+        (|ctx| {
+            let ($($ctx,)+) = ctx;
+            $(
+                #[allow(unused_mut)]
+                let mut $ctx $(: $ty)? = $ctx;
+            )+
+            let res = $body;
+            (($($ctx,)+), res)
+        })
+    };
+}
+
+#[cfg(test)]
+mod blocking_tests {
+    use alloc::string::String;
+    use alloc::string::ToString;
+    use anyhow::{anyhow, Result};
+    use core::time::Duration;
+
+    use crate::BlockingRetryableWithContext;
+    use crate::ExponentialBuilder;
+
+    #[test]
+    fn test_ctx_with_blocking_retry() -> Result<()> {
+        let backoff = ExponentialBuilder::default().with_min_delay(Duration::from_millis(1));
+
+        let ((calls, name), result) = ctx!(|calls: usize, name: String| {
+            calls += 1;
+            name.push('!');
+            Err::<(), _>(anyhow!("retryable"))
+        })
+        .retry(backoff)
+        .context((0, "hello".to_string()))
+        .call();
+
+        assert!(result.is_err());
+        // The function always returns error "retryable", so it should be executed
+        // 4 times (retry 3 times).
+        assert_eq!(calls, 4);
+        assert_eq!(name, "hello!!!!");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[cfg(any(feature = "tokio-sleep", feature = "gloo-timers-sleep"))]
+mod tests {
+    use alloc::string::String;
+    use alloc::string::ToString;
+    use anyhow::Result;
+    use core::time::Duration;
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::wasm_bindgen_test as test;
+
+    #[cfg(not(target_arch = "wasm32"))]
+    use tokio::test;
+
+    use crate::ExponentialBuilder;
+    use crate::RetryableWithContext;
+
+    #[test]
+    async fn test_ctx_with_retry() -> Result<()> {
+        let backoff = ExponentialBuilder::default().with_min_delay(Duration::from_millis(1));
+
+        let ((calls, name), result) = ctx!(async |calls: usize, name: String| {
+            calls += 1;
+            Ok::<_, anyhow::Error>(name.len() + calls)
+        })
+        .retry(backoff)
+        .context((0, "hello".to_string()))
+        .await;
+
+        assert_eq!(result?, 6);
+        assert_eq!(calls, 1);
+        assert_eq!(name, "hello");
+        Ok(())
+    }
+}
+
+/// Assert that a retry made `attempts` attempts, from its [`RetryReport`][crate::RetryReport]
+/// or [`RetryError`][crate::RetryError].
+///
+/// Only available with the `test-util` feature, see [`test`][crate::test].
+///
+/// # Examples
+///
+/// ```
+/// use backon::assert_attempts;
+/// use backon::test::NoopSleeper;
+/// use backon::BlockingRetryable;
+/// use backon::ConstantBuilder;
+///
+/// let (_, report) = (|| Err::<(), _>("unavailable"))
+///     .retry(ConstantBuilder::default().with_max_times(2))
+///     .sleep(NoopSleeper)
+///     .call_with_report();
+///
+/// assert_attempts!(report, 3);
+/// ```
+#[cfg(feature = "test-util")]
+#[macro_export]
+macro_rules! assert_attempts {
+    ($report:expr, $attempts:expr $(,)?) => {{
+        let (attempts, expected): (usize, usize) = ($report.attempts(), $attempts);
+        ::core::assert!(
+            attempts == expected,
+            "expected {} attempts, the retry made {}",
+            expected,
+            attempts
+        );
+    }};
+}
+
+/// Assert that a retry slept `slept` in total between its attempts, from its
+/// [`RetryReport`][crate::RetryReport].
+///
+/// Only available with the `test-util` feature, see [`test`][crate::test].
+#[cfg(feature = "test-util")]
+#[macro_export]
+macro_rules! assert_slept {
+    ($report:expr, $slept:expr $(,)?) => {{
+        let (slept, expected): (::core::time::Duration, ::core::time::Duration) =
+            ($report.slept(), $slept);
+        ::core::assert!(
+            slept == expected,
+            "expected the retry to sleep {:?}, it slept {:?}",
+            expected,
+            slept
+        );
+    }};
+}
@@ -0,0 +1,105 @@
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use core::time::Duration;
+
+use crate::GiveUpReason;
+use crate::RetryState;
+
+#[cfg(feature = "prometheus")]
+mod prometheus;
+#[cfg(feature = "prometheus")]
+pub use self::prometheus::PrometheusMetrics;
+
+/// RetryMetrics observes the progress of retries, to export counts and delays to a metrics
+/// system.
+///
+/// All methods do nothing by default, implement the ones interested in. They're called with
+/// the [`RetryState`] of the retry, carrying the index of the attempt and the name and tags
+/// set by `name` and `tag` to label the metrics with. It's set on a retry by `metrics`, and
+/// implemented for references and smart pointers of implementations, so one instance can be
+/// shared by many retries.
+///
+/// # Examples
+///
+/// ```
+/// use core::sync::atomic::{AtomicUsize, Ordering};
+/// use core::time::Duration;
+///
+/// use backon::BlockingRetryable;
+/// use backon::ConstantBuilder;
+/// use backon::GiveUpReason;
+/// use backon::RetryMetrics;
+/// use backon::RetryState;
+///
+/// #[derive(Default)]
+/// struct Counters {
+///     attempts: AtomicUsize,
+///     giveups: AtomicUsize,
+/// }
+///
+/// impl RetryMetrics for Counters {
+///     fn attempt_started(&self, _: &RetryState) {
+///         self.attempts.fetch_add(1, Ordering::Relaxed);
+///     }
+///
+///     fn gave_up(&self, _: GiveUpReason, _: &RetryState) {
+///         self.giveups.fetch_add(1, Ordering::Relaxed);
+///     }
+/// }
+///
+/// let counters = Counters::default();
+/// let result = (|| Err::<(), _>("unavailable"))
+///     .retry(ConstantBuilder::default().with_delay(Duration::from_millis(1)))
+///     .metrics(&counters)
+///     .call();
+///
+/// assert!(result.is_err());
+/// assert_eq!(counters.attempts.load(Ordering::Relaxed), 4);
+/// assert_eq!(counters.giveups.load(Ordering::Relaxed), 1);
+/// ```
+pub trait RetryMetrics {
+    /// Called when an attempt starts, its index starting from 1.
+    fn attempt_started(&self, _state: &RetryState) {}
+
+    /// Called when an attempt fails, whether it will be retried or not.
+    fn attempt_failed(&self, _state: &RetryState) {}
+
+    /// Called with the delay before retrying a failed attempt, before sleeping.
+    fn slept(&self, _delay: Duration, _state: &RetryState) {}
+
+    /// Called when the retry succeeds, the index of the attempt being the number of attempts
+    /// made.
+    fn succeeded(&self, _state: &RetryState) {}
+
+    /// Called when the retry ends unsuccessfully with the reason why, the index of the
+    /// attempt being the number of attempts made.
+    fn gave_up(&self, _reason: GiveUpReason, _state: &RetryState) {}
+}
+
+macro_rules! impl_retry_metrics_for_pointer {
+    ($($ty:ty),+) => {$(
+        impl<M: RetryMetrics + ?Sized> RetryMetrics for $ty {
+            fn attempt_started(&self, state: &RetryState) {
+                (**self).attempt_started(state)
+            }
+
+            fn attempt_failed(&self, state: &RetryState) {
+                (**self).attempt_failed(state)
+            }
+
+            fn slept(&self, delay: Duration, state: &RetryState) {
+                (**self).slept(delay, state)
+            }
+
+            fn succeeded(&self, state: &RetryState) {
+                (**self).succeeded(state)
+            }
+
+            fn gave_up(&self, reason: GiveUpReason, state: &RetryState) {
+                (**self).gave_up(reason, state)
+            }
+        }
+    )+};
+}
+
+impl_retry_metrics_for_pointer!(&M, Box<M>, Arc<M>);
@@ -0,0 +1,183 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::time::Duration;
+
+use ::prometheus::{HistogramOpts, HistogramVec, IntCounterVec, Opts, Registry};
+
+use super::RetryMetrics;
+use crate::GiveUpReason;
+use crate::RetryState;
+
+/// PrometheusMetrics exports the metrics of retries to a Prometheus [`Registry`].
+///
+/// The following metrics are registered, all labeled by `operation`, the name of the retry
+/// set by `name`, and by the tags declared by `register_with_tags`, the values set by `tag`:
+///
+/// - `backon_attempts_total`: the number of attempts started.
+/// - `backon_retries_total`: the number of failed attempts that are retried.
+/// - `backon_giveups_total`: the number of retries ended unsuccessfully, also labeled by
+///   `reason` as in [`GiveUpReason::as_str`].
+/// - `backon_sleep_seconds`: the histogram of delays before retrying.
+///
+/// Retries without a name or one of the tags have the label empty.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use backon::BlockingRetryable;
+/// use backon::ConstantBuilder;
+/// use backon::PrometheusMetrics;
+/// use prometheus::Registry;
+///
+/// let registry = Registry::new();
+/// let metrics = PrometheusMetrics::register_with_tags(&registry, &["bucket"]).unwrap();
+///
+/// let result = (|| Err::<(), _>("unavailable"))
+///     .retry(ConstantBuilder::default().with_delay(Duration::from_millis(1)))
+///     .name("s3_put_object")
+///     .tag("bucket", "photos")
+///     .metrics(&metrics)
+///     .call();
+///
+/// assert!(result.is_err());
+/// ```
+#[derive(Clone, Debug)]
+pub struct PrometheusMetrics {
+    tags: Vec<&'static str>,
+    attempts: IntCounterVec,
+    retries: IntCounterVec,
+    giveups: IntCounterVec,
+    sleep: HistogramVec,
+}
+
+impl PrometheusMetrics {
+    /// Create the metrics and register them to the registry.
+    pub fn register(registry: &Registry) -> ::prometheus::Result<Self> {
+        Self::register_with_tags(registry, &[])
+    }
+
+    /// Create the metrics also labeled by the `tags` of the retries, and register them to
+    /// the registry.
+    pub fn register_with_tags(
+        registry: &Registry,
+        tags: &[&'static str],
+    ) -> ::prometheus::Result<Self> {
+        let labels: Vec<&str> = core::iter::once("operation")
+            .chain(tags.iter().copied())
+            .collect();
+        let mut giveup_labels = labels.clone();
+        giveup_labels.push("reason");
+        let metrics = PrometheusMetrics {
+            tags: tags.to_vec(),
+            attempts: IntCounterVec::new(
+                Opts::new("backon_attempts_total", "Number of attempts started."),
+                &labels,
+            )?,
+            retries: IntCounterVec::new(
+                Opts::new("backon_retries_total", "Number of failed attempts retried."),
+                &labels,
+            )?,
+            giveups: IntCounterVec::new(
+                Opts::new(
+                    "backon_giveups_total",
+                    "Number of retries ended unsuccessfully.",
+                ),
+                &giveup_labels,
+            )?,
+            sleep: HistogramVec::new(
+                HistogramOpts::new("backon_sleep_seconds", "Delays before retrying."),
+                &labels,
+            )?,
+        };
+
+        registry.register(Box::new(metrics.attempts.clone()))?;
+        registry.register(Box::new(metrics.retries.clone()))?;
+        registry.register(Box::new(metrics.giveups.clone()))?;
+        registry.register(Box::new(metrics.sleep.clone()))?;
+        Ok(metrics)
+    }
+
+    /// Returns the values of the labels of the retry: its name, then its tags.
+    fn labels<'a>(&self, state: &'a RetryState) -> Vec<&'a str> {
+        let tag = |key: &str| {
+            state
+                .tags()
+                .iter()
+                .find(|(k, _)| *k == key)
+                .map_or("", |(_, value)| value.as_ref())
+        };
+        core::iter::once(state.name().unwrap_or(""))
+            .chain(self.tags.iter().map(|key| tag(key)))
+            .collect()
+    }
+}
+
+impl RetryMetrics for PrometheusMetrics {
+    fn attempt_started(&self, state: &RetryState) {
+        self.attempts.with_label_values(&self.labels(state)).inc();
+    }
+
+    fn slept(&self, delay: Duration, state: &RetryState) {
+        let labels = self.labels(state);
+        self.retries.with_label_values(&labels).inc();
+        self.sleep
+            .with_label_values(&labels)
+            .observe(delay.as_secs_f64());
+    }
+
+    fn gave_up(&self, reason: GiveUpReason, state: &RetryState) {
+        let mut labels = self.labels(state);
+        labels.push(reason.as_str());
+        self.giveups.with_label_values(&labels).inc();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BlockingRetryable;
+    use crate::ExponentialBuilder;
+
+    #[test]
+    fn test_prometheus_metrics() {
+        let registry = Registry::new();
+        let metrics = PrometheusMetrics::register_with_tags(&registry, &["bucket"]).unwrap();
+
+        // The metrics are labeled by the name and the tags of the retry.
+        let backoff = ExponentialBuilder::default().with_min_delay(Duration::from_millis(1));
+        let result = (|| Err::<(), _>("unavailable"))
+            .retry(backoff)
+            .name("fetch")
+            .tag("bucket", "photos")
+            .tag("region", "eu")
+            .metrics(&metrics)
+            .call();
+        assert!(result.is_err());
+
+        let labels = ["fetch", "photos"];
+        assert_eq!(metrics.attempts.with_label_values(&labels).get(), 4);
+        assert_eq!(metrics.retries.with_label_values(&labels).get(), 3);
+        assert_eq!(
+            metrics
+                .giveups
+                .with_label_values(&["fetch", "photos", "backoff_exhausted"])
+                .get(),
+            1
+        );
+        assert_eq!(
+            metrics.sleep.with_label_values(&labels).get_sample_count(),
+            3
+        );
+        assert_eq!(registry.gather().len(), 4);
+
+        // Retries without a name or a tag have the label empty.
+        let result = (|| Err::<(), _>("unavailable"))
+            .retry(backoff.with_max_times(0))
+            .metrics(&metrics)
+            .call();
+        assert!(result.is_err());
+        assert_eq!(metrics.attempts.with_label_values(&["", ""]).get(), 1);
+    }
+}
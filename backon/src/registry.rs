@@ -0,0 +1,166 @@
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use core::future::Future;
+
+use crate::Backoff;
+use crate::BackoffBuilder;
+use crate::BlockingRetry;
+use crate::BlockingRetryable;
+use crate::ExponentialBuilder;
+use crate::Retry;
+use crate::Retryable;
+use crate::SharedBackoffBuilder;
+
+/// PolicyRegistry is a set of backoffs registered by name, fetched where operations are
+/// retried.
+///
+/// So the retry policies of a codebase are governed in one place instead of being spread
+/// across call sites. Names without a backoff fall back to the default one, the default
+/// [`ExponentialBuilder`] unless set by `with_default`. The retries are named after their
+/// policy, see [`Retry::name`].
+///
+/// # Examples
+///
+/// ```
+/// use core::time::Duration;
+///
+/// use backon::ConstantBuilder;
+/// use backon::ExponentialBuilder;
+/// use backon::PolicyRegistry;
+///
+/// let registry = PolicyRegistry::new()
+///     .with_default(ExponentialBuilder::standard())
+///     .register("s3_put", ExponentialBuilder::conservative())
+///     .register("cache_get", ConstantBuilder::default().with_delay(Duration::from_millis(10)));
+///
+/// let result = registry
+///     .blocking_retry("cache_get", || Ok::<_, &str>("hit"))
+///     .call();
+/// assert_eq!(result, Ok("hit"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct PolicyRegistry {
+    policies: BTreeMap<String, SharedBackoffBuilder>,
+    default: SharedBackoffBuilder,
+}
+
+impl Default for PolicyRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PolicyRegistry {
+    /// Create a registry without backoffs, falling back to the default
+    /// [`ExponentialBuilder`].
+    pub fn new() -> Self {
+        PolicyRegistry {
+            policies: BTreeMap::new(),
+            default: SharedBackoffBuilder::new(ExponentialBuilder::default()),
+        }
+    }
+
+    /// Set the backoff used for the names without one.
+    pub fn with_default(mut self, builder: impl BackoffBuilder + Clone + 'static) -> Self {
+        self.default = SharedBackoffBuilder::new(builder);
+        self
+    }
+
+    /// Register the backoff of `name`, replacing the one registered before.
+    pub fn register(
+        mut self,
+        name: impl Into<String>,
+        builder: impl BackoffBuilder + Clone + 'static,
+    ) -> Self {
+        self.insert(name, builder);
+        self
+    }
+
+    /// Register the backoff of `name` in place, like `register`.
+    pub fn insert(
+        &mut self,
+        name: impl Into<String>,
+        builder: impl BackoffBuilder + Clone + 'static,
+    ) {
+        self.policies
+            .insert(name.into(), SharedBackoffBuilder::new(builder));
+    }
+
+    /// Returns the backoff registered for `name`, without falling back to the default one.
+    pub fn get(&self, name: &str) -> Option<&SharedBackoffBuilder> {
+        self.policies.get(name)
+    }
+
+    /// Returns the backoff of `name`, or the default one if it has none.
+    pub fn policy(&self, name: &str) -> SharedBackoffBuilder {
+        self.get(name).unwrap_or(&self.default).clone()
+    }
+
+    /// Returns whether a backoff is registered for `name`.
+    pub fn contains(&self, name: &str) -> bool {
+        self.policies.contains_key(name)
+    }
+
+    /// Retry `f` with the backoff of `name`, the retry named `name`.
+    pub fn retry<T, E, Fut, FutureFn>(
+        &self,
+        name: &'static str,
+        f: FutureFn,
+    ) -> Retry<Box<dyn Backoff>, T, E, Fut, FutureFn>
+    where
+        Fut: Future<Output = Result<T, E>>,
+        FutureFn: FnMut() -> Fut,
+    {
+        f.retry(self.policy(name)).name(name)
+    }
+
+    /// Retry the blocking `f` with the backoff of `name`, the retry named `name`.
+    pub fn blocking_retry<T, E, F>(
+        &self,
+        name: &'static str,
+        f: F,
+    ) -> BlockingRetry<Box<dyn Backoff>, T, E, F>
+    where
+        F: FnMut() -> Result<T, E>,
+    {
+        f.retry(self.policy(name)).name(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+    use core::time::Duration;
+
+    use super::*;
+    use crate::ConstantBuilder;
+
+    #[test]
+    fn test_policy_registry() {
+        let registry = PolicyRegistry::new()
+            .with_default(ConstantBuilder::default().with_max_times(1))
+            .register(
+                "s3_put",
+                ConstantBuilder::default().with_delay(Duration::from_millis(2)),
+            );
+        assert!(registry.contains("s3_put"));
+
+        let delays: Vec<_> = registry.policy("s3_put").build().collect();
+        assert_eq!(delays, [Duration::from_millis(2); 3]);
+        // Unknown names fall back to the default backoff.
+        assert!(registry.get("s3_get").is_none());
+        assert_eq!(registry.policy("s3_get").build().count(), 1);
+
+        let mut attempts = 0;
+        let result = registry
+            .blocking_retry("s3_get", || {
+                attempts += 1;
+                Err::<(), _>("unavailable")
+            })
+            .sleep(|_| {})
+            .call();
+        assert_eq!(result, Err("unavailable"));
+        assert_eq!(attempts, 2);
+    }
+}
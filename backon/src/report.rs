@@ -0,0 +1,185 @@
+use alloc::borrow::Cow;
+use alloc::vec::Vec;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::ready;
+use core::task::Context;
+use core::task::Poll;
+use core::time::Duration;
+
+use crate::RetryState;
+
+/// RetryReport summarizes how a retry went, returned by `call_with_report` and
+/// `await_with_report` alongside its result.
+///
+/// # Examples
+///
+/// ```
+/// use core::time::Duration;
+///
+/// use backon::BlockingRetryable;
+/// use backon::ConstantBuilder;
+///
+/// let (result, report) = (|| Err::<(), _>("unavailable"))
+///     .retry(ConstantBuilder::default().with_delay(Duration::from_millis(1)))
+///     .call_with_report();
+///
+/// assert!(result.is_err());
+/// assert_eq!(report.attempts(), 4);
+/// assert_eq!(report.slept(), Duration::from_millis(3));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RetryReport {
+    name: Option<&'static str>,
+    tags: Vec<(&'static str, Cow<'static, str>)>,
+    attempts: usize,
+    elapsed: Duration,
+    delays: Vec<Duration>,
+    planned_delays: Vec<Duration>,
+    latencies: Vec<Duration>,
+}
+
+impl RetryReport {
+    /// Returns the name of the retried operation set by `name`.
+    pub fn name(&self) -> Option<&'static str> {
+        self.name
+    }
+
+    /// Returns the key-value tags of the retried operation set by `tag`.
+    pub fn tags(&self) -> &[(&'static str, Cow<'static, str>)] {
+        &self.tags
+    }
+
+    /// Returns the number of attempts made.
+    pub fn attempts(&self) -> usize {
+        self.attempts
+    }
+
+    /// Returns the time elapsed from the start of the first attempt to the end of the retry,
+    /// measured by the [`Clock`](crate::Clock) of the retry.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Returns the total time slept between attempts.
+    pub fn slept(&self) -> Duration {
+        self.delays.iter().sum()
+    }
+
+    /// Returns the number of failed attempts retried immediately, without delay.
+    pub fn immediate_retries(&self) -> usize {
+        self.delays.iter().filter(|dur| dur.is_zero()).count()
+    }
+
+    /// Returns the delay slept after each failed attempt that was retried, in order.
+    pub fn delays(&self) -> &[Duration] {
+        &self.delays
+    }
+
+    /// Returns how long each attempt took to run, in order.
+    ///
+    /// Attempts are only measured if the retry has a [`Clock`](crate::Clock).
+    pub fn latencies(&self) -> &[Duration] {
+        &self.latencies
+    }
+
+    /// Returns the delay proposed by the backoff after each failed attempt that was retried,
+    /// in order.
+    ///
+    /// They include the jitter chosen by the backoff, and differ from [`RetryReport::delays`]
+    /// when errors tell how long to wait before retrying. Pass them to
+    /// [`ReplayBuilder`](crate::ReplayBuilder) to replay the identical schedule.
+    pub fn planned_delays(&self) -> &[Duration] {
+        &self.planned_delays
+    }
+
+    pub(crate) fn record_delay(&mut self, planned: Duration, dur: Duration) {
+        self.planned_delays.push(planned);
+        self.delays.push(dur);
+    }
+
+    pub(crate) fn record_latency(&mut self, dur: Duration) {
+        self.latencies.push(dur);
+    }
+
+    /// Fill in the totals of the finished retry.
+    pub(crate) fn finish(&mut self, state: &RetryState) {
+        self.name = state.name();
+        self.tags = state.tags().to_vec();
+        self.attempts = state.attempt();
+        self.elapsed = state.elapsed();
+    }
+}
+
+/// ScheduleSummary compares the delays planned by the backoff with the delays actually slept
+/// by a retry, passed to the function set by `on_backoff_exhausted`.
+///
+/// They differ when errors tell how long to wait before retrying, like `Retry-After`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ScheduleSummary {
+    planned: Vec<Duration>,
+    actual: Vec<Duration>,
+}
+
+impl ScheduleSummary {
+    /// Returns the delays proposed by the backoff before each retry, in order.
+    pub fn planned(&self) -> &[Duration] {
+        &self.planned
+    }
+
+    /// Returns the delays slept before each retry, in order.
+    pub fn actual(&self) -> &[Duration] {
+        &self.actual
+    }
+
+    /// Returns the total delay proposed by the backoff.
+    pub fn total_planned(&self) -> Duration {
+        self.planned.iter().sum()
+    }
+
+    /// Returns the total delay slept.
+    pub fn total_actual(&self) -> Duration {
+        self.actual.iter().sum()
+    }
+
+    pub(crate) fn record(&mut self, planned: Duration, actual: Duration) {
+        self.planned.push(planned);
+        self.actual.push(actual);
+    }
+}
+
+/// A retry future that is reported on, yielding a [`RetryReport`] alongside its output.
+///
+/// Created by `await_with_report`.
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct WithReport<R> {
+    retry: R,
+}
+
+impl<R> WithReport<R> {
+    pub(crate) fn new(retry: R) -> Self {
+        WithReport { retry }
+    }
+}
+
+/// A stub trait for the retries whose progress can be reported on.
+#[doc(hidden)]
+pub trait Reported {
+    /// Returns the state of the retry.
+    fn retry_state(&mut self) -> &mut RetryState;
+}
+
+impl<R: Future + Reported> Future for WithReport<R> {
+    type Output = (R::Output, RetryReport);
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Safety: This is safe because we don't move the `WithReport` struct or the retry
+        // inside it.
+        let this = unsafe { self.get_unchecked_mut() };
+        let mut retry = unsafe { Pin::new_unchecked(&mut this.retry) };
+        let output = ready!(retry.as_mut().poll(cx));
+        // Safety: The retry is not moved, only its state is read.
+        let state = unsafe { retry.get_unchecked_mut() }.retry_state();
+        Poll::Ready((output, state.take_report()))
+    }
+}
@@ -1,4 +1,9 @@
+use alloc::borrow::Cow;
+use alloc::boxed::Box;
+use core::any;
+use core::fmt;
 use core::future::Future;
+use core::ops::ControlFlow;
 use core::pin::Pin;
 use core::task::ready;
 use core::task::Context;
@@ -6,10 +11,56 @@ use core::task::Poll;
 use core::time::Duration;
 
 use crate::backoff::BackoffBuilder;
+use crate::clock::SharedClock;
+use crate::erased::Parts;
+#[cfg(feature = "anyhow")]
+use crate::error::WhenDowncast;
+use crate::error::{
+    BackoffBy, Classify, ErrorClassifier, ErrorCollector, LastError, MapErrBefore, MapError,
+    ReduceErrors, SelectError, Unmapped, UnwrapPermanent, WhenRetryableError, WhenWithState,
+    WrapRetryError,
+};
+#[cfg(feature = "defmt")]
+use crate::hooks::Defmt;
+#[cfg(feature = "logging")]
+use crate::hooks::Log;
+#[cfg(feature = "opentelemetry")]
+use crate::hooks::OpenTelemetry;
+#[cfg(feature = "sentry")]
+use crate::hooks::Sentry;
+#[cfg(feature = "slog")]
+use crate::hooks::Slog;
+#[cfg(feature = "tracing")]
+use crate::hooks::Trace;
+use crate::hooks::{
+    AttemptTimeout, Chain, Hooks, Metrics, NoHooks, Observe, OnBackoffExhausted, OnFatal, OnStart,
+    OnTransient, Recover,
+};
+use crate::report::Reported;
 use crate::sleep::MaybeSleeper;
+use crate::state::CurrentAttempt;
+use crate::AttemptTimedOut;
 use crate::Backoff;
+use crate::BackoffChoice;
+use crate::BudgetWarning;
+use crate::Clock;
+use crate::Deadline;
 use crate::DefaultSleeper;
+use crate::DynRetry;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+use crate::FailureBudget;
+use crate::FinalError;
+use crate::GiveUpReason;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+use crate::RetryBudget;
+use crate::RetryEvent;
+use crate::RetryMetrics;
+use crate::RetrySnapshot;
+use crate::RetryState;
+use crate::RetryableError;
+use crate::ScheduleSummary;
 use crate::Sleeper;
+use crate::WithReport;
 
 /// Retryable will add retry support for functions that produce futures with results.
 ///
@@ -46,7 +97,7 @@ pub trait Retryable<
     FutureFn: FnMut() -> Fut,
 >
 {
-    /// Generate a new retry
+    /// Generate a new retry.
     fn retry(self, builder: B) -> Retry<B::Backoff, T, E, Fut, FutureFn>;
 }
 
@@ -61,7 +112,54 @@ where
     }
 }
 
+/// RetryableDefault adds `retry_default` to the functions of [`Retryable`], starting a retry
+/// with the process-wide default backoff, see [`set_default_backoff`][crate::set_default_backoff].
+///
+/// Only available with the `std` feature.
+#[cfg(feature = "std")]
+pub trait RetryableDefault<T, E, Fut: Future<Output = Result<T, E>>, FutureFn: FnMut() -> Fut> {
+    /// Generate a new retry with the default backoff.
+    fn retry_default(self) -> Retry<Box<dyn Backoff>, T, E, Fut, FutureFn>;
+}
+
+#[cfg(feature = "std")]
+impl<T, E, Fut, FutureFn> RetryableDefault<T, E, Fut, FutureFn> for FutureFn
+where
+    Fut: Future<Output = Result<T, E>>,
+    FutureFn: FnMut() -> Fut,
+{
+    fn retry_default(self) -> Retry<Box<dyn Backoff>, T, E, Fut, FutureFn> {
+        Retry::new(self, crate::default_backoff().build())
+    }
+}
+
 /// Struct generated by [`Retryable`].
+///
+/// Polling it doesn't allocate, neither for a successful first attempt nor for every retry,
+/// unless the backoff, the sleeper or the functions set on it do. Only the options keeping
+/// data allocate, once when they're set or when the retry ends: `tag`, `clock`, `backoff_by`,
+/// `wrap_err_with_history`, `reduce_errors`, `await_with_report` and the limits set by
+/// `budget_warning`, `failure_budget`, `retry_budget`, `deadline` and `with_test_budget`.
+///
+/// # Size
+///
+/// The retry holds its function, the future of the current attempt or the sleep after it,
+/// the builder of its backoff until it's built, its options and its [`RetryState`], up to
+/// 256 bytes on 64-bit targets, as the options most retries don't set are boxed. A retry of
+/// `ExponentialBuilder` sleeping with tokio takes up to 512 bytes plus its future, asserted by
+/// the tests. [`core::mem::size_of_val`] measures a retry in its instantiation. Where
+/// thousands are in flight, keep retried futures small by boxing what they hold across
+/// awaits, box the retry with `Box::pin` so a task only holds a pointer, or [`erase`] it
+/// to share its loop.
+///
+/// # Unpin
+///
+/// The retry is [`Unpin`] as long as the futures of its attempts and its sleeps are, whatever
+/// its other parts, so it can be stored in a collection polling its futures in place or
+/// polled with `Pin::new` without pinning it first. Otherwise, `Box::pin` it, or [`erase`] it
+/// into a [`DynRetry`], which is always `Unpin`.
+///
+/// [`erase`]: Retry::erase
 pub struct Retry<
     B: Backoff,
     T,
@@ -71,16 +169,70 @@ pub struct Retry<
     SF: MaybeSleeper = DefaultSleeper,
     RF = fn(&E) -> bool,
     NF = fn(&E, Duration),
+    EC = LastError,
+    ME = Unmapped,
+    HK = NoHooks,
 > {
     backoff: B,
     retryable: RF,
     notify: NF,
+    errors: EC,
+    map_err: ME,
+    hooks: HK,
+    retry_state: RetryState,
     future_fn: FutureFn,
     sleep_fn: SF,
 
     state: State<T, E, Fut, SF::Sleep>,
 }
 
+impl<B, T, E, Fut, FutureFn, SF, RF, NF, EC, ME, HK>
+    Retry<B, T, E, Fut, FutureFn, SF, RF, NF, EC, ME, HK>
+where
+    B: Backoff,
+    Fut: Future<Output = Result<T, E>>,
+    FutureFn: FnMut() -> Fut,
+    SF: MaybeSleeper,
+{
+    /// Returns the index of the current attempt, starting from 1 for the first attempt, or 0
+    /// before the retry starts.
+    pub fn attempt(&self) -> usize {
+        self.retry_state.attempt()
+    }
+
+    /// Returns the time elapsed since the first attempt started.
+    pub fn elapsed(&self) -> Duration {
+        self.retry_state.elapsed()
+    }
+
+    /// Capture the progress of the retry and the state of its backoff, to be restored by
+    /// `resume_from`.
+    pub fn snapshot(&self) -> RetrySnapshot<B>
+    where
+        B: Clone,
+    {
+        RetrySnapshot::new(self.retry_state.attempt(), self.backoff.clone())
+            .with_elapsed(self.retry_state.elapsed())
+    }
+}
+
+impl<B, T, E, Fut, FutureFn, SF, RF, NF, EC, ME, HK> fmt::Debug
+    for Retry<B, T, E, Fut, FutureFn, SF, RF, NF, EC, ME, HK>
+where
+    B: Backoff,
+    Fut: Future<Output = Result<T, E>>,
+    FutureFn: FnMut() -> Fut,
+    SF: MaybeSleeper,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Retry")
+            .field("backoff", &any::type_name::<B>())
+            .field("attempt", &self.retry_state.attempt())
+            .field("next_delay", &self.retry_state.next_delay())
+            .finish_non_exhaustive()
+    }
+}
+
 impl<B, T, E, Fut, FutureFn> Retry<B, T, E, Fut, FutureFn>
 where
     B: Backoff,
@@ -93,6 +245,10 @@ where
             backoff,
             retryable: |_: &E| true,
             notify: |_: &E, _: Duration| {},
+            errors: LastError,
+            map_err: Unmapped,
+            hooks: NoHooks,
+            retry_state: RetryState::new(),
             future_fn,
             sleep_fn: DefaultSleeper::default(),
             state: State::Idle,
@@ -100,14 +256,20 @@ where
     }
 }
 
-impl<B, T, E, Fut, FutureFn, SF, RF, NF> Retry<B, T, E, Fut, FutureFn, SF, RF, NF>
+// The builders return the retry with its parameters spelled out, one per option.
+#[allow(clippy::type_complexity)]
+impl<B, T, E, Fut, FutureFn, SF, RF, NF, EC, ME, HK>
+    Retry<B, T, E, Fut, FutureFn, SF, RF, NF, EC, ME, HK>
 where
     B: Backoff,
     Fut: Future<Output = Result<T, E>>,
     FutureFn: FnMut() -> Fut,
     SF: MaybeSleeper,
-    RF: FnMut(&E) -> bool,
-    NF: FnMut(&E, Duration),
+    RF: ErrorClassifier<ME::Error>,
+    NF: Notify<ME::Error>,
+    HK: Hooks<T, ME::Error>,
+    ME: MapError<E>,
+    EC: ErrorCollector<ME::Error>,
 {
     /// Set the sleeper for retrying.
     ///
@@ -139,108 +301,1037 @@ where
     ///     Ok(())
     /// }
     /// ```
-    pub fn sleep<SN: Sleeper>(self, sleep_fn: SN) -> Retry<B, T, E, Fut, FutureFn, SN, RF, NF> {
+    pub fn sleep<SN: Sleeper>(
+        self,
+        sleep_fn: SN,
+    ) -> Retry<B, T, E, Fut, FutureFn, SN, RF, NF, EC, ME, HK> {
+        Retry {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+            state: State::Idle,
+        }
+    }
+    /// Set the function mapping the errors of the retried function before they are passed to
+    /// the conditions and notify.
+    ///
+    /// This allows reusing the same conditions for functions returning differently wrapped
+    /// errors. It resets the conditions, notify and error handling set before, so it must be
+    /// called first.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::io;
+    ///
+    /// use backon::ExponentialBuilder;
+    /// use backon::Retryable;
+    ///
+    /// #[derive(Debug)]
+    /// enum Error {
+    ///     Timeout,
+    ///     Other(io::Error),
+    /// }
+    ///
+    /// // Shared by all call sites.
+    /// fn is_timeout(e: &Error) -> bool {
+    ///     matches!(e, Error::Timeout)
+    /// }
+    ///
+    /// async fn fetch() -> Result<String, io::Error> {
+    ///     Err(io::ErrorKind::TimedOut.into())
+    /// }
+    ///
+    /// #[tokio::main(flavor = "current_thread")]
+    /// async fn main() -> Result<(), Error> {
+    ///     let content = fetch
+    ///         .retry(ExponentialBuilder::default())
+    ///         .map_err_before(|e| match e.kind() {
+    ///             io::ErrorKind::TimedOut => Error::Timeout,
+    ///             _ => Error::Other(e),
+    ///         })
+    ///         .when(is_timeout)
+    ///         .await?;
+    ///     println!("fetch succeeded: {}", content);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn map_err_before<E2, MN: FnMut(E) -> E2>(
+        self,
+        map_err: MN,
+    ) -> Retry<
+        B,
+        T,
+        E,
+        Fut,
+        FutureFn,
+        SF,
+        fn(&E2) -> bool,
+        fn(&E2, Duration),
+        LastError,
+        MapErrBefore<MN>,
+    > {
+        Retry {
+            backoff: self.backoff,
+            retryable: |_: &E2| true,
+            notify: |_: &E2, _: Duration| {},
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            errors: LastError,
+            retry_state: self.retry_state,
+            map_err: MapErrBefore(map_err),
+            hooks: NoHooks,
+            state: self.state,
+        }
+    }
+
+    /// Set the conditions for retrying.
+    ///
+    /// If not specified, all errors are considered retryable.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use anyhow::Result;
+    /// use backon::ExponentialBuilder;
+    /// use backon::Retryable;
+    ///
+    /// async fn fetch() -> Result<String> {
+    ///     Ok(reqwest::get("https://www.rust-lang.org")
+    ///         .await?
+    ///         .text()
+    ///         .await?)
+    /// }
+    ///
+    /// #[tokio::main(flavor = "current_thread")]
+    /// async fn main() -> Result<()> {
+    ///     let content = fetch
+    ///         .retry(ExponentialBuilder::default())
+    ///         .when(|e| e.to_string() == "EOF")
+    ///         .await?;
+    ///     println!("fetch succeeded: {}", content);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn when<RN: FnMut(&ME::Error) -> bool>(
+        self,
+        retryable: RN,
+    ) -> Retry<B, T, E, Fut, FutureFn, SF, RN, NF, EC, ME, HK> {
+        Retry {
+            backoff: self.backoff,
+            retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+            state: self.state,
+        }
+    }
+
+    /// Set the conditions for retrying with access to the [`RetryState`].
+    ///
+    /// The state tells the index of the failed attempt and the delay slept before it, the
+    /// backoff only proposing the next delay once the error is retried. This replaces the
+    /// conditions set by `when`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use anyhow::Result;
+    /// use backon::ExponentialBuilder;
+    /// use backon::Retryable;
+    ///
+    /// async fn write() -> Result<()> {
+    ///     Ok(())
+    /// }
+    ///
+    /// #[tokio::main(flavor = "current_thread")]
+    /// async fn main() -> Result<()> {
+    ///     write
+    ///         .retry(ExponentialBuilder::default())
+    ///         // The first attempt may have been applied already.
+    ///         .when_with_state(|e, state| state.attempt() == 1 && e.to_string() == "EOF")
+    ///         .await?;
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn when_with_state<RN: FnMut(&ME::Error, &RetryState) -> bool>(
+        self,
+        retryable: RN,
+    ) -> Retry<B, T, E, Fut, FutureFn, SF, WhenWithState<RN>, NF, EC, ME, HK> {
+        Retry {
+            backoff: self.backoff,
+            retryable: WhenWithState(retryable),
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+            state: self.state,
+        }
+    }
+
+    /// Set the conditions for retrying with the error passed by value.
+    ///
+    /// The function returns [`ControlFlow::Continue`] with the error to retry, or
+    /// [`ControlFlow::Break`] with the error to end the retry with. So errors can be enriched
+    /// on every attempt, for example with the attempt index from [`RetryState`]. This replaces
+    /// the conditions set by `when`.
+    pub fn classify<RN: FnMut(ME::Error, &RetryState) -> ControlFlow<ME::Error, ME::Error>>(
+        self,
+        retryable: RN,
+    ) -> Retry<B, T, E, Fut, FutureFn, SF, Classify<RN>, NF, EC, ME, HK> {
+        Retry {
+            backoff: self.backoff,
+            retryable: Classify(retryable),
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+            state: self.state,
+        }
+    }
+
+    /// Set the conditions for retrying by the error's [`RetryableError`] implementation.
+    ///
+    /// The delay suggested by [`RetryableError::retry_after`] is used instead of the backoff's
+    /// delay if present. This replaces the conditions set by `when`.
+    pub fn when_retryable(
+        self,
+    ) -> Retry<B, T, E, Fut, FutureFn, SF, WhenRetryableError, NF, EC, ME, HK>
+    where
+        ME::Error: RetryableError,
+    {
+        Retry {
+            backoff: self.backoff,
+            retryable: WhenRetryableError,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+            state: self.state,
+        }
+    }
+
+    /// Set the function choosing the backoff of every error, see [`BackoffChoice`].
+    ///
+    /// Errors of the class returned by the function are retried with its own backoff,
+    /// independently of the other classes, and those returning [`BackoffChoice::Default`] with
+    /// the backoff of the retry. It applies to the conditions set before, so it must be called
+    /// after `when` and the like.
+    pub fn backoff_by<BB: BackoffBuilder, BN: FnMut(&ME::Error) -> BackoffChoice<BB>>(
+        self,
+        choose: BN,
+    ) -> Retry<B, T, E, Fut, FutureFn, SF, BackoffBy<RF, BN, BB>, NF, EC, ME, HK> {
+        Retry {
+            backoff: self.backoff,
+            retryable: BackoffBy::new(self.retryable, choose),
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+            state: self.state,
+        }
+    }
+
+    /// Set the conditions for retrying [`anyhow::Error`]s caused by `X`.
+    ///
+    /// The whole chain of the error is inspected, so errors with context attached are retried
+    /// as well. This replaces the conditions set by `when`.
+    #[cfg(feature = "anyhow")]
+    pub fn when_downcast<X: core::error::Error + 'static>(
+        self,
+    ) -> Retry<B, T, E, Fut, FutureFn, SF, WhenDowncast<X>, NF, EC, ME, HK>
+    where
+        ME: MapError<E, Error = anyhow::Error>,
+    {
+        Retry {
+            backoff: self.backoff,
+            retryable: WhenDowncast::new(),
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+            state: self.state,
+        }
+    }
+
+    /// Set the function reducing the errors of all attempts into the returned one.
+    ///
+    /// The function is called with the reduced error so far and the error of the next attempt.
+    /// If not specified, the error of the last attempt is returned.
+    ///
+    /// This replaces the previous reducer, and must be set before `wrap_err`.
+    pub fn reduce_errors<RN: FnMut(ME::Error, ME::Error) -> ME::Error>(
+        self,
+        reduce: RN,
+    ) -> Retry<B, T, E, Fut, FutureFn, SF, RF, NF, ReduceErrors<ME::Error, RN>, ME, HK>
+    where
+        EC: ErrorCollector<ME::Error, Error = ME::Error>,
+    {
+        Retry {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            errors: ReduceErrors::new(reduce),
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+            state: self.state,
+        }
+    }
+
+    /// Set the policy selecting the error returned when the retry ends unsuccessfully.
+    ///
+    /// If not specified, the error of the last attempt is returned. See [`FinalError`] for
+    /// the policies.
+    ///
+    /// This replaces the previous reducer, and must be set before `wrap_err`.
+    pub fn final_error(
+        self,
+        policy: FinalError<ME::Error>,
+    ) -> Retry<B, T, E, Fut, FutureFn, SF, RF, NF, SelectError<ME::Error>, ME, HK>
+    where
+        EC: ErrorCollector<ME::Error, Error = ME::Error>,
+    {
+        Retry {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            errors: SelectError::new(policy),
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+            state: self.state,
+        }
+    }
+
+    /// End the retry at errors wrapped by [`non_retryable`](crate::non_retryable), and unwrap
+    /// the returned [`Permanent`](crate::Permanent) error.
+    ///
+    /// Permanent errors end the retry regardless of the conditions set by `when`.
+    pub fn unwrap_permanent(
+        self,
+    ) -> Retry<B, T, E, Fut, FutureFn, SF, RF, NF, UnwrapPermanent<EC>, ME, HK>
+    where
+        UnwrapPermanent<EC>: ErrorCollector<ME::Error>,
+    {
+        Retry {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            errors: UnwrapPermanent(self.errors),
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+            state: self.state,
+        }
+    }
+
+    /// Wrap the error that ends the retry into a [`RetryError`](crate::RetryError).
+    ///
+    /// The wrapper records the number of attempts made and keeps the original error as its
+    /// [`source`](core::error::Error::source).
+    pub fn wrap_err(
+        self,
+    ) -> Retry<B, T, E, Fut, FutureFn, SF, RF, NF, WrapRetryError<EC, EC::Error>, ME, HK> {
+        Retry {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            errors: WrapRetryError::new(self.errors, 0),
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+            state: self.state,
+        }
+    }
+
+    /// Wrap the error that ends the retry into a [`RetryError`](crate::RetryError), keeping
+    /// the history of up to `cap` most recent failed attempts.
+    ///
+    /// Every entry of the history records the error, the delay slept after it and when it
+    /// happened, so the failure timeline can be reported once the retry ends.
+    pub fn wrap_err_with_history(
+        self,
+        cap: usize,
+    ) -> Retry<B, T, E, Fut, FutureFn, SF, RF, NF, WrapRetryError<EC, EC::Error>, ME, HK> {
+        Retry {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            errors: WrapRetryError::new(self.errors, cap),
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+            state: self.state,
+        }
+    }
+
+    /// Set to notify for all retry attempts.
+    ///
+    /// When a retry happens, the input function will be invoked with the error and the sleep duration before pausing.
+    ///
+    /// If not specified, this operation does nothing.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use core::time::Duration;
+    ///
+    /// use anyhow::Result;
+    /// use backon::ExponentialBuilder;
+    /// use backon::Retryable;
+    ///
+    /// async fn fetch() -> Result<String> {
+    ///     Ok(reqwest::get("https://www.rust-lang.org")
+    ///         .await?
+    ///         .text()
+    ///         .await?)
+    /// }
+    ///
+    /// #[tokio::main(flavor = "current_thread")]
+    /// async fn main() -> Result<()> {
+    ///     let content = fetch
+    ///         .retry(ExponentialBuilder::default())
+    ///         .notify(|err: &anyhow::Error, dur: Duration| {
+    ///             println!("retrying error {:?} with sleeping {:?}", err, dur);
+    ///         })
+    ///         .await?;
+    ///     println!("fetch succeeded: {}", content);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn notify<NN: FnMut(&ME::Error, Duration)>(
+        self,
+        notify: NN,
+    ) -> Retry<B, T, E, Fut, FutureFn, SF, RF, NN, EC, ME, HK> {
+        Retry {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify,
+            sleep_fn: self.sleep_fn,
+            future_fn: self.future_fn,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+            state: self.state,
+        }
+    }
+
+    /// Set to notify for all retry attempts with access to the [`RetryState`].
+    ///
+    /// The state tells the session ID of the retry and the index of the failed attempt, so
+    /// the notifications can be correlated with the logs of the retried operation. This
+    /// replaces the function set by `notify`.
+    pub fn notify_with_state<NN: FnMut(&ME::Error, Duration, &RetryState)>(
+        self,
+        notify: NN,
+    ) -> Retry<B, T, E, Fut, FutureFn, SF, RF, NotifyWithState<NN>, EC, ME, HK> {
+        Retry {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: NotifyWithState(notify),
+            sleep_fn: self.sleep_fn,
+            future_fn: self.future_fn,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+            state: self.state,
+        }
+    }
+
+    /// Set the function called once before the first attempt starts.
+    ///
+    /// It's called in addition to the functions set before, so per-call setup like starting
+    /// a timer or logging the operation can live in the retry.
+    pub fn on_start<ON: FnMut(&RetryState)>(
+        self,
+        f: ON,
+    ) -> Retry<B, T, E, Fut, FutureFn, SF, RF, NF, EC, ME, Chain<HK, OnStart<ON>>> {
+        Retry {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, OnStart(f)),
+            state: self.state,
+        }
+    }
+
+    /// Set the function called with every error that will be retried and the delay before
+    /// the next attempt.
+    ///
+    /// It's called after `notify`, and in addition to the functions set before.
+    pub fn on_transient_error<TN: FnMut(&ME::Error, Duration)>(
+        self,
+        f: TN,
+    ) -> Retry<B, T, E, Fut, FutureFn, SF, RF, NF, EC, ME, Chain<HK, OnTransient<TN>>> {
+        Retry {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, OnTransient(f)),
+            state: self.state,
+        }
+    }
+
+    /// Set the function called with the error that ends the retry unsuccessfully and the
+    /// reason why.
+    ///
+    /// Both non-retryable errors and errors left when the backoff is exhausted end the retry.
+    /// It's called in addition to the functions set before.
+    pub fn on_fatal_error<FN: FnMut(&ME::Error, GiveUpReason)>(
+        self,
+        f: FN,
+    ) -> Retry<B, T, E, Fut, FutureFn, SF, RF, NF, EC, ME, Chain<HK, OnFatal<FN>>> {
+        Retry {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, OnFatal(f)),
+            state: self.state,
+        }
+    }
+
+    /// Set the function called when the retry gives up because the backoff is exhausted,
+    /// with the last error and the [`ScheduleSummary`] of the delays planned and slept.
+    ///
+    /// Unlike `on_fatal_error` it's not called for errors that are not retryable, so an
+    /// exhausted policy can be told apart from a fatal error.
+    pub fn on_backoff_exhausted<BN: FnMut(&ME::Error, &ScheduleSummary)>(
+        self,
+        f: BN,
+    ) -> Retry<B, T, E, Fut, FutureFn, SF, RF, NF, EC, ME, Chain<HK, OnBackoffExhausted<BN>>> {
+        Retry {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, OnBackoffExhausted::new(f)),
+            state: self.state,
+        }
+    }
+
+    /// Set the function recovering from the error that ends the retry unsuccessfully.
+    ///
+    /// Both non-retryable errors and errors left when the backoff is exhausted are passed to
+    /// it, and the value it returns is returned as the result of the retry instead. Errors
+    /// that are not recovered are passed on to `on_fatal_error` and returned as usual.
+    ///
+    /// Recovered retries still gave up: their metrics, events and traces record them as
+    /// giving up, only `on_fatal_error` isn't called.
+    pub fn recover<RN: FnMut(&ME::Error) -> Option<T>>(
+        self,
+        f: RN,
+    ) -> Retry<B, T, E, Fut, FutureFn, SF, RF, NF, EC, ME, Chain<HK, Recover<RN>>> {
+        Retry {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, Recover(f)),
+            state: self.state,
+        }
+    }
+
+    /// Set the [`RetryMetrics`] observing the progress of the retry.
+    ///
+    /// It's called in addition to the metrics set before.
+    pub fn metrics<MN: RetryMetrics>(
+        self,
+        metrics: MN,
+    ) -> Retry<B, T, E, Fut, FutureFn, SF, RF, NF, EC, ME, Chain<HK, Metrics<MN>>> {
+        Retry {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, Metrics(metrics)),
+            state: self.state,
+        }
+    }
+
+    /// Set the function observing every step of the retry as a [`RetryEvent`].
+    ///
+    /// It's called in addition to the other hooks and observers set before, so a single
+    /// function can follow the whole lifecycle of the retry. Its events are no longer delivered
+    /// to the observer set by `set_global_observer`.
+    pub fn observe<FN: FnMut(RetryEvent<'_, ME::Error>)>(
+        mut self,
+        f: FN,
+    ) -> Retry<B, T, E, Fut, FutureFn, SF, RF, NF, EC, ME, Chain<HK, Observe<FN>>> {
+        self.retry_state.set_observed();
+        Retry {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, Observe(f)),
+            state: self.state,
+        }
+    }
+
+    /// Record the retry in a `tracing` span named `retry`, with the `operation` and
+    /// `session_id` fields set.
+    ///
+    /// The retried function runs inside the span, and every sleep inside a child span named
+    /// `sleep` with the `operation`, `attempt` and `delay` fields, so subscribers show what a
+    /// waiting retry backs off on. Failed attempts are emitted as `WARN` events, sleeps,
+    /// resumes and successes as `DEBUG` events and giving up as an `ERROR` event, with the
+    /// attempt number, delay, error and reason as fields.
+    #[cfg(feature = "tracing")]
+    pub fn trace(
+        self,
+        operation: &'static str,
+    ) -> Retry<B, T, E, Fut, FutureFn, SF, RF, NF, EC, ME, Chain<HK, Trace>>
+    where
+        ME::Error: fmt::Display,
+    {
+        Retry {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, Trace::new(operation)),
+            state: self.state,
+        }
+    }
+
+    /// Record the retry on the current OpenTelemetry span and in OpenTelemetry metrics, with
+    /// the `operation.name` attribute set to `operation`.
+    ///
+    /// Every failed attempt is added to the span active when this is called as a
+    /// `retry.attempt_failed` event and giving up as a `retry.gave_up` event, carrying the
+    /// `retry.attempt`, `retry.delay`, `error.type` and `exception.message` attributes. The
+    /// `backon.retry.attempts`, `backon.retry.retries` and `backon.retry.giveups` counters and
+    /// the `backon.retry.sleep.duration` histogram are reported to the global meter provider.
+    #[cfg(feature = "opentelemetry")]
+    pub fn opentelemetry(
+        self,
+        operation: &'static str,
+    ) -> Retry<B, T, E, Fut, FutureFn, SF, RF, NF, EC, ME, Chain<HK, OpenTelemetry>>
+    where
+        ME::Error: fmt::Display,
+    {
+        Retry {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, OpenTelemetry::new(operation)),
+            state: self.state,
+        }
+    }
+
+    /// Log the errors that will be retried as warnings and the error that ends the retry as
+    /// an error with `defmt`, for embedded targets logging over RTT.
+    #[cfg(feature = "defmt")]
+    pub fn defmt(self) -> Retry<B, T, E, Fut, FutureFn, SF, RF, NF, EC, ME, Chain<HK, Defmt>>
+    where
+        ME::Error: defmt::Format,
+    {
+        Retry {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, Defmt),
+            state: self.state,
+        }
+    }
+
+    /// Record every error that will be retried and the error that ends the retry as Sentry
+    /// breadcrumbs in the `retry` category, with the attempt, the delay and the error message.
+    ///
+    /// When the retry gives up, the number of attempts is set as the `retry.attempts` tag of
+    /// the current scope, so the error reported afterwards shows the history of the retry.
+    #[cfg(feature = "sentry")]
+    pub fn sentry(self) -> Retry<B, T, E, Fut, FutureFn, SF, RF, NF, EC, ME, Chain<HK, Sentry>>
+    where
+        ME::Error: fmt::Display,
+    {
+        Retry {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, Sentry),
+            state: self.state,
+        }
+    }
+
+    /// Log the errors that will be retried at `retry_level` and the error that ends the
+    /// retry at `give_up_level` with the `log` crate, under the `backon` target.
+    #[cfg(feature = "logging")]
+    pub fn log(
+        self,
+        retry_level: log::Level,
+        give_up_level: log::Level,
+    ) -> Retry<B, T, E, Fut, FutureFn, SF, RF, NF, EC, ME, Chain<HK, Log>>
+    where
+        ME::Error: fmt::Display,
+    {
+        Retry {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, Log::new(retry_level, give_up_level)),
+            state: self.state,
+        }
+    }
+
+    /// Log the errors that will be retried as warnings and the error that ends the retry as
+    /// an error to `logger`, with the attempt number, delay, error and reason as key-value
+    /// pairs.
+    #[cfg(feature = "slog")]
+    pub fn slog(
+        self,
+        logger: slog::Logger,
+    ) -> Retry<B, T, E, Fut, FutureFn, SF, RF, NF, EC, ME, Chain<HK, Slog>>
+    where
+        ME::Error: fmt::Display,
+    {
         Retry {
             backoff: self.backoff,
             retryable: self.retryable,
             notify: self.notify,
             future_fn: self.future_fn,
-            sleep_fn,
-            state: State::Idle,
+            sleep_fn: self.sleep_fn,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, Slog::new(logger)),
+            state: self.state,
         }
     }
 
-    /// Set the conditions for retrying.
-    ///
-    /// If not specified, all errors are considered retryable.
+    /// Set whether the retried operation is idempotent, `true` by default.
     ///
-    /// # Examples
-    ///
-    /// ```no_run
-    /// use anyhow::Result;
-    /// use backon::ExponentialBuilder;
-    /// use backon::Retryable;
-    ///
-    /// async fn fetch() -> Result<String> {
-    ///     Ok(reqwest::get("https://www.rust-lang.org")
-    ///         .await?
-    ///         .text()
-    ///         .await?)
-    /// }
+    /// Operations that are not idempotent, like writes, are only retried at errors telling
+    /// the operation had no effect by [`RetryableError::is_safe_to_retry`], so this works
+    /// together with `when_retryable`. Other errors end the retry with
+    /// [`GiveUpReason::Ambiguous`] instead of risking applying the operation twice.
+    pub fn idempotent(mut self, idempotent: bool) -> Self {
+        self.retry_state.set_idempotent(idempotent);
+        self
+    }
+
+    /// Set the name of the retried operation, like `s3_put_object`.
     ///
-    /// #[tokio::main(flavor = "current_thread")]
-    /// async fn main() -> Result<()> {
-    ///     let content = fetch
-    ///         .retry(ExponentialBuilder::default())
-    ///         .when(|e| e.to_string() == "EOF")
-    ///         .await?;
-    ///     println!("fetch succeeded: {}", content);
+    /// It's included in the events passed to `observe`, the [`RetryReport`](crate::RetryReport)
+    /// and the output of the `tracing` and `opentelemetry` integrations, so they can be
+    /// aggregated per operation.
+    pub fn name(mut self, name: &'static str) -> Self {
+        self.retry_state.set_name(name);
+        self
+    }
+
+    /// Add a key-value tag describing the retried operation, included wherever the name set
+    /// by `name` is.
+    pub fn tag(mut self, key: &'static str, value: impl Into<Cow<'static, str>>) -> Self {
+        self.retry_state.add_tag(key, value.into());
+        self
+    }
+
+    /// Continue a retry from a [`RetrySnapshot`] taken by `snapshot`, like one persisted
+    /// before the process restarted.
     ///
-    ///     Ok(())
-    /// }
-    /// ```
-    pub fn when<RN: FnMut(&E) -> bool>(
-        self,
-        retryable: RN,
-    ) -> Retry<B, T, E, Fut, FutureFn, SF, RN, NF> {
+    /// The backoff of the snapshot replaces the one passed to `retry`, so the delays pick up
+    /// where they were instead of starting over, and the attempts and elapsed time count from
+    /// the snapshot. The next attempt starts right away.
+    pub fn resume_from<S: Backoff>(
+        mut self,
+        snapshot: RetrySnapshot<S>,
+    ) -> Retry<S, T, E, Fut, FutureFn, SF, RF, NF, EC, ME, HK> {
+        self.retry_state.resume(&snapshot);
         Retry {
-            backoff: self.backoff,
-            retryable,
+            backoff: snapshot.into_backoff(),
+            retryable: self.retryable,
             notify: self.notify,
             future_fn: self.future_fn,
             sleep_fn: self.sleep_fn,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
             state: self.state,
         }
     }
 
-    /// Set to notify for all retry attempts.
+    /// Set the [`Clock`] measuring the elapsed time and the latency of the attempts, reported
+    /// by [`RetryState`], the events passed to `observe` and the report.
     ///
-    /// When a retry happens, the input function will be invoked with the error and the sleep duration before pausing.
+    /// It defaults to [`StdClock`](crate::StdClock) with the `std` feature outside of
+    /// `wasm32`. Without it, the retry isn't timed unless a clock is set.
+    pub fn clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.retry_state.set_clock(SharedClock::new(clock));
+        self
+    }
+
+    /// Seed the random numbers of the retry, the jitter of its backoff and its ID, instead of
+    /// drawing them from the thread's RNG.
     ///
-    /// If not specified, this operation does nothing.
+    /// Together with a [`Clock`] set by `clock`, the delays, the events and the report of the
+    /// retry are the same on every run, so tests can assert on them exactly.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.retry_state.set_seed(seed);
+        self
+    }
+
+    /// Panic if the retry makes more than `max_attempts` attempts or sleeps more than
+    /// `max_total_sleep` in total, so tests assert a code path never retries more than it
+    /// should, like "this call is retried at most twice".
     ///
-    /// # Examples
+    /// The panic happens before the retry over the budget sleeps. Only available with the
+    /// `test-util` feature.
+    #[cfg(feature = "test-util")]
+    pub fn with_test_budget(mut self, max_attempts: usize, max_total_sleep: Duration) -> Self {
+        self.retry_state
+            .set_test_budget(max_attempts, max_total_sleep);
+        self
+    }
+
+    /// Set the ID identifying this retry in logs and events, returned by
+    /// [`RetryState::session_id`].
     ///
-    /// ```no_run
-    /// use core::time::Duration;
+    /// A random ID is generated when the retry is built if it's not set, the same for all its
+    /// attempts. The retried operation reads it with `current_attempt`, so its logs can be
+    /// correlated with the events of the retry.
+    pub fn session_id(mut self, id: u64) -> Self {
+        self.retry_state.set_session_id(id);
+        self
+    }
+
+    /// Set the [`BudgetWarning`] raised to the function set by `observe` once a fraction of
+    /// the attempts or time of the retry is consumed.
+    pub fn budget_warning(mut self, warning: BudgetWarning) -> Self {
+        self.retry_state.set_budget_warning(warning);
+        self
+    }
+
+    /// Set the most time the retry sleeps in total between attempts, combined with the
+    /// limits of the backoff like its maximum number of retries.
     ///
-    /// use anyhow::Result;
-    /// use backon::ExponentialBuilder;
-    /// use backon::Retryable;
+    /// The retry gives up with [`GiveUpReason::MaxTotalSleepReached`] instead of sleeping past
+    /// it, telling it apart from [`GiveUpReason::BackoffExhausted`] if the backoff runs out
+    /// first.
+    pub fn max_total_sleep(mut self, max: Duration) -> Self {
+        self.retry_state.set_max_total_sleep(max);
+        self
+    }
+
+    /// Attach the [`FailureBudget`] shared with other retries.
     ///
-    /// async fn fetch() -> Result<String> {
-    ///     Ok(reqwest::get("https://www.rust-lang.org")
-    ///         .await?
-    ///         .text()
-    ///         .await?)
-    /// }
+    /// Every attempt failed with a retryable error is recorded in the budget, and the retry
+    /// ends at the next one while the budget is exhausted.
+    #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+    pub fn failure_budget(mut self, budget: FailureBudget) -> Self {
+        self.retry_state.set_failure_budget(budget);
+        self
+    }
+
+    /// Attach the [`RetryBudget`] shared with other retries.
     ///
-    /// #[tokio::main(flavor = "current_thread")]
-    /// async fn main() -> Result<()> {
-    ///     let content = fetch
-    ///         .retry(ExponentialBuilder::default())
-    ///         .notify(|err: &anyhow::Error, dur: Duration| {
-    ///             println!("retrying error {:?} with sleeping {:?}", err, dur);
-    ///         })
-    ///         .await?;
-    ///     println!("fetch succeeded: {}", content);
+    /// Every successful attempt deposits in the budget, and every retry withdraws from it,
+    /// the retry ending with [`GiveUpReason::BudgetExhausted`] once it's empty.
+    #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+    pub fn retry_budget(mut self, budget: RetryBudget) -> Self {
+        self.retry_state.set_retry_budget(budget);
+        self
+    }
+
+    /// Set the [`Deadline`] the retry must be done by, inherited from the context of the call.
     ///
-    ///     Ok(())
-    /// }
-    /// ```
-    pub fn notify<NN: FnMut(&E, Duration)>(
-        self,
-        notify: NN,
-    ) -> Retry<B, T, E, Fut, FutureFn, SF, RF, NN> {
+    /// The delays are shortened for every retry to start with the least time an attempt
+    /// needs left, the retry ending with [`GiveUpReason::DeadlineExceeded`] once it can't.
+    /// Attempts running out of time are dropped, failing with [`AttemptTimedOut`].
+    pub fn deadline(
+        mut self,
+        deadline: Deadline,
+    ) -> Retry<B, T, E, Fut, FutureFn, SF, RF, NF, EC, ME, Chain<HK, AttemptTimeout>>
+    where
+        ME::Error: From<AttemptTimedOut>,
+    {
+        self.retry_state.set_deadline(deadline);
         Retry {
             backoff: self.backoff,
             retryable: self.retryable,
-            notify,
-            sleep_fn: self.sleep_fn,
+            notify: self.notify,
             future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, AttemptTimeout),
             state: self.state,
         }
     }
+
+    /// Return a future yielding the [`RetryReport`](crate::RetryReport) of the retry alongside its output.
+    pub fn await_with_report(mut self) -> WithReport<Self> {
+        self.retry_state.start_report();
+        WithReport::new(self)
+    }
+}
+
+impl<B, T, E, Fut, FutureFn, SF, RF, NF, EC, ME, HK>
+    Retry<B, T, E, Fut, FutureFn, SF, RF, NF, EC, ME, HK>
+where
+    B: Backoff,
+    Fut: Future<Output = Result<T, E>>,
+    FutureFn: FnMut() -> Fut,
+    SF: Sleeper,
+    RF: ErrorClassifier<ME::Error>,
+    NF: Notify<ME::Error>,
+    HK: Hooks<T, ME::Error>,
+    ME: MapError<E>,
+    EC: ErrorCollector<ME::Error>,
+{
+    /// Erase the types of the retry, returning a [`DynRetry`] retrying the same way with a
+    /// retry loop compiled once for every `T` and error, instead of once for every retry.
+    ///
+    /// So binaries retrying in many places don't compile the retry loop for each of them.
+    /// Everything set on the retry must be `Send`.
+    pub fn erase<'a>(self) -> DynRetry<'a, T, ME::Error, EC::Error>
+    where
+        T: 'a,
+        E: 'a,
+        B: 'a,
+        Fut: Send + 'a,
+        FutureFn: Send + 'a,
+        SF: Send,
+        SF::Sleep: Send + 'a,
+        RF: Send + 'a,
+        NF: Send + 'a,
+        EC: Send + 'a,
+        ME: Send + 'a,
+        HK: Send + 'a,
+    {
+        let parts = Parts {
+            backoff: Box::new(self.backoff),
+            retryable: Box::new(self.retryable),
+            notify: Box::new(self.notify),
+            errors: Box::new(self.errors),
+            hooks: Box::new(self.hooks),
+            retry_state: self.retry_state,
+        };
+        DynRetry::new(parts, self.future_fn, self.map_err, self.sleep_fn)
+    }
+}
+
+/// A stub trait allowing both `FnMut(&E, Duration)` and `FnMut(&E, Duration, &RetryState)` to
+/// be used as the notify function of retries.
+#[doc(hidden)]
+pub trait Notify<E> {
+    fn notify(&mut self, err: &E, dur: Duration, state: &RetryState);
+}
+
+/// All `FnMut(&E, Duration)` implements `Notify` by ignoring the state.
+impl<E, F: FnMut(&E, Duration)> Notify<E> for F {
+    fn notify(&mut self, err: &E, dur: Duration, _: &RetryState) {
+        self(err, dur)
+    }
+}
+
+/// Notify function that accepts the retry state, set by `notify_with_state`.
+#[doc(hidden)]
+pub struct NotifyWithState<F>(pub(crate) F);
+
+impl<E, F: FnMut(&E, Duration, &RetryState)> Notify<E> for NotifyWithState<F> {
+    fn notify(&mut self, err: &E, dur: Duration, state: &RetryState) {
+        (self.0)(err, dur, state)
+    }
 }
 
 /// State maintains internal state of retry.
@@ -248,20 +1339,57 @@ where
 enum State<T, E, Fut: Future<Output = Result<T, E>>, SleepFut: Future<Output = ()>> {
     #[default]
     Idle,
-    Polling(Fut),
+    /// Polling the attempt, timed out by the timer if the retry has a deadline, boxed so it
+    /// doesn't take room in every retry.
+    Polling(Fut, Option<Pin<Box<SleepFut>>>),
     Sleeping(SleepFut),
 }
 
-impl<B, T, E, Fut, FutureFn, SF, RF, NF> Future for Retry<B, T, E, Fut, FutureFn, SF, RF, NF>
+// Only the futures of the attempts and the sleeps are pinned by `poll`, the other parts are
+// only ever accessed by `&mut`.
+impl<B, T, E, Fut, FutureFn, SF, RF, NF, EC, ME, HK> Unpin
+    for Retry<B, T, E, Fut, FutureFn, SF, RF, NF, EC, ME, HK>
+where
+    B: Backoff,
+    Fut: Future<Output = Result<T, E>> + Unpin,
+    FutureFn: FnMut() -> Fut,
+    SF: MaybeSleeper,
+    SF::Sleep: Unpin,
+{
+}
+
+impl<B, T, E, Fut, FutureFn, SF, RF, NF, EC, ME, HK> Reported
+    for Retry<B, T, E, Fut, FutureFn, SF, RF, NF, EC, ME, HK>
 where
     B: Backoff,
     Fut: Future<Output = Result<T, E>>,
     FutureFn: FnMut() -> Fut,
     SF: Sleeper,
-    RF: FnMut(&E) -> bool,
-    NF: FnMut(&E, Duration),
+    RF: ErrorClassifier<ME::Error>,
+    NF: Notify<ME::Error>,
+    HK: Hooks<T, ME::Error>,
+    ME: MapError<E>,
+    EC: ErrorCollector<ME::Error>,
 {
-    type Output = Result<T, E>;
+    fn retry_state(&mut self) -> &mut RetryState {
+        &mut self.retry_state
+    }
+}
+
+impl<B, T, E, Fut, FutureFn, SF, RF, NF, EC, ME, HK> Future
+    for Retry<B, T, E, Fut, FutureFn, SF, RF, NF, EC, ME, HK>
+where
+    B: Backoff,
+    Fut: Future<Output = Result<T, E>>,
+    FutureFn: FnMut() -> Fut,
+    SF: Sleeper,
+    RF: ErrorClassifier<ME::Error>,
+    NF: Notify<ME::Error>,
+    HK: Hooks<T, ME::Error>,
+    ME: MapError<E>,
+    EC: ErrorCollector<ME::Error>,
+{
+    type Output = Result<T, EC::Error>;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         // Safety: This is safe because we don't move the `Retry` struct itself,
@@ -273,29 +1401,79 @@ where
         loop {
             match &mut this.state {
                 State::Idle => {
-                    let fut = (this.future_fn)();
-                    this.state = State::Polling(fut);
+                    this.retry_state.start_attempt();
+                    this.hooks.on_attempt(&this.retry_state);
+                    let fut = {
+                        let _current = CurrentAttempt::enter(&this.retry_state);
+                        this.hooks.enter();
+                        let res = (this.future_fn)();
+                        this.hooks.exit();
+                        res
+                    };
+                    let timer = (this.retry_state.attempt_timeout())
+                        .map(|timeout| Box::pin(this.sleep_fn.sleep(timeout)));
+                    this.state = State::Polling(fut, timer);
                     continue;
                 }
-                State::Polling(fut) => {
+                State::Polling(fut, timer) => {
                     // Safety: This is safe because we don't move the `Retry` struct and this fut,
                     // only its internal state.
                     //
                     // We do the exactly same thing like `pin_project` but without depending on it directly.
                     let mut fut = unsafe { Pin::new_unchecked(fut) };
 
-                    match ready!(fut.as_mut().poll(cx)) {
-                        Ok(v) => return Poll::Ready(Ok(v)),
+                    let res = {
+                        let _current = CurrentAttempt::enter(&this.retry_state);
+                        this.hooks.enter();
+                        let res = fut.as_mut().poll(cx);
+                        this.hooks.exit();
+                        res
+                    };
+                    let res = match res {
+                        Poll::Ready(res) => res.map_err(|err| this.map_err.map_err(err)),
+                        Poll::Pending => {
+                            match timer.as_mut().map(|timer| timer.as_mut().poll(cx)) {
+                                Some(Poll::Ready(())) => {
+                                    // The timer is done, so it's never polled again: every
+                                    // attempt arms a new one.
+                                    *timer = None;
+                                    match this.hooks.timed_out() {
+                                        Some(err) => Err(err),
+                                        None => return Poll::Pending,
+                                    }
+                                }
+                                _ => return Poll::Pending,
+                            }
+                        }
+                    };
+                    match res {
+                        Ok(v) => {
+                            this.retry_state.succeed_attempt();
+                            this.hooks.on_success(&v, &this.retry_state);
+                            return Poll::Ready(Ok(v));
+                        }
                         Err(err) => {
                             // If input error is not retryable, return error directly.
-                            if !(this.retryable)(&err) {
-                                return Poll::Ready(Err(err));
-                            }
-                            match this.backoff.next() {
-                                None => return Poll::Ready(Err(err)),
-                                Some(dur) => {
-                                    (this.notify)(&err, dur);
-                                    this.state = State::Sleeping(this.sleep_fn.sleep(dur));
+                            match this.retry_state.retry_delay(
+                                err,
+                                &mut this.backoff,
+                                &mut this.retryable,
+                                &mut this.errors,
+                            ) {
+                                Err((err, reason)) => {
+                                    if let Some(v) = this.hooks.recover(&err) {
+                                        this.hooks.on_recovered(&err, reason, &this.retry_state);
+                                        return Poll::Ready(Ok(v));
+                                    }
+                                    this.hooks.on_fatal(&err, reason, &this.retry_state);
+                                    return Poll::Ready(Err(this.errors.finish(err, reason)));
+                                }
+                                Ok((err, dur)) => {
+                                    this.notify.notify(&err, dur, &this.retry_state);
+                                    this.hooks.on_transient(&err, dur, &this.retry_state);
+                                    this.errors.collect(err, dur, &this.retry_state);
+                                    let sl = this.sleep_fn.sleep(dur);
+                                    this.state = State::Sleeping(sl);
                                     continue;
                                 }
                             }
@@ -309,7 +1487,12 @@ where
                     // We do the exactly same thing like `pin_project` but without depending on it directly.
                     let mut sl = unsafe { Pin::new_unchecked(sl) };
 
-                    ready!(sl.as_mut().poll(cx));
+                    this.hooks.enter_sleep();
+                    let res = sl.as_mut().poll(cx);
+                    this.hooks.exit_sleep();
+                    ready!(res);
+
+                    this.hooks.on_resume(&this.retry_state);
                     this.state = State::Idle;
                     continue;
                 }
@@ -334,8 +1517,36 @@ mod default_sleeper_tests {
     use tokio::test;
 
     use super::*;
+    use crate::ExponentialBackoff;
     use crate::ExponentialBuilder;
 
+    type Ready = core::future::Ready<Result<(), &'static str>>;
+
+    // A retry whose futures are `Unpin` is `Unpin`, even if its other parts aren't.
+    static_assertions::assert_impl_all!(
+        Retry<
+            ExponentialBackoff,
+            core::marker::PhantomPinned,
+            &'static str,
+            core::future::Ready<Result<core::marker::PhantomPinned, &'static str>>,
+            fn() -> core::future::Ready<Result<core::marker::PhantomPinned, &'static str>>,
+            fn(Duration) -> core::future::Ready<()>,
+        >: Unpin
+    );
+    static_assertions::assert_not_impl_any!(
+        Retry<ExponentialBackoff, (), &'static str, Ready, fn() -> Ready>: Unpin
+    );
+    static_assertions::assert_impl_all!(DynRetry<'static, (), &'static str>: Unpin);
+
+    // The sizes documented by `Retry`, for a function returning a ready future retried with
+    // the default backoff and sleeper.
+    #[cfg(target_pointer_width = "64")]
+    static_assertions::const_assert!(size_of::<RetryState>() <= 256);
+    #[cfg(target_pointer_width = "64")]
+    static_assertions::const_assert!(
+        size_of::<Retry<ExponentialBackoff, (), &'static str, Ready, fn() -> Ready>>() <= 512
+    );
+
     async fn always_error() -> anyhow::Result<()> {
         Err(anyhow::anyhow!("test_query meets error"))
     }
@@ -351,6 +1562,23 @@ mod default_sleeper_tests {
         Ok(())
     }
 
+    #[test]
+    async fn test_retry_with_report() -> anyhow::Result<()> {
+        let backoff = ExponentialBuilder::default()
+            .with_min_delay(Duration::from_millis(1))
+            .with_max_times(2);
+        let (result, report) = always_error.retry(backoff).await_with_report().await;
+
+        assert!(result.is_err());
+        assert_eq!(report.attempts(), 3);
+        assert_eq!(
+            report.delays(),
+            [Duration::from_millis(1), Duration::from_millis(2)]
+        );
+        assert_eq!(report.slept(), Duration::from_millis(3));
+        Ok(())
+    }
+
     #[test]
     async fn test_retry_with_not_retryable_error() -> anyhow::Result<()> {
         let error_times = Mutex::new(0);
@@ -401,6 +1629,52 @@ mod default_sleeper_tests {
         Ok(())
     }
 
+    #[derive(Debug)]
+    enum TestError {
+        Throttled,
+        NotFound,
+    }
+
+    impl RetryableError for TestError {
+        fn is_retryable(&self) -> bool {
+            matches!(self, TestError::Throttled)
+        }
+
+        fn retry_after(&self) -> Option<Duration> {
+            Some(Duration::from_millis(2))
+        }
+    }
+
+    #[test]
+    async fn test_retry_when_retryable() -> anyhow::Result<()> {
+        let error_times = Mutex::new(0);
+        let mut sleeps: Vec<Duration> = vec![];
+
+        let f = || async {
+            let mut x = error_times.lock().await;
+            *x += 1;
+            Err::<(), _>(if *x < 3 {
+                TestError::Throttled
+            } else {
+                TestError::NotFound
+            })
+        };
+
+        let backoff = ExponentialBuilder::default().with_min_delay(Duration::from_millis(1));
+        let result = f
+            .retry(backoff)
+            .when_retryable()
+            .notify(|_, dur| sleeps.push(dur))
+            .await;
+
+        assert!(matches!(result, Err(TestError::NotFound)));
+        // `f` stops retrying at the first non-retryable error.
+        assert_eq!(*error_times.lock().await, 3);
+        // The delay suggested by the error replaces the backoff's delay.
+        assert_eq!(sleeps, vec![Duration::from_millis(2); 2]);
+        Ok(())
+    }
+
     #[test]
     async fn test_fn_mut_when_and_notify() -> anyhow::Result<()> {
         let mut calls_retryable: Vec<()> = vec![];
@@ -428,6 +1702,45 @@ mod default_sleeper_tests {
         assert_eq!(calls_notify.len(), 3);
         Ok(())
     }
+
+    #[test]
+    async fn test_retry_session_id() -> anyhow::Result<()> {
+        let ids = Mutex::new(Vec::new());
+        let result = (|| async {
+            // The attempt stays current when it's polled again.
+            let mut yielded = false;
+            core::future::poll_fn(|cx| {
+                if yielded {
+                    return Poll::Ready(());
+                }
+                yielded = true;
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            })
+            .await;
+            let (id, attempt) = crate::current_attempt().unwrap();
+            ids.lock().await.push((id, attempt));
+            Err::<(), _>(anyhow::anyhow!("unavailable"))
+        })
+        .retry(
+            ExponentialBuilder::default()
+                .with_min_delay(Duration::from_millis(1))
+                .with_max_times(2),
+        )
+        .notify_with_state(|_, _, state| {
+            ids.try_lock()
+                .unwrap()
+                .push((state.session_id(), state.attempt()))
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(crate::current_attempt(), None);
+        let ids = ids.lock().await;
+        let id = ids[0].0;
+        assert_eq!(*ids, vec![(id, 1), (id, 1), (id, 2), (id, 2), (id, 3)]);
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -460,3 +1773,277 @@ mod custom_sleeper_tests {
         Ok(())
     }
 }
+
+#[cfg(test)]
+#[cfg(feature = "test-util")]
+mod deadline_tests {
+    use alloc::vec::Vec;
+    use core::future::pending;
+    use core::pin::pin;
+    use core::task::Context;
+    use core::time::Duration;
+    use std::io;
+    use std::sync::Arc;
+    use std::task::Wake;
+
+    use super::*;
+    use crate::test::ManualSleeper;
+    use crate::ConstantBuilder;
+
+    struct Noop;
+
+    impl Wake for Noop {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    #[test]
+    fn test_retry_with_attempt_timeout() {
+        let waker = Arc::new(Noop).into();
+        let mut cx = Context::from_waker(&waker);
+        let backoff = ConstantBuilder::default()
+            .with_delay(Duration::from_secs(1))
+            .with_max_times(5);
+        let deadline =
+            Deadline::after(Duration::from_secs(10)).with_attempt_timeout(Duration::from_secs(3));
+
+        // The first two attempts hang, and are timed out after 3s.
+        let sleeper = ManualSleeper::new();
+        let mut attempts = 0;
+        let mut errors = Vec::new();
+        let mut retry = pin!((|| {
+            attempts += 1;
+            let attempt = attempts;
+            async move {
+                if attempt < 3 {
+                    pending::<()>().await;
+                }
+                Ok::<_, io::Error>(attempt)
+            }
+        })
+        .retry(backoff)
+        .sleep(sleeper.clone())
+        .clock(sleeper.clone())
+        .deadline(deadline)
+        .notify(|err, _| errors.push(err.kind())));
+        for _ in 0..2 {
+            assert!(retry.as_mut().poll(&mut cx).is_pending());
+            sleeper.advance(Duration::from_secs(3));
+            assert!(retry.as_mut().poll(&mut cx).is_pending());
+            sleeper.advance(Duration::from_secs(1));
+        }
+        assert!(matches!(retry.as_mut().poll(&mut cx), Poll::Ready(Ok(3))));
+        assert_eq!(errors, [io::ErrorKind::TimedOut; 2]);
+
+        // The timeout of the attempts shrinks to the time left until the deadline.
+        let sleeper = ManualSleeper::new();
+        let mut retry = pin!((|| pending::<io::Result<()>>())
+            .retry(backoff)
+            .sleep(sleeper.clone())
+            .clock(sleeper.clone())
+            .deadline(deadline.with_min_attempt(Duration::from_secs(1)))
+            .wrap_err());
+        assert!(retry.as_mut().poll(&mut cx).is_pending());
+        sleeper.advance(Duration::from_secs(3));
+        assert!(retry.as_mut().poll(&mut cx).is_pending());
+        sleeper.advance(Duration::from_secs(1));
+        assert!(retry.as_mut().poll(&mut cx).is_pending());
+        sleeper.advance(Duration::from_secs(3));
+        assert!(retry.as_mut().poll(&mut cx).is_pending());
+        sleeper.advance(Duration::from_secs(1));
+        assert!(retry.as_mut().poll(&mut cx).is_pending());
+        // The third attempt starts 8s in, and is timed out after 2s.
+        sleeper.advance(Duration::from_secs(1));
+        assert!(retry.as_mut().poll(&mut cx).is_pending());
+        sleeper.advance(Duration::from_secs(1));
+        let Poll::Ready(Err(err)) = retry.as_mut().poll(&mut cx) else {
+            panic!("the retry didn't give up at the deadline");
+        };
+        assert_eq!(err.attempts(), 3);
+        assert_eq!(err.reason(), GiveUpReason::DeadlineExceeded);
+        assert_eq!(err.get_ref().kind(), io::ErrorKind::TimedOut);
+        assert_eq!(sleeper.now(), Duration::from_secs(10));
+    }
+
+    /// Sleeper whose sleeps panic if they're polled once done, like many hand-written futures.
+    #[derive(Clone)]
+    struct Fused(ManualSleeper);
+
+    struct FusedSleep(Option<crate::test::ManualSleep>);
+
+    impl Sleeper for Fused {
+        type Sleep = FusedSleep;
+
+        fn sleep(&self, dur: Duration) -> Self::Sleep {
+            FusedSleep(Some(self.0.sleep(dur)))
+        }
+    }
+
+    impl Future for FusedSleep {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            let sleep = self.0.as_mut().expect("sleep polled after it's done");
+            ready!(Pin::new(sleep).poll(cx));
+            self.0 = None;
+            Poll::Ready(())
+        }
+    }
+
+    #[test]
+    fn test_retry_with_attempt_timeout_in_a_row() {
+        let waker = Arc::new(Noop).into();
+        let mut cx = Context::from_waker(&waker);
+        let backoff = ConstantBuilder::default()
+            .with_delay(Duration::ZERO)
+            .with_max_times(2);
+        let deadline =
+            Deadline::after(Duration::from_secs(10)).with_attempt_timeout(Duration::from_secs(3));
+
+        // Both the typed and the erased retry arm a new timer for every attempt.
+        for erase in [false, true] {
+            let sleeper = ManualSleeper::new();
+            let mut attempts = 0;
+            let retry = (|| {
+                attempts += 1;
+                let attempt = attempts;
+                async move {
+                    if attempt < 3 {
+                        pending::<()>().await;
+                    }
+                    Ok::<_, io::Error>(attempt)
+                }
+            })
+            .retry(backoff)
+            .sleep(Fused(sleeper.clone()))
+            .clock(sleeper.clone())
+            .deadline(deadline);
+            let mut retry: Pin<Box<dyn Future<Output = io::Result<usize>> + Send + '_>> = if erase {
+                Box::pin(retry.erase())
+            } else {
+                Box::pin(retry)
+            };
+
+            assert!(retry.as_mut().poll(&mut cx).is_pending());
+            sleeper.advance(Duration::from_secs(3));
+            // The first attempt times out, the second starts right away and hangs.
+            assert!(retry.as_mut().poll(&mut cx).is_pending());
+            sleeper.advance(Duration::from_secs(3));
+            // The second attempt times out too, the third succeeds.
+            assert!(matches!(retry.as_mut().poll(&mut cx), Poll::Ready(Ok(3))));
+            assert_eq!(sleeper.now(), Duration::from_secs(6));
+        }
+
+        // Without the hook failing the attempts, the attempt keeps running once its timer is
+        // done, and the timer isn't polled again.
+        let sleeper = ManualSleeper::new();
+        let mut retry = pin!((|| pending::<io::Result<()>>())
+            .retry(backoff)
+            .sleep(Fused(sleeper.clone()))
+            .clock(sleeper.clone())
+            .deadline(deadline)
+            .map_err_before(|e| e));
+        assert!(retry.as_mut().poll(&mut cx).is_pending());
+        sleeper.advance(Duration::from_secs(3));
+        assert!(retry.as_mut().poll(&mut cx).is_pending());
+        assert!(retry.as_mut().poll(&mut cx).is_pending());
+    }
+}
+
+#[cfg(test)]
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+mod alloc_tests {
+    use core::cell::Cell;
+    use core::future::ready;
+    use core::pin::pin;
+    use core::task::Context;
+    use core::time::Duration;
+    use std::alloc::GlobalAlloc;
+    use std::alloc::Layout;
+    use std::alloc::System;
+    use std::sync::Arc;
+    use std::task::Wake;
+
+    use super::*;
+    use crate::ConstantBuilder;
+    use crate::RetryableWithContext;
+
+    /// Counts the allocations of the current thread, so tests running in parallel don't
+    /// count each other's.
+    struct CountingAlloc;
+
+    std::thread_local! {
+        static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    unsafe impl GlobalAlloc for CountingAlloc {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let _ = ALLOCATIONS.try_with(|n| n.set(n.get() + 1));
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOC: CountingAlloc = CountingAlloc;
+
+    fn allocations<R>(f: impl FnOnce() -> R) -> (R, usize) {
+        let before = ALLOCATIONS.with(Cell::get);
+        let res = f();
+        (res, ALLOCATIONS.with(Cell::get) - before)
+    }
+
+    struct Noop;
+
+    impl Wake for Noop {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    #[test]
+    fn test_retry_without_allocation() {
+        let waker = Arc::new(Noop).into();
+        let mut cx = Context::from_waker(&waker);
+        let backoff = ConstantBuilder::default().with_delay(Duration::from_millis(1));
+
+        let (res, n) = allocations(|| {
+            let retry = pin!((|| ready(Ok::<_, &str>(1)))
+                .retry(backoff)
+                .sleep(|_| ready(())));
+            retry.poll(&mut cx)
+        });
+        assert_eq!(res, Poll::Ready(Ok(1)));
+        assert_eq!(n, 0, "a successful first attempt allocated");
+
+        let mut attempts = 0;
+        let (res, n) = allocations(|| {
+            let retry = pin!((|| {
+                attempts += 1;
+                ready(if attempts < 4 {
+                    Err("unavailable")
+                } else {
+                    Ok(attempts)
+                })
+            })
+            .retry(backoff)
+            .sleep(|_| ready(())));
+            retry.poll(&mut cx)
+        });
+        assert_eq!(res, Poll::Ready(Ok(4)));
+        assert_eq!(n, 0, "the retries allocated");
+
+        let (res, n) = allocations(|| {
+            let retry = pin!((|v: usize| ready((v + 1, Err::<(), _>("unavailable"))))
+                .retry(backoff)
+                .context(0)
+                .when(|e| *e == "unavailable")
+                .notify(|_, _| {})
+                .name("fetch")
+                .sleep(|_| ready(())));
+            retry.poll(&mut cx)
+        });
+        assert_eq!(res, Poll::Ready((4, Err("unavailable"))));
+        assert_eq!(n, 0, "the retries with context allocated");
+    }
+}
@@ -1,4 +1,8 @@
+use alloc::borrow::Cow;
+use core::any;
+use core::fmt;
 use core::future::Future;
+use core::ops::ControlFlow;
 use core::pin::Pin;
 use core::task::ready;
 use core::task::Context;
@@ -6,10 +10,54 @@ use core::task::Poll;
 use core::time::Duration;
 
 use crate::backoff::BackoffBuilder;
+use crate::clock::SharedClock;
+#[cfg(feature = "anyhow")]
+use crate::error::WhenDowncast;
+use crate::error::{
+    BackoffBy, Classify, ErrorClassifier, ErrorCollector, LastError, MapErrBefore, MapError,
+    ReduceErrors, SelectError, Unmapped, UnwrapPermanent, WhenRetryableError, WhenWithState,
+    WrapRetryError,
+};
+#[cfg(feature = "defmt")]
+use crate::hooks::Defmt;
+#[cfg(feature = "logging")]
+use crate::hooks::Log;
+#[cfg(feature = "opentelemetry")]
+use crate::hooks::OpenTelemetry;
+#[cfg(feature = "sentry")]
+use crate::hooks::Sentry;
+#[cfg(feature = "slog")]
+use crate::hooks::Slog;
+#[cfg(feature = "tracing")]
+use crate::hooks::Trace;
+use crate::hooks::{
+    Chain, Hooks, Metrics, NoHooks, Observe, OnBackoffExhausted, OnFatal, OnStart, OnTransient,
+    Recover,
+};
+use crate::report::Reported;
+use crate::retry::NotifyWithState;
 use crate::sleep::MaybeSleeper;
+use crate::state::CurrentAttempt;
 use crate::Backoff;
+use crate::BackoffChoice;
+use crate::BudgetWarning;
+use crate::Clock;
+use crate::Deadline;
 use crate::DefaultSleeper;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+use crate::FailureBudget;
+use crate::FinalError;
+use crate::GiveUpReason;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+use crate::RetryBudget;
+use crate::RetryEvent;
+use crate::RetryMetrics;
+use crate::RetrySnapshot;
+use crate::RetryState;
+use crate::RetryableError;
+use crate::ScheduleSummary;
 use crate::Sleeper;
+use crate::WithReport;
 
 /// `RetryableWithContext` adds retry support for functions that produce futures with results
 /// and context.
@@ -100,6 +148,9 @@ where
 }
 
 /// Retry struct generated by [`RetryableWithContext`].
+///
+/// Like [`Retry`](crate::Retry), polling it doesn't allocate unless the options keeping data
+/// are set, and it's [`Unpin`] as long as the futures of its attempts and its sleeps are.
 pub struct RetryWithContext<
     B: Backoff,
     T,
@@ -110,16 +161,72 @@ pub struct RetryWithContext<
     SF: MaybeSleeper = DefaultSleeper,
     RF = fn(&E) -> bool,
     NF = fn(&E, Duration),
+    EC = LastError,
+    ME = Unmapped,
+    HK = NoHooks,
+    CS = PleaseProvideAContext,
 > {
     backoff: B,
     retryable: RF,
     notify: NF,
+    errors: EC,
+    map_err: ME,
+    hooks: HK,
+    retry_state: RetryState,
     future_fn: FutureFn,
     sleep_fn: SF,
+    ctx: Option<CS>,
 
     state: State<T, E, Ctx, Fut, SF::Sleep>,
 }
 
+impl<B, T, E, Ctx, Fut, FutureFn, SF, RF, NF, EC, ME, HK, CS>
+    RetryWithContext<B, T, E, Ctx, Fut, FutureFn, SF, RF, NF, EC, ME, HK, CS>
+where
+    B: Backoff,
+    Fut: Future<Output = (Ctx, Result<T, E>)>,
+    FutureFn: FnMut(Ctx) -> Fut,
+    SF: MaybeSleeper,
+{
+    /// Returns the index of the current attempt, starting from 1 for the first attempt, or 0
+    /// before the retry starts.
+    pub fn attempt(&self) -> usize {
+        self.retry_state.attempt()
+    }
+
+    /// Returns the time elapsed since the first attempt started.
+    pub fn elapsed(&self) -> Duration {
+        self.retry_state.elapsed()
+    }
+
+    /// Capture the progress of the retry and the state of its backoff, to be restored by
+    /// `resume_from`.
+    pub fn snapshot(&self) -> RetrySnapshot<B>
+    where
+        B: Clone,
+    {
+        RetrySnapshot::new(self.retry_state.attempt(), self.backoff.clone())
+            .with_elapsed(self.retry_state.elapsed())
+    }
+}
+
+impl<B, T, E, Ctx, Fut, FutureFn, SF, RF, NF, EC, ME, HK, CS> fmt::Debug
+    for RetryWithContext<B, T, E, Ctx, Fut, FutureFn, SF, RF, NF, EC, ME, HK, CS>
+where
+    B: Backoff,
+    Fut: Future<Output = (Ctx, Result<T, E>)>,
+    FutureFn: FnMut(Ctx) -> Fut,
+    SF: MaybeSleeper,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RetryWithContext")
+            .field("backoff", &any::type_name::<B>())
+            .field("attempt", &self.retry_state.attempt())
+            .field("next_delay", &self.retry_state.next_delay())
+            .finish_non_exhaustive()
+    }
+}
+
 impl<B, T, E, Ctx, Fut, FutureFn> RetryWithContext<B, T, E, Ctx, Fut, FutureFn>
 where
     B: Backoff,
@@ -132,22 +239,31 @@ where
             backoff,
             retryable: |_: &E| true,
             notify: |_: &E, _: Duration| {},
+            errors: LastError,
+            map_err: Unmapped,
+            hooks: NoHooks,
+            retry_state: RetryState::new(),
             future_fn,
             sleep_fn: DefaultSleeper::default(),
+            ctx: Some(PleaseProvideAContext),
             state: State::Idle(None),
         }
     }
 }
 
-impl<B, T, E, Ctx, Fut, FutureFn, SF, RF, NF>
-    RetryWithContext<B, T, E, Ctx, Fut, FutureFn, SF, RF, NF>
+#[allow(clippy::type_complexity)]
+impl<B, T, E, Ctx, Fut, FutureFn, SF, RF, NF, EC, ME, HK, CS>
+    RetryWithContext<B, T, E, Ctx, Fut, FutureFn, SF, RF, NF, EC, ME, HK, CS>
 where
     B: Backoff,
     Fut: Future<Output = (Ctx, Result<T, E>)>,
     FutureFn: FnMut(Ctx) -> Fut,
     SF: Sleeper,
-    RF: FnMut(&E) -> bool,
-    NF: FnMut(&E, Duration),
+    RF: ErrorClassifier<ME::Error>,
+    NF: NotifyWithContext<ME::Error, Ctx>,
+    HK: Hooks<T, ME::Error>,
+    ME: MapError<E>,
+    EC: ErrorCollector<ME::Error>,
 {
     /// Set the sleeper for retrying.
     ///
@@ -157,18 +273,18 @@ where
     pub fn sleep<SN: Sleeper>(
         self,
         sleep_fn: SN,
-    ) -> RetryWithContext<B, T, E, Ctx, Fut, FutureFn, SN, RF, NF> {
-        assert!(
-            matches!(self.state, State::Idle(None)),
-            "sleep must be set before context"
-        );
-
+    ) -> RetryWithContext<B, T, E, Ctx, Fut, FutureFn, SN, RF, NF, EC, ME, HK, CS> {
         RetryWithContext {
             backoff: self.backoff,
             retryable: self.retryable,
             notify: self.notify,
             future_fn: self.future_fn,
             sleep_fn,
+            ctx: self.ctx,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
             state: State::Idle(None),
         }
     }
@@ -179,14 +295,112 @@ where
     pub fn context(
         self,
         context: Ctx,
-    ) -> RetryWithContext<B, T, E, Ctx, Fut, FutureFn, SF, RF, NF> {
+    ) -> RetryWithContext<B, T, E, Ctx, Fut, FutureFn, SF, RF, NF, EC, ME, HK, ContextValue<Ctx>>
+    {
+        RetryWithContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: Some(ContextValue(context)),
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+            state: State::Idle(None),
+        }
+    }
+
+    /// Set a function to build the context for retrying.
+    ///
+    /// The function is only invoked once the retry is polled for the first time.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use anyhow::anyhow;
+    /// use anyhow::Result;
+    /// use backon::ExponentialBuilder;
+    /// use backon::RetryableWithContext;
+    ///
+    /// struct Test;
+    ///
+    /// impl Test {
+    ///     async fn hello(&mut self) -> Result<usize> {
+    ///         Err(anyhow!("not retryable"))
+    ///     }
+    /// }
+    ///
+    /// #[tokio::main(flavor = "current_thread")]
+    /// async fn main() -> Result<()> {
+    ///     let (_, result) = {
+    ///         |mut v: Test| async {
+    ///             let res = v.hello().await;
+    ///             (v, res)
+    ///         }
+    ///     }
+    ///     .retry(ExponentialBuilder::default())
+    ///     .context_with(|| Test)
+    ///     .await;
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn context_with<CN: FnOnce() -> Ctx>(
+        self,
+        context: CN,
+    ) -> RetryWithContext<B, T, E, Ctx, Fut, FutureFn, SF, RF, NF, EC, ME, HK, CN> {
         RetryWithContext {
             backoff: self.backoff,
             retryable: self.retryable,
             notify: self.notify,
             future_fn: self.future_fn,
             sleep_fn: self.sleep_fn,
-            state: State::Idle(Some(context)),
+            ctx: Some(context),
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+            state: State::Idle(None),
+        }
+    }
+    /// Set the function mapping the errors of the retried function before they are passed to
+    /// the conditions and notify.
+    ///
+    /// This allows reusing the same conditions for functions returning differently wrapped
+    /// errors. It resets the conditions, notify and error handling set before, so it must be
+    /// called first.
+    pub fn map_err_before<E2, MN: FnMut(E) -> E2>(
+        self,
+        map_err: MN,
+    ) -> RetryWithContext<
+        B,
+        T,
+        E,
+        Ctx,
+        Fut,
+        FutureFn,
+        SF,
+        fn(&E2) -> bool,
+        fn(&E2, Duration),
+        LastError,
+        MapErrBefore<MN>,
+        NoHooks,
+        CS,
+    > {
+        RetryWithContext {
+            backoff: self.backoff,
+            retryable: |_: &E2| true,
+            notify: |_: &E2, _: Duration| {},
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            errors: LastError,
+            retry_state: self.retry_state,
+            map_err: MapErrBefore(map_err),
+            hooks: NoHooks,
+            state: self.state,
         }
     }
 
@@ -219,16 +433,317 @@ where
     ///     Ok(())
     /// }
     /// ```
-    pub fn when<RN: FnMut(&E) -> bool>(
+    pub fn when<RN: FnMut(&ME::Error) -> bool>(
         self,
         retryable: RN,
-    ) -> RetryWithContext<B, T, E, Ctx, Fut, FutureFn, SF, RN, NF> {
+    ) -> RetryWithContext<B, T, E, Ctx, Fut, FutureFn, SF, RN, NF, EC, ME, HK, CS> {
         RetryWithContext {
             backoff: self.backoff,
             retryable,
             notify: self.notify,
             future_fn: self.future_fn,
             sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+            state: self.state,
+        }
+    }
+
+    /// Set the conditions for retrying with access to the [`RetryState`].
+    ///
+    /// The state tells the index of the failed attempt and the delay slept before it, the
+    /// backoff only proposing the next delay once the error is retried. This replaces the
+    /// conditions set by `when`.
+    pub fn when_with_state<RN: FnMut(&ME::Error, &RetryState) -> bool>(
+        self,
+        retryable: RN,
+    ) -> RetryWithContext<B, T, E, Ctx, Fut, FutureFn, SF, WhenWithState<RN>, NF, EC, ME, HK, CS>
+    {
+        RetryWithContext {
+            backoff: self.backoff,
+            retryable: WhenWithState(retryable),
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+            state: self.state,
+        }
+    }
+
+    /// Set the conditions for retrying with the error passed by value.
+    ///
+    /// The function returns [`ControlFlow::Continue`] with the error to retry, or
+    /// [`ControlFlow::Break`] with the error to end the retry with. So errors can be enriched
+    /// on every attempt, for example with the attempt index from [`RetryState`]. This replaces
+    /// the conditions set by `when`.
+    pub fn classify<RN: FnMut(ME::Error, &RetryState) -> ControlFlow<ME::Error, ME::Error>>(
+        self,
+        retryable: RN,
+    ) -> RetryWithContext<B, T, E, Ctx, Fut, FutureFn, SF, Classify<RN>, NF, EC, ME, HK, CS> {
+        RetryWithContext {
+            backoff: self.backoff,
+            retryable: Classify(retryable),
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+            state: self.state,
+        }
+    }
+
+    /// Set the conditions for retrying by the error's [`RetryableError`] implementation.
+    ///
+    /// The delay suggested by [`RetryableError::retry_after`] is used instead of the backoff's
+    /// delay if present. This replaces the conditions set by `when`.
+    pub fn when_retryable(
+        self,
+    ) -> RetryWithContext<B, T, E, Ctx, Fut, FutureFn, SF, WhenRetryableError, NF, EC, ME, HK, CS>
+    where
+        ME::Error: RetryableError,
+    {
+        RetryWithContext {
+            backoff: self.backoff,
+            retryable: WhenRetryableError,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+            state: self.state,
+        }
+    }
+
+    /// Set the function choosing the backoff of every error, see [`BackoffChoice`].
+    ///
+    /// Errors of the class returned by the function are retried with its own backoff,
+    /// independently of the other classes, and those returning [`BackoffChoice::Default`] with
+    /// the backoff of the retry. It applies to the conditions set before, so it must be called
+    /// after `when` and the like.
+    pub fn backoff_by<BB: BackoffBuilder, BN: FnMut(&ME::Error) -> BackoffChoice<BB>>(
+        self,
+        choose: BN,
+    ) -> RetryWithContext<B, T, E, Ctx, Fut, FutureFn, SF, BackoffBy<RF, BN, BB>, NF, EC, ME, HK, CS>
+    {
+        RetryWithContext {
+            backoff: self.backoff,
+            retryable: BackoffBy::new(self.retryable, choose),
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+            state: self.state,
+        }
+    }
+
+    /// Set the conditions for retrying [`anyhow::Error`]s caused by `X`.
+    ///
+    /// The whole chain of the error is inspected, so errors with context attached are retried
+    /// as well. This replaces the conditions set by `when`.
+    #[cfg(feature = "anyhow")]
+    pub fn when_downcast<X: core::error::Error + 'static>(
+        self,
+    ) -> RetryWithContext<B, T, E, Ctx, Fut, FutureFn, SF, WhenDowncast<X>, NF, EC, ME, HK, CS>
+    where
+        ME: MapError<E, Error = anyhow::Error>,
+    {
+        RetryWithContext {
+            backoff: self.backoff,
+            retryable: WhenDowncast::new(),
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+            state: self.state,
+        }
+    }
+
+    /// Set the function reducing the errors of all attempts into the returned one.
+    ///
+    /// The function is called with the reduced error so far and the error of the next attempt.
+    /// If not specified, the error of the last attempt is returned.
+    ///
+    /// This replaces the previous reducer, and must be set before `wrap_err`.
+    pub fn reduce_errors<RN: FnMut(ME::Error, ME::Error) -> ME::Error>(
+        self,
+        reduce: RN,
+    ) -> RetryWithContext<
+        B,
+        T,
+        E,
+        Ctx,
+        Fut,
+        FutureFn,
+        SF,
+        RF,
+        NF,
+        ReduceErrors<ME::Error, RN>,
+        ME,
+        HK,
+        CS,
+    >
+    where
+        EC: ErrorCollector<ME::Error, Error = ME::Error>,
+    {
+        RetryWithContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            errors: ReduceErrors::new(reduce),
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+            state: self.state,
+        }
+    }
+
+    /// Set the policy selecting the error returned when the retry ends unsuccessfully.
+    ///
+    /// If not specified, the error of the last attempt is returned. See [`FinalError`] for
+    /// the policies.
+    ///
+    /// This replaces the previous reducer, and must be set before `wrap_err`.
+    pub fn final_error(
+        self,
+        policy: FinalError<ME::Error>,
+    ) -> RetryWithContext<B, T, E, Ctx, Fut, FutureFn, SF, RF, NF, SelectError<ME::Error>, ME, HK, CS>
+    where
+        EC: ErrorCollector<ME::Error, Error = ME::Error>,
+    {
+        RetryWithContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            errors: SelectError::new(policy),
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+            state: self.state,
+        }
+    }
+
+    /// End the retry at errors wrapped by [`non_retryable`](crate::non_retryable), and unwrap
+    /// the returned [`Permanent`](crate::Permanent) error.
+    ///
+    /// Permanent errors end the retry regardless of the conditions set by `when`.
+    pub fn unwrap_permanent(
+        self,
+    ) -> RetryWithContext<B, T, E, Ctx, Fut, FutureFn, SF, RF, NF, UnwrapPermanent<EC>, ME, HK, CS>
+    where
+        UnwrapPermanent<EC>: ErrorCollector<ME::Error>,
+    {
+        RetryWithContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            errors: UnwrapPermanent(self.errors),
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+            state: self.state,
+        }
+    }
+
+    /// Wrap the error that ends the retry into a [`RetryError`](crate::RetryError).
+    ///
+    /// The wrapper records the number of attempts made and keeps the original error as its
+    /// [`source`](core::error::Error::source).
+    pub fn wrap_err(
+        self,
+    ) -> RetryWithContext<
+        B,
+        T,
+        E,
+        Ctx,
+        Fut,
+        FutureFn,
+        SF,
+        RF,
+        NF,
+        WrapRetryError<EC, EC::Error>,
+        ME,
+        HK,
+        CS,
+    > {
+        RetryWithContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            errors: WrapRetryError::new(self.errors, 0),
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+            state: self.state,
+        }
+    }
+
+    /// Wrap the error that ends the retry into a [`RetryError`](crate::RetryError), keeping
+    /// the history of up to `cap` most recent failed attempts.
+    ///
+    /// Every entry of the history records the error, the delay slept after it and when it
+    /// happened, so the failure timeline can be reported once the retry ends.
+    pub fn wrap_err_with_history(
+        self,
+        cap: usize,
+    ) -> RetryWithContext<
+        B,
+        T,
+        E,
+        Ctx,
+        Fut,
+        FutureFn,
+        SF,
+        RF,
+        NF,
+        WrapRetryError<EC, EC::Error>,
+        ME,
+        HK,
+        CS,
+    > {
+        RetryWithContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            errors: WrapRetryError::new(self.errors, cap),
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
             state: self.state,
         }
     }
@@ -268,21 +783,727 @@ where
     ///     Ok(())
     /// }
     /// ```
-    pub fn notify<NN: FnMut(&E, Duration)>(
+    pub fn notify<NN: FnMut(&ME::Error, Duration)>(
         self,
         notify: NN,
-    ) -> RetryWithContext<B, T, E, Ctx, Fut, FutureFn, SF, RF, NN> {
+    ) -> RetryWithContext<B, T, E, Ctx, Fut, FutureFn, SF, RF, NN, EC, ME, HK, CS> {
         RetryWithContext {
             backoff: self.backoff,
             retryable: self.retryable,
             notify,
             future_fn: self.future_fn,
             sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+            state: self.state,
+        }
+    }
+
+    /// Set the function called once before the first attempt starts.
+    ///
+    /// It's called in addition to the functions set before, so per-call setup like starting
+    /// a timer or logging the operation can live in the retry.
+    pub fn on_start<ON: FnMut(&RetryState)>(
+        self,
+        f: ON,
+    ) -> RetryWithContext<B, T, E, Ctx, Fut, FutureFn, SF, RF, NF, EC, ME, Chain<HK, OnStart<ON>>, CS>
+    {
+        RetryWithContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, OnStart(f)),
+            state: self.state,
+        }
+    }
+
+    /// Set to notify for all retry attempts with access to the context.
+    ///
+    /// When a retry happens, the input function will be invoked with the error, the sleep duration
+    /// and the context before pausing. This replaces the function set by [`RetryWithContext::notify`].
+    ///
+    /// If not specified, this operation does nothing.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use core::time::Duration;
+    ///
+    /// use anyhow::anyhow;
+    /// use anyhow::Result;
+    /// use backon::ExponentialBuilder;
+    /// use backon::RetryableWithContext;
+    ///
+    /// struct Request {
+    ///     id: u64,
+    /// }
+    ///
+    /// async fn send(req: &mut Request) -> Result<String> {
+    ///     Err(anyhow!("request {} failed", req.id))
+    /// }
+    ///
+    /// #[tokio::main(flavor = "current_thread")]
+    /// async fn main() -> Result<()> {
+    ///     let (_, content) = {
+    ///         |mut req: Request| async {
+    ///             let res = send(&mut req).await;
+    ///             (req, res)
+    ///         }
+    ///     }
+    ///     .retry(ExponentialBuilder::default())
+    ///     .context(Request { id: 42 })
+    ///     .notify_with_ctx(|err: &anyhow::Error, dur: Duration, req: &Request| {
+    ///         println!("retrying request {} error {:?} with sleeping {:?}", req.id, err, dur);
+    ///     })
+    ///     .await;
+    ///     println!("fetch succeeded: {}", content?);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn notify_with_ctx<NN: FnMut(&ME::Error, Duration, &Ctx)>(
+        self,
+        notify: NN,
+    ) -> RetryWithContext<
+        B,
+        T,
+        E,
+        Ctx,
+        Fut,
+        FutureFn,
+        SF,
+        RF,
+        NotifyFnWithContext<NN>,
+        EC,
+        ME,
+        HK,
+        CS,
+    > {
+        RetryWithContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: NotifyFnWithContext(notify),
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+            state: self.state,
+        }
+    }
+
+    /// Set to notify for all retry attempts with access to the [`RetryState`].
+    ///
+    /// The state tells the session ID of the retry and the index of the failed attempt, so
+    /// the notifications can be correlated with the logs of the retried operation. This
+    /// replaces the function set by `notify`.
+    pub fn notify_with_state<NN: FnMut(&ME::Error, Duration, &RetryState)>(
+        self,
+        notify: NN,
+    ) -> RetryWithContext<B, T, E, Ctx, Fut, FutureFn, SF, RF, NotifyWithState<NN>, EC, ME, HK, CS>
+    {
+        RetryWithContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: NotifyWithState(notify),
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+            state: self.state,
+        }
+    }
+
+    /// Set the function called with every error that will be retried and the delay before
+    /// the next attempt.
+    ///
+    /// It's called after `notify`, and in addition to the functions set before.
+    pub fn on_transient_error<TN: FnMut(&ME::Error, Duration)>(
+        self,
+        f: TN,
+    ) -> RetryWithContext<
+        B,
+        T,
+        E,
+        Ctx,
+        Fut,
+        FutureFn,
+        SF,
+        RF,
+        NF,
+        EC,
+        ME,
+        Chain<HK, OnTransient<TN>>,
+        CS,
+    > {
+        RetryWithContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, OnTransient(f)),
             state: self.state,
         }
     }
+
+    /// Set the function called with the error that ends the retry unsuccessfully and the
+    /// reason why.
+    ///
+    /// Both non-retryable errors and errors left when the backoff is exhausted end the retry.
+    /// It's called in addition to the functions set before.
+    pub fn on_fatal_error<FN: FnMut(&ME::Error, GiveUpReason)>(
+        self,
+        f: FN,
+    ) -> RetryWithContext<B, T, E, Ctx, Fut, FutureFn, SF, RF, NF, EC, ME, Chain<HK, OnFatal<FN>>, CS>
+    {
+        RetryWithContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, OnFatal(f)),
+            state: self.state,
+        }
+    }
+
+    /// Set the function called when the retry gives up because the backoff is exhausted,
+    /// with the last error and the [`ScheduleSummary`] of the delays planned and slept.
+    ///
+    /// Unlike `on_fatal_error` it's not called for errors that are not retryable, so an
+    /// exhausted policy can be told apart from a fatal error.
+    pub fn on_backoff_exhausted<BN: FnMut(&ME::Error, &ScheduleSummary)>(
+        self,
+        f: BN,
+    ) -> RetryWithContext<
+        B,
+        T,
+        E,
+        Ctx,
+        Fut,
+        FutureFn,
+        SF,
+        RF,
+        NF,
+        EC,
+        ME,
+        Chain<HK, OnBackoffExhausted<BN>>,
+        CS,
+    > {
+        RetryWithContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, OnBackoffExhausted::new(f)),
+            state: self.state,
+        }
+    }
+
+    /// Set the function recovering from the error that ends the retry unsuccessfully.
+    ///
+    /// Both non-retryable errors and errors left when the backoff is exhausted are passed to
+    /// it, and the value it returns is returned as the result of the retry instead. Errors
+    /// that are not recovered are passed on to `on_fatal_error` and returned as usual.
+    ///
+    /// Recovered retries still gave up: their metrics, events and traces record them as
+    /// giving up, only `on_fatal_error` isn't called.
+    pub fn recover<RN: FnMut(&ME::Error) -> Option<T>>(
+        self,
+        f: RN,
+    ) -> RetryWithContext<B, T, E, Ctx, Fut, FutureFn, SF, RF, NF, EC, ME, Chain<HK, Recover<RN>>, CS>
+    {
+        RetryWithContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, Recover(f)),
+            state: self.state,
+        }
+    }
+
+    /// Set the [`RetryMetrics`] observing the progress of the retry.
+    ///
+    /// It's called in addition to the metrics set before.
+    pub fn metrics<MN: RetryMetrics>(
+        self,
+        metrics: MN,
+    ) -> RetryWithContext<B, T, E, Ctx, Fut, FutureFn, SF, RF, NF, EC, ME, Chain<HK, Metrics<MN>>, CS>
+    {
+        RetryWithContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, Metrics(metrics)),
+            state: self.state,
+        }
+    }
+
+    /// Set the function observing every step of the retry as a [`RetryEvent`].
+    ///
+    /// It's called in addition to the other hooks and observers set before, so a single
+    /// function can follow the whole lifecycle of the retry. Its events are no longer delivered
+    /// to the observer set by `set_global_observer`.
+    pub fn observe<FN: FnMut(RetryEvent<'_, ME::Error>)>(
+        mut self,
+        f: FN,
+    ) -> RetryWithContext<B, T, E, Ctx, Fut, FutureFn, SF, RF, NF, EC, ME, Chain<HK, Observe<FN>>, CS>
+    {
+        self.retry_state.set_observed();
+        RetryWithContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, Observe(f)),
+            state: self.state,
+        }
+    }
+
+    /// Record the retry in a `tracing` span named `retry`, with the `operation` and
+    /// `session_id` fields set.
+    ///
+    /// The retried function runs inside the span, and every sleep inside a child span named
+    /// `sleep` with the `operation`, `attempt` and `delay` fields, so subscribers show what a
+    /// waiting retry backs off on. Failed attempts are emitted as `WARN` events, sleeps,
+    /// resumes and successes as `DEBUG` events and giving up as an `ERROR` event, with the
+    /// attempt number, delay, error and reason as fields.
+    #[cfg(feature = "tracing")]
+    pub fn trace(
+        self,
+        operation: &'static str,
+    ) -> RetryWithContext<B, T, E, Ctx, Fut, FutureFn, SF, RF, NF, EC, ME, Chain<HK, Trace>, CS>
+    where
+        ME::Error: fmt::Display,
+    {
+        RetryWithContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, Trace::new(operation)),
+            state: self.state,
+        }
+    }
+
+    /// Record the retry on the current OpenTelemetry span and in OpenTelemetry metrics, with
+    /// the `operation.name` attribute set to `operation`.
+    ///
+    /// Every failed attempt is added to the span active when this is called as a
+    /// `retry.attempt_failed` event and giving up as a `retry.gave_up` event, carrying the
+    /// `retry.attempt`, `retry.delay`, `error.type` and `exception.message` attributes. The
+    /// `backon.retry.attempts`, `backon.retry.retries` and `backon.retry.giveups` counters and
+    /// the `backon.retry.sleep.duration` histogram are reported to the global meter provider.
+    #[cfg(feature = "opentelemetry")]
+    pub fn opentelemetry(
+        self,
+        operation: &'static str,
+    ) -> RetryWithContext<
+        B,
+        T,
+        E,
+        Ctx,
+        Fut,
+        FutureFn,
+        SF,
+        RF,
+        NF,
+        EC,
+        ME,
+        Chain<HK, OpenTelemetry>,
+        CS,
+    >
+    where
+        ME::Error: fmt::Display,
+    {
+        RetryWithContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, OpenTelemetry::new(operation)),
+            state: self.state,
+        }
+    }
+
+    /// Log the errors that will be retried as warnings and the error that ends the retry as
+    /// an error with `defmt`, for embedded targets logging over RTT.
+    #[cfg(feature = "defmt")]
+    pub fn defmt(
+        self,
+    ) -> RetryWithContext<B, T, E, Ctx, Fut, FutureFn, SF, RF, NF, EC, ME, Chain<HK, Defmt>, CS>
+    where
+        ME::Error: defmt::Format,
+    {
+        RetryWithContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, Defmt),
+            state: self.state,
+        }
+    }
+
+    /// Record every error that will be retried and the error that ends the retry as Sentry
+    /// breadcrumbs in the `retry` category, with the attempt, the delay and the error message.
+    ///
+    /// When the retry gives up, the number of attempts is set as the `retry.attempts` tag of
+    /// the current scope, so the error reported afterwards shows the history of the retry.
+    #[cfg(feature = "sentry")]
+    pub fn sentry(
+        self,
+    ) -> RetryWithContext<B, T, E, Ctx, Fut, FutureFn, SF, RF, NF, EC, ME, Chain<HK, Sentry>, CS>
+    where
+        ME::Error: fmt::Display,
+    {
+        RetryWithContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, Sentry),
+            state: self.state,
+        }
+    }
+
+    /// Log the errors that will be retried at `retry_level` and the error that ends the
+    /// retry at `give_up_level` with the `log` crate, under the `backon` target.
+    #[cfg(feature = "logging")]
+    pub fn log(
+        self,
+        retry_level: log::Level,
+        give_up_level: log::Level,
+    ) -> RetryWithContext<B, T, E, Ctx, Fut, FutureFn, SF, RF, NF, EC, ME, Chain<HK, Log>, CS>
+    where
+        ME::Error: fmt::Display,
+    {
+        RetryWithContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, Log::new(retry_level, give_up_level)),
+            state: self.state,
+        }
+    }
+
+    /// Log the errors that will be retried as warnings and the error that ends the retry as
+    /// an error to `logger`, with the attempt number, delay, error and reason as key-value
+    /// pairs.
+    #[cfg(feature = "slog")]
+    pub fn slog(
+        self,
+        logger: slog::Logger,
+    ) -> RetryWithContext<B, T, E, Ctx, Fut, FutureFn, SF, RF, NF, EC, ME, Chain<HK, Slog>, CS>
+    where
+        ME::Error: fmt::Display,
+    {
+        RetryWithContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, Slog::new(logger)),
+            state: self.state,
+        }
+    }
+
+    /// Set whether the retried operation is idempotent, `true` by default.
+    ///
+    /// Operations that are not idempotent, like writes, are only retried at errors telling
+    /// the operation had no effect by [`RetryableError::is_safe_to_retry`], so this works
+    /// together with `when_retryable`. Other errors end the retry with
+    /// [`GiveUpReason::Ambiguous`] instead of risking applying the operation twice.
+    pub fn idempotent(mut self, idempotent: bool) -> Self {
+        self.retry_state.set_idempotent(idempotent);
+        self
+    }
+
+    /// Set the name of the retried operation, like `s3_put_object`.
+    ///
+    /// It's included in the events passed to `observe`, the [`RetryReport`](crate::RetryReport)
+    /// and the output of the `tracing` and `opentelemetry` integrations, so they can be
+    /// aggregated per operation.
+    pub fn name(mut self, name: &'static str) -> Self {
+        self.retry_state.set_name(name);
+        self
+    }
+
+    /// Add a key-value tag describing the retried operation, included wherever the name set
+    /// by `name` is.
+    pub fn tag(mut self, key: &'static str, value: impl Into<Cow<'static, str>>) -> Self {
+        self.retry_state.add_tag(key, value.into());
+        self
+    }
+
+    /// Continue a retry from a [`RetrySnapshot`] taken by `snapshot`, like one persisted
+    /// before the process restarted.
+    ///
+    /// The backoff of the snapshot replaces the one passed to `retry`, so the delays pick up
+    /// where they were instead of starting over, and the attempts and elapsed time count from
+    /// the snapshot. The next attempt starts right away.
+    pub fn resume_from<S: Backoff>(
+        mut self,
+        snapshot: RetrySnapshot<S>,
+    ) -> RetryWithContext<S, T, E, Ctx, Fut, FutureFn, SF, RF, NF, EC, ME, HK, CS> {
+        self.retry_state.resume(&snapshot);
+        RetryWithContext {
+            backoff: snapshot.into_backoff(),
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+            state: self.state,
+        }
+    }
+
+    /// Set the [`Clock`] measuring the elapsed time and the latency of the attempts, reported
+    /// by [`RetryState`], the events passed to `observe` and the report.
+    ///
+    /// It defaults to [`StdClock`](crate::StdClock) with the `std` feature outside of
+    /// `wasm32`. Without it, the retry isn't timed unless a clock is set.
+    pub fn clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.retry_state.set_clock(SharedClock::new(clock));
+        self
+    }
+
+    /// Seed the random numbers of the retry, the jitter of its backoff and its ID, instead of
+    /// drawing them from the thread's RNG.
+    ///
+    /// Together with a [`Clock`] set by `clock`, the delays, the events and the report of the
+    /// retry are the same on every run, so tests can assert on them exactly.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.retry_state.set_seed(seed);
+        self
+    }
+
+    /// Panic if the retry makes more than `max_attempts` attempts or sleeps more than
+    /// `max_total_sleep` in total, so tests assert a code path never retries more than it
+    /// should, like "this call is retried at most twice".
+    ///
+    /// The panic happens before the retry over the budget sleeps. Only available with the
+    /// `test-util` feature.
+    #[cfg(feature = "test-util")]
+    pub fn with_test_budget(mut self, max_attempts: usize, max_total_sleep: Duration) -> Self {
+        self.retry_state
+            .set_test_budget(max_attempts, max_total_sleep);
+        self
+    }
+
+    /// Set the ID identifying this retry in logs and events, returned by
+    /// [`RetryState::session_id`].
+    ///
+    /// A random ID is generated when the retry is built if it's not set, the same for all its
+    /// attempts. The retried operation reads it with `current_attempt`, so its logs can be
+    /// correlated with the events of the retry.
+    pub fn session_id(mut self, id: u64) -> Self {
+        self.retry_state.set_session_id(id);
+        self
+    }
+
+    /// Set the [`BudgetWarning`] raised to the function set by `observe` once a fraction of
+    /// the attempts or time of the retry is consumed.
+    pub fn budget_warning(mut self, warning: BudgetWarning) -> Self {
+        self.retry_state.set_budget_warning(warning);
+        self
+    }
+
+    /// Set the most time the retry sleeps in total between attempts, combined with the
+    /// limits of the backoff like its maximum number of retries.
+    ///
+    /// The retry gives up with [`GiveUpReason::MaxTotalSleepReached`] instead of sleeping past
+    /// it, telling it apart from [`GiveUpReason::BackoffExhausted`] if the backoff runs out
+    /// first.
+    pub fn max_total_sleep(mut self, max: Duration) -> Self {
+        self.retry_state.set_max_total_sleep(max);
+        self
+    }
+
+    /// Attach the [`FailureBudget`] shared with other retries.
+    ///
+    /// Every attempt failed with a retryable error is recorded in the budget, and the retry
+    /// ends at the next one while the budget is exhausted.
+    #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+    pub fn failure_budget(mut self, budget: FailureBudget) -> Self {
+        self.retry_state.set_failure_budget(budget);
+        self
+    }
+
+    /// Attach the [`RetryBudget`] shared with other retries.
+    ///
+    /// Every successful attempt deposits in the budget, and every retry withdraws from it,
+    /// the retry ending with [`GiveUpReason::BudgetExhausted`] once it's empty.
+    #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+    pub fn retry_budget(mut self, budget: RetryBudget) -> Self {
+        self.retry_state.set_retry_budget(budget);
+        self
+    }
+
+    /// Set the [`Deadline`] the retry must be done by, inherited from the context of the call.
+    ///
+    /// The delays are shortened for every retry to start with the least time an attempt
+    /// needs left, the retry ending with [`GiveUpReason::DeadlineExceeded`] once it can't.
+    /// Attempts own the context, so they aren't timed out, see [`Deadline`].
+    pub fn deadline(mut self, deadline: Deadline) -> Self {
+        self.retry_state.set_deadline(deadline);
+        self
+    }
+
+    /// Return a future yielding the [`RetryReport`](crate::RetryReport) of the retry alongside its output.
+    pub fn await_with_report(mut self) -> WithReport<Self> {
+        self.retry_state.start_report();
+        WithReport::new(self)
+    }
+}
+
+/// A stub trait allowing both `FnMut(&E, Duration)` and `FnMut(&E, Duration, &Ctx)` to be used
+/// as the notify function of retries with context.
+#[doc(hidden)]
+pub trait NotifyWithContext<E, Ctx> {
+    fn notify(&mut self, err: &E, dur: Duration, ctx: &Ctx, state: &RetryState);
+}
+
+/// All `FnMut(&E, Duration)` implements `NotifyWithContext` by ignoring the context.
+impl<E, Ctx, F: FnMut(&E, Duration)> NotifyWithContext<E, Ctx> for F {
+    fn notify(&mut self, err: &E, dur: Duration, _: &Ctx, _: &RetryState) {
+        self(err, dur)
+    }
+}
+
+/// Notify function that accepts the context, set by `notify_with_ctx`.
+#[doc(hidden)]
+pub struct NotifyFnWithContext<F>(pub(crate) F);
+
+impl<E, Ctx, F: FnMut(&E, Duration, &Ctx)> NotifyWithContext<E, Ctx> for NotifyFnWithContext<F> {
+    fn notify(&mut self, err: &E, dur: Duration, ctx: &Ctx, _: &RetryState) {
+        (self.0)(err, dur, ctx)
+    }
+}
+
+/// The notify function set by `notify_with_state` ignores the context.
+impl<E, Ctx, F: FnMut(&E, Duration, &RetryState)> NotifyWithContext<E, Ctx> for NotifyWithState<F> {
+    fn notify(&mut self, err: &E, dur: Duration, _: &Ctx, state: &RetryState) {
+        (self.0)(err, dur, state)
+    }
+}
+
+/// A stub trait for the source of the context that retries with context start with.
+///
+/// It's implemented by the value set by `context` and all `FnOnce() -> Ctx` set by
+/// `context_with`, but not by [`PleaseProvideAContext`]. So retries without context can't be
+/// started.
+#[doc(hidden)]
+pub trait ContextSource<Ctx> {
+    fn into_context(self) -> Ctx;
+}
+
+/// Context value set by `context`.
+#[doc(hidden)]
+pub struct ContextValue<Ctx>(pub(crate) Ctx);
+
+impl<Ctx> ContextSource<Ctx> for ContextValue<Ctx> {
+    fn into_context(self) -> Ctx {
+        self.0
+    }
 }
 
+/// All `FnOnce() -> Ctx` implements `ContextSource` by building the context lazily.
+impl<Ctx, F: FnOnce() -> Ctx> ContextSource<Ctx> for F {
+    fn into_context(self) -> Ctx {
+        self()
+    }
+}
+
+/// A placeholder type that does not implement [`ContextSource`] and will therefore fail to
+/// compile if a retry with context is started without a context.
+///
+/// Users should call `context` or `context_with` before starting the retry when this type
+/// appears in compilation errors.
+#[doc(hidden)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PleaseProvideAContext;
+
 /// State maintains internal state of retry.
 enum State<T, E, Ctx, Fut: Future<Output = (Ctx, Result<T, E>)>, SleepFut: Future<Output = ()>> {
     Idle(Option<Ctx>),
@@ -290,17 +1511,53 @@ enum State<T, E, Ctx, Fut: Future<Output = (Ctx, Result<T, E>)>, SleepFut: Futur
     Sleeping((Option<Ctx>, SleepFut)),
 }
 
-impl<B, T, E, Ctx, Fut, FutureFn, SF, RF, NF> Future
-    for RetryWithContext<B, T, E, Ctx, Fut, FutureFn, SF, RF, NF>
+// Only the futures of the attempts and the sleeps are pinned by `poll`, the context is moved
+// between them.
+impl<B, T, E, Ctx, Fut, FutureFn, SF, RF, NF, EC, ME, HK, CS> Unpin
+    for RetryWithContext<B, T, E, Ctx, Fut, FutureFn, SF, RF, NF, EC, ME, HK, CS>
+where
+    B: Backoff,
+    Fut: Future<Output = (Ctx, Result<T, E>)> + Unpin,
+    FutureFn: FnMut(Ctx) -> Fut,
+    SF: MaybeSleeper,
+    SF::Sleep: Unpin,
+{
+}
+
+impl<B, T, E, Ctx, Fut, FutureFn, SF, RF, NF, EC, ME, HK, CS> Reported
+    for RetryWithContext<B, T, E, Ctx, Fut, FutureFn, SF, RF, NF, EC, ME, HK, CS>
 where
     B: Backoff,
     Fut: Future<Output = (Ctx, Result<T, E>)>,
     FutureFn: FnMut(Ctx) -> Fut,
     SF: Sleeper,
-    RF: FnMut(&E) -> bool,
-    NF: FnMut(&E, Duration),
+    RF: ErrorClassifier<ME::Error>,
+    NF: NotifyWithContext<ME::Error, Ctx>,
+    HK: Hooks<T, ME::Error>,
+    ME: MapError<E>,
+    EC: ErrorCollector<ME::Error>,
+    CS: ContextSource<Ctx>,
 {
-    type Output = (Ctx, Result<T, E>);
+    fn retry_state(&mut self) -> &mut RetryState {
+        &mut self.retry_state
+    }
+}
+
+impl<B, T, E, Ctx, Fut, FutureFn, SF, RF, NF, EC, ME, HK, CS> Future
+    for RetryWithContext<B, T, E, Ctx, Fut, FutureFn, SF, RF, NF, EC, ME, HK, CS>
+where
+    B: Backoff,
+    Fut: Future<Output = (Ctx, Result<T, E>)>,
+    FutureFn: FnMut(Ctx) -> Fut,
+    SF: Sleeper,
+    RF: ErrorClassifier<ME::Error>,
+    NF: NotifyWithContext<ME::Error, Ctx>,
+    HK: Hooks<T, ME::Error>,
+    ME: MapError<E>,
+    EC: ErrorCollector<ME::Error>,
+    CS: ContextSource<Ctx>,
+{
+    type Output = (Ctx, Result<T, EC::Error>);
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         // Safety: This is safe because we don't move the `Retry` struct itself,
@@ -312,8 +1569,24 @@ where
         loop {
             match &mut this.state {
                 State::Idle(ctx) => {
-                    let ctx = ctx.take().expect("context must be valid");
-                    let fut = (this.future_fn)(ctx);
+                    this.retry_state.start_attempt();
+                    this.hooks.on_attempt(&this.retry_state);
+                    let ctx = match ctx.take() {
+                        Some(ctx) => ctx,
+                        // Build the context while starting the first attempt.
+                        None => this
+                            .ctx
+                            .take()
+                            .expect("context must be valid")
+                            .into_context(),
+                    };
+                    let fut = {
+                        let _current = CurrentAttempt::enter(&this.retry_state);
+                        this.hooks.enter();
+                        let res = (this.future_fn)(ctx);
+                        this.hooks.exit();
+                        res
+                    };
                     this.state = State::Polling(fut);
                     continue;
                 }
@@ -324,20 +1597,46 @@ where
                     // We do the exactly same thing like `pin_project` but without depending on it directly.
                     let mut fut = unsafe { Pin::new_unchecked(fut) };
 
-                    let (ctx, res) = ready!(fut.as_mut().poll(cx));
+                    let res = {
+                        let _current = CurrentAttempt::enter(&this.retry_state);
+                        this.hooks.enter();
+                        let res = fut.as_mut().poll(cx);
+                        this.hooks.exit();
+                        res
+                    };
+                    let (ctx, res) = ready!(res);
                     match res {
-                        Ok(v) => return Poll::Ready((ctx, Ok(v))),
+                        Ok(v) => {
+                            this.retry_state.succeed_attempt();
+                            this.hooks.on_success(&v, &this.retry_state);
+                            return Poll::Ready((ctx, Ok(v)));
+                        }
                         Err(err) => {
+                            let err = this.map_err.map_err(err);
                             // If input error is not retryable, return error directly.
-                            if !(this.retryable)(&err) {
-                                return Poll::Ready((ctx, Err(err)));
-                            }
-                            match this.backoff.next() {
-                                None => return Poll::Ready((ctx, Err(err))),
-                                Some(dur) => {
-                                    (this.notify)(&err, dur);
-                                    this.state =
-                                        State::Sleeping((Some(ctx), this.sleep_fn.sleep(dur)));
+                            match this.retry_state.retry_delay(
+                                err,
+                                &mut this.backoff,
+                                &mut this.retryable,
+                                &mut this.errors,
+                            ) {
+                                Err((err, reason)) => {
+                                    if let Some(v) = this.hooks.recover(&err) {
+                                        this.hooks.on_recovered(&err, reason, &this.retry_state);
+                                        return Poll::Ready((ctx, Ok(v)));
+                                    }
+                                    this.hooks.on_fatal(&err, reason, &this.retry_state);
+                                    return Poll::Ready((
+                                        ctx,
+                                        Err(this.errors.finish(err, reason)),
+                                    ));
+                                }
+                                Ok((err, dur)) => {
+                                    this.notify.notify(&err, dur, &ctx, &this.retry_state);
+                                    this.hooks.on_transient(&err, dur, &this.retry_state);
+                                    this.errors.collect(err, dur, &this.retry_state);
+                                    let sl = this.sleep_fn.sleep(dur);
+                                    this.state = State::Sleeping((Some(ctx), sl));
                                     continue;
                                 }
                             }
@@ -351,7 +1650,12 @@ where
                     // We do the exactly same thing like `pin_project` but without depending on it directly.
                     let mut sl = unsafe { Pin::new_unchecked(sl) };
 
-                    ready!(sl.as_mut().poll(cx));
+                    this.hooks.enter_sleep();
+                    let res = sl.as_mut().poll(cx);
+                    this.hooks.exit_sleep();
+                    ready!(res);
+
+                    this.hooks.on_resume(&this.retry_state);
                     let ctx = ctx.take().expect("context must be valid");
                     this.state = State::Idle(Some(ctx));
                     continue;
@@ -365,6 +1669,8 @@ where
 #[cfg(any(feature = "tokio-sleep", feature = "gloo-timers-sleep"))]
 mod tests {
     use alloc::string::ToString;
+    use alloc::vec;
+    use alloc::vec::Vec;
     use anyhow::{anyhow, Result};
     use core::time::Duration;
     use tokio::sync::Mutex;
@@ -416,4 +1722,67 @@ mod tests {
         assert_eq!(*error_times.lock().await, 1);
         Ok(())
     }
+
+    #[test]
+    async fn test_retry_with_context_unpin() {
+        let mut retry = (|attempts: usize| {
+            core::future::ready((attempts + 1, if attempts < 2 { Err(()) } else { Ok(()) }))
+        })
+        .retry(ExponentialBuilder::default())
+        .sleep(|_| core::future::ready(()))
+        .context(0);
+        // The futures of the attempts and the sleeps are `Unpin`, so is the retry.
+        let (attempts, result) = core::future::poll_fn(|cx| Pin::new(&mut retry).poll(cx)).await;
+        assert_eq!((attempts, result), (3, Ok(())));
+    }
+
+    #[test]
+    async fn test_retry_with_notify_with_ctx() -> Result<()> {
+        let mut notified: Vec<usize> = vec![];
+
+        let backoff = ExponentialBuilder::default().with_min_delay(Duration::from_millis(1));
+
+        let (_, result) = {
+            |mut v: usize| async move {
+                v += 1;
+                (v, Err::<(), anyhow::Error>(anyhow!("retryable")))
+            }
+        }
+        .retry(backoff)
+        .context(0)
+        .notify_with_ctx(|_, _, v: &usize| notified.push(*v))
+        .await;
+
+        assert!(result.is_err());
+        // The context is passed to notify after each failed attempt.
+        assert_eq!(notified, vec![1, 2, 3]);
+        Ok(())
+    }
+
+    #[test]
+    async fn test_retry_with_context_with() -> Result<()> {
+        let built = Mutex::new(0);
+
+        let backoff = ExponentialBuilder::default().with_min_delay(Duration::from_millis(1));
+
+        let retry = {
+            |mut v: usize| async move {
+                v += 1;
+                (v, Err::<(), anyhow::Error>(anyhow!("retryable")))
+            }
+        }
+        .retry(backoff)
+        .context_with(|| {
+            *built.try_lock().unwrap() += 1;
+            0
+        });
+        // The context is not built until the retry is polled.
+        assert_eq!(*built.lock().await, 0);
+
+        let (ctx, result) = retry.await;
+        assert!(result.is_err());
+        assert_eq!(ctx, 4);
+        assert_eq!(*built.lock().await, 1);
+        Ok(())
+    }
 }
@@ -0,0 +1,1698 @@
+use alloc::borrow::Cow;
+use alloc::boxed::Box;
+use core::any;
+use core::fmt;
+use core::future::Future;
+use core::marker::PhantomData;
+use core::ops::ControlFlow;
+use core::pin::Pin;
+use core::task::ready;
+use core::task::Context;
+use core::task::Poll;
+use core::time::Duration;
+
+use crate::backoff::BackoffBuilder;
+use crate::clock::SharedClock;
+#[cfg(feature = "anyhow")]
+use crate::error::WhenDowncast;
+use crate::error::{
+    BackoffBy, Classify, ErrorClassifier, ErrorCollector, LastError, MapErrBefore, MapError,
+    ReduceErrors, SelectError, Unmapped, UnwrapPermanent, WhenRetryableError, WhenWithState,
+    WrapRetryError,
+};
+#[cfg(feature = "defmt")]
+use crate::hooks::Defmt;
+#[cfg(feature = "logging")]
+use crate::hooks::Log;
+#[cfg(feature = "opentelemetry")]
+use crate::hooks::OpenTelemetry;
+#[cfg(feature = "sentry")]
+use crate::hooks::Sentry;
+#[cfg(feature = "slog")]
+use crate::hooks::Slog;
+#[cfg(feature = "tracing")]
+use crate::hooks::Trace;
+use crate::hooks::{
+    AttemptTimeout, Chain, Hooks, Metrics, NoHooks, Observe, OnBackoffExhausted, OnFatal, OnStart,
+    OnTransient, Recover,
+};
+use crate::report::Reported;
+use crate::retry::NotifyWithState;
+use crate::retry_with_context::{NotifyFnWithContext, NotifyWithContext, PleaseProvideAContext};
+use crate::sleep::MaybeSleeper;
+use crate::state::CurrentAttempt;
+use crate::AttemptTimedOut;
+use crate::Backoff;
+use crate::BackoffChoice;
+use crate::BudgetWarning;
+use crate::Clock;
+use crate::Deadline;
+use crate::DefaultSleeper;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+use crate::FailureBudget;
+use crate::FinalError;
+use crate::GiveUpReason;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+use crate::RetryBudget;
+use crate::RetryEvent;
+use crate::RetryMetrics;
+use crate::RetrySnapshot;
+use crate::RetryState;
+use crate::RetryableError;
+use crate::ScheduleSummary;
+use crate::Sleeper;
+use crate::WithReport;
+
+/// `RetryableWithMutContext` adds retry support for functions that borrow a context mutably
+/// and produce futures with results.
+///
+/// This means all types implementing `for<'a> FnMut(&'a mut Ctx) -> impl Future<Output = Result<T, E>> + 'a`
+/// can use `retry`, including `async fn`s that take `&mut Ctx` as their only argument.
+///
+/// Unlike [`RetryableWithContext`][crate::RetryableWithContext], the function doesn't need to
+/// hand the context back after every attempt. The context stays owned by the caller and is lent
+/// to each attempt in turn.
+///
+/// # Example
+///
+/// ```no_run
+/// use anyhow::anyhow;
+/// use anyhow::Result;
+/// use backon::ExponentialBuilder;
+/// use backon::RetryableWithMutContext;
+///
+/// struct Test;
+///
+/// impl Test {
+///     async fn hello(&mut self) -> Result<usize> {
+///         Err(anyhow!("not retryable"))
+///     }
+/// }
+///
+/// async fn hello(v: &mut Test) -> Result<usize> {
+///     v.hello().await
+/// }
+///
+/// #[tokio::main(flavor = "current_thread")]
+/// async fn main() -> Result<()> {
+///     let mut test = Test;
+///
+///     let result = hello
+///         .retry(ExponentialBuilder::default())
+///         .context(&mut test)
+///         .await;
+///
+///     Ok(())
+/// }
+/// ```
+pub trait RetryableWithMutContext<
+    'c,
+    B: BackoffBuilder,
+    T,
+    E,
+    Ctx: 'c,
+    FutureFn: for<'a> FutureFnWithMutContext<'a, Ctx, T, E>,
+>
+{
+    /// Generate a new retry
+    fn retry(self, builder: B) -> RetryWithMutContext<'c, B::Backoff, T, E, Ctx, FutureFn>;
+}
+
+impl<'c, B, T, E, Ctx, FutureFn> RetryableWithMutContext<'c, B, T, E, Ctx, FutureFn> for FutureFn
+where
+    B: BackoffBuilder,
+    Ctx: 'c,
+    FutureFn: for<'a> FutureFnWithMutContext<'a, Ctx, T, E>,
+{
+    fn retry(self, builder: B) -> RetryWithMutContext<'c, B::Backoff, T, E, Ctx, FutureFn> {
+        RetryWithMutContext::new(self, builder.build())
+    }
+}
+
+/// A stub trait for `for<'a> FnMut(&'a mut Ctx) -> impl Future<Output = Result<T, E>> + 'a`.
+///
+/// It allows the returned future to borrow the context. Retries require it for every `'a`, so
+/// the function can't keep the borrow of an attempt past the future of that attempt. The
+/// defaulted parameter implies `Ctx: 'c`, so contexts borrowing data are accepted.
+///
+/// ```compile_fail
+/// use backon::ExponentialBuilder;
+/// use backon::RetryableWithMutContext;
+///
+/// // Keeps the context lent to every attempt.
+/// fn keep<'k, 'c>(
+///     kept: &'k mut Vec<&'c mut u32>,
+/// ) -> impl FnMut(&'c mut u32) -> core::future::Ready<Result<(), ()>> + 'k {
+///     move |ctx| {
+///         kept.push(ctx);
+///         core::future::ready(Err(()))
+///     }
+/// }
+///
+/// let mut kept = Vec::new();
+/// let mut ctx = 0;
+/// let _ = keep(&mut kept)
+///     .retry(ExponentialBuilder::default())
+///     .context(&mut ctx);
+/// ```
+#[doc(hidden)]
+pub trait FutureFnWithMutContext<'c, Ctx, T, E, _Outlives = &'c mut Ctx> {
+    type Future: Future<Output = Result<T, E>>;
+
+    fn call(&mut self, ctx: &'c mut Ctx) -> Self::Future;
+}
+
+impl<'c, Ctx, T, E, F, Fut> FutureFnWithMutContext<'c, Ctx, T, E> for F
+where
+    F: FnMut(&'c mut Ctx) -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    type Future = Fut;
+
+    fn call(&mut self, ctx: &'c mut Ctx) -> Self::Future {
+        self(ctx)
+    }
+}
+
+/// Retry struct generated by [`RetryableWithMutContext`].
+///
+/// It's [`Unpin`] as long as the futures of its attempts and its sleeps are.
+pub struct RetryWithMutContext<
+    'c,
+    B: Backoff,
+    T,
+    E,
+    Ctx: 'c,
+    FutureFn: for<'a> FutureFnWithMutContext<'a, Ctx, T, E>,
+    SF: MaybeSleeper = DefaultSleeper,
+    RF = fn(&E) -> bool,
+    NF = fn(&E, Duration),
+    EC = LastError,
+    ME = Unmapped,
+    HK = NoHooks,
+    CS = PleaseProvideAContext,
+> {
+    backoff: B,
+    retryable: RF,
+    notify: NF,
+    errors: EC,
+    map_err: ME,
+    hooks: HK,
+    retry_state: RetryState,
+    future_fn: FutureFn,
+    sleep_fn: SF,
+    ctx: CS,
+    _ctx: PhantomData<&'c mut Ctx>,
+
+    state: State<<FutureFn as FutureFnWithMutContext<'c, Ctx, T, E>>::Future, SF::Sleep>,
+}
+
+impl<'c, B, T, E, Ctx, FutureFn, SF, RF, NF, EC, ME, HK, CS>
+    RetryWithMutContext<'c, B, T, E, Ctx, FutureFn, SF, RF, NF, EC, ME, HK, CS>
+where
+    B: Backoff,
+    Ctx: 'c,
+    FutureFn: for<'a> FutureFnWithMutContext<'a, Ctx, T, E>,
+    SF: MaybeSleeper,
+{
+    /// Returns the index of the current attempt, starting from 1 for the first attempt, or 0
+    /// before the retry starts.
+    pub fn attempt(&self) -> usize {
+        self.retry_state.attempt()
+    }
+
+    /// Returns the time elapsed since the first attempt started.
+    pub fn elapsed(&self) -> Duration {
+        self.retry_state.elapsed()
+    }
+
+    /// Capture the progress of the retry and the state of its backoff, to be restored by
+    /// `resume_from`.
+    pub fn snapshot(&self) -> RetrySnapshot<B>
+    where
+        B: Clone,
+    {
+        RetrySnapshot::new(self.retry_state.attempt(), self.backoff.clone())
+            .with_elapsed(self.retry_state.elapsed())
+    }
+}
+
+impl<'c, B, T, E, Ctx, FutureFn, SF, RF, NF, EC, ME, HK, CS> fmt::Debug
+    for RetryWithMutContext<'c, B, T, E, Ctx, FutureFn, SF, RF, NF, EC, ME, HK, CS>
+where
+    B: Backoff,
+    Ctx: 'c,
+    FutureFn: for<'a> FutureFnWithMutContext<'a, Ctx, T, E>,
+    SF: MaybeSleeper,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RetryWithMutContext")
+            .field("backoff", &any::type_name::<B>())
+            .field("attempt", &self.retry_state.attempt())
+            .field("next_delay", &self.retry_state.next_delay())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'c, B, T, E, Ctx, FutureFn> RetryWithMutContext<'c, B, T, E, Ctx, FutureFn>
+where
+    B: Backoff,
+    Ctx: 'c,
+    FutureFn: for<'a> FutureFnWithMutContext<'a, Ctx, T, E>,
+{
+    /// Create a new retry.
+    fn new(future_fn: FutureFn, backoff: B) -> Self {
+        RetryWithMutContext {
+            backoff,
+            retryable: |_: &E| true,
+            notify: |_: &E, _: Duration| {},
+            errors: LastError,
+            map_err: Unmapped,
+            hooks: NoHooks,
+            retry_state: RetryState::new(),
+            future_fn,
+            sleep_fn: DefaultSleeper::default(),
+            ctx: PleaseProvideAContext,
+            _ctx: PhantomData,
+            state: State::Idle,
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+impl<'c, B, T, E, Ctx, FutureFn, SF, RF, NF, EC, ME, HK, CS>
+    RetryWithMutContext<'c, B, T, E, Ctx, FutureFn, SF, RF, NF, EC, ME, HK, CS>
+where
+    B: Backoff,
+    Ctx: 'c,
+    FutureFn: for<'a> FutureFnWithMutContext<'a, Ctx, T, E>,
+    SF: MaybeSleeper,
+    RF: ErrorClassifier<ME::Error>,
+    NF: NotifyWithContext<ME::Error, Ctx>,
+    HK: Hooks<T, ME::Error>,
+    ME: MapError<E>,
+    EC: ErrorCollector<ME::Error>,
+{
+    /// Set the sleeper for retrying.
+    ///
+    /// The sleeper should implement the [`Sleeper`] trait. The simplest way is to use a closure that returns a `Future<Output=()>`.
+    ///
+    /// If not specified, we use the [`DefaultSleeper`].
+    pub fn sleep<SN: Sleeper>(
+        self,
+        sleep_fn: SN,
+    ) -> RetryWithMutContext<'c, B, T, E, Ctx, FutureFn, SN, RF, NF, EC, ME, HK, CS> {
+        RetryWithMutContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn,
+            ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+            state: State::Idle,
+        }
+    }
+
+    /// Set the context for retrying.
+    ///
+    /// The context is borrowed mutably by every attempt and stays owned by the caller.
+    pub fn context(
+        self,
+        context: &'c mut Ctx,
+    ) -> RetryWithMutContext<'c, B, T, E, Ctx, FutureFn, SF, RF, NF, EC, ME, HK, &'c mut Ctx> {
+        RetryWithMutContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: context,
+            _ctx: PhantomData,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+            state: self.state,
+        }
+    }
+    /// Set the function mapping the errors of the retried function before they are passed to
+    /// the conditions and notify.
+    ///
+    /// This allows reusing the same conditions for functions returning differently wrapped
+    /// errors. It resets the conditions, notify and error handling set before, so it must be
+    /// called first.
+    pub fn map_err_before<E2, MN: FnMut(E) -> E2>(
+        self,
+        map_err: MN,
+    ) -> RetryWithMutContext<
+        'c,
+        B,
+        T,
+        E,
+        Ctx,
+        FutureFn,
+        SF,
+        fn(&E2) -> bool,
+        fn(&E2, Duration),
+        LastError,
+        MapErrBefore<MN>,
+        NoHooks,
+        CS,
+    > {
+        RetryWithMutContext {
+            backoff: self.backoff,
+            retryable: |_: &E2| true,
+            notify: |_: &E2, _: Duration| {},
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: LastError,
+            retry_state: self.retry_state,
+            map_err: MapErrBefore(map_err),
+            hooks: NoHooks,
+            state: self.state,
+        }
+    }
+
+    /// Set the conditions for retrying.
+    ///
+    /// If not specified, all errors are considered retryable.
+    pub fn when<RN: FnMut(&ME::Error) -> bool>(
+        self,
+        retryable: RN,
+    ) -> RetryWithMutContext<'c, B, T, E, Ctx, FutureFn, SF, RN, NF, EC, ME, HK, CS> {
+        RetryWithMutContext {
+            backoff: self.backoff,
+            retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+            state: self.state,
+        }
+    }
+
+    /// Set the conditions for retrying with access to the [`RetryState`].
+    ///
+    /// The state tells the index of the failed attempt and the delay slept before it, the
+    /// backoff only proposing the next delay once the error is retried. This replaces the
+    /// conditions set by `when`.
+    pub fn when_with_state<RN: FnMut(&ME::Error, &RetryState) -> bool>(
+        self,
+        retryable: RN,
+    ) -> RetryWithMutContext<'c, B, T, E, Ctx, FutureFn, SF, WhenWithState<RN>, NF, EC, ME, HK, CS>
+    {
+        RetryWithMutContext {
+            backoff: self.backoff,
+            retryable: WhenWithState(retryable),
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+            state: self.state,
+        }
+    }
+
+    /// Set the conditions for retrying with the error passed by value.
+    ///
+    /// The function returns [`ControlFlow::Continue`] with the error to retry, or
+    /// [`ControlFlow::Break`] with the error to end the retry with. So errors can be enriched
+    /// on every attempt, for example with the attempt index from [`RetryState`]. This replaces
+    /// the conditions set by `when`.
+    pub fn classify<RN: FnMut(ME::Error, &RetryState) -> ControlFlow<ME::Error, ME::Error>>(
+        self,
+        retryable: RN,
+    ) -> RetryWithMutContext<'c, B, T, E, Ctx, FutureFn, SF, Classify<RN>, NF, EC, ME, HK, CS> {
+        RetryWithMutContext {
+            backoff: self.backoff,
+            retryable: Classify(retryable),
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+            state: self.state,
+        }
+    }
+
+    /// Set the conditions for retrying by the error's [`RetryableError`] implementation.
+    ///
+    /// The delay suggested by [`RetryableError::retry_after`] is used instead of the backoff's
+    /// delay if present. This replaces the conditions set by `when`.
+    pub fn when_retryable(
+        self,
+    ) -> RetryWithMutContext<'c, B, T, E, Ctx, FutureFn, SF, WhenRetryableError, NF, EC, ME, HK, CS>
+    where
+        ME::Error: RetryableError,
+    {
+        RetryWithMutContext {
+            backoff: self.backoff,
+            retryable: WhenRetryableError,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+            state: self.state,
+        }
+    }
+
+    /// Set the function choosing the backoff of every error, see [`BackoffChoice`].
+    ///
+    /// Errors of the class returned by the function are retried with its own backoff,
+    /// independently of the other classes, and those returning [`BackoffChoice::Default`] with
+    /// the backoff of the retry. It applies to the conditions set before, so it must be called
+    /// after `when` and the like.
+    pub fn backoff_by<BB: BackoffBuilder, BN: FnMut(&ME::Error) -> BackoffChoice<BB>>(
+        self,
+        choose: BN,
+    ) -> RetryWithMutContext<
+        'c,
+        B,
+        T,
+        E,
+        Ctx,
+        FutureFn,
+        SF,
+        BackoffBy<RF, BN, BB>,
+        NF,
+        EC,
+        ME,
+        HK,
+        CS,
+    > {
+        RetryWithMutContext {
+            backoff: self.backoff,
+            retryable: BackoffBy::new(self.retryable, choose),
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+            state: self.state,
+        }
+    }
+
+    /// Set the conditions for retrying [`anyhow::Error`]s caused by `X`.
+    ///
+    /// The whole chain of the error is inspected, so errors with context attached are retried
+    /// as well. This replaces the conditions set by `when`.
+    #[cfg(feature = "anyhow")]
+    pub fn when_downcast<X: core::error::Error + 'static>(
+        self,
+    ) -> RetryWithMutContext<'c, B, T, E, Ctx, FutureFn, SF, WhenDowncast<X>, NF, EC, ME, HK, CS>
+    where
+        ME: MapError<E, Error = anyhow::Error>,
+    {
+        RetryWithMutContext {
+            backoff: self.backoff,
+            retryable: WhenDowncast::new(),
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+            state: self.state,
+        }
+    }
+
+    /// Set the function reducing the errors of all attempts into the returned one.
+    ///
+    /// The function is called with the reduced error so far and the error of the next attempt.
+    /// If not specified, the error of the last attempt is returned.
+    ///
+    /// This replaces the previous reducer, and must be set before `wrap_err`.
+    pub fn reduce_errors<RN: FnMut(ME::Error, ME::Error) -> ME::Error>(
+        self,
+        reduce: RN,
+    ) -> RetryWithMutContext<
+        'c,
+        B,
+        T,
+        E,
+        Ctx,
+        FutureFn,
+        SF,
+        RF,
+        NF,
+        ReduceErrors<ME::Error, RN>,
+        ME,
+        HK,
+        CS,
+    >
+    where
+        EC: ErrorCollector<ME::Error, Error = ME::Error>,
+    {
+        RetryWithMutContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: ReduceErrors::new(reduce),
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+            state: self.state,
+        }
+    }
+
+    /// Set the policy selecting the error returned when the retry ends unsuccessfully.
+    ///
+    /// If not specified, the error of the last attempt is returned. See [`FinalError`] for
+    /// the policies.
+    ///
+    /// This replaces the previous reducer, and must be set before `wrap_err`.
+    pub fn final_error(
+        self,
+        policy: FinalError<ME::Error>,
+    ) -> RetryWithMutContext<
+        'c,
+        B,
+        T,
+        E,
+        Ctx,
+        FutureFn,
+        SF,
+        RF,
+        NF,
+        SelectError<ME::Error>,
+        ME,
+        HK,
+        CS,
+    >
+    where
+        EC: ErrorCollector<ME::Error, Error = ME::Error>,
+    {
+        RetryWithMutContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: SelectError::new(policy),
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+            state: self.state,
+        }
+    }
+
+    /// End the retry at errors wrapped by [`non_retryable`](crate::non_retryable), and unwrap
+    /// the returned [`Permanent`](crate::Permanent) error.
+    ///
+    /// Permanent errors end the retry regardless of the conditions set by `when`.
+    pub fn unwrap_permanent(
+        self,
+    ) -> RetryWithMutContext<'c, B, T, E, Ctx, FutureFn, SF, RF, NF, UnwrapPermanent<EC>, ME, HK, CS>
+    where
+        UnwrapPermanent<EC>: ErrorCollector<ME::Error>,
+    {
+        RetryWithMutContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: UnwrapPermanent(self.errors),
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+            state: self.state,
+        }
+    }
+
+    /// Wrap the error that ends the retry into a [`RetryError`](crate::RetryError).
+    ///
+    /// The wrapper records the number of attempts made and keeps the original error as its
+    /// [`source`](core::error::Error::source).
+    pub fn wrap_err(
+        self,
+    ) -> RetryWithMutContext<
+        'c,
+        B,
+        T,
+        E,
+        Ctx,
+        FutureFn,
+        SF,
+        RF,
+        NF,
+        WrapRetryError<EC, EC::Error>,
+        ME,
+        HK,
+        CS,
+    > {
+        RetryWithMutContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: WrapRetryError::new(self.errors, 0),
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+            state: self.state,
+        }
+    }
+
+    /// Wrap the error that ends the retry into a [`RetryError`](crate::RetryError), keeping
+    /// the history of up to `cap` most recent failed attempts.
+    ///
+    /// Every entry of the history records the error, the delay slept after it and when it
+    /// happened, so the failure timeline can be reported once the retry ends.
+    pub fn wrap_err_with_history(
+        self,
+        cap: usize,
+    ) -> RetryWithMutContext<
+        'c,
+        B,
+        T,
+        E,
+        Ctx,
+        FutureFn,
+        SF,
+        RF,
+        NF,
+        WrapRetryError<EC, EC::Error>,
+        ME,
+        HK,
+        CS,
+    > {
+        RetryWithMutContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: WrapRetryError::new(self.errors, cap),
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+            state: self.state,
+        }
+    }
+
+    /// Set to notify for all retry attempts.
+    ///
+    /// When a retry happens, the input function will be invoked with the error and the sleep duration before pausing.
+    ///
+    /// If not specified, this operation does nothing.
+    pub fn notify<NN: FnMut(&ME::Error, Duration)>(
+        self,
+        notify: NN,
+    ) -> RetryWithMutContext<'c, B, T, E, Ctx, FutureFn, SF, RF, NN, EC, ME, HK, CS> {
+        RetryWithMutContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+            state: self.state,
+        }
+    }
+
+    /// Set the function called once before the first attempt starts.
+    ///
+    /// It's called in addition to the functions set before, so per-call setup like starting
+    /// a timer or logging the operation can live in the retry.
+    pub fn on_start<ON: FnMut(&RetryState)>(
+        self,
+        f: ON,
+    ) -> RetryWithMutContext<
+        'c,
+        B,
+        T,
+        E,
+        Ctx,
+        FutureFn,
+        SF,
+        RF,
+        NF,
+        EC,
+        ME,
+        Chain<HK, OnStart<ON>>,
+        CS,
+    > {
+        RetryWithMutContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, OnStart(f)),
+            state: self.state,
+        }
+    }
+
+    /// Set to notify for all retry attempts with access to the context.
+    ///
+    /// When a retry happens, the input function will be invoked with the error, the sleep duration
+    /// and the context before pausing. This replaces the function set by [`RetryWithMutContext::notify`].
+    ///
+    /// If not specified, this operation does nothing.
+    pub fn notify_with_ctx<NN: FnMut(&ME::Error, Duration, &Ctx)>(
+        self,
+        notify: NN,
+    ) -> RetryWithMutContext<
+        'c,
+        B,
+        T,
+        E,
+        Ctx,
+        FutureFn,
+        SF,
+        RF,
+        NotifyFnWithContext<NN>,
+        EC,
+        ME,
+        HK,
+        CS,
+    > {
+        RetryWithMutContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: NotifyFnWithContext(notify),
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+            state: self.state,
+        }
+    }
+
+    /// Set to notify for all retry attempts with access to the [`RetryState`].
+    ///
+    /// The state tells the session ID of the retry and the index of the failed attempt, so
+    /// the notifications can be correlated with the logs of the retried operation. This
+    /// replaces the function set by `notify`.
+    pub fn notify_with_state<NN: FnMut(&ME::Error, Duration, &RetryState)>(
+        self,
+        notify: NN,
+    ) -> RetryWithMutContext<'c, B, T, E, Ctx, FutureFn, SF, RF, NotifyWithState<NN>, EC, ME, HK, CS>
+    {
+        RetryWithMutContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: NotifyWithState(notify),
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+            state: self.state,
+        }
+    }
+
+    /// Set the function called with every error that will be retried and the delay before
+    /// the next attempt.
+    ///
+    /// It's called after `notify`, and in addition to the functions set before.
+    pub fn on_transient_error<TN: FnMut(&ME::Error, Duration)>(
+        self,
+        f: TN,
+    ) -> RetryWithMutContext<
+        'c,
+        B,
+        T,
+        E,
+        Ctx,
+        FutureFn,
+        SF,
+        RF,
+        NF,
+        EC,
+        ME,
+        Chain<HK, OnTransient<TN>>,
+        CS,
+    > {
+        RetryWithMutContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, OnTransient(f)),
+            state: self.state,
+        }
+    }
+
+    /// Set the function called with the error that ends the retry unsuccessfully and the
+    /// reason why.
+    ///
+    /// Both non-retryable errors and errors left when the backoff is exhausted end the retry.
+    /// It's called in addition to the functions set before.
+    pub fn on_fatal_error<FN: FnMut(&ME::Error, GiveUpReason)>(
+        self,
+        f: FN,
+    ) -> RetryWithMutContext<
+        'c,
+        B,
+        T,
+        E,
+        Ctx,
+        FutureFn,
+        SF,
+        RF,
+        NF,
+        EC,
+        ME,
+        Chain<HK, OnFatal<FN>>,
+        CS,
+    > {
+        RetryWithMutContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, OnFatal(f)),
+            state: self.state,
+        }
+    }
+
+    /// Set the function called when the retry gives up because the backoff is exhausted,
+    /// with the last error and the [`ScheduleSummary`] of the delays planned and slept.
+    ///
+    /// Unlike `on_fatal_error` it's not called for errors that are not retryable, so an
+    /// exhausted policy can be told apart from a fatal error.
+    pub fn on_backoff_exhausted<BN: FnMut(&ME::Error, &ScheduleSummary)>(
+        self,
+        f: BN,
+    ) -> RetryWithMutContext<
+        'c,
+        B,
+        T,
+        E,
+        Ctx,
+        FutureFn,
+        SF,
+        RF,
+        NF,
+        EC,
+        ME,
+        Chain<HK, OnBackoffExhausted<BN>>,
+        CS,
+    > {
+        RetryWithMutContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, OnBackoffExhausted::new(f)),
+            state: self.state,
+        }
+    }
+
+    /// Set the function recovering from the error that ends the retry unsuccessfully.
+    ///
+    /// Both non-retryable errors and errors left when the backoff is exhausted are passed to
+    /// it, and the value it returns is returned as the result of the retry instead. Errors
+    /// that are not recovered are passed on to `on_fatal_error` and returned as usual.
+    ///
+    /// Recovered retries still gave up: their metrics, events and traces record them as
+    /// giving up, only `on_fatal_error` isn't called.
+    pub fn recover<RN: FnMut(&ME::Error) -> Option<T>>(
+        self,
+        f: RN,
+    ) -> RetryWithMutContext<
+        'c,
+        B,
+        T,
+        E,
+        Ctx,
+        FutureFn,
+        SF,
+        RF,
+        NF,
+        EC,
+        ME,
+        Chain<HK, Recover<RN>>,
+        CS,
+    > {
+        RetryWithMutContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, Recover(f)),
+            state: self.state,
+        }
+    }
+
+    /// Set the [`RetryMetrics`] observing the progress of the retry.
+    ///
+    /// It's called in addition to the metrics set before.
+    pub fn metrics<MN: RetryMetrics>(
+        self,
+        metrics: MN,
+    ) -> RetryWithMutContext<
+        'c,
+        B,
+        T,
+        E,
+        Ctx,
+        FutureFn,
+        SF,
+        RF,
+        NF,
+        EC,
+        ME,
+        Chain<HK, Metrics<MN>>,
+        CS,
+    > {
+        RetryWithMutContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, Metrics(metrics)),
+            state: self.state,
+        }
+    }
+
+    /// Set the function observing every step of the retry as a [`RetryEvent`].
+    ///
+    /// It's called in addition to the other hooks and observers set before, so a single
+    /// function can follow the whole lifecycle of the retry. Its events are no longer delivered
+    /// to the observer set by `set_global_observer`.
+    pub fn observe<FN: FnMut(RetryEvent<'_, ME::Error>)>(
+        mut self,
+        f: FN,
+    ) -> RetryWithMutContext<
+        'c,
+        B,
+        T,
+        E,
+        Ctx,
+        FutureFn,
+        SF,
+        RF,
+        NF,
+        EC,
+        ME,
+        Chain<HK, Observe<FN>>,
+        CS,
+    > {
+        self.retry_state.set_observed();
+        RetryWithMutContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, Observe(f)),
+            state: self.state,
+        }
+    }
+
+    /// Record the retry in a `tracing` span named `retry`, with the `operation` and
+    /// `session_id` fields set.
+    ///
+    /// The retried function runs inside the span, and every sleep inside a child span named
+    /// `sleep` with the `operation`, `attempt` and `delay` fields, so subscribers show what a
+    /// waiting retry backs off on. Failed attempts are emitted as `WARN` events, sleeps,
+    /// resumes and successes as `DEBUG` events and giving up as an `ERROR` event, with the
+    /// attempt number, delay, error and reason as fields.
+    #[cfg(feature = "tracing")]
+    pub fn trace(
+        self,
+        operation: &'static str,
+    ) -> RetryWithMutContext<'c, B, T, E, Ctx, FutureFn, SF, RF, NF, EC, ME, Chain<HK, Trace>, CS>
+    where
+        ME::Error: fmt::Display,
+    {
+        RetryWithMutContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, Trace::new(operation)),
+            state: self.state,
+        }
+    }
+
+    /// Record the retry on the current OpenTelemetry span and in OpenTelemetry metrics, with
+    /// the `operation.name` attribute set to `operation`.
+    ///
+    /// Every failed attempt is added to the span active when this is called as a
+    /// `retry.attempt_failed` event and giving up as a `retry.gave_up` event, carrying the
+    /// `retry.attempt`, `retry.delay`, `error.type` and `exception.message` attributes. The
+    /// `backon.retry.attempts`, `backon.retry.retries` and `backon.retry.giveups` counters and
+    /// the `backon.retry.sleep.duration` histogram are reported to the global meter provider.
+    #[cfg(feature = "opentelemetry")]
+    pub fn opentelemetry(
+        self,
+        operation: &'static str,
+    ) -> RetryWithMutContext<
+        'c,
+        B,
+        T,
+        E,
+        Ctx,
+        FutureFn,
+        SF,
+        RF,
+        NF,
+        EC,
+        ME,
+        Chain<HK, OpenTelemetry>,
+        CS,
+    >
+    where
+        ME::Error: fmt::Display,
+    {
+        RetryWithMutContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, OpenTelemetry::new(operation)),
+            state: self.state,
+        }
+    }
+
+    /// Log the errors that will be retried as warnings and the error that ends the retry as
+    /// an error with `defmt`, for embedded targets logging over RTT.
+    #[cfg(feature = "defmt")]
+    pub fn defmt(
+        self,
+    ) -> RetryWithMutContext<'c, B, T, E, Ctx, FutureFn, SF, RF, NF, EC, ME, Chain<HK, Defmt>, CS>
+    where
+        ME::Error: defmt::Format,
+    {
+        RetryWithMutContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, Defmt),
+            state: self.state,
+        }
+    }
+
+    /// Record every error that will be retried and the error that ends the retry as Sentry
+    /// breadcrumbs in the `retry` category, with the attempt, the delay and the error message.
+    ///
+    /// When the retry gives up, the number of attempts is set as the `retry.attempts` tag of
+    /// the current scope, so the error reported afterwards shows the history of the retry.
+    #[cfg(feature = "sentry")]
+    pub fn sentry(
+        self,
+    ) -> RetryWithMutContext<'c, B, T, E, Ctx, FutureFn, SF, RF, NF, EC, ME, Chain<HK, Sentry>, CS>
+    where
+        ME::Error: fmt::Display,
+    {
+        RetryWithMutContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, Sentry),
+            state: self.state,
+        }
+    }
+
+    /// Log the errors that will be retried at `retry_level` and the error that ends the
+    /// retry at `give_up_level` with the `log` crate, under the `backon` target.
+    #[cfg(feature = "logging")]
+    pub fn log(
+        self,
+        retry_level: log::Level,
+        give_up_level: log::Level,
+    ) -> RetryWithMutContext<'c, B, T, E, Ctx, FutureFn, SF, RF, NF, EC, ME, Chain<HK, Log>, CS>
+    where
+        ME::Error: fmt::Display,
+    {
+        RetryWithMutContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, Log::new(retry_level, give_up_level)),
+            state: self.state,
+        }
+    }
+
+    /// Log the errors that will be retried as warnings and the error that ends the retry as
+    /// an error to `logger`, with the attempt number, delay, error and reason as key-value
+    /// pairs.
+    #[cfg(feature = "slog")]
+    pub fn slog(
+        self,
+        logger: slog::Logger,
+    ) -> RetryWithMutContext<'c, B, T, E, Ctx, FutureFn, SF, RF, NF, EC, ME, Chain<HK, Slog>, CS>
+    where
+        ME::Error: fmt::Display,
+    {
+        RetryWithMutContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, Slog::new(logger)),
+            state: self.state,
+        }
+    }
+
+    /// Set whether the retried operation is idempotent, `true` by default.
+    ///
+    /// Operations that are not idempotent, like writes, are only retried at errors telling
+    /// the operation had no effect by [`RetryableError::is_safe_to_retry`], so this works
+    /// together with `when_retryable`. Other errors end the retry with
+    /// [`GiveUpReason::Ambiguous`] instead of risking applying the operation twice.
+    pub fn idempotent(mut self, idempotent: bool) -> Self {
+        self.retry_state.set_idempotent(idempotent);
+        self
+    }
+
+    /// Set the name of the retried operation, like `s3_put_object`.
+    ///
+    /// It's included in the events passed to `observe`, the [`RetryReport`](crate::RetryReport)
+    /// and the output of the `tracing` and `opentelemetry` integrations, so they can be
+    /// aggregated per operation.
+    pub fn name(mut self, name: &'static str) -> Self {
+        self.retry_state.set_name(name);
+        self
+    }
+
+    /// Add a key-value tag describing the retried operation, included wherever the name set
+    /// by `name` is.
+    pub fn tag(mut self, key: &'static str, value: impl Into<Cow<'static, str>>) -> Self {
+        self.retry_state.add_tag(key, value.into());
+        self
+    }
+
+    /// Continue a retry from a [`RetrySnapshot`] taken by `snapshot`, like one persisted
+    /// before the process restarted.
+    ///
+    /// The backoff of the snapshot replaces the one passed to `retry`, so the delays pick up
+    /// where they were instead of starting over, and the attempts and elapsed time count from
+    /// the snapshot. The next attempt starts right away.
+    pub fn resume_from<S: Backoff>(
+        mut self,
+        snapshot: RetrySnapshot<S>,
+    ) -> RetryWithMutContext<'c, S, T, E, Ctx, FutureFn, SF, RF, NF, EC, ME, HK, CS> {
+        self.retry_state.resume(&snapshot);
+        RetryWithMutContext {
+            backoff: snapshot.into_backoff(),
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: self.hooks,
+            state: self.state,
+        }
+    }
+
+    /// Set the [`Clock`] measuring the elapsed time and the latency of the attempts, reported
+    /// by [`RetryState`], the events passed to `observe` and the report.
+    ///
+    /// It defaults to [`StdClock`](crate::StdClock) with the `std` feature outside of
+    /// `wasm32`. Without it, the retry isn't timed unless a clock is set.
+    pub fn clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.retry_state.set_clock(SharedClock::new(clock));
+        self
+    }
+
+    /// Seed the random numbers of the retry, the jitter of its backoff and its ID, instead of
+    /// drawing them from the thread's RNG.
+    ///
+    /// Together with a [`Clock`] set by `clock`, the delays, the events and the report of the
+    /// retry are the same on every run, so tests can assert on them exactly.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.retry_state.set_seed(seed);
+        self
+    }
+
+    /// Panic if the retry makes more than `max_attempts` attempts or sleeps more than
+    /// `max_total_sleep` in total, so tests assert a code path never retries more than it
+    /// should, like "this call is retried at most twice".
+    ///
+    /// The panic happens before the retry over the budget sleeps. Only available with the
+    /// `test-util` feature.
+    #[cfg(feature = "test-util")]
+    pub fn with_test_budget(mut self, max_attempts: usize, max_total_sleep: Duration) -> Self {
+        self.retry_state
+            .set_test_budget(max_attempts, max_total_sleep);
+        self
+    }
+
+    /// Set the ID identifying this retry in logs and events, returned by
+    /// [`RetryState::session_id`].
+    ///
+    /// A random ID is generated when the retry is built if it's not set, the same for all its
+    /// attempts. The retried operation reads it with `current_attempt`, so its logs can be
+    /// correlated with the events of the retry.
+    pub fn session_id(mut self, id: u64) -> Self {
+        self.retry_state.set_session_id(id);
+        self
+    }
+
+    /// Set the [`BudgetWarning`] raised to the function set by `observe` once a fraction of
+    /// the attempts or time of the retry is consumed.
+    pub fn budget_warning(mut self, warning: BudgetWarning) -> Self {
+        self.retry_state.set_budget_warning(warning);
+        self
+    }
+
+    /// Set the most time the retry sleeps in total between attempts, combined with the
+    /// limits of the backoff like its maximum number of retries.
+    ///
+    /// The retry gives up with [`GiveUpReason::MaxTotalSleepReached`] instead of sleeping past
+    /// it, telling it apart from [`GiveUpReason::BackoffExhausted`] if the backoff runs out
+    /// first.
+    pub fn max_total_sleep(mut self, max: Duration) -> Self {
+        self.retry_state.set_max_total_sleep(max);
+        self
+    }
+
+    /// Attach the [`FailureBudget`] shared with other retries.
+    ///
+    /// Every attempt failed with a retryable error is recorded in the budget, and the retry
+    /// ends at the next one while the budget is exhausted.
+    #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+    pub fn failure_budget(mut self, budget: FailureBudget) -> Self {
+        self.retry_state.set_failure_budget(budget);
+        self
+    }
+
+    /// Attach the [`RetryBudget`] shared with other retries.
+    ///
+    /// Every successful attempt deposits in the budget, and every retry withdraws from it,
+    /// the retry ending with [`GiveUpReason::BudgetExhausted`] once it's empty.
+    #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+    pub fn retry_budget(mut self, budget: RetryBudget) -> Self {
+        self.retry_state.set_retry_budget(budget);
+        self
+    }
+
+    /// Set the [`Deadline`] the retry must be done by, inherited from the context of the call.
+    ///
+    /// The delays are shortened for every retry to start with the least time an attempt
+    /// needs left, the retry ending with [`GiveUpReason::DeadlineExceeded`] once it can't.
+    /// Attempts running out of time are dropped, failing with [`AttemptTimedOut`].
+    pub fn deadline(
+        mut self,
+        deadline: Deadline,
+    ) -> RetryWithMutContext<
+        'c,
+        B,
+        T,
+        E,
+        Ctx,
+        FutureFn,
+        SF,
+        RF,
+        NF,
+        EC,
+        ME,
+        Chain<HK, AttemptTimeout>,
+        CS,
+    >
+    where
+        ME::Error: From<AttemptTimedOut>,
+    {
+        self.retry_state.set_deadline(deadline);
+        RetryWithMutContext {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            notify: self.notify,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            ctx: self.ctx,
+            _ctx: PhantomData,
+            errors: self.errors,
+            retry_state: self.retry_state,
+            map_err: self.map_err,
+            hooks: Chain(self.hooks, AttemptTimeout),
+            state: self.state,
+        }
+    }
+
+    /// Return a future yielding the [`RetryReport`](crate::RetryReport) of the retry alongside its output.
+    pub fn await_with_report(mut self) -> WithReport<Self> {
+        self.retry_state.start_report();
+        WithReport::new(self)
+    }
+}
+
+/// State maintains internal state of retry.
+enum State<Fut, SleepFut> {
+    Idle,
+    /// Polling the attempt, timed out by the timer if the retry has a deadline, boxed so it
+    /// doesn't take room in every retry.
+    Polling(Fut, Option<Pin<Box<SleepFut>>>),
+    Sleeping(SleepFut),
+}
+
+// Only the futures of the attempts and the sleeps are pinned by `poll`.
+impl<'c, B, T, E, Ctx, FutureFn, SF, RF, NF, EC, ME, HK, CS> Unpin
+    for RetryWithMutContext<'c, B, T, E, Ctx, FutureFn, SF, RF, NF, EC, ME, HK, CS>
+where
+    B: Backoff,
+    Ctx: 'c,
+    FutureFn: for<'a> FutureFnWithMutContext<'a, Ctx, T, E>,
+    <FutureFn as FutureFnWithMutContext<'c, Ctx, T, E>>::Future: Unpin,
+    SF: MaybeSleeper,
+    SF::Sleep: Unpin,
+{
+}
+
+impl<'c, B, T, E, Ctx, FutureFn, SF, RF, NF, EC, ME, HK> Reported
+    for RetryWithMutContext<'c, B, T, E, Ctx, FutureFn, SF, RF, NF, EC, ME, HK, &'c mut Ctx>
+where
+    B: Backoff,
+    Ctx: 'c,
+    FutureFn: for<'a> FutureFnWithMutContext<'a, Ctx, T, E>,
+    SF: Sleeper,
+    RF: ErrorClassifier<ME::Error>,
+    NF: NotifyWithContext<ME::Error, Ctx>,
+    HK: Hooks<T, ME::Error>,
+    ME: MapError<E>,
+    EC: ErrorCollector<ME::Error>,
+{
+    fn retry_state(&mut self) -> &mut RetryState {
+        &mut self.retry_state
+    }
+}
+
+impl<'c, B, T, E, Ctx, FutureFn, SF, RF, NF, EC, ME, HK> Future
+    for RetryWithMutContext<'c, B, T, E, Ctx, FutureFn, SF, RF, NF, EC, ME, HK, &'c mut Ctx>
+where
+    B: Backoff,
+    Ctx: 'c,
+    FutureFn: for<'a> FutureFnWithMutContext<'a, Ctx, T, E>,
+    SF: Sleeper,
+    RF: ErrorClassifier<ME::Error>,
+    NF: NotifyWithContext<ME::Error, Ctx>,
+    HK: Hooks<T, ME::Error>,
+    ME: MapError<E>,
+    EC: ErrorCollector<ME::Error>,
+{
+    type Output = Result<T, EC::Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Safety: This is safe because we don't move the `Retry` struct itself,
+        // only its internal state.
+        //
+        // We do the exactly same thing like `pin_project` but without depending on it directly.
+        let this = unsafe { self.get_unchecked_mut() };
+
+        loop {
+            match &mut this.state {
+                State::Idle => {
+                    this.retry_state.start_attempt();
+                    this.hooks.on_attempt(&this.retry_state);
+                    let ctx: *mut Ctx = &mut *this.ctx;
+                    // Safety: The context lives for `'c` and is only lent to one attempt at a
+                    // time: the future of the previous attempt is always dropped before the
+                    // next one is created or the context is accessed again. The function
+                    // accepts a borrow of any lifetime, so it can't keep the borrow of an
+                    // attempt past the future of that attempt.
+                    let fut = {
+                        let _current = CurrentAttempt::enter(&this.retry_state);
+                        this.hooks.enter();
+                        let res = this.future_fn.call(unsafe { &mut *ctx });
+                        this.hooks.exit();
+                        res
+                    };
+                    let timer = (this.retry_state.attempt_timeout())
+                        .map(|timeout| Box::pin(this.sleep_fn.sleep(timeout)));
+                    this.state = State::Polling(fut, timer);
+                    continue;
+                }
+                State::Polling(fut, timer) => {
+                    // Safety: This is safe because we don't move the `Retry` struct and this fut,
+                    // only its internal state.
+                    //
+                    // We do the exactly same thing like `pin_project` but without depending on it directly.
+                    let mut fut = unsafe { Pin::new_unchecked(fut) };
+
+                    let res = {
+                        let _current = CurrentAttempt::enter(&this.retry_state);
+                        this.hooks.enter();
+                        let res = fut.as_mut().poll(cx);
+                        this.hooks.exit();
+                        res
+                    };
+                    let res = match res {
+                        Poll::Ready(res) => res.map_err(|err| this.map_err.map_err(err)),
+                        Poll::Pending => {
+                            match timer.as_mut().map(|timer| timer.as_mut().poll(cx)) {
+                                Some(Poll::Ready(())) => {
+                                    // The timer is done, so it's never polled again: every
+                                    // attempt arms a new one.
+                                    *timer = None;
+                                    match this.hooks.timed_out() {
+                                        Some(err) => Err(err),
+                                        None => return Poll::Pending,
+                                    }
+                                }
+                                _ => return Poll::Pending,
+                            }
+                        }
+                    };
+                    // Drop the finished or timed out future to release its borrow of the
+                    // context.
+                    this.state = State::Idle;
+                    match res {
+                        Ok(v) => {
+                            this.retry_state.succeed_attempt();
+                            this.hooks.on_success(&v, &this.retry_state);
+                            return Poll::Ready(Ok(v));
+                        }
+                        Err(err) => {
+                            // If input error is not retryable, return error directly.
+                            match this.retry_state.retry_delay(
+                                err,
+                                &mut this.backoff,
+                                &mut this.retryable,
+                                &mut this.errors,
+                            ) {
+                                Err((err, reason)) => {
+                                    if let Some(v) = this.hooks.recover(&err) {
+                                        this.hooks.on_recovered(&err, reason, &this.retry_state);
+                                        return Poll::Ready(Ok(v));
+                                    }
+                                    this.hooks.on_fatal(&err, reason, &this.retry_state);
+                                    return Poll::Ready(Err(this.errors.finish(err, reason)));
+                                }
+                                Ok((err, dur)) => {
+                                    this.notify.notify(&err, dur, this.ctx, &this.retry_state);
+                                    this.hooks.on_transient(&err, dur, &this.retry_state);
+                                    this.errors.collect(err, dur, &this.retry_state);
+                                    let sl = this.sleep_fn.sleep(dur);
+                                    this.state = State::Sleeping(sl);
+                                    continue;
+                                }
+                            }
+                        }
+                    }
+                }
+                State::Sleeping(sl) => {
+                    // Safety: This is safe because we don't move the `Retry` struct and this fut,
+                    // only its internal state.
+                    //
+                    // We do the exactly same thing like `pin_project` but without depending on it directly.
+                    let mut sl = unsafe { Pin::new_unchecked(sl) };
+
+                    this.hooks.enter_sleep();
+                    let res = sl.as_mut().poll(cx);
+                    this.hooks.exit_sleep();
+                    ready!(res);
+
+                    this.hooks.on_resume(&this.retry_state);
+                    this.state = State::Idle;
+                    continue;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(any(feature = "tokio-sleep", feature = "gloo-timers-sleep"))]
+mod tests {
+    use alloc::string::ToString;
+    use anyhow::{anyhow, Result};
+    use core::time::Duration;
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::wasm_bindgen_test as test;
+
+    #[cfg(not(target_arch = "wasm32"))]
+    use tokio::test;
+
+    use super::*;
+    use crate::ExponentialBuilder;
+
+    struct Test {
+        calls: usize,
+    }
+
+    impl Test {
+        async fn hello(&mut self) -> Result<usize> {
+            self.calls += 1;
+            Err(anyhow!("retryable"))
+        }
+    }
+
+    async fn hello(v: &mut Test) -> Result<usize> {
+        v.hello().await
+    }
+
+    #[test]
+    async fn test_retry_with_mut_context() -> Result<()> {
+        let mut test = Test { calls: 0 };
+
+        let backoff = ExponentialBuilder::default().with_min_delay(Duration::from_millis(1));
+        let result = hello
+            .retry(backoff)
+            .context(&mut test)
+            .when(|e| e.to_string() == "retryable")
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!("retryable", result.unwrap_err().to_string());
+        // `hello` always returns error "retryable", so it should be executed
+        // 4 times (retry 3 times).
+        assert_eq!(test.calls, 4);
+        Ok(())
+    }
+}
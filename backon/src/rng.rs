@@ -0,0 +1,243 @@
+//! The RNG drawing the jitter of the backoffs and the IDs of the retries.
+//!
+//! With the `fastrand` feature, enabled by default, it's the thread-local RNG of
+//! [fastrand](https://docs.rs/fastrand). Without it, the `jitter` feature draws from a
+//! xorshift RNG of a few lines instead, for embedded builds without the dependency: it's
+//! thread-local and seeded randomly with the `std` feature, and shared by the whole program
+//! and seeded with a constant otherwise, so builds without `std` must seed it with
+//! [`seed_jitter_rng`], or replace it with [`set_jitter_rng`].
+
+use core::mem;
+use core::ptr;
+use core::sync::atomic::AtomicPtr;
+use core::sync::atomic::Ordering;
+
+/// The random numbers set by `set_jitter_rng`, or null.
+static SOURCE: AtomicPtr<()> = AtomicPtr::new(ptr::null_mut());
+
+/// Draw the random numbers of the jitter from `rng`, like a hardware RNG, instead of the RNG
+/// of backon.
+///
+/// Retries given a seed with `seed` draw from the RNG of backon seeded anyway, so their delays
+/// stay the same for the same seed.
+///
+/// Only available with the `jitter` feature.
+///
+/// # Examples
+///
+/// ```
+/// fn hardware_rng() -> u64 {
+///     // Read the random number generator of the board.
+///     0x2545_f491_4f6c_dd1d
+/// }
+///
+/// backon::set_jitter_rng(hardware_rng);
+/// ```
+pub fn set_jitter_rng(rng: fn() -> u64) {
+    SOURCE.store(rng as *mut (), Ordering::Release);
+}
+
+/// Seed the RNG of backon, the one of the current thread with `std`.
+///
+/// Only available with the `jitter` feature.
+pub fn seed_jitter_rng(seed: u64) {
+    imp::seed(seed);
+}
+
+/// Returns the RNG set by `set_jitter_rng`.
+fn source() -> Option<fn() -> u64> {
+    let rng = SOURCE.load(Ordering::Acquire);
+    // Safety: the pointer was cast from a `fn() -> u64` by `set_jitter_rng`.
+    (!rng.is_null()).then(|| unsafe { mem::transmute::<*mut (), fn() -> u64>(rng) })
+}
+
+/// Draw a random number from `source` if it's set and no seed was given, or from the RNG of
+/// backon otherwise.
+fn draw(source: Option<fn() -> u64>) -> u64 {
+    match source {
+        Some(rng) if !imp::is_seeded() => rng(),
+        _ => imp::u64(),
+    }
+}
+
+/// Returns a random `u64`.
+pub(crate) fn u64() -> u64 {
+    draw(source())
+}
+
+/// Returns a random `f32` in `0.0..1.0`.
+pub(crate) fn f32() -> f32 {
+    // The 24 high bits, as many as the mantissa of an `f32` holds.
+    (u64() >> 40) as f32 / 16_777_216.0
+}
+
+/// Run `f`, drawing its random numbers from the RNG of backon seeded with `seed`, and
+/// updating `seed` to the one of the RNG after it, so the next run continues the sequence.
+///
+/// The RNG is restored after `f`, so seeded retries don't consume it.
+pub(crate) fn seeded<R>(seed: &mut u64, f: impl FnOnce() -> R) -> R {
+    let saved = imp::get_seed();
+    let was_seeded = imp::set_seeded(true);
+    imp::seed(*seed);
+    let res = f();
+    *seed = imp::get_seed();
+    imp::seed(saved);
+    imp::set_seeded(was_seeded);
+    res
+}
+
+#[cfg(feature = "fastrand")]
+mod imp {
+    pub(super) use fastrand::get_seed;
+    pub(super) use fastrand::seed;
+
+    pub(super) use super::flag::is_seeded;
+    pub(super) use super::flag::set_seeded;
+
+    pub(super) fn u64() -> u64 {
+        fastrand::u64(..)
+    }
+}
+
+#[cfg(not(feature = "fastrand"))]
+mod imp {
+    pub(super) use super::flag::is_seeded;
+    pub(super) use super::flag::set_seeded;
+    pub(super) use super::state::get_seed;
+    pub(super) use super::state::set_state;
+
+    /// The state of xorshift can't be 0, so a seed of 0 is replaced by another one.
+    pub(super) const DEFAULT_SEED: u64 = 0x9e37_79b9_7f4a_7c15;
+
+    pub(super) fn seed(seed: u64) {
+        set_state(if seed == 0 { DEFAULT_SEED } else { seed });
+    }
+
+    /// Step the xorshift64 RNG.
+    pub(super) fn u64() -> u64 {
+        let mut x = get_seed();
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        set_state(x);
+        x
+    }
+}
+
+#[cfg(all(not(feature = "fastrand"), feature = "std"))]
+mod state {
+    use core::cell::Cell;
+    use std::collections::hash_map::RandomState;
+    use std::hash::BuildHasher;
+
+    std::thread_local! {
+        static STATE: Cell<u64> = Cell::new(RandomState::new().hash_one(std::thread::current().id()) | 1);
+    }
+
+    pub(crate) fn get_seed() -> u64 {
+        STATE.with(Cell::get)
+    }
+
+    pub(crate) fn set_state(state: u64) {
+        STATE.with(|s| s.set(state));
+    }
+}
+
+#[cfg(all(not(feature = "fastrand"), not(feature = "std")))]
+mod state {
+    use core::sync::atomic::AtomicU32;
+    use core::sync::atomic::Ordering;
+
+    // Halves of the state, as not every target has 64-bit atomics. Drawing from several
+    // threads at once may mix their halves, which is still a random state.
+    static LOW: AtomicU32 = AtomicU32::new(0x7f4a_7c15);
+    static HIGH: AtomicU32 = AtomicU32::new(0x9e37_79b9);
+
+    pub(crate) fn get_seed() -> u64 {
+        let state =
+            u64::from(HIGH.load(Ordering::Relaxed)) << 32 | u64::from(LOW.load(Ordering::Relaxed));
+        if state == 0 {
+            super::imp::DEFAULT_SEED
+        } else {
+            state
+        }
+    }
+
+    pub(crate) fn set_state(state: u64) {
+        LOW.store(state as u32, Ordering::Relaxed);
+        HIGH.store((state >> 32) as u32, Ordering::Relaxed);
+    }
+}
+
+/// Whether the random numbers are drawn by a seeded retry.
+#[cfg(feature = "std")]
+mod flag {
+    use core::cell::Cell;
+
+    std::thread_local! {
+        static SEEDED: Cell<bool> = const { Cell::new(false) };
+    }
+
+    pub(crate) fn is_seeded() -> bool {
+        SEEDED.with(Cell::get)
+    }
+
+    /// Set whether a seeded retry draws, returning whether one drew before.
+    pub(crate) fn set_seeded(seeded: bool) -> bool {
+        SEEDED.with(|s| s.replace(seeded))
+    }
+}
+
+/// Whether the random numbers are drawn by a seeded retry.
+#[cfg(not(feature = "std"))]
+mod flag {
+    use core::sync::atomic::AtomicBool;
+    use core::sync::atomic::Ordering;
+
+    static SEEDED: AtomicBool = AtomicBool::new(false);
+
+    pub(crate) fn is_seeded() -> bool {
+        SEEDED.load(Ordering::Relaxed)
+    }
+
+    /// Set whether a seeded retry draws, returning whether one drew before.
+    pub(crate) fn set_seeded(seeded: bool) -> bool {
+        SEEDED.swap(seeded, Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seeded() {
+        let saved = imp::get_seed();
+        let mut seed = 42;
+        let first = seeded(&mut seed, || [u64(), u64()]);
+        assert_ne!(seed, 42);
+        assert_ne!(first[0], first[1]);
+        assert_eq!(imp::get_seed(), saved);
+
+        let mut again = 42;
+        assert_eq!(seeded(&mut again, || [u64(), u64()]), first);
+        // Seeded draws continue from their seed.
+        assert_ne!(seeded(&mut seed, u64), first[0]);
+
+        for _ in 0..1000 {
+            assert!((0.0..1.0).contains(&f32()));
+        }
+    }
+
+    #[test]
+    fn test_jitter_rng() {
+        let rng = || 7;
+        assert_eq!(draw(Some(rng)), 7);
+        // Seeded retries keep drawing from the RNG of backon.
+        let mut seed = 42;
+        let drawn = seeded(&mut seed, || draw(Some(rng)));
+        let mut seed = 42;
+        assert_eq!(seeded(&mut seed, || draw(None)), drawn);
+        assert_ne!(drawn, 7);
+    }
+}
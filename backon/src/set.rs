@@ -0,0 +1,504 @@
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use core::fmt;
+use core::future::poll_fn;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::Context;
+use core::task::Poll;
+use core::task::Waker;
+use core::time::Duration;
+use std::sync::Arc as StdArc;
+use std::task::Wake;
+
+use crate::sleep::MaybeSleeper;
+use crate::sync;
+use crate::sync::Arc;
+use crate::sync::Mutex;
+use crate::Clock;
+use crate::DefaultSleeper;
+use crate::Sleeper;
+use crate::StdClock;
+
+/// RetrySet drives many retried futures at once, their sleeps sharing a single timer.
+///
+/// Every retry sleeping with its own runtime timer registers and cancels a timer at every
+/// failed attempt, which adds up with thousands of retries in flight. The retries pushed to
+/// the set sleep with its [`SetSleeper`] instead: their sleeps are kept in order by the set,
+/// which only arms one timer of its own sleeper, the one ending with the earliest sleep.
+///
+/// Woken futures are polled in the order they were woken, at most once each per poll of the
+/// set, so a future waking itself again doesn't starve the others. [`RetrySet::next`] returns
+/// the output of the futures as they finish, and [`RetrySet::stats`] the counts of the set.
+///
+/// The sleeps of a `SetSleeper` only end while its set is polled, so it must only be used
+/// by the retries pushed to the set. Only available with the `std` feature outside of
+/// `wasm32`.
+///
+/// # Examples
+///
+/// ```
+/// use core::time::Duration;
+///
+/// use backon::ConstantBuilder;
+/// use backon::RetrySet;
+/// use backon::Retryable;
+///
+/// async fn fetch(id: u32) -> Result<u32, std::io::Error> {
+///     Ok(id)
+/// }
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let mut set = RetrySet::new();
+/// for id in 0..1000 {
+///     let retry = (move || fetch(id))
+///         .retry(ConstantBuilder::default().with_delay(Duration::from_millis(10)))
+///         .sleep(set.sleeper());
+///     set.push(retry);
+/// }
+///
+/// let mut fetched = 0;
+/// while let Some(result) = set.next().await {
+///     fetched += result.is_ok() as usize;
+/// }
+/// assert_eq!(fetched, 1000);
+/// assert_eq!(set.stats().succeeded, 1000);
+/// # }
+/// ```
+pub struct RetrySet<T, E, SF: MaybeSleeper = DefaultSleeper> {
+    tasks: Vec<Option<Task<T, E>>>,
+    free: Vec<usize>,
+    shared: Arc<Shared>,
+    sleeper: SF,
+    /// The timer of the sleeper, ending at the deadline it was armed for.
+    timer: Option<(Duration, Pin<Box<SF::Sleep>>)>,
+    stats: RetrySetStats,
+}
+
+struct Task<T, E> {
+    fut: Pin<Box<dyn Future<Output = Result<T, E>> + Send>>,
+    waker: Waker,
+}
+
+/// RetrySetStats are the counts of a [`RetrySet`], returned by [`RetrySet::stats`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct RetrySetStats {
+    /// The futures pushed to the set.
+    pub pushed: u64,
+    /// The futures that finished with `Ok`.
+    pub succeeded: u64,
+    /// The futures that finished with `Err`.
+    pub failed: u64,
+    /// The futures pushed that haven't finished yet.
+    pub in_flight: usize,
+    /// The sleeps of the retries of the set that haven't ended yet.
+    pub sleeping: usize,
+    /// The times a future of the set was polled.
+    pub polls: u64,
+    /// The timers armed by the set with its sleeper, at most one at a time.
+    pub timers_armed: u64,
+}
+
+/// The state shared by a set, the wakers of its futures and its sleeper.
+#[derive(Default)]
+struct Shared {
+    ready: Mutex<Ready>,
+    sleeps: Mutex<Sleeps>,
+}
+
+/// The futures woken since they were last polled, in order.
+#[derive(Default)]
+struct Ready {
+    queue: VecDeque<usize>,
+    queued: Vec<bool>,
+    /// The waker of the task polling the set.
+    waker: Option<Waker>,
+}
+
+/// The sleeps of the retries, by their deadline.
+#[derive(Default)]
+struct Sleeps {
+    wakers: BTreeMap<(Duration, u64), Waker>,
+    next_id: u64,
+}
+
+impl Shared {
+    /// Queue the future `id` to be polled, waking the set.
+    fn wake(&self, id: usize) {
+        let waker = {
+            let mut ready = sync::lock(&self.ready);
+            if ready.queued.get(id).copied().unwrap_or(true) {
+                return;
+            }
+            ready.queued[id] = true;
+            ready.queue.push_back(id);
+            ready.waker.clone()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+
+    /// End the sleeps due at `now`, returning the earliest deadline of the others.
+    fn fire(&self, now: Duration) -> Option<Duration> {
+        let due = {
+            let mut sleeps = sync::lock(&self.sleeps);
+            let later = sleeps.wakers.split_off(&(now, u64::MAX));
+            core::mem::replace(&mut sleeps.wakers, later)
+        };
+        for waker in due.into_values() {
+            waker.wake();
+        }
+        let sleeps = sync::lock(&self.sleeps);
+        sleeps.wakers.keys().next().map(|(deadline, _)| *deadline)
+    }
+}
+
+/// The waker of a future of the set.
+struct TaskWaker {
+    id: usize,
+    shared: Arc<Shared>,
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: StdArc<Self>) {
+        self.shared.wake(self.id);
+    }
+
+    fn wake_by_ref(self: &StdArc<Self>) {
+        self.shared.wake(self.id);
+    }
+}
+
+#[cfg(feature = "tokio-sleep")]
+impl<T, E> RetrySet<T, E> {
+    /// Create an empty set arming its timer with the [`DefaultSleeper`].
+    pub fn new() -> Self {
+        Self::with_sleeper(DefaultSleeper::default())
+    }
+}
+
+#[cfg(feature = "tokio-sleep")]
+impl<T, E> Default for RetrySet<T, E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, E, SF: Sleeper> RetrySet<T, E, SF> {
+    /// Create an empty set arming its timer with `sleeper`.
+    pub fn with_sleeper(sleeper: SF) -> Self {
+        RetrySet {
+            tasks: Vec::new(),
+            free: Vec::new(),
+            shared: Arc::new(Shared::default()),
+            sleeper,
+            timer: None,
+            stats: RetrySetStats::default(),
+        }
+    }
+
+    /// Returns the sleeper of the retries of the set, sleeping with the timer of the set.
+    pub fn sleeper(&self) -> SetSleeper {
+        SetSleeper {
+            shared: self.shared.clone(),
+        }
+    }
+
+    /// Push a future, like a retry sleeping with [`RetrySet::sleeper`], to be driven by the
+    /// set.
+    pub fn push(&mut self, fut: impl Future<Output = Result<T, E>> + Send + 'static) {
+        let id = self.free.pop().unwrap_or(self.tasks.len());
+        let waker = Waker::from(StdArc::new(TaskWaker {
+            id,
+            shared: self.shared.clone(),
+        }));
+        let task = Task {
+            fut: Box::pin(fut),
+            waker,
+        };
+        if id == self.tasks.len() {
+            self.tasks.push(Some(task));
+            sync::lock(&self.shared.ready).queued.push(false);
+        } else {
+            self.tasks[id] = Some(task);
+        }
+        self.stats.pushed += 1;
+        self.stats.in_flight += 1;
+        self.shared.wake(id);
+    }
+
+    /// Returns the number of futures in the set.
+    pub fn len(&self) -> usize {
+        self.stats.in_flight
+    }
+
+    /// Returns whether the set has no futures.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the counts of the set.
+    pub fn stats(&self) -> RetrySetStats {
+        RetrySetStats {
+            sleeping: sync::lock(&self.shared.sleeps).wakers.len(),
+            ..self.stats
+        }
+    }
+
+    /// Wait for the next future of the set to finish, returning its output, or `None` once
+    /// the set is empty.
+    pub async fn next(&mut self) -> Option<Result<T, E>> {
+        poll_fn(|cx| self.poll_next(cx)).await
+    }
+
+    /// Poll the futures of the set woken since they were last polled, returning the output
+    /// of the first one finishing, or `None` if the set is empty.
+    pub fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<T, E>>> {
+        sync::lock(&self.shared.ready).waker = Some(cx.waker().clone());
+        loop {
+            if self.is_empty() {
+                return Poll::Ready(None);
+            }
+            self.shared.fire(StdClock.now());
+
+            // Only the futures woken before polling the set are polled, once each.
+            let woken = sync::lock(&self.shared.ready).queue.len();
+            for _ in 0..woken {
+                let Some(id) = self.pop_ready() else {
+                    break;
+                };
+                let Some(task) = &mut self.tasks[id] else {
+                    continue;
+                };
+                self.stats.polls += 1;
+                let mut task_cx = Context::from_waker(&task.waker);
+                if let Poll::Ready(output) = task.fut.as_mut().poll(&mut task_cx) {
+                    self.tasks[id] = None;
+                    self.free.push(id);
+                    self.stats.in_flight -= 1;
+                    match &output {
+                        Ok(_) => self.stats.succeeded += 1,
+                        Err(_) => self.stats.failed += 1,
+                    }
+                    return Poll::Ready(Some(output));
+                }
+            }
+
+            if !sync::lock(&self.shared.ready).queue.is_empty() {
+                // Futures woken while polling the others wait for the next poll of the set.
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            match self.poll_timer(cx) {
+                Poll::Ready(()) => continue,
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+
+    /// Pop the next woken future.
+    fn pop_ready(&self) -> Option<usize> {
+        let mut ready = sync::lock(&self.shared.ready);
+        let id = ready.queue.pop_front()?;
+        ready.queued[id] = false;
+        Some(id)
+    }
+
+    /// Arm the timer for the earliest sleep and poll it, ready once sleeps are due.
+    fn poll_timer(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        let now = StdClock.now();
+        let Some(deadline) = self.shared.fire(now) else {
+            self.timer = None;
+            return Poll::Pending;
+        };
+        if !sync::lock(&self.shared.ready).queue.is_empty() {
+            return Poll::Ready(());
+        }
+        match &mut self.timer {
+            Some((armed, _)) if *armed == deadline => {}
+            timer => {
+                let sleep = self.sleeper.sleep(deadline.saturating_sub(now));
+                *timer = Some((deadline, Box::pin(sleep)));
+                self.stats.timers_armed += 1;
+            }
+        }
+        let (_, sleep) = self.timer.as_mut().expect("the timer must be armed");
+        match sleep.as_mut().poll(cx) {
+            Poll::Ready(()) => {
+                self.timer = None;
+                Poll::Ready(())
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<T, E, SF: MaybeSleeper> fmt::Debug for RetrySet<T, E, SF> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RetrySet")
+            .field("stats", &self.stats)
+            .finish_non_exhaustive()
+    }
+}
+
+/// SetSleeper is the sleeper of the retries of a [`RetrySet`], returned by
+/// [`RetrySet::sleeper`], sleeping with the timer of the set.
+#[derive(Clone)]
+pub struct SetSleeper {
+    shared: Arc<Shared>,
+}
+
+impl fmt::Debug for SetSleeper {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SetSleeper").finish_non_exhaustive()
+    }
+}
+
+impl Sleeper for SetSleeper {
+    type Sleep = SetSleep;
+
+    fn sleep(&self, dur: Duration) -> Self::Sleep {
+        SetSleep {
+            shared: self.shared.clone(),
+            deadline: StdClock.now().saturating_add(dur),
+            key: None,
+        }
+    }
+}
+
+/// The sleep of a [`SetSleeper`], ending once the set it belongs to is polled after its
+/// deadline.
+#[doc(hidden)]
+pub struct SetSleep {
+    shared: Arc<Shared>,
+    deadline: Duration,
+    /// The key of the waker registered in the sleeps of the set.
+    key: Option<(Duration, u64)>,
+}
+
+impl fmt::Debug for SetSleep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SetSleep")
+            .field("deadline", &self.deadline)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Future for SetSleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let mut sleeps = sync::lock(&self.shared.sleeps);
+        if let Some(key) = self.key {
+            // Ended by the set, which removed its waker.
+            let Some(waker) = sleeps.wakers.get_mut(&key) else {
+                drop(sleeps);
+                self.key = None;
+                return Poll::Ready(());
+            };
+            if !waker.will_wake(cx.waker()) {
+                waker.clone_from(cx.waker());
+            }
+            return Poll::Pending;
+        }
+        if self.deadline <= StdClock.now() {
+            return Poll::Ready(());
+        }
+        let key = (self.deadline, sleeps.next_id);
+        sleeps.next_id += 1;
+        sleeps.wakers.insert(key, cx.waker().clone());
+        drop(sleeps);
+        self.key = Some(key);
+        Poll::Pending
+    }
+}
+
+impl Drop for SetSleep {
+    fn drop(&mut self) {
+        if let Some(key) = self.key {
+            sync::lock(&self.shared.sleeps).wakers.remove(&key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    #[cfg(not(backon_loom))]
+    async fn test_retry_set() {
+        use core::future::ready;
+        use core::sync::atomic::AtomicUsize;
+        use core::sync::atomic::Ordering;
+
+        use crate::ConstantBuilder;
+        use crate::Retryable;
+
+        let mut set = RetrySet::new();
+        let attempts = StdArc::new(AtomicUsize::new(0));
+        for id in 0..100 {
+            let attempts = attempts.clone();
+            let retry = (move || {
+                // Every other operation fails its first two attempts.
+                let n = attempts.fetch_add(1, Ordering::Relaxed);
+                ready(if id % 2 == 0 && n < 100 {
+                    Err(id)
+                } else {
+                    Ok(id)
+                })
+            })
+            .retry(
+                ConstantBuilder::default()
+                    .with_delay(Duration::from_millis(5))
+                    .with_max_times(1),
+            )
+            .sleep(set.sleeper());
+            set.push(retry);
+        }
+        assert_eq!(set.len(), 100);
+
+        let mut outputs = Vec::new();
+        while let Some(output) = set.next().await {
+            outputs.push(output);
+        }
+        assert_eq!(outputs.len(), 100);
+        let stats = set.stats();
+        assert_eq!(stats.pushed, 100);
+        assert_eq!(stats.succeeded + stats.failed, 100);
+        assert_eq!(stats.in_flight, 0);
+        assert_eq!(stats.sleeping, 0);
+        // The sleeps after the first attempts of the failing operations share the timer.
+        assert!(stats.timers_armed < 10, "{stats:?}");
+    }
+
+    #[test]
+    #[cfg(backon_loom)]
+    fn test_retry_set_loom() {
+        struct Noop;
+
+        impl Wake for Noop {
+            fn wake(self: StdArc<Self>) {}
+        }
+
+        loom::model(|| {
+            let shared = Arc::new(Shared::default());
+            let sleeper = SetSleeper {
+                shared: shared.clone(),
+            };
+            let mut sleep = Box::pin(sleeper.sleep(Duration::from_secs(3600)));
+            let waker = Waker::from(StdArc::new(Noop));
+            let mut cx = Context::from_waker(&waker);
+            assert!(sleep.as_mut().poll(&mut cx).is_pending());
+
+            let thread = loom::thread::spawn(move || shared.fire(Duration::MAX));
+            assert_eq!(thread.join().unwrap(), None);
+            assert!(sleep.as_mut().poll(&mut cx).is_ready());
+        });
+    }
+}
@@ -1,7 +1,4 @@
-use core::{
-    future::{Future, Ready},
-    time::Duration,
-};
+use core::{future::Future, time::Duration};
 
 /// A sleeper is used to generate a future that completes after a specified duration.
 pub trait Sleeper: 'static {
@@ -52,13 +49,15 @@ pub type DefaultSleeper = GlooTimersSleep;
 /// A placeholder type that does not implement [`Sleeper`] and will therefore fail to compile if used as one.
 ///
 /// Users should enable a feature of this crate that provides a valid [`Sleeper`] implementation when this type appears in compilation errors. Alternatively, a custom [`Sleeper`] implementation should be provided where necessary, such as in [`crate::Retry::sleeper`].
+#[cfg(all(not(feature = "tokio-sleep"), not(feature = "gloo-timers-sleep")))]
 #[doc(hidden)]
 #[derive(Clone, Copy, Debug, Default)]
 pub struct PleaseEnableAFeatureOrProvideACustomSleeper;
 
 /// Implement `MaybeSleeper` but not `Sleeper`.
+#[cfg(all(not(feature = "tokio-sleep"), not(feature = "gloo-timers-sleep")))]
 impl MaybeSleeper for PleaseEnableAFeatureOrProvideACustomSleeper {
-    type Sleep = Ready<()>;
+    type Sleep = core::future::Ready<()>;
 }
 
 /// The default implementation of `Sleeper` uses `tokio::time::sleep`.
@@ -78,6 +77,56 @@ impl Sleeper for TokioSleeper {
     }
 }
 
+/// A sleeper for retries within a [turmoil](https://docs.rs/turmoil) simulation.
+///
+/// It sleeps with `tokio::time::sleep` on the simulated time of the host, so retries advance
+/// with the simulation instead of stalling it or sleeping for real. It's also a
+/// [`Clock`](crate::Clock) reading the simulated time, to set with `clock` so the elapsed times
+/// and latencies of the retry are simulated ones as well. Blocking retries would stall the
+/// simulation, so it's only a [`Sleeper`].
+///
+/// Only available with the `turmoil` feature.
+///
+/// # Examples
+///
+/// ```
+/// use backon::ExponentialBuilder;
+/// use backon::Retryable;
+/// use backon::TurmoilSleeper;
+///
+/// let mut sim = turmoil::Builder::new().build();
+/// sim.client("client", async {
+///     let result = (|| async { Err::<(), _>("unavailable") })
+///         .retry(ExponentialBuilder::default())
+///         .sleep(TurmoilSleeper)
+///         .clock(TurmoilSleeper)
+///         .await;
+///     assert!(result.is_err());
+///     Ok(())
+/// });
+/// sim.run().unwrap();
+/// ```
+#[cfg(all(not(target_arch = "wasm32"), feature = "turmoil"))]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TurmoilSleeper;
+
+#[cfg(all(not(target_arch = "wasm32"), feature = "turmoil"))]
+impl Sleeper for TurmoilSleeper {
+    type Sleep = tokio::time::Sleep;
+
+    fn sleep(&self, dur: Duration) -> Self::Sleep {
+        tokio::time::sleep(dur)
+    }
+}
+
+#[cfg(all(not(target_arch = "wasm32"), feature = "turmoil"))]
+impl crate::Clock for TurmoilSleeper {
+    fn now(&self) -> Duration {
+        // Outside of a simulation the time doesn't move.
+        turmoil::sim_elapsed().unwrap_or_default()
+    }
+}
+
 /// The default implementation of `Sleeper` utilizes `gloo_timers::future::sleep`.
 #[cfg(all(target_arch = "wasm32", feature = "gloo-timers-sleep"))]
 #[derive(Clone, Copy, Debug, Default)]
@@ -91,3 +140,30 @@ impl Sleeper for GlooTimersSleep {
         gloo_timers::future::sleep(dur)
     }
 }
+
+#[cfg(test)]
+#[cfg(all(not(target_arch = "wasm32"), feature = "turmoil"))]
+mod tests {
+    use super::*;
+    use crate::ConstantBuilder;
+    use crate::Retryable;
+
+    #[test]
+    fn test_turmoil_sleeper() {
+        let mut sim = turmoil::Builder::new().build();
+        sim.client("client", async {
+            let (result, report) = (|| async { Err::<(), _>("unavailable") })
+                .retry(ConstantBuilder::default().with_delay(Duration::from_secs(2)))
+                .sleep(TurmoilSleeper)
+                .clock(TurmoilSleeper)
+                .await_with_report()
+                .await;
+            assert!(result.is_err());
+            assert_eq!(report.slept(), Duration::from_secs(6));
+            assert!(report.elapsed() >= Duration::from_secs(6));
+            Ok(())
+        });
+        // The 6s slept are simulated, so the test doesn't last them.
+        sim.run().unwrap();
+    }
+}
@@ -0,0 +1,133 @@
+use core::time::Duration;
+
+/// RetrySnapshot captures the progress of a retry, returned by `snapshot` and restored by
+/// `resume_from`.
+///
+/// It holds the number of attempts made, the time elapsed and the backoff with its internal
+/// state, so a long-lived job can persist it and resume after a restart without going back
+/// to the initial delays. With the `serde` feature, it's serializable together with the
+/// backoffs of this crate.
+///
+/// # Examples
+///
+/// ```
+/// use core::time::Duration;
+///
+/// use backon::BackoffBuilder;
+/// use backon::BlockingRetryable;
+/// use backon::ExponentialBuilder;
+/// use backon::RetrySnapshot;
+///
+/// let backoff = ExponentialBuilder::default()
+///     .with_min_delay(Duration::from_millis(1))
+///     .build();
+/// // Restored from storage after a restart, with 2 attempts already made.
+/// let snapshot = RetrySnapshot::new(2, backoff);
+///
+/// let mut attempts = Vec::new();
+/// let result = (|| Err::<(), _>("unavailable"))
+///     .retry(ExponentialBuilder::default())
+///     .resume_from(snapshot)
+///     .notify(|_, _| {})
+///     .when_with_state(|_, state| {
+///         attempts.push(state.attempt());
+///         true
+///     })
+///     .call();
+///
+/// assert!(result.is_err());
+/// assert_eq!(attempts, vec![3, 4, 5, 6]);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RetrySnapshot<B> {
+    attempt: usize,
+    #[cfg_attr(feature = "serde", serde(default))]
+    elapsed: Duration,
+    backoff: B,
+}
+
+impl<B> RetrySnapshot<B> {
+    /// Create a snapshot of a retry that made `attempt` attempts with `backoff`.
+    pub fn new(attempt: usize, backoff: B) -> Self {
+        RetrySnapshot {
+            attempt,
+            elapsed: Duration::ZERO,
+            backoff,
+        }
+    }
+
+    /// Set the time the retry already spent before the snapshot.
+    pub fn with_elapsed(mut self, elapsed: Duration) -> Self {
+        self.elapsed = elapsed;
+        self
+    }
+
+    /// Returns the number of attempts made before the snapshot.
+    pub fn attempt(&self) -> usize {
+        self.attempt
+    }
+
+    /// Returns the time elapsed since the first attempt started, at the snapshot.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Returns the backoff, in the state it had at the snapshot.
+    pub fn backoff(&self) -> &B {
+        &self.backoff
+    }
+
+    /// Consume the snapshot, returning the backoff.
+    pub fn into_backoff(self) -> B {
+        self.backoff
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+    use alloc::vec::Vec;
+    use core::time::Duration;
+
+    use super::*;
+    use crate::BackoffBuilder;
+    use crate::BlockingRetryable;
+    #[cfg(feature = "serde")]
+    use crate::ExponentialBackoff;
+    use crate::ExponentialBuilder;
+
+    #[test]
+    fn test_resume_from_snapshot() {
+        let builder = ExponentialBuilder::default()
+            .with_min_delay(Duration::from_millis(1))
+            .with_max_times(4);
+        let mut backoff = builder.build();
+        assert_eq!(backoff.next(), Some(Duration::from_millis(1)));
+        assert_eq!(backoff.next(), Some(Duration::from_millis(2)));
+
+        let snapshot = RetrySnapshot::new(2, backoff);
+        #[cfg(feature = "serde")]
+        let snapshot: RetrySnapshot<ExponentialBackoff> =
+            serde_json::from_str(&serde_json::to_string(&snapshot).unwrap()).unwrap();
+
+        let mut delays = Vec::new();
+        let mut attempts = Vec::new();
+        let result = (|| Err::<(), _>("unavailable"))
+            .retry(builder)
+            .resume_from(snapshot)
+            .notify(|_, dur| delays.push(dur))
+            .when_with_state(|_, state| {
+                attempts.push(state.attempt());
+                true
+            })
+            .call();
+
+        assert!(result.is_err());
+        assert_eq!(attempts, vec![3, 4, 5]);
+        assert_eq!(
+            delays,
+            vec![Duration::from_millis(4), Duration::from_millis(8)]
+        );
+    }
+}
@@ -0,0 +1,344 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::fmt;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::Context;
+use core::task::Poll;
+
+use crate::backoff::BackoffBuilder;
+use crate::sleep::MaybeSleeper;
+use crate::Backoff;
+use crate::DefaultSleeper;
+use crate::Sleeper;
+
+/// SpeculativeRetryable adds speculative retries to functions returning futures.
+///
+/// Instead of waiting for an attempt to fail before the next one, a speculative retry
+/// launches the next attempt once the delay of the backoff has passed, while the attempts
+/// before it are still running, up to `max_parallel` attempts at once. The first attempt
+/// succeeding wins and the others are cancelled, so a slow replica only delays a read by
+/// the delay it takes to give up waiting on it alone, which is what bounds tail latency.
+///
+/// # Examples
+///
+/// ```
+/// use core::time::Duration;
+///
+/// use backon::ConstantBuilder;
+/// use backon::SpeculativeRetryable;
+///
+/// async fn read(replica: usize) -> Result<String, std::io::Error> {
+///     if replica == 1 {
+///         // The first replica is stuck.
+///         tokio::time::sleep(Duration::from_secs(10)).await;
+///     }
+///     Ok(format!("read from replica {replica}"))
+/// }
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> Result<(), std::io::Error> {
+/// let mut replica = 0;
+/// let speculated = (|| {
+///     replica += 1;
+///     read(replica)
+/// })
+/// .speculate(ConstantBuilder::default().with_delay(Duration::from_millis(20)))
+/// .max_parallel(2)
+/// .await?;
+///
+/// assert_eq!(speculated.value, "read from replica 2");
+/// assert_eq!(speculated.attempt, 2);
+/// # Ok(())
+/// # }
+/// ```
+pub trait SpeculativeRetryable<
+    B: BackoffBuilder,
+    T,
+    E,
+    Fut: Future<Output = Result<T, E>>,
+    FutureFn: FnMut() -> Fut,
+>
+{
+    /// Generate a new speculative retry.
+    fn speculate(self, builder: B) -> Speculative<B::Backoff, T, E, Fut, FutureFn>;
+}
+
+impl<B, T, E, Fut, FutureFn> SpeculativeRetryable<B, T, E, Fut, FutureFn> for FutureFn
+where
+    B: BackoffBuilder,
+    Fut: Future<Output = Result<T, E>>,
+    FutureFn: FnMut() -> Fut,
+{
+    fn speculate(self, builder: B) -> Speculative<B::Backoff, T, E, Fut, FutureFn> {
+        Speculative {
+            backoff: builder.build(),
+            retryable: |_: &E| true,
+            future_fn: self,
+            sleep_fn: DefaultSleeper::default(),
+            max_parallel: 2,
+            attempts: Vec::new(),
+            launched: 0,
+            due: true,
+            sleep: None,
+            last_err: None,
+        }
+    }
+}
+
+/// Struct generated by [`SpeculativeRetryable`], resolving to the [`Speculated`] output of
+/// the attempt that won.
+///
+/// Every attempt is boxed, as several run at once. Attempts failing with an error retried by
+/// `when` are given up on, the next one still launching after the delay of the backoff; the
+/// retry ends with the last error once the backoff is exhausted and every attempt failed, or
+/// at once with an error not retried.
+pub struct Speculative<
+    B: Backoff,
+    T,
+    E,
+    Fut: Future<Output = Result<T, E>>,
+    FutureFn: FnMut() -> Fut,
+    SF: MaybeSleeper = DefaultSleeper,
+    RF = fn(&E) -> bool,
+> {
+    backoff: B,
+    retryable: RF,
+    future_fn: FutureFn,
+    sleep_fn: SF,
+    max_parallel: usize,
+
+    /// The attempts running, with their index.
+    attempts: Vec<(usize, Pin<Box<Fut>>)>,
+    launched: usize,
+    /// Whether the delay before the next attempt has passed.
+    due: bool,
+    sleep: Option<Pin<Box<SF::Sleep>>>,
+    last_err: Option<E>,
+}
+
+/// Speculated is the output of a [`Speculative`] retry.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Speculated<T> {
+    /// The value returned by the attempt that won.
+    pub value: T,
+    /// The index of the attempt that won, starting from 1.
+    pub attempt: usize,
+    /// The number of attempts launched, including the ones cancelled.
+    pub launched: usize,
+}
+
+impl<B, T, E, Fut, FutureFn, SF, RF> Speculative<B, T, E, Fut, FutureFn, SF, RF>
+where
+    B: Backoff,
+    Fut: Future<Output = Result<T, E>>,
+    FutureFn: FnMut() -> Fut,
+    SF: MaybeSleeper,
+    RF: FnMut(&E) -> bool,
+{
+    /// Set the sleeper waiting for the delays between attempts.
+    pub fn sleep<SN: Sleeper>(self, sleep_fn: SN) -> Speculative<B, T, E, Fut, FutureFn, SN, RF> {
+        Speculative {
+            backoff: self.backoff,
+            retryable: self.retryable,
+            future_fn: self.future_fn,
+            sleep_fn,
+            max_parallel: self.max_parallel,
+            attempts: Vec::new(),
+            launched: 0,
+            due: true,
+            sleep: None,
+            last_err: None,
+        }
+    }
+
+    /// Set the errors given up on while the other attempts go on, those for which
+    /// `retryable` returns `true`. The others end the retry, like with [`Retry::when`].
+    ///
+    /// [`Retry::when`]: crate::Retry::when
+    pub fn when<RN: FnMut(&E) -> bool>(
+        self,
+        retryable: RN,
+    ) -> Speculative<B, T, E, Fut, FutureFn, SF, RN> {
+        Speculative {
+            backoff: self.backoff,
+            retryable,
+            future_fn: self.future_fn,
+            sleep_fn: self.sleep_fn,
+            max_parallel: self.max_parallel,
+            attempts: self.attempts,
+            launched: self.launched,
+            due: self.due,
+            sleep: self.sleep,
+            last_err: self.last_err,
+        }
+    }
+
+    /// Set the most attempts running at once, 2 by default. The next attempt waits for one
+    /// of them to fail once its delay has passed.
+    pub fn max_parallel(mut self, max_parallel: usize) -> Self {
+        self.max_parallel = max_parallel.max(1);
+        self
+    }
+
+    /// Returns the number of attempts launched.
+    pub fn launched(&self) -> usize {
+        self.launched
+    }
+}
+
+// The attempts and the sleep are boxed, nothing is pinned in place.
+impl<B, T, E, Fut, FutureFn, SF, RF> Unpin for Speculative<B, T, E, Fut, FutureFn, SF, RF>
+where
+    B: Backoff,
+    Fut: Future<Output = Result<T, E>>,
+    FutureFn: FnMut() -> Fut,
+    SF: MaybeSleeper,
+{
+}
+
+impl<B, T, E, Fut, FutureFn, SF, RF> fmt::Debug for Speculative<B, T, E, Fut, FutureFn, SF, RF>
+where
+    B: Backoff,
+    Fut: Future<Output = Result<T, E>>,
+    FutureFn: FnMut() -> Fut,
+    SF: MaybeSleeper,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Speculative")
+            .field("max_parallel", &self.max_parallel)
+            .field("running", &self.attempts.len())
+            .field("launched", &self.launched)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<B, T, E, Fut, FutureFn, SF, RF> Future for Speculative<B, T, E, Fut, FutureFn, SF, RF>
+where
+    B: Backoff,
+    Fut: Future<Output = Result<T, E>>,
+    FutureFn: FnMut() -> Fut,
+    SF: Sleeper,
+    RF: FnMut(&E) -> bool,
+{
+    type Output = Result<Speculated<T>, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        loop {
+            if this.due && this.attempts.len() < this.max_parallel {
+                this.due = false;
+                this.launched += 1;
+                this.attempts
+                    .push((this.launched, Box::pin((this.future_fn)())));
+                this.sleep = this
+                    .backoff
+                    .next()
+                    .map(|dur| Box::pin(this.sleep_fn.sleep(dur)));
+            }
+
+            let mut i = 0;
+            while i < this.attempts.len() {
+                let (attempt, fut) = &mut this.attempts[i];
+                match fut.as_mut().poll(cx) {
+                    Poll::Pending => i += 1,
+                    Poll::Ready(Ok(value)) => {
+                        let attempt = *attempt;
+                        // The stragglers are cancelled by dropping them.
+                        this.attempts.clear();
+                        return Poll::Ready(Ok(Speculated {
+                            value,
+                            attempt,
+                            launched: this.launched,
+                        }));
+                    }
+                    Poll::Ready(Err(err)) => {
+                        drop(this.attempts.swap_remove(i));
+                        if !(this.retryable)(&err) {
+                            this.attempts.clear();
+                            return Poll::Ready(Err(err));
+                        }
+                        this.last_err = Some(err);
+                    }
+                }
+            }
+
+            if let Some(sleep) = &mut this.sleep {
+                if sleep.as_mut().poll(cx).is_ready() {
+                    this.sleep = None;
+                    this.due = true;
+                    continue;
+                }
+            }
+            if this.attempts.is_empty() && this.sleep.is_none() && !this.due {
+                let err = this
+                    .last_err
+                    .take()
+                    .expect("the last attempt must have failed");
+                return Poll::Ready(Err(err));
+            }
+            if this.due && this.attempts.len() < this.max_parallel {
+                continue;
+            }
+            return Poll::Pending;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::future::ready;
+    use core::time::Duration;
+
+    use super::*;
+    use crate::ConstantBuilder;
+
+    #[tokio::test]
+    async fn test_speculative() {
+        let backoff = ConstantBuilder::default().with_delay(Duration::from_millis(10));
+
+        // The first attempt is stuck, the second one wins.
+        let mut attempts = 0;
+        let speculated = (|| {
+            attempts += 1;
+            let stuck = attempts == 1;
+            async move {
+                if stuck {
+                    tokio::time::sleep(Duration::from_secs(10)).await;
+                }
+                Ok::<_, &str>(attempts)
+            }
+        })
+        .speculate(backoff)
+        .await
+        .unwrap();
+        assert_eq!(
+            speculated,
+            Speculated {
+                value: 2,
+                attempt: 2,
+                launched: 2,
+            }
+        );
+
+        // Every attempt fails, the last error ends the retry.
+        let mut attempts = 0;
+        let result = (|| {
+            attempts += 1;
+            ready(Err::<(), _>(attempts))
+        })
+        .speculate(backoff)
+        .max_parallel(3)
+        .await;
+        assert_eq!(result, Err(4));
+
+        // An error not retried ends it at once.
+        let result = (|| ready(Err::<(), _>("invalid")))
+            .speculate(backoff)
+            .when(|e| *e != "invalid")
+            .await;
+        assert_eq!(result, Err("invalid"));
+    }
+}
@@ -0,0 +1,547 @@
+use alloc::borrow::Cow;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::ops::ControlFlow;
+use core::time::Duration;
+
+use crate::clock::SharedClock;
+use crate::error::{ErrorClassifier, ErrorCollector};
+use crate::Backoff;
+use crate::BudgetWarning;
+#[cfg(all(feature = "timing", feature = "std", not(target_arch = "wasm32")))]
+use crate::Clock;
+use crate::Deadline;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+use crate::FailureBudget;
+use crate::GiveUpReason;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+use crate::RetryBudget;
+use crate::RetryReport;
+use crate::RetrySnapshot;
+#[cfg(all(feature = "timing", feature = "std", not(target_arch = "wasm32")))]
+use crate::StdClock;
+
+/// RetryState is a read-only view of the progress of a retry.
+///
+/// It's passed to the conditions set by `when_with_state` and the function set by
+/// `notify_with_state` together with the error of the failed attempt.
+#[derive(Clone, Debug, Default)]
+pub struct RetryState {
+    name: Option<&'static str>,
+    tags: Vec<(&'static str, Cow<'static, str>)>,
+    session_id: u64,
+    session_id_set: bool,
+    attempt: usize,
+    next_delay: Option<Duration>,
+    last_delay: Duration,
+    total_delay: Duration,
+    max_total_delay: Option<Duration>,
+    non_idempotent: bool,
+    observed: bool,
+    clock: Option<SharedClock>,
+    started_at: Option<Duration>,
+    attempt_started_at: Option<Duration>,
+    attempt_latency: Duration,
+    resumed_elapsed: Duration,
+    report: Option<Box<RetryReport>>,
+    #[cfg(feature = "jitter")]
+    seed: Option<u64>,
+    limits: Option<Box<Limits>>,
+}
+
+/// The limits of a retry set by options most retries don't set, boxed so they don't take
+/// room in every retry.
+#[derive(Clone, Debug, Default)]
+struct Limits {
+    budget_warning: Option<BudgetWarning>,
+    budget_warned: bool,
+    budget_warning_due: Option<f32>,
+    #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+    failure_budget: Option<FailureBudget>,
+    #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+    retry_budget: Option<RetryBudget>,
+    deadline: Option<Deadline>,
+    #[cfg(feature = "test-util")]
+    test_budget: Option<(usize, Duration)>,
+}
+
+impl RetryState {
+    /// Create the state of a retry being built, generating its ID.
+    pub(crate) fn new() -> Self {
+        RetryState {
+            session_id: session_id(),
+            ..RetryState::default()
+        }
+    }
+
+    /// Returns the name of the retried operation set by `name`.
+    pub fn name(&self) -> Option<&'static str> {
+        self.name
+    }
+
+    /// Returns the key-value tags of the retried operation set by `tag`, in order.
+    pub fn tags(&self) -> &[(&'static str, Cow<'static, str>)] {
+        &self.tags
+    }
+
+    /// Returns the ID of the retry, set by `session_id` or generated when the retry is built.
+    ///
+    /// It stays the same across the attempts of the retry. Together with
+    /// [`RetryState::attempt`] it identifies each attempt, so the logs of the retried
+    /// operation can be correlated with the events of the retry, see `current_attempt`.
+    pub fn session_id(&self) -> u64 {
+        self.session_id
+    }
+
+    /// Returns the index of the current attempt, starting from 1 for the first attempt.
+    pub fn attempt(&self) -> usize {
+        self.attempt
+    }
+
+    /// Returns the delay proposed by the backoff before the next attempt, or `None` if the
+    /// backoff is exhausted.
+    pub fn next_delay(&self) -> Option<Duration> {
+        self.next_delay
+    }
+
+    /// Returns the time elapsed since the first attempt started, measured by the
+    /// [`Clock`](crate::Clock) of the retry.
+    ///
+    /// It's zero without a clock, unless one is set by `clock` or the `timing` and `std`
+    /// features are enabled outside of `wasm32`.
+    pub fn elapsed(&self) -> Duration {
+        let elapsed = match (self.started_at, self.now()) {
+            (Some(started_at), Some(now)) => now.saturating_sub(started_at),
+            _ => Duration::ZERO,
+        };
+        elapsed + self.resumed_elapsed
+    }
+
+    /// Returns the time left until the [`Deadline`] set by `deadline`, or `None` without one.
+    pub fn remaining(&self) -> Option<Duration> {
+        Some(self.deadline()?.remaining(self.elapsed()))
+    }
+
+    /// Returns the timeout of an attempt starting now, the time left until the [`Deadline`]
+    /// set by `deadline` or its attempt timeout if it's shorter, or `None` without one.
+    pub fn attempt_timeout(&self) -> Option<Duration> {
+        Some(self.deadline()?.attempt_timeout(self.elapsed()))
+    }
+
+    /// Returns the deadline set by `deadline`.
+    fn deadline(&self) -> Option<&Deadline> {
+        self.limits.as_ref()?.deadline.as_ref()
+    }
+
+    /// Returns the delay slept after the last failed attempt.
+    pub(crate) fn last_delay(&self) -> Duration {
+        self.last_delay
+    }
+
+    /// Returns how long the last finished attempt took to run, measured like
+    /// [`RetryState::elapsed`].
+    pub fn attempt_latency(&self) -> Duration {
+        self.attempt_latency
+    }
+
+    /// Set the clock measuring the attempts instead of the default one.
+    pub(crate) fn set_clock(&mut self, clock: SharedClock) {
+        self.clock = Some(clock);
+    }
+
+    /// Returns the current time read from the clock of the retry, if it has one.
+    fn now(&self) -> Option<Duration> {
+        match &self.clock {
+            Some(clock) => Some(clock.now()),
+            #[cfg(all(feature = "timing", feature = "std", not(target_arch = "wasm32")))]
+            None => Some(StdClock.now()),
+            #[cfg(not(all(feature = "timing", feature = "std", not(target_arch = "wasm32"))))]
+            None => None,
+        }
+    }
+
+    /// Attach the failure budget shared with other retries.
+    #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+    pub(crate) fn set_failure_budget(&mut self, budget: FailureBudget) {
+        self.limits_mut().failure_budget = Some(budget);
+    }
+
+    /// Attach the retry budget shared with other retries.
+    #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+    pub(crate) fn set_retry_budget(&mut self, budget: RetryBudget) {
+        self.limits_mut().retry_budget = Some(budget);
+    }
+
+    /// Set the deadline the retry must be done by.
+    pub(crate) fn set_deadline(&mut self, deadline: Deadline) {
+        self.limits_mut().deadline = Some(deadline);
+    }
+
+    /// Set the most time the retry sleeps in total between attempts.
+    pub(crate) fn set_max_total_sleep(&mut self, max: Duration) {
+        self.max_total_delay = Some(max);
+    }
+
+    /// Set the name of the retried operation.
+    pub(crate) fn set_name(&mut self, name: &'static str) {
+        self.name = Some(name);
+    }
+
+    /// Add a key-value tag of the retried operation.
+    pub(crate) fn add_tag(&mut self, key: &'static str, value: Cow<'static, str>) {
+        self.tags.push((key, value));
+    }
+
+    /// Seed the random numbers drawn by the retry.
+    #[cfg(feature = "jitter")]
+    pub(crate) fn set_seed(&mut self, seed: u64) {
+        self.seed = Some(seed);
+        // The generated ID is drawn again from the seeded RNG, so seeded retries are
+        // reproducible.
+        if !self.session_id_set {
+            self.session_id = self.seeded(crate::rng::u64);
+        }
+    }
+
+    /// Without the `jitter` feature, the retry draws no random numbers to seed.
+    #[cfg(not(feature = "jitter"))]
+    pub(crate) fn set_seed(&mut self, _: u64) {}
+
+    /// Run `f`, there's no RNG to seed without the `jitter` feature.
+    #[cfg(not(feature = "jitter"))]
+    fn seeded<R>(&mut self, f: impl FnOnce() -> R) -> R {
+        f()
+    }
+
+    /// Run `f`, the random numbers it draws from the thread's RNG, like the jitter of the
+    /// backoffs, drawn from the seeded RNG of the retry instead if it has one.
+    #[cfg(feature = "jitter")]
+    fn seeded<R>(&mut self, f: impl FnOnce() -> R) -> R {
+        match &mut self.seed {
+            Some(seed) => crate::rng::seeded(seed, f),
+            None => f(),
+        }
+    }
+
+    /// Set the most attempts and time slept in total allowed by a test.
+    #[cfg(feature = "test-util")]
+    pub(crate) fn set_test_budget(&mut self, max_attempts: usize, max_total_sleep: Duration) {
+        self.limits_mut().test_budget = Some((max_attempts, max_total_sleep));
+    }
+
+    /// Panic if retrying after sleeping `dur` exceeds the budget set by a test.
+    #[cfg(feature = "test-util")]
+    fn check_test_budget(&self, dur: Duration) {
+        let Some((max_attempts, max_total_sleep)) =
+            self.limits.as_ref().and_then(|l| l.test_budget)
+        else {
+            return;
+        };
+        let name = self.name.unwrap_or("retry");
+        assert!(
+            self.attempt < max_attempts,
+            "{name} exceeded its test budget of {max_attempts} attempts"
+        );
+        let total = self.total_delay.saturating_add(dur);
+        assert!(
+            total <= max_total_sleep,
+            "{name} exceeded its test budget of {max_total_sleep:?} slept, sleeping {total:?}"
+        );
+    }
+
+    /// Set the ID of the retry instead of generating one.
+    pub(crate) fn set_session_id(&mut self, id: u64) {
+        self.session_id = id;
+        self.session_id_set = true;
+    }
+
+    /// Continue the retry from the progress captured by a snapshot.
+    pub(crate) fn resume<B>(&mut self, snapshot: &RetrySnapshot<B>) {
+        self.attempt = snapshot.attempt();
+        self.resumed_elapsed = snapshot.elapsed();
+    }
+
+    /// Mark the retried operation as not idempotent.
+    pub(crate) fn set_idempotent(&mut self, idempotent: bool) {
+        self.non_idempotent = !idempotent;
+    }
+
+    /// Start recording the report returned with the result of the retry.
+    pub(crate) fn start_report(&mut self) {
+        self.report = Some(Box::default());
+    }
+
+    /// Take the report of the finished retry.
+    pub(crate) fn take_report(&mut self) -> RetryReport {
+        let mut report = self.report.take().map(|report| *report).unwrap_or_default();
+        report.finish(self);
+        report
+    }
+
+    /// Set the warning raised once a fraction of the budget of the retry is consumed.
+    pub(crate) fn set_budget_warning(&mut self, warning: BudgetWarning) {
+        self.limits_mut().budget_warning = Some(warning);
+    }
+
+    /// Returns the limits, set for the first time.
+    fn limits_mut(&mut self) -> &mut Limits {
+        self.limits.get_or_insert_with(Box::default)
+    }
+
+    /// Returns the fraction of the budget consumed if the warning is due at the failed
+    /// attempt that will be retried.
+    pub(crate) fn budget_warning_due(&self) -> Option<f32> {
+        self.limits.as_ref()?.budget_warning_due
+    }
+
+    /// Mark the retry as observed by its own observer instead of the global one.
+    pub(crate) fn set_observed(&mut self) {
+        self.observed = true;
+    }
+
+    /// Returns whether the retry is observed by its own observer.
+    #[cfg(feature = "std")]
+    pub(crate) fn is_observed(&self) -> bool {
+        self.observed
+    }
+
+    /// Record the start of a new attempt.
+    pub(crate) fn start_attempt(&mut self) {
+        self.attempt += 1;
+        if let Some(now) = self.now() {
+            self.started_at.get_or_insert(now);
+            self.attempt_started_at = Some(now);
+        }
+    }
+
+    /// Record the end of the current attempt.
+    pub(crate) fn finish_attempt(&mut self) {
+        if let (Some(started_at), Some(now)) = (self.attempt_started_at.take(), self.now()) {
+            self.attempt_latency = now.saturating_sub(started_at);
+            if let Some(report) = &mut self.report {
+                report.record_latency(self.attempt_latency);
+            }
+        }
+    }
+
+    /// Finish the attempt that succeeded, depositing in the retry budget.
+    pub(crate) fn succeed_attempt(&mut self) {
+        self.finish_attempt();
+        #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+        if let Some(budget) = self.limits.as_ref().and_then(|l| l.retry_budget.as_ref()) {
+            budget.record_success();
+        }
+    }
+
+    /// Raise the budget warning if it's due at the failed attempt that will be retried.
+    fn warn_budget(&mut self) {
+        let Some(limits) = &self.limits else {
+            return;
+        };
+        let due = match limits.budget_warning.filter(|_| !limits.budget_warned) {
+            Some(warning) => Some(warning.consumed(self)).filter(|c| warning.is_reached(*c)),
+            None => None,
+        };
+        let limits = self.limits_mut();
+        limits.budget_warned |= due.is_some();
+        limits.budget_warning_due = due;
+    }
+
+    /// Decide how long to sleep before retrying the failed attempt.
+    ///
+    /// Returns the error to retry with the delay, or the error to end the retry with and the
+    /// reason why.
+    pub(crate) fn retry_delay<E>(
+        &mut self,
+        err: E,
+        backoff: &mut impl Backoff,
+        retryable: &mut (impl ErrorClassifier<E> + ?Sized),
+        errors: &mut (impl ErrorCollector<E> + ?Sized),
+    ) -> Result<(E, Duration), (E, GiveUpReason)> {
+        self.finish_attempt();
+        // Classified first, so errors that aren't retried take no step of the backoff and
+        // nothing from the failure budget.
+        if errors.is_permanent(&err) {
+            return Err((err, GiveUpReason::NotRetryable));
+        }
+        let err = match retryable.classify(err, self) {
+            ControlFlow::Continue(err) => err,
+            ControlFlow::Break(err) => return Err((err, GiveUpReason::NotRetryable)),
+        };
+        if self.non_idempotent && !retryable.is_safe_to_retry(&err) {
+            return Err((err, GiveUpReason::Ambiguous));
+        }
+        #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+        if let Some(budget) = self.limits.as_ref().and_then(|l| l.failure_budget.as_ref()) {
+            budget.record_failure();
+        }
+        #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+        if self
+            .limits
+            .as_ref()
+            .and_then(|l| l.failure_budget.as_ref())
+            .is_some_and(FailureBudget::is_exhausted)
+        {
+            return Err((err, GiveUpReason::BudgetExhausted));
+        }
+
+        self.next_delay = self.seeded(|| match retryable.backoff(&err) {
+            Some(next_delay) => next_delay,
+            None => backoff.next(),
+        });
+        match self.next_delay {
+            Some(planned) => {
+                let dur = retryable.retry_after(&err).unwrap_or(planned);
+                let dur = match self.deadline() {
+                    Some(deadline) => match deadline.fit_delay(self.elapsed(), dur) {
+                        Some(fit) => fit,
+                        None => return Err((err, GiveUpReason::DeadlineExceeded)),
+                    },
+                    None => dur,
+                };
+                if self
+                    .max_total_delay
+                    .is_some_and(|max| self.total_delay.saturating_add(dur) > max)
+                {
+                    return Err((err, GiveUpReason::MaxTotalSleepReached));
+                }
+                #[cfg(feature = "test-util")]
+                self.check_test_budget(dur);
+                // Withdrawn last, so only the retries actually slept for take from the budget.
+                #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+                if self
+                    .limits
+                    .as_ref()
+                    .and_then(|l| l.retry_budget.as_ref())
+                    .is_some_and(|budget| !budget.try_retry())
+                {
+                    return Err((err, GiveUpReason::BudgetExhausted));
+                }
+                self.total_delay = self.total_delay.saturating_add(dur);
+                self.last_delay = dur;
+                self.warn_budget();
+                if let Some(report) = &mut self.report {
+                    report.record_delay(planned, dur);
+                }
+                Ok((err, dur))
+            }
+            None => Err((err, GiveUpReason::BackoffExhausted)),
+        }
+    }
+}
+
+/// Returns the session ID and the index of the attempt of the retry whose function is called
+/// or polled on this thread, or `None` outside of an attempt.
+///
+/// It's set while the retried function is called and its future is polled, so the retried
+/// operation can label its own logs with the attempt they belong to, even when the future
+/// moves between the threads of a runtime. The attempt of the innermost retry is returned
+/// for nested retries. Tasks spawned by the attempt don't inherit it, they're polled outside
+/// of it.
+///
+/// # Examples
+///
+/// ```
+/// use backon::BlockingRetryable;
+/// use backon::ConstantBuilder;
+///
+/// let attempt = (|| {
+///     let (session_id, attempt) = backon::current_attempt().unwrap();
+///     println!("attempt {attempt} of retry {session_id:x}");
+///     Ok::<_, std::io::Error>(attempt)
+/// })
+/// .retry(ConstantBuilder::default())
+/// .session_id(7)
+/// .call()
+/// .unwrap();
+/// assert_eq!(attempt, 1);
+/// ```
+#[cfg(feature = "std")]
+pub fn current_attempt() -> Option<(u64, usize)> {
+    CURRENT_ATTEMPT.with(core::cell::Cell::get)
+}
+
+#[cfg(feature = "std")]
+std::thread_local! {
+    static CURRENT_ATTEMPT: core::cell::Cell<Option<(u64, usize)>> =
+        const { core::cell::Cell::new(None) };
+}
+
+/// Marks the attempt of a retry as the current one of the thread, see [`current_attempt`],
+/// until it's dropped.
+pub(crate) struct CurrentAttempt {
+    #[cfg(feature = "std")]
+    prev: Option<(u64, usize)>,
+}
+
+impl CurrentAttempt {
+    /// Mark the attempt of `state` as the current one.
+    #[cfg(feature = "std")]
+    pub(crate) fn enter(state: &RetryState) -> Self {
+        let current = Some((state.session_id, state.attempt));
+        CurrentAttempt {
+            prev: CURRENT_ATTEMPT.with(|c| c.replace(current)),
+        }
+    }
+
+    /// Without the `std` feature, there's no current attempt to mark.
+    #[cfg(not(feature = "std"))]
+    pub(crate) fn enter(_: &RetryState) -> Self {
+        CurrentAttempt {}
+    }
+}
+
+#[cfg(feature = "std")]
+impl Drop for CurrentAttempt {
+    fn drop(&mut self) {
+        CURRENT_ATTEMPT.with(|c| c.set(self.prev));
+    }
+}
+
+/// Generate the ID of a retry, hashed with the random keys of the std hasher. Retries are
+/// built before they're seeded, so the ID isn't drawn from the thread's RNG, which seeded
+/// retries don't consume.
+#[cfg(feature = "std")]
+fn session_id() -> u64 {
+    use core::hash::BuildHasher;
+
+    std::collections::hash_map::RandomState::new().hash_one(())
+}
+
+/// Generate the ID of a retry.
+#[cfg(all(not(feature = "std"), feature = "jitter"))]
+fn session_id() -> u64 {
+    crate::rng::u64()
+}
+
+/// Generate the ID of a retry, counting the retries without the RNG of the `jitter` feature.
+#[cfg(all(
+    not(feature = "std"),
+    not(feature = "jitter"),
+    target_has_atomic = "ptr"
+))]
+fn session_id() -> u64 {
+    use core::sync::atomic::AtomicUsize;
+    use core::sync::atomic::Ordering;
+
+    static NEXT: AtomicUsize = AtomicUsize::new(1);
+    NEXT.fetch_add(1, Ordering::Relaxed) as u64
+}
+
+/// Generate the ID of a retry, counting the retries on targets without atomic read-modify-write
+/// operations, like `thumbv6m`. The count is loaded and stored separately, so retries built
+/// concurrently, like from an interrupt, may get the same ID unless it's set by `session_id`.
+#[cfg(all(
+    not(feature = "std"),
+    not(feature = "jitter"),
+    not(target_has_atomic = "ptr")
+))]
+fn session_id() -> u64 {
+    use core::sync::atomic::AtomicU32;
+    use core::sync::atomic::Ordering;
+
+    static NEXT: AtomicU32 = AtomicU32::new(1);
+    let id = NEXT.load(Ordering::Relaxed);
+    NEXT.store(id.wrapping_add(1), Ordering::Relaxed);
+    u64::from(id)
+}
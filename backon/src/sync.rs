@@ -0,0 +1,56 @@
+//! The synchronization primitives of the state shared between retries, like the one of
+//! [`FailureBudget`](crate::FailureBudget) and [`WatchedPolicy`](crate::WatchedPolicy).
+//!
+//! Built with `--cfg backon_loom`, they're the ones of [loom](https://docs.rs/loom), so
+//! downstream crates running their own loom tests explore the interleavings of backon's types
+//! as well. New shared state must use them instead of the ones of `std`, and come with a loom
+//! test named `*_loom`, run by `RUSTFLAGS="--cfg backon_loom" cargo test --lib loom`.
+//!
+//! State updated by every attempt, like the failures of a `FailureBudget`, uses the atomics
+//! instead of a lock, and documents the orderings it relies on.
+
+#[cfg(not(backon_loom))]
+pub(crate) use alloc::sync::Arc;
+#[cfg(all(not(backon_loom), not(target_arch = "wasm32")))]
+pub(crate) use core::sync::atomic::AtomicU64;
+#[cfg(all(not(backon_loom), not(target_arch = "wasm32")))]
+pub(crate) use core::sync::atomic::AtomicUsize;
+#[cfg(all(not(backon_loom), not(target_arch = "wasm32")))]
+pub(crate) use std::sync::Condvar;
+#[cfg(not(backon_loom))]
+pub(crate) use std::sync::Mutex;
+#[cfg(not(backon_loom))]
+pub(crate) use std::sync::MutexGuard;
+#[cfg(not(backon_loom))]
+pub(crate) use std::sync::RwLock;
+
+#[cfg(all(backon_loom, not(target_arch = "wasm32")))]
+pub(crate) use loom::sync::atomic::AtomicU64;
+#[cfg(all(backon_loom, not(target_arch = "wasm32")))]
+pub(crate) use loom::sync::atomic::AtomicUsize;
+#[cfg(backon_loom)]
+pub(crate) use loom::sync::Arc;
+#[cfg(all(backon_loom, not(target_arch = "wasm32")))]
+pub(crate) use loom::sync::Condvar;
+#[cfg(backon_loom)]
+pub(crate) use loom::sync::Mutex;
+#[cfg(backon_loom)]
+pub(crate) use loom::sync::MutexGuard;
+#[cfg(backon_loom)]
+pub(crate) use loom::sync::RwLock;
+
+/// Lock `mutex`, ignoring whether a thread panicked while holding it.
+pub(crate) fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+/// Wait for `condvar` to be notified, ignoring whether a thread panicked while holding the
+/// lock, like `lock`.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn wait<'a, T>(condvar: &Condvar, guard: MutexGuard<'a, T>) -> MutexGuard<'a, T> {
+    condvar
+        .wait(guard)
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
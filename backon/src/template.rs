@@ -0,0 +1,187 @@
+use core::fmt;
+use core::future::Future;
+
+use crate::BackoffBuilder;
+use crate::DefaultSleeper;
+use crate::Retry;
+use crate::Retryable;
+use crate::Sleeper;
+
+/// RetryTemplate is a retry configured once, its backoff, the errors it retries and its
+/// sleeper, applied to as many functions as needed.
+///
+/// Its constructors and `when` are `const`, so it can be a `static` shared by every call
+/// site. Applying it with [`retry`](RetryTemplate::retry) only copies its parts into the
+/// retry: the backoff is built from a clone of its builder. The errors are classified by a
+/// function pointer, so the template is `Copy`, `Send` and `Sync` as long as its builder and
+/// its sleeper are.
+///
+/// # Examples
+///
+/// ```
+/// use core::time::Duration;
+///
+/// use backon::ExponentialBuilder;
+/// use backon::RetryTemplate;
+/// use backon::TokioSleeper;
+///
+/// static FETCH: RetryTemplate<ExponentialBuilder, std::io::Error, TokioSleeper> =
+///     RetryTemplate::new(ExponentialBuilder::new().with_min_delay(Duration::from_millis(1)))
+///         .when(|e| e.kind() == std::io::ErrorKind::TimedOut);
+///
+/// async fn fetch() -> Result<String, std::io::Error> {
+///     Ok("hello, world!".to_string())
+/// }
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> Result<(), std::io::Error> {
+/// let content = FETCH.retry(fetch).await?;
+/// assert_eq!(content, "hello, world!");
+/// # Ok(())
+/// # }
+/// ```
+pub struct RetryTemplate<B, E, SF = DefaultSleeper> {
+    builder: B,
+    retryable: fn(&E) -> bool,
+    sleeper: SF,
+}
+
+/// The classifier of templates not given one, retrying every error.
+fn retry_all<E>(_: &E) -> bool {
+    true
+}
+
+impl<B, E> RetryTemplate<B, E> {
+    /// Create a template retrying every error with the backoff of `builder`, sleeping with
+    /// the [`DefaultSleeper`].
+    pub const fn new(builder: B) -> Self {
+        RetryTemplate {
+            builder,
+            retryable: retry_all::<E>,
+            sleeper: DefaultSleeper {},
+        }
+    }
+}
+
+impl<B, E, SF: Sleeper> RetryTemplate<B, E, SF> {
+    /// Create a template retrying every error with the backoff of `builder`, sleeping with
+    /// `sleeper`.
+    pub const fn with_sleeper(builder: B, sleeper: SF) -> Self {
+        RetryTemplate {
+            builder,
+            retryable: retry_all::<E>,
+            sleeper,
+        }
+    }
+}
+
+impl<B, E, SF> RetryTemplate<B, E, SF> {
+    /// Set the sleeper of the retries.
+    pub fn sleep<SN: Sleeper>(self, sleeper: SN) -> RetryTemplate<B, E, SN> {
+        RetryTemplate {
+            builder: self.builder,
+            retryable: self.retryable,
+            sleeper,
+        }
+    }
+
+    /// Set the errors retried, those for which `retryable` returns `true`, like
+    /// [`Retry::when`].
+    pub const fn when(mut self, retryable: fn(&E) -> bool) -> Self {
+        self.retryable = retryable;
+        self
+    }
+
+    /// Returns the builder of the backoff of the retries.
+    pub const fn builder(&self) -> &B {
+        &self.builder
+    }
+
+    /// Retry `f` as configured by the template.
+    #[allow(clippy::type_complexity)]
+    pub fn retry<T, Fut, FutureFn>(
+        &self,
+        f: FutureFn,
+    ) -> Retry<B::Backoff, T, E, Fut, FutureFn, SF, fn(&E) -> bool>
+    where
+        B: BackoffBuilder + Clone,
+        SF: Sleeper + Clone,
+        Fut: Future<Output = Result<T, E>>,
+        FutureFn: FnMut() -> Fut,
+    {
+        f.retry(self.builder.clone())
+            .sleep(self.sleeper.clone())
+            .when(self.retryable)
+    }
+}
+
+impl<B: Clone, E, SF: Clone> Clone for RetryTemplate<B, E, SF> {
+    fn clone(&self) -> Self {
+        RetryTemplate {
+            builder: self.builder.clone(),
+            retryable: self.retryable,
+            sleeper: self.sleeper.clone(),
+        }
+    }
+}
+
+impl<B: Copy, E, SF: Copy> Copy for RetryTemplate<B, E, SF> {}
+
+impl<B: fmt::Debug, E, SF: fmt::Debug> fmt::Debug for RetryTemplate<B, E, SF> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RetryTemplate")
+            .field("builder", &self.builder)
+            .field("sleeper", &self.sleeper)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::future::ready;
+    use core::future::Ready;
+    use core::time::Duration;
+
+    use super::*;
+    use crate::ConstantBuilder;
+
+    type NoSleep = fn(Duration) -> Ready<()>;
+
+    fn no_sleep(_: Duration) -> Ready<()> {
+        ready(())
+    }
+
+    static TEMPLATE: RetryTemplate<ConstantBuilder, &str, NoSleep> = RetryTemplate::with_sleeper(
+        ConstantBuilder::new().with_max_times(2),
+        no_sleep as NoSleep,
+    )
+    .when(|e| *e == "unavailable");
+
+    static_assertions::assert_impl_all!(
+        RetryTemplate<ConstantBuilder, &str, NoSleep>: Copy, Send, Sync
+    );
+
+    #[tokio::test]
+    async fn test_retry_template() {
+        let mut attempts = 0;
+        let result = TEMPLATE
+            .retry(|| {
+                attempts += 1;
+                ready(Err::<(), _>("unavailable"))
+            })
+            .await;
+        assert_eq!(result, Err("unavailable"));
+        assert_eq!(attempts, 3);
+
+        // Errors not retried by the template end the retry at once.
+        let mut attempts = 0;
+        let result = TEMPLATE
+            .retry(|| {
+                attempts += 1;
+                ready(Err::<(), _>("invalid"))
+            })
+            .await;
+        assert_eq!(result, Err("invalid"));
+        assert_eq!(attempts, 1);
+    }
+}
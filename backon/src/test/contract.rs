@@ -0,0 +1,232 @@
+use core::fmt;
+use core::time::Duration;
+
+use crate::BackoffBuilder;
+use crate::BackoffPolicy;
+
+/// ScheduleContract is the invariants expected of the delays of a backoff, checked by `check`.
+///
+/// So custom backoffs are property-tested against the same contracts as the ones of backon:
+/// delays not decreasing without jitter, within a minimum and a maximum delay, and summing
+/// to at most a total. Only the first `limit` delays are checked, 1000 by default, so backoffs
+/// retrying forever are checked as well.
+///
+/// # Examples
+///
+/// ```
+/// use core::time::Duration;
+///
+/// use backon::test::ScheduleContract;
+/// use backon::test::ScheduleViolation;
+/// use backon::ConstantBuilder;
+///
+/// let contract = ScheduleContract::new()
+///     .non_decreasing()
+///     .within(Duration::from_millis(100), Duration::from_secs(1));
+///
+/// let builder = ConstantBuilder::default().with_delay(Duration::from_millis(500));
+/// assert_eq!(contract.check_builder(builder), Ok(()));
+///
+/// let delays = [200, 100].map(Duration::from_millis);
+/// assert!(matches!(
+///     contract.check(delays),
+///     Err(ScheduleViolation::Decreasing { index: 1, .. })
+/// ));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScheduleContract {
+    non_decreasing: bool,
+    min_delay: Duration,
+    max_delay: Option<Duration>,
+    max_total: Option<Duration>,
+    limit: usize,
+}
+
+impl Default for ScheduleContract {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ScheduleContract {
+    /// Create a contract without invariants.
+    pub fn new() -> Self {
+        ScheduleContract {
+            non_decreasing: false,
+            min_delay: Duration::ZERO,
+            max_delay: None,
+            max_total: None,
+            limit: 1000,
+        }
+    }
+
+    /// Create the contract of a stock backoff without jitter, see [`BackoffPolicy::planned`]:
+    /// its delays don't decrease, stay between its minimum and maximum delays, and sum to at
+    /// most its [`max_total_delay`](BackoffPolicy::max_total_delay).
+    ///
+    /// The backoff is expected to be valid, see [`BackoffPolicy::try_build`].
+    pub fn of(policy: impl Into<BackoffPolicy>) -> Self {
+        let policy = policy.into();
+        let (min_delay, max_delay) = policy.delay_range();
+        let contract = Self::new().non_decreasing().with_min_delay(min_delay);
+        let contract = match max_delay {
+            Some(max_delay) => contract.with_max_delay(max_delay),
+            None => contract,
+        };
+        match policy.planned().max_total_delay() {
+            Some(max_total) => contract.with_max_total(max_total),
+            None => contract,
+        }
+    }
+
+    /// Expect every delay to be at least the one before it.
+    pub fn non_decreasing(mut self) -> Self {
+        self.non_decreasing = true;
+        self
+    }
+
+    /// Expect every delay to be within `min_delay..=max_delay`.
+    pub fn within(self, min_delay: Duration, max_delay: Duration) -> Self {
+        self.with_min_delay(min_delay).with_max_delay(max_delay)
+    }
+
+    /// Expect every delay to be at least `min_delay`.
+    pub fn with_min_delay(mut self, min_delay: Duration) -> Self {
+        self.min_delay = min_delay;
+        self
+    }
+
+    /// Expect every delay to be at most `max_delay`.
+    pub fn with_max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = Some(max_delay);
+        self
+    }
+
+    /// Expect the delays to sum to at most `max_total`.
+    pub fn with_max_total(mut self, max_total: Duration) -> Self {
+        self.max_total = Some(max_total);
+        self
+    }
+
+    /// Set how many delays are checked at most.
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    /// Check the delays of the backoff built by `builder`.
+    pub fn check_builder(&self, builder: impl BackoffBuilder) -> Result<(), ScheduleViolation> {
+        self.check(builder.build())
+    }
+
+    /// Check `delays`, returning the first invariant they break.
+    pub fn check(
+        &self,
+        delays: impl IntoIterator<Item = Duration>,
+    ) -> Result<(), ScheduleViolation> {
+        let mut previous = None;
+        let mut total = Duration::ZERO;
+        for (index, delay) in delays.into_iter().take(self.limit).enumerate() {
+            if let Some(previous) =
+                previous.filter(|previous| self.non_decreasing && delay < *previous)
+            {
+                return Err(ScheduleViolation::Decreasing {
+                    index,
+                    previous,
+                    delay,
+                });
+            }
+            if delay < self.min_delay || self.max_delay.is_some_and(|max| delay > max) {
+                return Err(ScheduleViolation::OutOfRange { index, delay });
+            }
+            total = total.saturating_add(delay);
+            if let Some(max_total) = self.max_total.filter(|max_total| total > *max_total) {
+                return Err(ScheduleViolation::TotalExceeded { total, max_total });
+            }
+            previous = Some(delay);
+        }
+        Ok(())
+    }
+}
+
+/// The invariant of a [`ScheduleContract`] broken by the delays of a backoff.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ScheduleViolation {
+    /// The delay at `index` is lower than the one before it.
+    Decreasing {
+        /// The index of the delay, from 0.
+        index: usize,
+        /// The delay before it.
+        previous: Duration,
+        /// The delay.
+        delay: Duration,
+    },
+    /// The delay at `index` isn't within the minimum and the maximum delay.
+    OutOfRange {
+        /// The index of the delay, from 0.
+        index: usize,
+        /// The delay.
+        delay: Duration,
+    },
+    /// The delays sum to more than the total expected.
+    TotalExceeded {
+        /// The sum of the delays so far.
+        total: Duration,
+        /// The total expected at most.
+        max_total: Duration,
+    },
+}
+
+impl fmt::Display for ScheduleViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScheduleViolation::Decreasing {
+                index,
+                previous,
+                delay,
+            } => write!(f, "delay {index} decreased from {previous:?} to {delay:?}"),
+            ScheduleViolation::OutOfRange { index, delay } => {
+                write!(f, "delay {index} of {delay:?} is out of range")
+            }
+            ScheduleViolation::TotalExceeded { total, max_total } => {
+                write!(f, "delays sum to {total:?}, more than {max_total:?}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ScheduleViolation {}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+
+    use super::*;
+    use crate::FibonacciBuilder;
+
+    #[test]
+    fn test_schedule_contract() {
+        let builder = FibonacciBuilder::default()
+            .with_max_delay(Duration::from_secs(5))
+            .with_max_times(8);
+        assert_eq!(ScheduleContract::of(builder).check_builder(builder), Ok(()));
+        // The contract is the one of the backoff without jitter.
+        let contract = ScheduleContract::of(builder.with_jitter());
+        assert_eq!(contract, ScheduleContract::of(builder));
+
+        let contract = ScheduleContract::new().with_max_total(Duration::from_secs(3));
+        assert_eq!(
+            contract.check([2, 2].map(Duration::from_secs)),
+            Err(ScheduleViolation::TotalExceeded {
+                total: Duration::from_secs(4),
+                max_total: Duration::from_secs(3),
+            })
+        );
+        let contract = ScheduleContract::new().with_min_delay(Duration::from_secs(1));
+        assert_eq!(
+            contract.check([Duration::ZERO]).unwrap_err().to_string(),
+            "delay 0 of 0ns is out of range"
+        );
+    }
+}
@@ -0,0 +1,527 @@
+//! Helpers for testing code that retries.
+//!
+//! - [`NoopSleeper`] retries immediately.
+//! - [`RecordingSleeper`] retries immediately and records the delays it was asked to sleep,
+//!   also formatted for golden-file tests.
+//! - [`ManualSleeper`] sleeps until the test moves its time forward with `advance`.
+//! - [`flaky`] builds functions failing a number of times before they succeed.
+//! - [`assert_attempts!`](crate::assert_attempts) and [`assert_slept!`](crate::assert_slept)
+//!   check the attempts and the time slept by a retry, from its
+//!   [`RetryReport`](crate::RetryReport).
+//! - [`ScheduleContract`] checks the invariants of the delays of a backoff, and with the
+//!   `proptest` feature, [`strategy`] generates the builders of backon to property-test them.
+//!
+//! The sleepers are both [`Sleeper`] and [`BlockingSleeper`].
+//!
+//! Only available with the `test-util` feature.
+//!
+//! # Examples
+//!
+//! ```
+//! use core::time::Duration;
+//!
+//! use backon::assert_attempts;
+//! use backon::test::RecordingSleeper;
+//! use backon::BlockingRetryable;
+//! use backon::ExponentialBuilder;
+//!
+//! let sleeper = RecordingSleeper::new();
+//! let (result, report) = (|| Err::<(), _>("unavailable"))
+//!     .retry(ExponentialBuilder::default())
+//!     .sleep(sleeper.clone())
+//!     .call_with_report();
+//!
+//! assert!(result.is_err());
+//! assert_attempts!(report, 4);
+//! assert_eq!(sleeper.delays(), [1, 2, 4].map(Duration::from_secs));
+//! ```
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::future;
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::Ordering;
+use core::task::Context;
+use core::task::Poll;
+use core::task::Waker;
+use core::time::Duration;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+
+use crate::duration::Humane;
+use crate::BlockingSleeper;
+use crate::Clock;
+use crate::Sleeper;
+
+mod contract;
+pub use self::contract::ScheduleContract;
+pub use self::contract::ScheduleViolation;
+
+#[cfg(feature = "proptest")]
+pub mod strategy;
+
+/// NoopSleeper doesn't sleep, so retries are immediate.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoopSleeper;
+
+impl Sleeper for NoopSleeper {
+    type Sleep = future::Ready<()>;
+
+    fn sleep(&self, _: Duration) -> Self::Sleep {
+        future::ready(())
+    }
+}
+
+impl BlockingSleeper for NoopSleeper {
+    fn sleep(&self, _: Duration) {}
+}
+
+/// RecordingSleeper doesn't sleep, but records the delays of the retries, shared by its
+/// clones.
+///
+/// The delays are also returned by `schedule` in a stable format, one line per delay with
+/// the attempt it follows, so snapshot tests compare it to a golden file and catch the
+/// changes of the retry configuration.
+///
+/// # Examples
+///
+/// ```
+/// use backon::test::RecordingSleeper;
+/// use backon::BlockingRetryable;
+/// use backon::FibonacciBuilder;
+///
+/// let sleeper = RecordingSleeper::new();
+/// let _ = (|| Err::<(), _>("unavailable"))
+///     .retry(FibonacciBuilder::default().with_max_times(4))
+///     .sleep(sleeper.clone())
+///     .call();
+///
+/// assert_eq!(sleeper.schedule(), "1: 1s\n2: 1s\n3: 2s\n4: 3s\n");
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct RecordingSleeper {
+    delays: Arc<Mutex<Vec<Duration>>>,
+}
+
+impl RecordingSleeper {
+    /// Create a sleeper without delays recorded.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the delays recorded, in order.
+    pub fn delays(&self) -> Vec<Duration> {
+        self.lock().clone()
+    }
+
+    /// Returns the delays recorded with the attempt they follow, counted from 1 since the
+    /// sleeper was created or cleared.
+    pub fn records(&self) -> Vec<(usize, Duration)> {
+        self.lock()
+            .iter()
+            .enumerate()
+            .map(|(i, dur)| (i + 1, *dur))
+            .collect()
+    }
+
+    /// Returns the delays recorded as lines like `2: 500ms`, the attempt followed by the
+    /// delay after it, written in the largest unit dividing it.
+    pub fn schedule(&self) -> String {
+        self.records()
+            .into_iter()
+            .map(|(attempt, dur)| format!("{attempt}: {}\n", Humane(dur)))
+            .collect()
+    }
+
+    /// Returns the sum of the delays recorded.
+    pub fn total(&self) -> Duration {
+        self.lock().iter().sum()
+    }
+
+    /// Forget the delays recorded.
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    fn record(&self, dur: Duration) {
+        self.lock().push(dur);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<Duration>> {
+        self.delays.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Sleeper for RecordingSleeper {
+    type Sleep = future::Ready<()>;
+
+    fn sleep(&self, dur: Duration) -> Self::Sleep {
+        self.record(dur);
+        future::ready(())
+    }
+}
+
+impl BlockingSleeper for RecordingSleeper {
+    fn sleep(&self, dur: Duration) {
+        self.record(dur);
+    }
+}
+
+/// ManualSleeper sleeps on a time of its own, moved forward by the test with `advance`.
+///
+/// So tests decide when each retry happens, and check the state in between. Its clones share
+/// the same time, which is also a [`Clock`] to set with `clock`, so the elapsed times of the
+/// retry follow it. Blocking retries can't wait for the test, so their sleeps move the time
+/// forward instead.
+///
+/// # Examples
+///
+/// ```
+/// use core::future::Future;
+/// use core::pin::pin;
+/// use core::task::Context;
+/// use core::time::Duration;
+/// use std::sync::Arc;
+/// use std::task::Wake;
+///
+/// use backon::test::ManualSleeper;
+/// use backon::ConstantBuilder;
+/// use backon::Retryable;
+///
+/// struct Noop;
+///
+/// impl Wake for Noop {
+///     fn wake(self: Arc<Self>) {}
+/// }
+///
+/// let sleeper = ManualSleeper::new();
+/// let mut retry = pin!((|| async { Err::<(), _>("unavailable") })
+///     .retry(ConstantBuilder::default().with_max_times(1))
+///     .sleep(sleeper.clone()));
+/// let waker = Arc::new(Noop).into();
+/// let mut cx = Context::from_waker(&waker);
+///
+/// // The first attempt failed, the retry waits for 1s.
+/// assert!(retry.as_mut().poll(&mut cx).is_pending());
+/// assert_eq!(sleeper.pending(), 1);
+///
+/// sleeper.advance(Duration::from_secs(1));
+/// assert!(retry.as_mut().poll(&mut cx).is_ready());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ManualSleeper {
+    state: Arc<Mutex<ManualState>>,
+}
+
+#[derive(Debug, Default)]
+struct ManualState {
+    now: Duration,
+    next_id: u64,
+    sleeps: Vec<(u64, Duration, Waker)>,
+}
+
+impl ManualSleeper {
+    /// Create a sleeper whose time starts at zero.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the current time of the sleeper.
+    pub fn now(&self) -> Duration {
+        self.lock().now
+    }
+
+    /// Move the time forward by `dur`, waking up the sleeps that are due.
+    pub fn advance(&self, dur: Duration) {
+        let due: Vec<_> = {
+            let mut state = self.lock();
+            state.now = state.now.saturating_add(dur);
+            let now = state.now;
+            let (due, sleeps) = state
+                .sleeps
+                .drain(..)
+                .partition(|(_, deadline, _)| *deadline <= now);
+            state.sleeps = sleeps;
+            due
+        };
+        for (_, _, waker) in due {
+            waker.wake();
+        }
+    }
+
+    /// Returns the number of sleeps waiting for the time to move forward.
+    pub fn pending(&self) -> usize {
+        self.lock().sleeps.len()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, ManualState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Sleeper for ManualSleeper {
+    type Sleep = ManualSleep;
+
+    fn sleep(&self, dur: Duration) -> Self::Sleep {
+        let mut state = self.lock();
+        state.next_id += 1;
+        ManualSleep {
+            sleeper: self.clone(),
+            id: state.next_id,
+            deadline: state.now.saturating_add(dur),
+        }
+    }
+}
+
+impl BlockingSleeper for ManualSleeper {
+    fn sleep(&self, dur: Duration) {
+        self.advance(dur);
+    }
+}
+
+impl Clock for ManualSleeper {
+    fn now(&self) -> Duration {
+        ManualSleeper::now(self)
+    }
+}
+
+/// The future returned by [`ManualSleeper`], ready once its time reaches the deadline.
+#[derive(Debug)]
+pub struct ManualSleep {
+    sleeper: ManualSleeper,
+    id: u64,
+    deadline: Duration,
+}
+
+impl Future for ManualSleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let mut state = self.sleeper.lock();
+        state.sleeps.retain(|(id, _, _)| *id != self.id);
+        if state.now >= self.deadline {
+            return Poll::Ready(());
+        }
+        state
+            .sleeps
+            .push((self.id, self.deadline, cx.waker().clone()));
+        Poll::Pending
+    }
+}
+
+impl Drop for ManualSleep {
+    fn drop(&mut self) {
+        self.sleeper
+            .lock()
+            .sleeps
+            .retain(|(id, _, _)| *id != self.id);
+    }
+}
+
+/// Build a [`Flaky`] operation, failing `failures` times with the errors returned by `err`
+/// for the index of the attempt, before succeeding with `ok`.
+///
+/// # Examples
+///
+/// ```
+/// use backon::test::flaky;
+/// use backon::test::NoopSleeper;
+/// use backon::BlockingRetryable;
+/// use backon::ConstantBuilder;
+///
+/// let op = flaky(2, "done", |attempt| format!("attempt {attempt} failed"));
+/// let result = op
+///     .blocking()
+///     .retry(ConstantBuilder::default())
+///     .sleep(NoopSleeper)
+///     .call();
+///
+/// assert_eq!(result, Ok("done"));
+/// assert_eq!(op.attempts(), 3);
+/// ```
+pub fn flaky<T: Clone, E, F: Fn(usize) -> E>(failures: usize, ok: T, err: F) -> Flaky<T, F> {
+    Flaky {
+        failures,
+        ok,
+        err: Arc::new(err),
+        attempts: Arc::new(AtomicUsize::new(0)),
+        latency: None,
+    }
+}
+
+/// Flaky is an operation failing a number of times before it succeeds, built by [`flaky`].
+///
+/// It's retried through the functions returned by `blocking` and `future`, which share the
+/// attempts counted by its clones.
+pub struct Flaky<T, F> {
+    failures: usize,
+    ok: T,
+    err: Arc<F>,
+    attempts: Arc<AtomicUsize>,
+    latency: Option<(Duration, ManualSleeper)>,
+}
+
+impl<T: Clone, E, F: Fn(usize) -> E> Flaky<T, F> {
+    /// Make every attempt take `latency`, moving the time of `time` forward.
+    ///
+    /// With `time` set as the clock of the retry, the latencies of its report are `latency`.
+    pub fn with_latency(mut self, latency: Duration, time: &ManualSleeper) -> Self {
+        self.latency = Some((latency, time.clone()));
+        self
+    }
+
+    /// Returns how many attempts were made.
+    pub fn attempts(&self) -> usize {
+        self.attempts.load(Ordering::SeqCst)
+    }
+
+    /// Returns the operation as a function, for [`BlockingRetryable`](crate::BlockingRetryable).
+    pub fn blocking(&self) -> impl FnMut() -> Result<T, E> {
+        let this = self.clone();
+        move || this.attempt()
+    }
+
+    /// Returns the operation as an async function, for [`Retryable`](crate::Retryable).
+    pub fn future(&self) -> impl FnMut() -> future::Ready<Result<T, E>> {
+        let this = self.clone();
+        move || future::ready(this.attempt())
+    }
+
+    fn attempt(&self) -> Result<T, E> {
+        let attempt = self.attempts.fetch_add(1, Ordering::SeqCst) + 1;
+        if let Some((latency, time)) = &self.latency {
+            time.advance(*latency);
+        }
+        if attempt <= self.failures {
+            Err((self.err)(attempt))
+        } else {
+            Ok(self.ok.clone())
+        }
+    }
+}
+
+impl<T: Clone, F> Clone for Flaky<T, F> {
+    fn clone(&self) -> Self {
+        Flaky {
+            failures: self.failures,
+            ok: self.ok.clone(),
+            err: self.err.clone(),
+            attempts: self.attempts.clone(),
+            latency: self.latency.clone(),
+        }
+    }
+}
+
+impl<T: fmt::Debug, F> fmt::Debug for Flaky<T, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Flaky")
+            .field("failures", &self.failures)
+            .field("ok", &self.ok)
+            .field("attempts", &self.attempts)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::pin::pin;
+    use std::task::Wake;
+
+    use super::*;
+    use crate::BlockingRetryable;
+    use crate::ConstantBuilder;
+    use crate::Retryable;
+
+    struct Noop;
+
+    impl Wake for Noop {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    #[test]
+    fn test_manual_sleeper() {
+        let sleeper = ManualSleeper::new();
+        let mut retry = pin!((|| async { Err::<(), _>("unavailable") })
+            .retry(ConstantBuilder::default().with_max_times(2))
+            .sleep(sleeper.clone())
+            .clock(sleeper.clone()));
+        let waker = Arc::new(Noop).into();
+        let mut cx = Context::from_waker(&waker);
+
+        assert!(retry.as_mut().poll(&mut cx).is_pending());
+        sleeper.advance(Duration::from_millis(500));
+        assert!(retry.as_mut().poll(&mut cx).is_pending());
+        assert_eq!(sleeper.pending(), 1);
+
+        sleeper.advance(Duration::from_millis(500));
+        assert!(retry.as_mut().poll(&mut cx).is_pending());
+        assert_eq!(retry.elapsed(), Duration::from_secs(1));
+
+        sleeper.advance(Duration::from_secs(1));
+        assert_eq!(
+            retry.as_mut().poll(&mut cx),
+            Poll::Ready(Err("unavailable"))
+        );
+        assert_eq!(sleeper.pending(), 0);
+    }
+
+    #[test]
+    fn test_blocking_sleepers() {
+        let recording = RecordingSleeper::new();
+        let manual = ManualSleeper::new();
+        let f = || Err::<(), _>("unavailable");
+
+        let err = f
+            .retry(ConstantBuilder::default())
+            .sleep(recording.clone())
+            .call();
+        assert_eq!(err, Err("unavailable"));
+        assert_eq!(recording.total(), Duration::from_secs(3));
+        assert_eq!(recording.records()[2], (3, Duration::from_secs(1)));
+        recording.clear();
+        let _ = f
+            .retry(ConstantBuilder::default().with_delay(Duration::from_millis(1500)))
+            .sleep(recording.clone())
+            .call();
+        assert_eq!(recording.schedule(), "1: 1500ms\n2: 1500ms\n3: 1500ms\n");
+
+        let (_, report) = f
+            .retry(ConstantBuilder::default())
+            .sleep(manual.clone())
+            .clock(manual.clone())
+            .call_with_report();
+        crate::assert_attempts!(report, 4);
+        crate::assert_slept!(report, Duration::from_secs(3));
+        assert_eq!(manual.now(), Duration::from_secs(3));
+    }
+
+    #[test]
+    fn test_flaky() {
+        let time = ManualSleeper::new();
+        let op = flaky(2, 42, |attempt| attempt).with_latency(Duration::from_millis(10), &time);
+
+        let (result, report) = op
+            .blocking()
+            .retry(ConstantBuilder::default().with_delay(Duration::ZERO))
+            .sleep(NoopSleeper)
+            .clock(time.clone())
+            .call_with_report();
+        assert_eq!(result, Ok(42));
+        assert_eq!(report.latencies(), [Duration::from_millis(10); 3]);
+
+        // The attempts go on from the ones made before.
+        let mut retry = pin!(op
+            .future()
+            .retry(ConstantBuilder::default())
+            .sleep(NoopSleeper));
+        let waker = Arc::new(Noop).into();
+        let mut cx = Context::from_waker(&waker);
+        assert_eq!(retry.as_mut().poll(&mut cx), Poll::Ready(Ok(42)));
+        assert_eq!(op.attempts(), 4);
+    }
+}
@@ -0,0 +1,118 @@
+//! [proptest] strategies generating the builders of backon.
+//!
+//! They generate valid builders, with delays up to 10s, factors up to 4, up to 32 retries
+//! and with or without jitter, to property-test code taking any backoff, or custom backoffs
+//! against the [`ScheduleContract`](super::ScheduleContract) of the stock ones.
+//!
+//! Only available with the `proptest` feature.
+//!
+//! # Examples
+//!
+//! ```
+//! use backon::test::strategy;
+//! use backon::test::ScheduleContract;
+//! use proptest::prelude::*;
+//!
+//! proptest!(|(policy in strategy::backoff_policies())| {
+//!     let planned = policy.planned();
+//!     prop_assert_eq!(ScheduleContract::of(policy).check_builder(planned), Ok(()));
+//! });
+//! ```
+
+use core::time::Duration;
+
+use proptest::option;
+use proptest::prelude::*;
+
+use crate::BackoffPolicy;
+use crate::ConstantBuilder;
+use crate::ExponentialBuilder;
+use crate::FibonacciBuilder;
+
+fn delays() -> impl Strategy<Value = Duration> {
+    (0..=10_000u64).prop_map(Duration::from_millis)
+}
+
+/// Returns the minimum delay and, maybe, a maximum delay no lower than it.
+fn delay_range() -> impl Strategy<Value = (Duration, Option<Duration>)> {
+    (delays(), option::of(delays())).prop_map(|(min, extra)| (min, extra.map(|extra| min + extra)))
+}
+
+/// Generate [`ConstantBuilder`]s.
+pub fn constant_builders() -> impl Strategy<Value = ConstantBuilder> {
+    (delays(), 1..=32usize, any::<bool>()).prop_map(|(delay, max_times, jitter)| {
+        let builder = ConstantBuilder::default()
+            .with_delay(delay)
+            .with_max_times(max_times);
+        if jitter {
+            builder.with_jitter()
+        } else {
+            builder
+        }
+    })
+}
+
+/// Generate [`ExponentialBuilder`]s.
+pub fn exponential_builders() -> impl Strategy<Value = ExponentialBuilder> {
+    (delay_range(), 1.0..=4.0f32, 1..=32usize, any::<bool>()).prop_map(
+        |((min_delay, max_delay), factor, max_times, jitter)| {
+            let builder = ExponentialBuilder::default()
+                .with_min_delay(min_delay)
+                .with_factor(factor)
+                .with_max_times(max_times);
+            let builder = match max_delay {
+                Some(max_delay) => builder.with_max_delay(max_delay),
+                None => builder.without_max_delay(),
+            };
+            if jitter {
+                builder.with_jitter()
+            } else {
+                builder
+            }
+        },
+    )
+}
+
+/// Generate [`FibonacciBuilder`]s.
+pub fn fibonacci_builders() -> impl Strategy<Value = FibonacciBuilder> {
+    (delay_range(), 1..=32usize, any::<bool>()).prop_map(
+        |((min_delay, max_delay), max_times, jitter)| {
+            let builder = FibonacciBuilder::default()
+                .with_min_delay(min_delay)
+                .with_max_times(max_times);
+            let builder = match max_delay {
+                Some(max_delay) => builder.with_max_delay(max_delay),
+                None => builder.without_max_delay(),
+            };
+            if jitter {
+                builder.with_jitter()
+            } else {
+                builder
+            }
+        },
+    )
+}
+
+/// Generate [`BackoffPolicy`]s of every builder.
+pub fn backoff_policies() -> impl Strategy<Value = BackoffPolicy> {
+    prop_oneof![
+        constant_builders().prop_map(BackoffPolicy::from),
+        exponential_builders().prop_map(BackoffPolicy::from),
+        fibonacci_builders().prop_map(BackoffPolicy::from),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::ScheduleContract;
+
+    proptest! {
+        #[test]
+        fn test_backoff_policies(policy in backoff_policies()) {
+            prop_assert!(policy.try_build().is_ok());
+            let contract = ScheduleContract::of(policy);
+            prop_assert_eq!(contract.check_builder(policy.planned()), Ok(()));
+        }
+    }
+}
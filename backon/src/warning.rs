@@ -0,0 +1,90 @@
+use core::time::Duration;
+
+use crate::RetryState;
+
+/// BudgetWarning tells a retry to warn once a fraction of its attempts or time is consumed,
+/// before it gives up.
+///
+/// The warning is delivered as [`RetryEvent::BudgetWarning`](crate::RetryEvent::BudgetWarning)
+/// to the function set by `observe`, at most once per retry. Services can use it to shed load
+/// or degrade before the retry finally fails.
+///
+/// # Examples
+///
+/// ```
+/// use core::time::Duration;
+///
+/// use backon::BlockingRetryable;
+/// use backon::BudgetWarning;
+/// use backon::ConstantBuilder;
+/// use backon::RetryEvent;
+///
+/// let mut warned = None;
+/// let result = (|| Err::<(), _>("unavailable"))
+///     .retry(
+///         ConstantBuilder::default()
+///             .with_delay(Duration::from_millis(1))
+///             .with_max_times(4),
+///     )
+///     .budget_warning(BudgetWarning::new(0.8).with_max_attempts(5))
+///     .observe(|event: RetryEvent<'_, &str>| {
+///         if let RetryEvent::BudgetWarning { attempt, .. } = event {
+///             warned = Some(attempt);
+///         }
+///     })
+///     .call();
+///
+/// assert!(result.is_err());
+/// assert_eq!(warned, Some(4));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BudgetWarning {
+    fraction: f32,
+    max_attempts: Option<usize>,
+    deadline: Option<Duration>,
+}
+
+impl BudgetWarning {
+    /// Create a warning raised once `fraction` of the budget is consumed, like `0.8` for 80%.
+    ///
+    /// The budget is set by `with_max_attempts` and `with_deadline`, whichever is consumed
+    /// first.
+    pub fn new(fraction: f32) -> Self {
+        BudgetWarning {
+            fraction,
+            max_attempts: None,
+            deadline: None,
+        }
+    }
+
+    /// Set the number of attempts the retry is expected to make at most, including the first
+    /// one.
+    pub fn with_max_attempts(mut self, max_attempts: usize) -> Self {
+        self.max_attempts = Some(max_attempts);
+        self
+    }
+
+    /// Set the time the retry is expected to take at most, from the start of the first
+    /// attempt.
+    pub fn with_deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Returns the fraction of the budget consumed by the retry so far.
+    pub(crate) fn consumed(&self, state: &RetryState) -> f32 {
+        let attempts = self
+            .max_attempts
+            .map(|max| state.attempt() as f32 / max as f32)
+            .unwrap_or_default();
+        if let Some(deadline) = self.deadline {
+            return attempts.max(state.elapsed().as_secs_f32() / deadline.as_secs_f32());
+        }
+        attempts
+    }
+
+    /// Returns whether the consumed budget reached the fraction to warn at.
+    pub(crate) fn is_reached(&self, consumed: f32) -> bool {
+        consumed >= self.fraction
+    }
+}
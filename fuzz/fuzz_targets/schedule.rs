@@ -0,0 +1,10 @@
+#![no_main]
+
+use backon::fuzz;
+use backon::BackoffPolicy;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|input: (BackoffPolicy, u16)| {
+    let (policy, limit) = input;
+    fuzz::exercise(policy, limit.into());
+});